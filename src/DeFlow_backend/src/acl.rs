@@ -0,0 +1,265 @@
+// Who may do what with a workflow.
+//
+// The owner has full access. Other principals get a `WorkflowRole` through
+// `share_workflow`; each role includes the ones below it, and canister controllers
+// pass every check. Every grant and revocation is appended to the audit log.
+
+use crate::storage;
+use crate::types::{AclAuditEntry, AclChange, Workflow, WorkflowAcl, WorkflowRole, WorkflowShare};
+use candid::Principal;
+use ic_cdk::{api, caller, query, update};
+
+pub const MAX_SHARES_PER_WORKFLOW: usize = 50;
+
+/// Highest role `principal` holds on `workflow`; the owner counts as Admin.
+pub fn role_of(workflow: &Workflow, acl: Option<&WorkflowAcl>, principal: &str) -> Option<WorkflowRole> {
+    if workflow.owner.as_deref() == Some(principal) {
+        return Some(WorkflowRole::Admin);
+    }
+    acl.and_then(|acl| acl.shares.iter().find(|share| share.principal == principal))
+        .map(|share| share.role)
+}
+
+fn caller_role(workflow: &Workflow) -> Option<WorkflowRole> {
+    let principal = caller();
+    if api::is_controller(&principal) {
+        return Some(WorkflowRole::Admin);
+    }
+    role_of(workflow, storage::get_workflow_acl(&workflow.id).as_ref(), &principal.to_text())
+}
+
+/// Whether the caller holds at least `required` on `workflow`.
+pub fn caller_can(workflow: &Workflow, required: WorkflowRole) -> bool {
+    caller_role(workflow).is_some_and(|role| role >= required)
+}
+
+/// Load a workflow the caller holds at least `required` on.
+pub fn authorize(workflow_id: &str, required: WorkflowRole) -> Result<Workflow, String> {
+    let workflow = storage::get_workflow(workflow_id)
+        .ok_or_else(|| "Workflow not found".to_string())?;
+    if !caller_can(&workflow, required) {
+        return Err(format!("Access denied: this requires {:?} access to workflow {}", required, workflow_id));
+    }
+    Ok(workflow)
+}
+
+/// Whether the caller may add or remove Admin shares: the owner and controllers only.
+fn caller_manages_admins(workflow: &Workflow) -> bool {
+    let principal = caller();
+    api::is_controller(&principal) || workflow.owner.as_deref() == Some(principal.to_text().as_str())
+}
+
+fn parse_principal(principal: &str) -> Result<String, String> {
+    let parsed = Principal::from_text(principal.trim())
+        .map_err(|e| format!("Invalid principal '{}': {}", principal, e))?;
+    if parsed == Principal::anonymous() {
+        return Err("Workflows cannot be shared with the anonymous principal".to_string());
+    }
+    Ok(parsed.to_text())
+}
+
+/// Give `principal` exactly `role`. Returns the change to log, or None if they already hold it.
+fn apply_grant(
+    acl: &mut WorkflowAcl,
+    owner: Option<&str>,
+    actor: &str,
+    manages_admins: bool,
+    principal: &str,
+    role: WorkflowRole,
+    now: u64,
+) -> Result<Option<AclChange>, String> {
+    if owner == Some(principal) {
+        return Err("The owner already has full access".to_string());
+    }
+    let existing = acl.shares.iter().position(|share| share.principal == principal);
+    let previous = existing.map(|index| acl.shares[index].role);
+    if previous == Some(role) {
+        return Ok(None);
+    }
+    if (role == WorkflowRole::Admin || previous == Some(WorkflowRole::Admin)) && !manages_admins {
+        return Err("Only the workflow owner can grant or change Admin access".to_string());
+    }
+
+    let share = WorkflowShare {
+        principal: principal.to_string(),
+        role,
+        granted_by: actor.to_string(),
+        granted_at: now,
+    };
+    match existing {
+        Some(index) => acl.shares[index] = share,
+        None if acl.shares.len() >= MAX_SHARES_PER_WORKFLOW => {
+            return Err(format!("A workflow can be shared with at most {} principals", MAX_SHARES_PER_WORKFLOW));
+        }
+        None => acl.shares.push(share),
+    }
+    Ok(Some(AclChange::Granted { principal: principal.to_string(), role, previous }))
+}
+
+/// Remove `principal`'s share. Anyone may give up their own access.
+fn apply_revoke(
+    acl: &mut WorkflowAcl,
+    actor: &str,
+    actor_role: Option<WorkflowRole>,
+    manages_admins: bool,
+    principal: &str,
+) -> Result<AclChange, String> {
+    let index = acl.shares.iter().position(|share| share.principal == principal)
+        .ok_or_else(|| format!("{} has no shared access to revoke", principal))?;
+    let role = acl.shares[index].role;
+
+    if actor != principal {
+        if actor_role != Some(WorkflowRole::Admin) {
+            return Err("Access denied: this requires Admin access to the workflow".to_string());
+        }
+        if role == WorkflowRole::Admin && !manages_admins {
+            return Err("Only the workflow owner can revoke Admin access".to_string());
+        }
+    }
+
+    acl.shares.remove(index);
+    Ok(AclChange::Revoked { principal: principal.to_string(), role })
+}
+
+fn log_change(workflow_id: &str, actor: &str, change: AclChange) {
+    storage::append_acl_audit_entry(AclAuditEntry {
+        id: 0,
+        workflow_id: workflow_id.to_string(),
+        actor: actor.to_string(),
+        change,
+        timestamp: api::time(),
+    });
+}
+
+fn load_acl(workflow_id: &str) -> WorkflowAcl {
+    storage::get_workflow_acl(workflow_id).unwrap_or_else(|| WorkflowAcl {
+        workflow_id: workflow_id.to_string(),
+        shares: Vec::new(),
+    })
+}
+
+/// Drop every share of a deleted workflow, logging each as revoked by `actor`.
+pub fn forget_workflow(workflow_id: &str, actor: &str) {
+    if let Some(acl) = storage::remove_workflow_acl(workflow_id) {
+        for share in acl.shares {
+            log_change(workflow_id, actor, AclChange::Revoked { principal: share.principal, role: share.role });
+        }
+    }
+}
+
+/// Grant `principal` a role on a workflow, replacing any role they held.
+#[update]
+pub fn share_workflow(workflow_id: String, principal: String, role: WorkflowRole) -> Result<(), String> {
+    let workflow = authorize(&workflow_id, WorkflowRole::Admin)?;
+    let principal = parse_principal(&principal)?;
+    let actor = caller().to_text();
+
+    let mut acl = load_acl(&workflow_id);
+    let change = apply_grant(
+        &mut acl, workflow.owner.as_deref(), &actor, caller_manages_admins(&workflow), &principal, role, api::time(),
+    )?;
+    if let Some(change) = change {
+        storage::insert_workflow_acl(acl);
+        log_change(&workflow_id, &actor, change);
+    }
+    Ok(())
+}
+
+/// Remove `principal`'s access to a workflow.
+#[update]
+pub fn revoke_workflow_access(workflow_id: String, principal: String) -> Result<(), String> {
+    let workflow = storage::get_workflow(&workflow_id)
+        .ok_or_else(|| "Workflow not found".to_string())?;
+    let principal = parse_principal(&principal)?;
+    let actor = caller().to_text();
+
+    let mut acl = load_acl(&workflow_id);
+    let change = apply_revoke(&mut acl, &actor, caller_role(&workflow), caller_manages_admins(&workflow), &principal)?;
+    storage::insert_workflow_acl(acl);
+    log_change(&workflow_id, &actor, change);
+    Ok(())
+}
+
+#[query]
+pub fn list_workflow_shares(workflow_id: String) -> Result<Vec<WorkflowShare>, String> {
+    authorize(&workflow_id, WorkflowRole::Admin)?;
+    Ok(load_acl(&workflow_id).shares)
+}
+
+/// Grants and revocations on a workflow, oldest first.
+#[query]
+pub fn get_workflow_acl_audit_log(workflow_id: String) -> Result<Vec<AclAuditEntry>, String> {
+    authorize(&workflow_id, WorkflowRole::Admin)?;
+    Ok(storage::list_acl_audit_entries(&workflow_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "owner";
+
+    fn workflow() -> Workflow {
+        Workflow { id: "wf_1".to_string(), owner: Some(OWNER.to_string()), ..Workflow::default() }
+    }
+
+    fn empty_acl() -> WorkflowAcl {
+        WorkflowAcl { workflow_id: "wf_1".to_string(), shares: Vec::new() }
+    }
+
+    #[test]
+    fn test_roles_come_from_ownership_or_shares() {
+        let mut acl = empty_acl();
+        apply_grant(&mut acl, Some(OWNER), OWNER, true, "alice", WorkflowRole::Runner, 1).unwrap();
+
+        assert_eq!(role_of(&workflow(), Some(&acl), OWNER), Some(WorkflowRole::Admin));
+        assert_eq!(role_of(&workflow(), Some(&acl), "alice"), Some(WorkflowRole::Runner));
+        assert_eq!(role_of(&workflow(), Some(&acl), "mallory"), None);
+        assert_eq!(role_of(&Workflow { owner: None, ..workflow() }, None, OWNER), None);
+        assert!(WorkflowRole::Runner >= WorkflowRole::Viewer && WorkflowRole::Runner < WorkflowRole::Editor);
+    }
+
+    #[test]
+    fn test_grants_replace_roles_and_only_owners_manage_admins() {
+        let mut acl = empty_acl();
+        let change = apply_grant(&mut acl, Some(OWNER), OWNER, true, "alice", WorkflowRole::Viewer, 1).unwrap();
+        assert_eq!(change, Some(AclChange::Granted { principal: "alice".to_string(), role: WorkflowRole::Viewer, previous: None }));
+
+        let change = apply_grant(&mut acl, Some(OWNER), OWNER, true, "alice", WorkflowRole::Editor, 2).unwrap();
+        assert_eq!(change, Some(AclChange::Granted {
+            principal: "alice".to_string(),
+            role: WorkflowRole::Editor,
+            previous: Some(WorkflowRole::Viewer),
+        }));
+        assert_eq!(acl.shares.len(), 1);
+        assert_eq!(apply_grant(&mut acl, Some(OWNER), OWNER, true, "alice", WorkflowRole::Editor, 3).unwrap(), None);
+
+        // A shared Admin can hand out lesser roles but not Admin itself
+        apply_grant(&mut acl, Some(OWNER), OWNER, true, "bob", WorkflowRole::Admin, 4).unwrap();
+        assert!(apply_grant(&mut acl, Some(OWNER), "bob", false, "carol", WorkflowRole::Runner, 5).is_ok());
+        assert!(apply_grant(&mut acl, Some(OWNER), "bob", false, "carol", WorkflowRole::Admin, 6).is_err());
+        assert!(apply_grant(&mut acl, Some(OWNER), "bob", false, OWNER, WorkflowRole::Viewer, 7).is_err());
+    }
+
+    #[test]
+    fn test_revocation_rules() {
+        let mut acl = empty_acl();
+        for (principal, role) in [("alice", WorkflowRole::Viewer), ("bob", WorkflowRole::Admin), ("carol", WorkflowRole::Admin)] {
+            apply_grant(&mut acl, Some(OWNER), OWNER, true, principal, role, 1).unwrap();
+        }
+
+        // Viewers cannot revoke others
+        assert!(apply_revoke(&mut acl, "alice", Some(WorkflowRole::Viewer), false, "bob").is_err());
+        // Shared Admins cannot remove each other
+        assert!(apply_revoke(&mut acl, "bob", Some(WorkflowRole::Admin), false, "carol").is_err());
+        assert_eq!(
+            apply_revoke(&mut acl, "bob", Some(WorkflowRole::Admin), false, "alice").unwrap(),
+            AclChange::Revoked { principal: "alice".to_string(), role: WorkflowRole::Viewer }
+        );
+        // Anyone can give up their own access
+        assert!(apply_revoke(&mut acl, "carol", Some(WorkflowRole::Admin), false, "carol").is_ok());
+        assert!(apply_revoke(&mut acl, OWNER, Some(WorkflowRole::Admin), true, "bob").is_ok());
+        assert!(acl.shares.is_empty());
+        assert!(apply_revoke(&mut acl, OWNER, Some(WorkflowRole::Admin), true, "bob").is_err());
+    }
+}
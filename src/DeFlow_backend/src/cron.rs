@@ -0,0 +1,584 @@
+// Cron schedules shared by every scheduler in the canister.
+//
+// Standard five-field expressions (minute hour day-of-month month day-of-week)
+// with lists, ranges, steps and month/weekday names, plus the `@daily`-style
+// aliases. Schedules are evaluated in UTC, a fixed UTC offset or one of the
+// IANA zones listed in `ZONES`, given either separately or as a `CRON_TZ=` prefix:
+//
+//   */15 9-17 * * mon-fri
+//   0 0 1,15 * *
+//   CRON_TZ=Europe/Berlin 30 8 * * 1
+//   @weekly
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_HOUR: i64 = 3_600;
+const SECONDS_PER_DAY: i64 = 86_400;
+// Long enough for expressions that only match on 29 February.
+const MAX_SEARCH_YEARS: i64 = 8;
+const MAX_OFFSET_SECONDS: i64 = 14 * SECONDS_PER_HOUR;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Daylight saving rules for the supported IANA zones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DstRule {
+    None,
+    NorthAmerica,
+    Europe,
+    Australia,
+    NewZealand,
+}
+
+// (name, standard offset in minutes, daylight saving rule)
+const ZONES: &[(&str, i64, DstRule)] = &[
+    ("UTC", 0, DstRule::None),
+    ("Etc/UTC", 0, DstRule::None),
+    ("America/New_York", -300, DstRule::NorthAmerica),
+    ("America/Toronto", -300, DstRule::NorthAmerica),
+    ("America/Chicago", -360, DstRule::NorthAmerica),
+    ("America/Denver", -420, DstRule::NorthAmerica),
+    ("America/Phoenix", -420, DstRule::None),
+    ("America/Los_Angeles", -480, DstRule::NorthAmerica),
+    ("America/Vancouver", -480, DstRule::NorthAmerica),
+    ("America/Anchorage", -540, DstRule::NorthAmerica),
+    ("America/Mexico_City", -360, DstRule::None),
+    ("America/Sao_Paulo", -180, DstRule::None),
+    ("America/Argentina/Buenos_Aires", -180, DstRule::None),
+    ("Pacific/Honolulu", -600, DstRule::None),
+    ("Europe/London", 0, DstRule::Europe),
+    ("Europe/Dublin", 0, DstRule::Europe),
+    ("Europe/Lisbon", 0, DstRule::Europe),
+    ("Europe/Paris", 60, DstRule::Europe),
+    ("Europe/Berlin", 60, DstRule::Europe),
+    ("Europe/Madrid", 60, DstRule::Europe),
+    ("Europe/Rome", 60, DstRule::Europe),
+    ("Europe/Amsterdam", 60, DstRule::Europe),
+    ("Europe/Zurich", 60, DstRule::Europe),
+    ("Europe/Stockholm", 60, DstRule::Europe),
+    ("Europe/Warsaw", 60, DstRule::Europe),
+    ("Europe/Athens", 120, DstRule::Europe),
+    ("Europe/Helsinki", 120, DstRule::Europe),
+    ("Europe/Istanbul", 180, DstRule::None),
+    ("Europe/Moscow", 180, DstRule::None),
+    ("Africa/Lagos", 60, DstRule::None),
+    ("Africa/Johannesburg", 120, DstRule::None),
+    ("Africa/Nairobi", 180, DstRule::None),
+    ("Asia/Dubai", 240, DstRule::None),
+    ("Asia/Karachi", 300, DstRule::None),
+    ("Asia/Kolkata", 330, DstRule::None),
+    ("Asia/Dhaka", 360, DstRule::None),
+    ("Asia/Bangkok", 420, DstRule::None),
+    ("Asia/Jakarta", 420, DstRule::None),
+    ("Asia/Singapore", 480, DstRule::None),
+    ("Asia/Hong_Kong", 480, DstRule::None),
+    ("Asia/Shanghai", 480, DstRule::None),
+    ("Asia/Taipei", 480, DstRule::None),
+    ("Asia/Seoul", 540, DstRule::None),
+    ("Asia/Tokyo", 540, DstRule::None),
+    ("Australia/Perth", 480, DstRule::None),
+    ("Australia/Brisbane", 600, DstRule::None),
+    ("Australia/Adelaide", 570, DstRule::Australia),
+    ("Australia/Sydney", 600, DstRule::Australia),
+    ("Australia/Melbourne", 600, DstRule::Australia),
+    ("Pacific/Auckland", 720, DstRule::NewZealand),
+];
+
+/// Timezone a cron schedule is evaluated in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timezone {
+    name: String,
+    standard_offset: i64,
+    dst: DstRule,
+}
+
+impl Timezone {
+    pub fn utc() -> Self {
+        Timezone { name: "UTC".to_string(), standard_offset: 0, dst: DstRule::None }
+    }
+
+    /// Parse an IANA name (`Europe/Berlin`) or a UTC offset (`+05:30`, `UTC-8`, `GMT+0100`).
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let name = name.trim();
+        if let Some((zone, minutes, dst)) = ZONES.iter().find(|(zone, _, _)| zone.eq_ignore_ascii_case(name)) {
+            return Ok(Timezone { name: zone.to_string(), standard_offset: minutes * 60, dst: *dst });
+        }
+
+        let upper = name.to_ascii_uppercase();
+        let offset = upper.strip_prefix("UTC")
+            .or_else(|| upper.strip_prefix("GMT"))
+            .unwrap_or(if upper == "Z" { "" } else { &upper });
+        let offset_seconds = parse_offset(offset)
+            .ok_or_else(|| format!(
+                "Unknown timezone '{}': use a UTC offset such as +05:30 or one of {}",
+                name,
+                ZONES.iter().map(|(zone, _, _)| *zone).collect::<Vec<_>>().join(", ")
+            ))?;
+
+        Ok(Timezone {
+            name: format_offset(offset_seconds),
+            standard_offset: offset_seconds,
+            dst: DstRule::None,
+        })
+    }
+
+    /// Offset from UTC in seconds at the given UTC instant.
+    fn offset_at(&self, utc: i64) -> i64 {
+        if self.in_daylight_time(utc) {
+            self.standard_offset + SECONDS_PER_HOUR
+        } else {
+            self.standard_offset
+        }
+    }
+
+    fn in_daylight_time(&self, utc: i64) -> bool {
+        let std = self.standard_offset;
+        let daylight = std + SECONDS_PER_HOUR;
+        let (year, _, _) = civil_from_days((utc + std).div_euclid(SECONDS_PER_DAY));
+        // Transition instants in UTC; local rules are converted with the offset in force before the change.
+        let local = |month: u32, day: u32, hour: i64, offset: i64| {
+            days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * SECONDS_PER_HOUR - offset
+        };
+
+        match self.dst {
+            DstRule::None => false,
+            DstRule::NorthAmerica => {
+                let start = local(3, first_sunday(year, 3) + 7, 2, std);
+                let end = local(11, first_sunday(year, 11), 2, daylight);
+                utc >= start && utc < end
+            }
+            DstRule::Europe => {
+                let start = local(3, last_sunday(year, 3), 1, 0);
+                let end = local(10, last_sunday(year, 10), 1, 0);
+                utc >= start && utc < end
+            }
+            DstRule::Australia => {
+                let end = local(4, first_sunday(year, 4), 3, daylight);
+                let start = local(10, first_sunday(year, 10), 2, std);
+                utc < end || utc >= start
+            }
+            DstRule::NewZealand => {
+                let end = local(4, first_sunday(year, 4), 3, daylight);
+                let start = local(9, last_sunday(year, 9), 2, std);
+                utc < end || utc >= start
+            }
+        }
+    }
+
+    /// UTC instant (seconds) of a wall-clock time in this zone.
+    pub fn local_to_utc(&self, local: i64) -> i64 {
+        self.to_utc(local, i64::MIN).unwrap_or(local - self.standard_offset)
+    }
+
+    /// Earliest UTC instant after `after` showing the given wall-clock time.
+    /// Times skipped by a daylight saving jump are shifted forward by the jump.
+    fn to_utc(&self, local: i64, after: i64) -> Option<i64> {
+        let mut offsets = vec![self.standard_offset];
+        if self.dst != DstRule::None {
+            offsets.insert(0, self.standard_offset + SECONDS_PER_HOUR);
+        }
+
+        let valid: Vec<i64> = offsets.iter()
+            .filter(|offset| self.offset_at(local - **offset) == **offset)
+            .map(|offset| local - offset)
+            .collect();
+
+        if valid.is_empty() {
+            let shifted = local - self.standard_offset;
+            return (shifted > after).then_some(shifted);
+        }
+        valid.into_iter().filter(|utc| *utc > after).min()
+    }
+}
+
+/// A parsed cron expression together with the timezone it runs in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+    timezone: Timezone,
+}
+
+impl CronSchedule {
+    /// Parse an expression, optionally prefixed with `CRON_TZ=<zone>`. A separately
+    /// supplied timezone must agree with the prefix when both are given.
+    pub fn parse(expression: &str, timezone: Option<&str>) -> Result<Self, String> {
+        let mut expression = expression.trim();
+        if expression.is_empty() {
+            return Err("Cron expression cannot be empty".to_string());
+        }
+
+        let mut zone = match timezone.map(str::trim).filter(|tz| !tz.is_empty()) {
+            Some(tz) => Some(Timezone::parse(tz)?),
+            None => None,
+        };
+
+        if let Some(rest) = expression.strip_prefix("CRON_TZ=").or_else(|| expression.strip_prefix("TZ=")) {
+            let (name, remainder) = rest.split_once(char::is_whitespace)
+                .ok_or("Cron expression is missing its fields after the timezone")?;
+            let prefixed = Timezone::parse(name)?;
+            if let Some(existing) = &zone {
+                if existing.name != prefixed.name {
+                    return Err(format!(
+                        "Cron expression timezone '{}' conflicts with schedule timezone '{}'",
+                        prefixed.name, existing.name
+                    ));
+                }
+            }
+            zone = Some(prefixed);
+            expression = remainder.trim();
+        }
+
+        let expanded = if expression.starts_with('@') {
+            expand_alias(expression)?
+        } else {
+            expression
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Cron expression must have 5 fields (minute hour day-of-month month day-of-week), got {}",
+                fields.len()
+            ));
+        }
+
+        let mut days_of_week = parse_field(fields[4], "day-of-week", 0, 7, &WEEKDAY_NAMES, 0)?;
+        // Both 0 and 7 mean Sunday.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        let schedule = CronSchedule {
+            minutes: parse_field(fields[0], "minute", 0, 59, &[], 0)?,
+            hours: parse_field(fields[1], "hour", 0, 23, &[], 0)?,
+            days_of_month: parse_field(fields[2], "day-of-month", 1, 31, &[], 0)?,
+            months: parse_field(fields[3], "month", 1, 12, &MONTH_NAMES, 1)?,
+            days_of_week,
+            day_of_month_restricted: !fields[2].starts_with('*'),
+            day_of_week_restricted: !fields[4].starts_with('*'),
+            timezone: zone.unwrap_or_else(Timezone::utc),
+        };
+
+        if schedule.day_of_month_restricted && !schedule.day_of_week_restricted {
+            let reachable = (1..=12u32)
+                .filter(|month| schedule.months & (1 << month) != 0)
+                .any(|month| (1..=days_in_month(2000, month)).any(|day| schedule.days_of_month & (1 << day) != 0));
+            if !reachable {
+                return Err(format!("Cron expression '{}' never fires: no selected day exists in the selected months", expanded));
+            }
+        }
+
+        Ok(schedule)
+    }
+
+    /// Next fire time strictly after `after_ns`, in nanoseconds since the epoch.
+    pub fn next_after(&self, after_ns: u64) -> Result<u64, String> {
+        let after = (after_ns / NANOS_PER_SECOND) as i64;
+        // Wall-clock minute following `after`, searched field by field.
+        let mut local = (after + self.timezone.offset_at(after)).div_euclid(60) * 60 + 60;
+        let limit = local + MAX_SEARCH_YEARS * 366 * SECONDS_PER_DAY;
+
+        while local < limit {
+            let days = local.div_euclid(SECONDS_PER_DAY);
+            let (year, month, day) = civil_from_days(days);
+
+            if self.months & (1 << month) == 0 {
+                let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                local = days_from_civil(next_year, next_month, 1) * SECONDS_PER_DAY;
+                continue;
+            }
+            if !self.day_matches(day, weekday(days)) {
+                local = (days + 1) * SECONDS_PER_DAY;
+                continue;
+            }
+
+            let seconds = local.rem_euclid(SECONDS_PER_DAY);
+            let hour = seconds / SECONDS_PER_HOUR;
+            if self.hours & (1 << hour) == 0 {
+                local = days * SECONDS_PER_DAY + (hour + 1) * SECONDS_PER_HOUR;
+                continue;
+            }
+            if self.minutes & (1 << (seconds % SECONDS_PER_HOUR / 60)) == 0 {
+                local += 60;
+                continue;
+            }
+
+            if let Some(utc) = self.timezone.to_utc(local, after) {
+                return Ok(utc as u64 * NANOS_PER_SECOND);
+            }
+            local += 60;
+        }
+
+        Err(format!("Cron schedule has no fire time in the next {} years", MAX_SEARCH_YEARS))
+    }
+
+    // Vixie cron semantics: when both day fields are restricted either one may match.
+    fn day_matches(&self, day: u32, weekday: u32) -> bool {
+        let dom = self.days_of_month & (1 << day) != 0;
+        let dow = self.days_of_week & (1 << weekday) != 0;
+        if self.day_of_month_restricted && self.day_of_week_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+}
+
+/// Shared next-fire-time calculator used by every cron-based scheduler.
+pub fn next_fire_time(expression: &str, timezone: Option<&str>, after_ns: u64) -> Result<u64, String> {
+    CronSchedule::parse(expression, timezone)?.next_after(after_ns)
+}
+
+pub fn validate_expression(expression: &str, timezone: Option<&str>) -> Result<(), String> {
+    CronSchedule::parse(expression, timezone).map(|_| ())
+}
+
+fn expand_alias(alias: &str) -> Result<&'static str, String> {
+    match alias.to_ascii_lowercase().as_str() {
+        "@yearly" | "@annually" => Ok("0 0 1 1 *"),
+        "@monthly" => Ok("0 0 1 * *"),
+        "@weekly" => Ok("0 0 * * 0"),
+        "@daily" | "@midnight" => Ok("0 0 * * *"),
+        "@hourly" => Ok("0 * * * *"),
+        _ => Err(format!("Unknown cron alias '{}'", alias)),
+    }
+}
+
+/// Parse one field into a bitset of allowed values.
+fn parse_field(field: &str, label: &str, min: u32, max: u32, names: &[&str], first_name_value: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse()
+                    .map_err(|_| format!("Invalid step '{}' in {} field", step, label))?;
+                if step == 0 || step > max {
+                    return Err(format!("Step {} out of range in {} field", step, label));
+                }
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, label, min, max, names, first_name_value)?,
+                parse_value(end, label, min, max, names, first_name_value)?,
+            )
+        } else {
+            let value = parse_value(range, label, min, max, names, first_name_value)?;
+            // `5/15` means "from 5 to the end of the range every 15".
+            (value, if step.is_some() { max } else { value })
+        };
+
+        if start > end {
+            return Err(format!("Invalid range '{}' in {} field", range, label));
+        }
+
+        let step = step.unwrap_or(1);
+        let mut value = start;
+        while value <= end {
+            bits |= 1 << value;
+            value += step;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn parse_value(value: &str, label: &str, min: u32, max: u32, names: &[&str], first_name_value: u32) -> Result<u32, String> {
+    let parsed = match names.iter().position(|name| name.eq_ignore_ascii_case(value)) {
+        Some(index) => index as u32 + first_name_value,
+        None => value.parse::<u32>()
+            .map_err(|_| format!("Invalid value '{}' in {} field", value, label))?,
+    };
+
+    if parsed < min || parsed > max {
+        return Err(format!("Value {} out of range {}-{} in {} field", parsed, min, max, label));
+    }
+    Ok(parsed)
+}
+
+/// `+05:30`, `-0800`, `+5` or empty (UTC) to seconds.
+fn parse_offset(offset: &str) -> Option<i64> {
+    if offset.is_empty() {
+        return Some(0);
+    }
+
+    let sign = match offset.as_bytes()[0] {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = &offset[1..];
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+
+    let hours: i64 = hours.parse().ok()?;
+    let minutes: i64 = minutes.parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    let seconds = hours * SECONDS_PER_HOUR + minutes * 60;
+    (seconds <= MAX_OFFSET_SECONDS).then_some(sign * seconds)
+}
+
+fn format_offset(seconds: i64) -> String {
+    if seconds == 0 {
+        return "UTC".to_string();
+    }
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    format!("UTC{}{:02}:{:02}", sign, seconds / SECONDS_PER_HOUR, seconds % SECONDS_PER_HOUR / 60)
+}
+
+// Proleptic Gregorian calendar conversions (days since 1970-01-01).
+
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// 0 = Sunday.
+fn weekday(days: i64) -> u32 {
+    (days + 4).rem_euclid(7) as u32
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn first_sunday(year: i64, month: u32) -> u32 {
+    1 + (7 - weekday(days_from_civil(year, month, 1))) % 7
+}
+
+fn last_sunday(year: i64, month: u32) -> u32 {
+    let last = days_in_month(year, month);
+    last - weekday(days_from_civil(year, month, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i64, month: u32, day: u32, hour: i64, minute: i64) -> u64 {
+        let seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * SECONDS_PER_HOUR + minute * 60;
+        seconds as u64 * NANOS_PER_SECOND
+    }
+
+    fn next(expression: &str, timezone: Option<&str>, after: u64) -> u64 {
+        next_fire_time(expression, timezone, after).unwrap()
+    }
+
+    #[test]
+    fn civil_conversions_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(days_from_civil(2028, 2, 29)), (2028, 2, 29));
+        // 16 October 2026 is a Friday.
+        assert_eq!(weekday(days_from_civil(2026, 10, 16)), 5);
+    }
+
+    #[test]
+    fn steps_lists_and_ranges() {
+        assert_eq!(next("*/15 * * * *", None, at(2026, 10, 16, 10, 7)), at(2026, 10, 16, 10, 15));
+        assert_eq!(next("*/15 * * * *", None, at(2026, 10, 16, 10, 15)), at(2026, 10, 16, 10, 30));
+        assert_eq!(next("0 0-12/6 1,15 * *", None, at(2026, 1, 1, 7, 0)), at(2026, 1, 1, 12, 0));
+        assert_eq!(next("0 0-12/6 1,15 * *", None, at(2026, 1, 1, 12, 0)), at(2026, 1, 15, 0, 0));
+        assert_eq!(next("30 9 * jan-mar *", None, at(2026, 10, 16, 0, 0)), at(2027, 1, 1, 9, 30));
+    }
+
+    #[test]
+    fn weekday_names_and_sunday_aliases() {
+        assert_eq!(next("0 9 * * mon-fri", None, at(2026, 10, 16, 10, 0)), at(2026, 10, 19, 9, 0));
+        assert_eq!(next("0 0 * * 7", None, at(2026, 10, 16, 0, 0)), at(2026, 10, 18, 0, 0));
+        assert_eq!(next("@weekly", None, at(2026, 10, 16, 0, 0)), at(2026, 10, 18, 0, 0));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        assert_eq!(next("0 0 1 * mon", None, at(2026, 10, 16, 0, 0)), at(2026, 10, 19, 0, 0));
+        assert_eq!(next("0 0 1 * *", None, at(2026, 10, 16, 0, 0)), at(2026, 11, 1, 0, 0));
+        assert_eq!(next("@monthly", None, at(2026, 10, 16, 0, 0)), at(2026, 11, 1, 0, 0));
+        assert_eq!(next("0 0 29 2 *", None, at(2026, 3, 1, 0, 0)), at(2028, 2, 29, 0, 0));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in [
+            "", "* * *", "61 * * * *", "* 24 * * *", "5-1 * * * *", "*/0 * * * *",
+            "0 0 30 2 *", "0 0 * foo *", "@often", "CRON_TZ=Mars/Base 0 0 * * *",
+        ] {
+            assert!(validate_expression(expression, None).is_err(), "{} should be rejected", expression);
+        }
+        let error = validate_expression("0 0 * * *", Some("Nowhere/City")).unwrap_err();
+        assert!(error.contains("Europe/Berlin") && error.contains("+05:30"), "{}", error);
+    }
+
+    #[test]
+    fn fixed_offset_timezones() {
+        assert_eq!(next("0 9 * * *", Some("+05:30"), at(2026, 10, 16, 0, 0)), at(2026, 10, 16, 3, 30));
+        assert_eq!(next("0 9 * * *", Some("UTC-8"), at(2026, 10, 16, 0, 0)), at(2026, 10, 16, 17, 0));
+        assert_eq!(Timezone::parse("GMT+0100").unwrap().name, "UTC+01:00");
+        assert!(Timezone::parse("+15:00").is_err());
+    }
+
+    #[test]
+    fn iana_timezones_follow_daylight_saving() {
+        assert_eq!(next("0 9 * * *", Some("America/New_York"), at(2026, 7, 1, 0, 0)), at(2026, 7, 1, 13, 0));
+        assert_eq!(next("0 9 * * *", Some("America/New_York"), at(2026, 12, 1, 0, 0)), at(2026, 12, 1, 14, 0));
+        assert_eq!(next("0 9 * * *", Some("Australia/Sydney"), at(2026, 7, 1, 0, 0)), at(2026, 7, 1, 23, 0));
+        assert_eq!(next("0 9 * * *", Some("Australia/Sydney"), at(2026, 12, 1, 0, 0)), at(2026, 12, 1, 22, 0));
+        // 02:30 does not exist in Berlin on 29 March 2026; it runs once the clocks have jumped.
+        assert_eq!(next("30 2 * * *", Some("Europe/Berlin"), at(2026, 3, 29, 0, 0)), at(2026, 3, 29, 1, 30));
+        assert_eq!(next("30 2 * * *", Some("Europe/Berlin"), at(2026, 3, 29, 1, 30)), at(2026, 3, 30, 0, 30));
+    }
+
+    #[test]
+    fn timezone_prefix_must_agree_with_argument() {
+        let expression = "CRON_TZ=Asia/Tokyo 0 9 * * *";
+        assert_eq!(next(expression, None, at(2026, 10, 16, 1, 0)), at(2026, 10, 17, 0, 0));
+        assert_eq!(next(expression, Some("asia/tokyo"), at(2026, 10, 16, 1, 0)), at(2026, 10, 17, 0, 0));
+        assert!(validate_expression(expression, Some("UTC")).is_err());
+    }
+}
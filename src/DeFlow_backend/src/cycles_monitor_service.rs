@@ -6,6 +6,9 @@ use serde::Serialize;
 use ic_cdk::api::{canister_balance, canister_balance128};
 use std::collections::HashMap;
 
+// A monitor that stays low is reported again at most once per day
+const REALERT_INTERVAL_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
 #[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
 pub struct CyclesMonitorService {
     monitored_canisters: HashMap<String, CyclesMonitorConfig>,
@@ -205,6 +208,53 @@ impl CyclesMonitorService {
         })
     }
 
+    /// Threshold alerts for every monitor at `current_cycles`, paired with the monitor owner
+    /// and recorded in the history. Run by the periodic cycles check, which emits them as events.
+    pub fn check_all(&mut self, current_cycles: u128, now: u64) -> Vec<(CyclesAlert, String)> {
+        let mut monitor_ids: Vec<String> = self.monitored_canisters.keys().cloned().collect();
+        monitor_ids.sort();
+
+        let mut triggered = Vec::new();
+        for monitor_id in monitor_ids {
+            let Some(config) = self.monitored_canisters.get_mut(&monitor_id) else { continue };
+            if current_cycles > config.warning_threshold {
+                config.last_alert_sent = None;
+                continue;
+            }
+            if config.last_alert_sent.is_some_and(|sent| now.saturating_sub(sent) < REALERT_INTERVAL_NS) {
+                continue;
+            }
+            config.last_alert_sent = Some(now);
+
+            let alert = |alert_type: AlertType, threshold: u128| CyclesAlert {
+                monitor_id: monitor_id.clone(),
+                canister_id: config.canister_id.clone().unwrap_or_else(|| "current".to_string()),
+                alert_type,
+                current_cycles,
+                threshold,
+                timestamp: now,
+                notification_sent: false,
+            };
+            let mut alerts = Vec::new();
+            if current_cycles <= config.critical_threshold {
+                alerts.push(alert(AlertType::Critical, config.critical_threshold));
+                if config.auto_topup {
+                    alerts.push(alert(AlertType::TopupRequested, config.topup_amount));
+                }
+            } else {
+                alerts.push(alert(AlertType::Warning, config.warning_threshold));
+            }
+
+            for alert in alerts {
+                self.alert_history.push(alert.clone());
+                triggered.push((alert, config.owner.clone()));
+            }
+        }
+
+        self.last_check_time = now;
+        triggered
+    }
+
     async fn get_cycles_data(&self, config: &CyclesMonitorConfig) -> Result<CyclesData, String> {
         let current_cycles = if config.canister_id.is_some() {
             // For external canisters, we'd need to make an inter-canister call
@@ -439,4 +489,62 @@ Benefits for ICP Developers:
 ✅ Multi-channel alerting system
         "#)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(owner: &str, auto_topup: bool) -> CyclesMonitorConfig {
+        CyclesMonitorConfig {
+            canister_id: None,
+            warning_threshold: 10_000_000_000_000,
+            critical_threshold: 1_000_000_000_000,
+            auto_topup,
+            topup_amount: 5_000_000_000_000,
+            notification_channels: Vec::new(),
+            owner: owner.to_string(),
+            created_at: 0,
+            last_alert_sent: None,
+        }
+    }
+
+    #[test]
+    fn periodic_check_raises_cycles_events_once_per_interval() {
+        let mut service = CyclesMonitorService {
+            monitored_canisters: HashMap::from([
+                ("a".to_string(), monitor("alice", true)),
+                ("b".to_string(), monitor("bob", false)),
+            ]),
+            alert_history: Vec::new(),
+            last_check_time: 0,
+        };
+
+        assert!(service.check_all(20_000_000_000_000, 1).is_empty());
+
+        let alerts = service.check_all(500_000_000_000, 2);
+        let kinds: Vec<(&str, &str)> = alerts.iter()
+            .map(|(alert, owner)| {
+                let (event_type, _) = crate::system_events::cycles_alert(alert, owner).unwrap();
+                (event_type, owner.as_str())
+            })
+            .collect();
+        assert_eq!(kinds, vec![
+            (crate::system_events::CYCLES_LOW, "alice"),
+            (crate::system_events::CYCLES_TOPUP_REQUESTED, "alice"),
+            (crate::system_events::CYCLES_LOW, "bob"),
+        ]);
+
+        // Still low: quiet until the re-alert interval passes
+        assert!(service.check_all(500_000_000_000, 3).is_empty());
+        assert_eq!(service.check_all(500_000_000_000, 2 + REALERT_INTERVAL_NS).len(), 3);
+
+        // Recovering re-arms the monitors
+        assert!(service.check_all(20_000_000_000_000, 4 + REALERT_INTERVAL_NS).is_empty());
+        assert!(matches!(
+            service.check_all(5_000_000_000_000, 5 + REALERT_INTERVAL_NS)[0].0.alert_type,
+            AlertType::Warning
+        ));
+        assert_eq!(service.get_cycles_statistics().total_alerts, 8);
+    }
+}
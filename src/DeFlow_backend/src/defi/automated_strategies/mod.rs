@@ -401,6 +401,14 @@ impl AutomatedStrategyManager {
                             
                             // Schedule next execution
                             strategy_mut.next_execution = Some(current_time + strategy_mut.config.execution_interval_minutes * 60 * 1_000_000_000);
+
+                            crate::system_events::emit(
+                                crate::system_events::STRATEGY_EXECUTED,
+                                crate::system_events::strategy_executed(strategy_mut, &result),
+                            );
+                            if let Some((event_type, threshold)) = apply_exit_thresholds(strategy_mut) {
+                                crate::system_events::emit(event_type, crate::system_events::strategy_exit(strategy_mut, threshold));
+                            }
                         }
 
                         results.push(result);
@@ -427,6 +435,11 @@ impl AutomatedStrategyManager {
                             
                             strategy_mut.execution_history.push(failed_result.clone());
                             results.push(failed_result);
+
+                            crate::system_events::emit(
+                                crate::system_events::STRATEGY_FAILED,
+                                crate::system_events::strategy_failed(strategy_mut, &e.to_string()),
+                            );
                         }
                     }
                 }
@@ -656,6 +669,20 @@ impl AutomatedStrategyManager {
     }
 }
 
+/// Stop a strategy whose ROI crossed its stop-loss or take-profit.
+/// Returns the system event to raise and the threshold that was hit.
+pub fn apply_exit_thresholds(strategy: &mut ActiveStrategy) -> Option<(&'static str, f64)> {
+    let roi = strategy.performance_metrics.roi_percentage;
+    let exit = match (strategy.config.stop_loss_percentage, strategy.config.take_profit_percentage) {
+        (Some(stop_loss), _) if roi <= -stop_loss.abs() => (crate::system_events::STRATEGY_STOP_LOSS, stop_loss),
+        (_, Some(take_profit)) if roi >= take_profit => (crate::system_events::STRATEGY_TAKE_PROFIT, take_profit),
+        _ => return None,
+    };
+    strategy.status = StrategyStatus::Stopped;
+    strategy.next_execution = None;
+    Some(exit)
+}
+
 /// Strategy analytics summary
 #[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
 pub struct StrategyAnalytics {
@@ -482,4 +482,23 @@ mod tests {
             panic!("Expected error for excessive capital allocation");
         }
     }
+
+    #[test]
+    fn test_exit_thresholds_stop_strategy() {
+        // ROI of 5% sits between the -10% stop-loss and the 25% take-profit
+        let mut strategy = create_test_active_strategy();
+        assert!(crate::defi::automated_strategies::apply_exit_thresholds(&mut strategy).is_none());
+        assert_eq!(strategy.status, StrategyStatus::Active);
+
+        strategy.performance_metrics.roi_percentage = -12.0;
+        let exit = crate::defi::automated_strategies::apply_exit_thresholds(&mut strategy);
+        assert_eq!(exit, Some((crate::system_events::STRATEGY_STOP_LOSS, 10.0)));
+        assert_eq!(strategy.status, StrategyStatus::Stopped);
+        assert!(strategy.next_execution.is_none());
+
+        let mut strategy = create_test_active_strategy();
+        strategy.performance_metrics.roi_percentage = 30.0;
+        let exit = crate::defi::automated_strategies::apply_exit_thresholds(&mut strategy);
+        assert_eq!(exit, Some((crate::system_events::STRATEGY_TAKE_PROFIT, 25.0)));
+    }
 }
\ No newline at end of file
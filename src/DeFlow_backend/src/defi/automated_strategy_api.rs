@@ -3,15 +3,68 @@
 
 use super::automated_strategies::*;
 use super::yield_farming::ChainId;
+use crate::defi_storage::{self, StrategyRecord, StrategyUserRecord};
 use candid::{CandidType, Deserialize};
 use ic_cdk::api;
 use serde::Serialize;
 use std::cell::RefCell;
 use std::collections::HashMap;
 
-// Global strategy manager instance
+// Global strategy manager instance, shared with strategy_api
 thread_local! {
-    static STRATEGY_MANAGER: RefCell<AutomatedStrategyManager> = RefCell::new(AutomatedStrategyManager::new());
+    pub(crate) static STRATEGY_MANAGER: RefCell<AutomatedStrategyManager> = RefCell::new(AutomatedStrategyManager::new());
+}
+
+/// Write a strategy and its risk limits through to stable memory, or drop it once it is gone.
+pub(crate) fn persist_strategy(strategy_id: &str) {
+    STRATEGY_MANAGER.with(|manager| {
+        let manager = manager.borrow();
+        match manager.active_strategies.get(strategy_id) {
+            Some(strategy) => defi_storage::save_strategy(StrategyRecord {
+                strategy: strategy.clone(),
+                risk_limits: manager.risk_manager.strategy_limits.get(strategy_id).cloned(),
+            }),
+            None => defi_storage::remove_strategy(strategy_id),
+        }
+    });
+}
+
+/// Write a user's strategy preferences and risk limits through to stable memory.
+pub(crate) fn persist_strategy_user(user_id: &str) {
+    STRATEGY_MANAGER.with(|manager| {
+        let manager = manager.borrow();
+        defi_storage::save_strategy_user(user_id.to_string(), StrategyUserRecord {
+            preferences: manager.user_preferences.get(user_id).cloned(),
+            risk_limits: manager.risk_manager.user_limits.get(user_id).cloned(),
+        });
+    });
+}
+
+/// Replace the manager's strategies and user settings with the persisted ones.
+pub fn restore_strategies() {
+    STRATEGY_MANAGER.with(|manager| {
+        let mut manager = manager.borrow_mut();
+        manager.active_strategies.clear();
+        manager.risk_manager.strategy_limits.clear();
+        for record in defi_storage::list_strategies() {
+            let id = record.strategy.id.clone();
+            if let Some(limits) = record.risk_limits {
+                manager.risk_manager.strategy_limits.insert(id.clone(), limits);
+            }
+            manager.active_strategies.insert(id, record.strategy);
+        }
+
+        manager.user_preferences.clear();
+        manager.risk_manager.user_limits.clear();
+        for (user_id, record) in defi_storage::list_strategy_users() {
+            if let Some(preferences) = record.preferences {
+                manager.user_preferences.insert(user_id.clone(), preferences);
+            }
+            if let Some(limits) = record.risk_limits {
+                manager.risk_manager.user_limits.insert(user_id, limits);
+            }
+        }
+    });
 }
 
 /// Initialize the automated strategy system
@@ -27,11 +80,14 @@ pub async fn create_automated_strategy(config: StrategyConfig) -> Result<String,
     let caller = api::caller();
     let user_id = caller.to_text();
     
-    STRATEGY_MANAGER.with(|manager| {
+    let strategy_id = STRATEGY_MANAGER.with(|manager| {
         manager.borrow_mut()
-            .create_strategy(user_id, config)
+            .create_strategy(user_id.clone(), config)
             .map_err(|e| format!("Failed to create strategy: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    persist_strategy_user(&user_id);
+    Ok(strategy_id)
 }
 
 /// Create strategy from custom workflow
@@ -55,9 +111,11 @@ pub async fn create_strategy_from_workflow(workflow_definition: WorkflowDefiniti
     // Create the strategy
     let strategy_id = STRATEGY_MANAGER.with(|manager| {
         manager.borrow_mut()
-            .create_strategy(user_id, strategy_config)
+            .create_strategy(user_id.clone(), strategy_config)
             .map_err(|e| format!("Failed to create strategy: {}", e))
     })?;
+    persist_strategy(&strategy_id);
+    persist_strategy_user(&user_id);
     
     Ok(StrategyCreationResponse {
         strategy_id,
@@ -74,17 +132,41 @@ pub async fn activate_strategy(strategy_id: String, capital_amount: f64) -> Resu
         manager.borrow_mut()
             .activate_strategy(&strategy_id, capital_amount)
             .map_err(|e| format!("Failed to activate strategy: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    Ok(())
 }
 
 /// Execute all eligible strategies
 #[ic_cdk::update]
 pub async fn execute_strategies() -> Result<Vec<StrategyExecutionResult>, String> {
-    // Due to async/borrow lifetime issues, we need to structure this differently
-    // In a real implementation, we would use proper async coordination
-    
-    // Return empty result for now to avoid lifetime issues
-    Ok(Vec::new())
+    // Run on a copy so no borrow of the manager is held across awaits
+    let mut manager = STRATEGY_MANAGER.with(|manager| manager.borrow().clone());
+    let started: HashMap<String, u64> = manager.active_strategies.iter()
+        .map(|(id, strategy)| (id.clone(), strategy.last_updated))
+        .collect();
+
+    let results = manager.execute_strategies().await.map_err(|e| e.to_string())?;
+
+    let mut merged = Vec::new();
+    STRATEGY_MANAGER.with(|live| {
+        let mut live = live.borrow_mut();
+        for (id, strategy) in manager.active_strategies {
+            // Strategies changed by another call meanwhile keep that change
+            if let Some(current) = live.active_strategies.get_mut(&id) {
+                if started.get(&id) == Some(&current.last_updated) && current.last_updated != strategy.last_updated {
+                    *current = strategy;
+                    merged.push(id);
+                }
+            }
+        }
+        live.last_execution = manager.last_execution;
+    });
+    for id in &merged {
+        persist_strategy(id);
+    }
+
+    Ok(results)
 }
 
 /// Get user's active strategies
@@ -119,7 +201,9 @@ pub async fn pause_strategy(strategy_id: String) -> Result<(), String> {
         manager.borrow_mut()
             .pause_strategy(&strategy_id)
             .map_err(|e| format!("Failed to pause strategy: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    Ok(())
 }
 
 /// Resume a paused strategy
@@ -129,7 +213,9 @@ pub async fn resume_strategy(strategy_id: String) -> Result<(), String> {
         manager.borrow_mut()
             .resume_strategy(&strategy_id)
             .map_err(|e| format!("Failed to resume strategy: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    Ok(())
 }
 
 /// Stop a strategy permanently
@@ -139,7 +225,9 @@ pub async fn stop_strategy(strategy_id: String) -> Result<(), String> {
         manager.borrow_mut()
             .stop_strategy(&strategy_id)
             .map_err(|e| format!("Failed to stop strategy: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    Ok(())
 }
 
 /// Update strategy configuration
@@ -149,7 +237,9 @@ pub async fn update_strategy_config(strategy_id: String, new_config: StrategyCon
         manager.borrow_mut()
             .update_strategy_config(&strategy_id, new_config)
             .map_err(|e| format!("Failed to update strategy config: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    Ok(())
 }
 
 /// Get comprehensive strategy analytics for user
@@ -190,15 +280,18 @@ pub async fn create_strategy_from_template(template_id: String, customization: T
     let caller = api::caller();
     let user_id = caller.to_text();
     
-    STRATEGY_MANAGER.with(|manager| {
+    let strategy_id = STRATEGY_MANAGER.with(|manager| {
         let mut manager_ref = manager.borrow_mut();
         let config = manager_ref.strategy_registry
             .create_config_from_template(&template_id, customization)
             .map_err(|e| format!("Failed to create config from template: {}", e))?;
         
-        manager_ref.create_strategy(user_id, config)
+        manager_ref.create_strategy(user_id.clone(), config)
             .map_err(|e| format!("Failed to create strategy from template: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    persist_strategy_user(&user_id);
+    Ok(strategy_id)
 }
 
 /// Get strategy recommendations for user
@@ -321,9 +414,11 @@ pub async fn set_user_risk_limits(limits: UserRiskLimits) -> Result<(), String>
     STRATEGY_MANAGER.with(|manager| {
         manager.borrow_mut()
             .risk_manager
-            .set_user_risk_limits(user_id, limits)
+            .set_user_risk_limits(user_id.clone(), limits)
             .map_err(|e| format!("Failed to set risk limits: {}", e))
-    })
+    })?;
+    persist_strategy_user(&user_id);
+    Ok(())
 }
 
 /// Set strategy-specific risk limits
@@ -332,9 +427,11 @@ pub async fn set_strategy_risk_limits(strategy_id: String, limits: StrategyRiskL
     STRATEGY_MANAGER.with(|manager| {
         manager.borrow_mut()
             .risk_manager
-            .set_strategy_risk_limits(strategy_id, limits)
+            .set_strategy_risk_limits(strategy_id.clone(), limits)
             .map_err(|e| format!("Failed to set strategy risk limits: {}", e))
-    })
+    })?;
+    persist_strategy(&strategy_id);
+    Ok(())
 }
 
 /// Trigger emergency stop for strategy
@@ -581,12 +678,18 @@ pub fn get_strategy_details(strategy_id: String) -> Result<StrategyDetails, Stri
 /// Manual strategy coordination trigger (for admin use)
 #[ic_cdk::update]
 pub async fn trigger_strategy_coordination() -> Result<CoordinationResult, String> {
-    STRATEGY_MANAGER.with(|manager| {
-        manager.borrow_mut()
-            .coordination_engine
-            .coordinate_strategies(&mut manager.borrow_mut().active_strategies)
+    let result = STRATEGY_MANAGER.with(|manager| {
+        let mut manager = manager.borrow_mut();
+        let manager = &mut *manager;
+        manager.coordination_engine
+            .coordinate_strategies(&mut manager.active_strategies)
             .map_err(|e| format!("Failed to coordinate strategies: {}", e))
-    })
+    })?;
+    let strategy_ids: Vec<String> = STRATEGY_MANAGER.with(|manager| manager.borrow().active_strategies.keys().cloned().collect());
+    for strategy_id in &strategy_ids {
+        persist_strategy(strategy_id);
+    }
+    Ok(result)
 }
 
 /// Update market data (for admin use)
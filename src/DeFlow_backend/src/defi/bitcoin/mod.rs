@@ -12,7 +12,7 @@ use serde::Serialize;
 use ic_cdk::api::management_canister::bitcoin::{
     bitcoin_get_balance, bitcoin_get_utxos, bitcoin_send_transaction,
     BitcoinNetwork as ICPBitcoinNetwork, GetBalanceRequest, GetUtxosRequest, 
-    SendTransactionRequest, Utxo
+    SendTransactionRequest, Utxo, UtxoFilter
 };
 use ic_cdk::api::management_canister::ecdsa::{
     ecdsa_public_key, sign_with_ecdsa, EcdsaKeyId, EcdsaPublicKeyArgument, SignWithEcdsaArgument
@@ -132,6 +132,31 @@ pub async fn get_bitcoin_utxos(network: ICPBitcoinNetwork, address: String) -> R
     }
 }
 
+/// Every UTXO of an address, following pagination, together with the current tip height
+pub async fn get_bitcoin_utxo_set(network: ICPBitcoinNetwork, address: String) -> Result<(Vec<Utxo>, u32), String> {
+    // Bound the paging so a dust-spammed address cannot stall the caller
+    const MAX_PAGES: usize = 10;
+
+    let mut utxos = Vec::new();
+    let mut filter = None;
+    for _ in 0..MAX_PAGES {
+        let request = GetUtxosRequest {
+            address: address.clone(),
+            network,
+            filter,
+        };
+        let (response,) = bitcoin_get_utxos(request)
+            .await
+            .map_err(|(code, msg)| format!("Bitcoin UTXOs error {}: {}", code as u8, msg))?;
+        utxos.extend(response.utxos);
+        match response.next_page {
+            Some(page) => filter = Some(UtxoFilter::Page(page)),
+            None => return Ok((utxos, response.tip_height)),
+        }
+    }
+    Err(format!("Address {} has more UTXOs than can be paged in one call", address))
+}
+
 #[allow(dead_code)]
 pub async fn send_bitcoin_transaction(network: ICPBitcoinNetwork, transaction: Vec<u8>) -> Result<String, String> {
     let request = SendTransactionRequest {
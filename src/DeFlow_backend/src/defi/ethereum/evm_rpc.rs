@@ -4,7 +4,10 @@
 use candid::{CandidType, Deserialize, Principal};
 use serde::Serialize;
 use ic_cdk::api::call::call_raw;
+use ic_cdk::api::management_canister::http_request::{HttpHeader, HttpMethod};
 use super::{EvmChain, EthereumError};
+use crate::http_client::{outcall_cycles, HttpClient, HttpRequest};
+use crate::http_transforms::TransformStep;
 
 /// Official EVM RPC canister principal
 pub const EVM_RPC_CANISTER_ID: &str = "7hfb6-caaaa-aaaar-qadga-cai";
@@ -176,6 +179,151 @@ impl EvmRpcService {
         }
     }
 
+    /// Get the latest block number
+    pub async fn eth_block_number(&self, chain: &EvmChain) -> Result<u64, EthereumError> {
+        let result = self.json_rpc_request(chain, "eth_blockNumber", serde_json::json!([]), 1024).await?;
+        result.as_str()
+            .and_then(parse_hex_quantity)
+            .ok_or_else(|| EthereumError::SerializationError("Invalid block number format".to_string()))
+    }
+
+    /// Get logs matching an address and topic filter over an inclusive block range
+    pub async fn eth_get_logs(
+        &self,
+        chain: &EvmChain,
+        addresses: &[String],
+        topics: &[Vec<String>],
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<EvmLog>, EthereumError> {
+        // An empty alternatives list is a wildcard (null) for that topic position
+        let topics: Vec<serde_json::Value> = topics.iter()
+            .map(|alternatives| match alternatives.len() {
+                0 => serde_json::Value::Null,
+                1 => serde_json::json!(alternatives[0]),
+                _ => serde_json::json!(alternatives),
+            })
+            .collect();
+        let filter = serde_json::json!([{
+            "address": addresses,
+            "topics": topics,
+            "fromBlock": format!("0x{:x}", from_block),
+            "toBlock": format!("0x{:x}", to_block),
+        }]);
+
+        let result = self.json_rpc_request(chain, "eth_getLogs", filter, 512 * 1024).await?;
+        serde_json::from_value(result)
+            .map_err(|e| EthereumError::SerializationError(format!("Logs parse error: {}", e)))
+    }
+
+    /// Send a raw JSON-RPC request through the canister's `request` endpoint, or straight to
+    /// the chain's endpoint when it is a custom one
+    pub async fn json_rpc_request(
+        &self,
+        chain: &EvmChain,
+        method: &str,
+        params: serde_json::Value,
+        max_response_bytes: u64,
+    ) -> Result<serde_json::Value, EthereumError> {
+        let payload = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }).to_string();
+        let service = self.json_rpc_service(chain);
+        let body = match service {
+            JsonRpcService::Custom(api) => self.json_rpc_outcall(&api, payload, max_response_bytes).await?,
+            service => self.json_rpc_canister_request(service, method, payload, max_response_bytes).await?,
+        };
+
+        let mut response: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|e| EthereumError::SerializationError(format!("Invalid JSON-RPC response: {}", e)))?;
+        if let Some(error) = response.get("error").filter(|error| !error.is_null()) {
+            return Err(EthereumError::RpcError(error.to_string()));
+        }
+        response.get_mut("result")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| EthereumError::RpcError("No result in JSON-RPC response".to_string()))
+    }
+
+    async fn json_rpc_canister_request(
+        &self,
+        service: JsonRpcService,
+        method: &str,
+        payload: String,
+        max_response_bytes: u64,
+    ) -> Result<String, EthereumError> {
+        let cycles = self.calculate_cycles_for_call(method) as u128;
+
+        let call_result: Result<(JsonRpcRequestResult,), _> = ic_cdk::api::call::call_with_payment128(
+            self.canister_id,
+            "request",
+            (service, payload, max_response_bytes),
+            cycles,
+        ).await;
+
+        match call_result {
+            Ok((JsonRpcRequestResult::Ok(body),)) => Ok(body),
+            Ok((JsonRpcRequestResult::Err(error),)) => Err(EthereumError::RpcError(error.to_string())),
+            Err((code, msg)) => {
+                Err(EthereumError::NetworkError(format!("EVM RPC call failed: {} - {}", code as u8, msg)))
+            }
+        }
+    }
+
+    /// Call a custom endpoint with a direct outcall, keeping only `result` and `error` so the
+    /// per-request `id` and any provider extras cannot break consensus
+    async fn json_rpc_outcall(
+        &self,
+        api: &JsonRpcApi,
+        payload: String,
+        max_response_bytes: u64,
+    ) -> Result<String, EthereumError> {
+        let mut headers = vec![HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string() }];
+        headers.extend(api.headers.iter().flatten().map(|header| HttpHeader {
+            name: header.name.clone(),
+            value: header.value.clone(),
+        }));
+        let cycles = outcall_cycles(payload.len() as u64, max_response_bytes);
+
+        let response = HttpClient::request(HttpRequest {
+            url: api.url.clone(),
+            method: HttpMethod::POST,
+            headers,
+            body: Some(payload.into_bytes()),
+            max_response_bytes: Some(max_response_bytes),
+            cycles: Some(cycles),
+            transform: vec![
+                TransformStep::strip_headers(),
+                TransformStep::json_extract(&[("result", "$.result"), ("error", "$.error")]),
+            ],
+        }).await.map_err(EthereumError::NetworkError)?;
+
+        if !(200..300).contains(&response.status) {
+            return Err(EthereumError::NetworkError(format!("{} returned status {}: {}", api.url, response.status, response.body)));
+        }
+        Ok(response.body)
+    }
+
+    /// Provider used for raw JSON-RPC requests on a chain
+    fn json_rpc_service(&self, chain: &EvmChain) -> JsonRpcService {
+        match chain {
+            EvmChain::Ethereum => JsonRpcService::EthMainnet(JsonRpcProvider::PublicNode),
+            EvmChain::Arbitrum => JsonRpcService::ArbitrumOne(JsonRpcProvider::PublicNode),
+            EvmChain::Optimism => JsonRpcService::OptimismMainnet(JsonRpcProvider::PublicNode),
+            EvmChain::Base => JsonRpcService::BaseMainnet(JsonRpcProvider::PublicNode),
+            EvmChain::Polygon => JsonRpcService::Custom(JsonRpcApi {
+                url: "https://polygon-rpc.com".to_string(),
+                headers: None,
+            }),
+            EvmChain::Avalanche => JsonRpcService::Custom(JsonRpcApi {
+                url: "https://api.avax.network/ext/bc/C/rpc".to_string(),
+                headers: None,
+            }),
+        }
+    }
+
     /// Make RPC call to the EVM RPC canister
     async fn make_rpc_call<T: CandidType>(
         &self,
@@ -194,7 +342,7 @@ impl EvmRpcService {
 
         let call_result = call_raw(
             self.canister_id,
-            // Logging temporarily disabled
+            method,
             &candid::encode_one(&rpc_request)
                 .map_err(|e| EthereumError::SerializationError(format!("Request encoding error: {}", e)))?,
             cycles,
@@ -259,6 +407,7 @@ impl EvmRpcService {
             "eth_feeHistory" => 2_000_000_000, // 2B cycles);
             "eth_estimateGas" => 1_500_000_000, // 1.5B cycles);
             "eth_sendRawTransaction" => 3_000_000_000, // 3B cycles);
+            "eth_getLogs" => 3_000_000_000,
             _ => 1_000_000_000, // Default 1B cycles
         }
     }
@@ -447,6 +596,92 @@ pub struct EthEstimateGasRequest {
     pub data: Option<String>,
 }
 
+// Types of the canister's raw `request` endpoint
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum JsonRpcService {
+    EthMainnet(JsonRpcProvider),
+    ArbitrumOne(JsonRpcProvider),
+    BaseMainnet(JsonRpcProvider),
+    OptimismMainnet(JsonRpcProvider),
+    Custom(JsonRpcApi),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum JsonRpcProvider {
+    Alchemy,
+    Ankr,
+    BlockPi,
+    PublicNode,
+    Llama,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct JsonRpcApi {
+    pub url: String,
+    pub headers: Option<Vec<JsonRpcHeader>>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct JsonRpcHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum JsonRpcRequestResult {
+    Ok(String),
+    Err(JsonRpcRequestError),
+}
+
+// Only JSON-RPC errors carry a message worth decoding; the rest report their kind.
+// Variant names follow the canister's candid interface.
+#[allow(clippy::enum_variant_names)]
+#[derive(CandidType, Deserialize, Clone, Debug)]
+enum JsonRpcRequestError {
+    JsonRpcError(RpcErrorDetail),
+    ProviderError(candid::Reserved),
+    ValidationError(candid::Reserved),
+    HttpOutcallError(candid::Reserved),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+struct RpcErrorDetail {
+    code: i64,
+    message: String,
+}
+
+impl std::fmt::Display for JsonRpcRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JsonRpcRequestError::JsonRpcError(detail) => write!(f, "JSON-RPC error {}: {}", detail.code, detail.message),
+            JsonRpcRequestError::ProviderError(_) => write!(f, "EVM RPC provider error"),
+            JsonRpcRequestError::ValidationError(_) => write!(f, "EVM RPC validation error"),
+            JsonRpcRequestError::HttpOutcallError(_) => write!(f, "EVM RPC HTTP outcall error"),
+        }
+    }
+}
+
+/// Log entry as returned by `eth_getLogs`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EvmLog {
+    pub address: String,
+    pub topics: Vec<String>,
+    pub data: String,
+    pub block_number: Option<String>,
+    pub block_hash: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub log_index: Option<String>,
+    #[serde(default)]
+    pub removed: bool,
+}
+
+/// Parse a JSON-RPC hex quantity such as `0x1b4`
+pub fn parse_hex_quantity(value: &str) -> Option<u64> {
+    u64::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct EthSendRawTransactionRequest {
     pub raw_transaction_hex: String,
@@ -495,4 +730,21 @@ mod tests {
         assert!(ethereum_providers.contains(&RpcProvider::Alchemy));
         assert!(ethereum_providers.contains(&RpcProvider::Cloudflare));
     }
+
+    #[test]
+    fn test_custom_endpoints_use_transformed_outcalls() {
+        use crate::http_client::mock;
+        let service = EvmRpcService::new();
+
+        mock::respond_once("https://polygon-rpc.com", 200, r#"{"jsonrpc":"2.0","id":1,"result":"0x3e8"}"#);
+        let head = futures::executor::block_on(service.eth_block_number(&EvmChain::Polygon)).unwrap();
+        assert_eq!(head, 1000);
+        let request = &mock::requests()[0];
+        assert_eq!(request.transform[1], TransformStep::json_extract(&[("result", "$.result"), ("error", "$.error")]));
+        assert_eq!(request.max_response_bytes, Some(1024));
+
+        mock::respond_once("https://polygon-rpc.com", 200, r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32005,"message":"limit exceeded"}}"#);
+        let error = futures::executor::block_on(service.eth_block_number(&EvmChain::Polygon)).unwrap_err();
+        assert!(matches!(error, EthereumError::RpcError(message) if message.contains("limit exceeded")));
+    }
 }
\ No newline at end of file
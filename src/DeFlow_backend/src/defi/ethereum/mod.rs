@@ -14,7 +14,8 @@ pub mod tests;
 // pub mod l2_optimizer;
 // pub mod gas_estimator;
 // pub mod chain_config;
-// pub mod evm_rpc;
+#[allow(dead_code)] // Only the block number and log queries are used so far
+pub mod evm_rpc;
 // pub mod threshold_ecdsa;
 // pub mod icp_gas_estimator;
 // pub mod icp_service;
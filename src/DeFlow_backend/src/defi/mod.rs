@@ -186,6 +186,13 @@ pub fn with_defi_manager_mut<R>(f: impl FnOnce(&mut DeFiChainManager) -> R) -> R
     DEFI_CHAIN_MANAGER.with(|manager| f(&mut manager.borrow_mut()))
 }
 
+/// Rebuild the heap strategy, portfolio and price alert managers from stable memory.
+pub fn restore_persisted_state() {
+    automated_strategy_api::restore_strategies();
+    portfolio_api::restore_portfolios();
+    price_alert_service::restore_price_alerts();
+}
+
 // Initialize DeFi system
 pub async fn initialize_defi_system() -> Result<(), String> {
     
@@ -3,6 +3,7 @@
 
 use super::portfolio_manager::*;
 use super::yield_farming::ChainId;
+use crate::defi_storage::{self, PortfolioRecord};
 use candid::{CandidType, Principal};
 use ic_cdk::api::caller;
 use ic_cdk::{init, query, update};
@@ -11,7 +12,7 @@ use std::collections::HashMap;
 
 // Global portfolio manager state
 thread_local! {
-    static PORTFOLIO_MANAGER: std::cell::RefCell<AdvancedPortfolioManager> = 
+    pub(crate) static PORTFOLIO_MANAGER: std::cell::RefCell<AdvancedPortfolioManager> = 
         std::cell::RefCell::new(AdvancedPortfolioManager::new());
 }
 
@@ -22,6 +23,44 @@ pub fn init_portfolio_system() {
     });
 }
 
+/// Write a user's portfolio and settings through to stable memory.
+pub(crate) fn persist_portfolio(user_id: &str) {
+    PORTFOLIO_MANAGER.with(|pm| {
+        let manager = pm.borrow();
+        defi_storage::save_portfolio(user_id.to_string(), PortfolioRecord {
+            portfolio: manager.portfolios.get(user_id).cloned(),
+            auto_compound: manager.auto_compound_settings.get(user_id).cloned(),
+            notification_preferences: manager.notification_system.user_preferences.get(user_id).cloned(),
+            webhook_endpoint: manager.notification_system.webhook_endpoints.get(user_id).cloned(),
+        });
+    });
+}
+
+/// Replace the manager's portfolios and settings with the persisted ones.
+pub fn restore_portfolios() {
+    PORTFOLIO_MANAGER.with(|pm| {
+        let mut manager = pm.borrow_mut();
+        manager.portfolios.clear();
+        manager.auto_compound_settings.clear();
+        manager.notification_system.user_preferences.clear();
+        manager.notification_system.webhook_endpoints.clear();
+        for (user_id, record) in defi_storage::list_portfolios() {
+            if let Some(portfolio) = record.portfolio {
+                manager.portfolios.insert(user_id.clone(), portfolio);
+            }
+            if let Some(settings) = record.auto_compound {
+                manager.auto_compound_settings.insert(user_id.clone(), settings);
+            }
+            if let Some(preferences) = record.notification_preferences {
+                manager.notification_system.user_preferences.insert(user_id.clone(), preferences);
+            }
+            if let Some(endpoint) = record.webhook_endpoint {
+                manager.notification_system.webhook_endpoints.insert(user_id, endpoint);
+            }
+        }
+    });
+}
+
 /// Create a new portfolio for the user
 #[update]
 fn create_portfolio(config: PortfolioConfiguration) -> Result<String, String> {
@@ -30,9 +69,10 @@ fn create_portfolio(config: PortfolioConfiguration) -> Result<String, String> {
     PORTFOLIO_MANAGER.with(|pm| {
         pm.borrow_mut()
             .create_portfolio(user_id.clone(), config)
-            .map(|_| format!("Portfolio created successfully for user {}", user_id))
             .map_err(|e| e.to_string())
-    })
+    })?;
+    persist_portfolio(&user_id);
+    Ok(format!("Portfolio created successfully for user {}", user_id))
 }
 
 /// Add a new position to user's portfolio
@@ -43,9 +83,10 @@ fn add_position(position: Position) -> Result<String, String> {
     PORTFOLIO_MANAGER.with(|pm| {
         pm.borrow_mut()
             .add_position(&user_id, position)
-            .map(|_| "Position added successfully".to_string())
             .map_err(|e| e.to_string())
-    })
+    })?;
+    persist_portfolio(&user_id);
+    Ok("Position added successfully".to_string())
 }
 
 /// Remove a position from user's portfolio
@@ -53,11 +94,13 @@ fn add_position(position: Position) -> Result<String, String> {
 fn remove_position(position_id: String) -> Result<Position, String> {
     let user_id = caller().to_string();
     
-    PORTFOLIO_MANAGER.with(|pm| {
+    let position = PORTFOLIO_MANAGER.with(|pm| {
         pm.borrow_mut()
             .remove_position(&user_id, &position_id)
             .map_err(|e| e.to_string())
-    })
+    })?;
+    persist_portfolio(&user_id);
+    Ok(position)
 }
 
 /// Update an existing position
@@ -68,9 +111,10 @@ fn update_position(position_id: String, update: PositionUpdate) -> Result<String
     PORTFOLIO_MANAGER.with(|pm| {
         pm.borrow_mut()
             .update_position(&user_id, &position_id, update)
-            .map(|_| "Position updated successfully".to_string())
             .map_err(|e| e.to_string())
-    })
+    })?;
+    persist_portfolio(&user_id);
+    Ok("Position updated successfully".to_string())
 }
 
 /// Get comprehensive portfolio summary
@@ -153,10 +197,11 @@ fn setup_auto_compound(settings: AutoCompoundSettings) -> Result<String, String>
     
     PORTFOLIO_MANAGER.with(|pm| {
         pm.borrow_mut()
-            .setup_auto_compound(user_id, settings)
-            .map(|_| "Auto-compound settings configured successfully".to_string())
+            .setup_auto_compound(user_id.clone(), settings)
             .map_err(|e| e.to_string())
-    })
+    })?;
+    persist_portfolio(&user_id);
+    Ok("Auto-compound settings configured successfully".to_string())
 }
 
 /// Process automatic compounding (admin function)
@@ -193,9 +238,10 @@ fn set_notification_preferences(preferences: NotificationPreferences) -> Result<
     let user_id = caller().to_string();
     
     PORTFOLIO_MANAGER.with(|pm| {
-        pm.borrow_mut().notification_system.set_user_preferences(user_id, preferences);
-        Ok("Notification preferences updated successfully".to_string())
-    })
+        pm.borrow_mut().notification_system.set_user_preferences(user_id.clone(), preferences);
+    });
+    persist_portfolio(&user_id);
+    Ok("Notification preferences updated successfully".to_string())
 }
 
 /// Get user notifications
@@ -240,9 +286,10 @@ fn add_webhook_endpoint(endpoint: WebhookEndpoint) -> Result<String, String> {
     let user_id = caller().to_string();
     
     PORTFOLIO_MANAGER.with(|pm| {
-        pm.borrow_mut().notification_system.add_webhook_endpoint(user_id, endpoint);
-        Ok("Webhook endpoint added successfully".to_string())
-    })
+        pm.borrow_mut().notification_system.add_webhook_endpoint(user_id.clone(), endpoint);
+    });
+    persist_portfolio(&user_id);
+    Ok("Webhook endpoint added successfully".to_string())
 }
 
 /// Get risk assessment for portfolio
@@ -12,6 +12,7 @@ use ic_cdk::api::management_canister::http_request::{
 use super::real_protocol_integrations::{RealProtocolIntegrationManager};
 use super::price_alert_defi_integration::{execute_defi_action_from_alert, DeFiExecutionResult};
 use super::social_media_formatter::{format_social_post_with_defi, SocialPostData};
+use crate::defi_storage;
 
 #[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
 pub struct PriceAlertManager {
@@ -126,7 +127,7 @@ pub struct ActionResult {
 
 // Global state for price alert management
 thread_local! {
-    static PRICE_ALERT_MANAGER: RefCell<PriceAlertManager> = RefCell::new(PriceAlertManager::new());
+    pub(crate) static PRICE_ALERT_MANAGER: RefCell<PriceAlertManager> = RefCell::new(PriceAlertManager::new());
     static PROTOCOL_INTEGRATION: RefCell<Option<RealProtocolIntegrationManager>> = RefCell::new(None);
 }
 
@@ -231,6 +232,14 @@ impl PriceAlertManager {
             }
         }
 
+        // Announce the alert with its updated trigger count
+        if let Some(updated) = self.alerts.get(&alert.id) {
+            crate::system_events::emit(
+                crate::system_events::PRICE_ALERT_TRIGGERED,
+                crate::system_events::price_alert_triggered(updated, current_price, &self.format_condition(&alert.condition)),
+            );
+        }
+
         let trigger_event = AlertTriggerEvent {
             alert_id: alert.id.clone(),
             token_symbol: alert.token_symbol.clone(),
@@ -570,6 +579,17 @@ impl PriceAlertManager {
         Ok(())
     }
 
+    fn condition_met(&self, condition: &PriceCondition, current_price: &TokenPrice) -> bool {
+        match condition {
+            PriceCondition::Above(threshold) => current_price.price_usd > *threshold,
+            PriceCondition::Below(threshold) => current_price.price_usd < *threshold,
+            PriceCondition::PercentChange { base_price, change_percent, .. } => {
+                *base_price > 0.0
+                    && ((current_price.price_usd - base_price) / base_price * 100.0).abs() >= change_percent.abs()
+            }
+        }
+    }
+
     fn format_condition(&self, condition: &PriceCondition) -> String {
         match condition {
             PriceCondition::Above(price) => format!("above ${:.4}", price),
@@ -621,7 +641,42 @@ impl PriceAlertManager {
 
 // Global functions for canister interface
 pub async fn check_all_price_alerts() -> Result<Vec<AlertTriggerEvent>, String> {
-    let triggered_events = Vec::new();
+    let now = ic_cdk::api::time();
+    // Work on a copy so no borrow of the manager is held across HTTPS outcalls
+    let mut manager = PRICE_ALERT_MANAGER.with(|manager| manager.borrow().clone());
+    let due: Vec<PriceAlert> = manager.alerts.values()
+        .filter(|alert| alert.is_active && alert.expires_at.is_none_or(|expires_at| expires_at > now))
+        .cloned()
+        .collect();
+
+    let mut triggered_events = Vec::new();
+    for alert in due {
+        let Ok(price) = manager.get_current_price(&alert.token_symbol).await else {
+            continue;
+        };
+        if manager.condition_met(&alert.condition, &price) {
+            if let Ok(event) = manager.trigger_alert(&alert, &price).await {
+                triggered_events.push(event);
+            }
+        }
+    }
+
+    // Alerts created or removed during the check are left as they are
+    PRICE_ALERT_MANAGER.with(|live| {
+        let mut live = live.borrow_mut();
+        for (id, checked) in manager.alerts {
+            if let Some(alert) = live.alerts.get_mut(&id) {
+                if alert.triggered_count != checked.triggered_count || (alert.is_active && !checked.is_active) {
+                    alert.triggered_count = checked.triggered_count;
+                    alert.is_active = alert.is_active && checked.is_active;
+                    defi_storage::save_price_alert(alert.clone());
+                }
+            }
+        }
+        live.price_cache.extend(manager.price_cache);
+        live.last_check = now;
+    });
+
     Ok(triggered_events)
 }
 
@@ -635,10 +690,25 @@ pub fn init_price_alert_system() {
 /// Create a new price alert
 pub fn create_price_alert(alert: PriceAlert) -> Result<String, String> {
     PRICE_ALERT_MANAGER.with(|manager| {
-        manager.borrow_mut().create_alert(alert)
+        let mut manager = manager.borrow_mut();
+        let alert_id = manager.create_alert(alert)?;
+        if let Some(created) = manager.alerts.get(&alert_id) {
+            defi_storage::save_price_alert(created.clone());
+        }
+        Ok(alert_id)
     })
 }
 
+/// Replace the manager's alerts with the persisted ones.
+pub fn restore_price_alerts() {
+    PRICE_ALERT_MANAGER.with(|manager| {
+        let mut manager = manager.borrow_mut();
+        manager.alerts = defi_storage::list_price_alerts().into_iter()
+            .map(|alert| (alert.id.clone(), alert))
+            .collect();
+    });
+}
+
 /// Get user's price alerts
 pub fn get_user_price_alerts(user_id: &str) -> Vec<PriceAlert> {
     PRICE_ALERT_MANAGER.with(|manager| {
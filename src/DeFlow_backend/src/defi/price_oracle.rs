@@ -6,6 +6,11 @@ use candid::{CandidType, Deserialize};
 use serde::Serialize;
 use std::collections::HashMap;
 use ic_cdk::api::time;
+use ic_cdk::api::management_canister::http_request::{HttpHeader, HttpMethod};
+use crate::http_client::{outcall_cycles, parse_json_response, HttpClient, HttpRequest};
+use crate::http_transforms::TransformStep;
+
+const QUOTE_MAX_RESPONSE_BYTES: u64 = 8 * 1024;
 
 /// Cross-chain price oracle system for real-time price discovery
 #[derive(Debug, Clone)]
@@ -536,12 +541,38 @@ impl CoinGeckoOracle {
     }
 
     pub async fn get_price(&self, asset: &Asset) -> Result<Price, OracleError> {
-        if !self.supported_tokens.contains_key(&asset.symbol) {
-            return Err(OracleError::UnsupportedAsset(asset.symbol.clone()));
-        }
+        let id = self.supported_tokens.get(&asset.symbol)
+            .ok_or_else(|| OracleError::UnsupportedAsset(asset.symbol.clone()))?;
+
+        let url = format!(
+            "{}/simple/price?ids={}&vs_currencies=usd&include_market_cap=true&include_24hr_vol=true&include_24hr_change=true",
+            self.api_endpoint, id
+        );
+        // Keep only the quoted fields, rounded so replicas that straddle a tick still agree
+        let coin = format!("$['{}']", id);
+        let fields = [
+            ("price", format!("{}.usd", coin)),
+            ("change_24h", format!("{}.usd_24h_change", coin)),
+            ("volume_24h", format!("{}.usd_24h_vol", coin)),
+            ("market_cap", format!("{}.usd_market_cap", coin)),
+        ];
+        let fields: Vec<(&str, &str)> = fields.iter().map(|(name, path)| (*name, path.as_str())).collect();
+        let quote = fetch_quote(url, vec![
+            TransformStep::strip_headers(),
+            TransformStep::json_extract(&fields),
+            TransformStep::normalize_numbers(6, false),
+        ]).await?;
 
-        // In production, this would make HTTP calls to CoinGecko API
-        self.get_mock_coingecko_price(asset).await
+        Ok(Price {
+            asset: asset.clone(),
+            price_usd: quote_number(&quote, "price")?,
+            change_24h_percentage: quote_number(&quote, "change_24h").unwrap_or(0.0),
+            volume_24h_usd: quote_number(&quote, "volume_24h").unwrap_or(0.0),
+            market_cap_usd: quote_number(&quote, "market_cap").ok(),
+            last_updated: time(),
+            source: OracleProvider::CoinGecko,
+            confidence_score: 88.3,
+        })
     }
 
     pub async fn get_historical_prices(&self, asset: &Asset, timeframe: TimeFrame) -> Result<Vec<HistoricalPrice>, OracleError> {
@@ -585,33 +616,6 @@ impl CoinGeckoOracle {
         Ok(historical_prices)
     }
 
-    async fn get_mock_coingecko_price(&self, asset: &Asset) -> Result<Price, OracleError> {
-        let current_time = time();
-        
-        let (price_usd, change_24h, volume_24h, market_cap) = match asset.symbol.as_str() {
-            "BTC" => (43_275.50, 1.8, 22_300_000_000.0, Some(850_500_000_000.0)),
-            "ETH" => (2246.75, 2.0, 12_400_000_000.0, Some(269_800_000_000.0)),
-            "SOL" => (104.25, 4.1, 1_750_000_000.0, Some(45_300_000_000.0)),
-            "MATIC" => (0.885, -0.8, 440_000_000.0, Some(8_850_000_000.0)),
-            "AVAX" => (38.15, 3.5, 375_000_000.0, Some(14_000_000_000.0)),
-            "USDC" => (1.0001, 0.001, 6_500_000_000.0, Some(32_500_000_000.0)),
-            "USDT" => (0.9999, -0.001, 25_000_000_000.0, Some(95_000_000_000.0)),
-            "DAI" => (1.0002, 0.002, 180_000_000.0, Some(5_300_000_000.0)),
-            _ => return Err(OracleError::UnsupportedAsset(asset.symbol.clone())),
-        };
-
-        Ok(Price {
-            asset: asset.clone(),
-            price_usd,
-            change_24h_percentage: change_24h,
-            volume_24h_usd: volume_24h,
-            market_cap_usd: market_cap,
-            last_updated: current_time,
-            source: OracleProvider::CoinGecko,
-            confidence_score: 88.3,
-        })
-    }
-
     pub fn get_health_status(&self) -> OracleHealthStatus {
         OracleHealthStatus {
             is_healthy: true,
@@ -656,29 +660,21 @@ impl BinanceOracle {
             return Err(OracleError::UnsupportedAsset(asset.symbol.clone()));
         }
 
-        // In production, this would call Binance API
-        self.get_mock_binance_price(asset).await
-    }
-
-    async fn get_mock_binance_price(&self, asset: &Asset) -> Result<Price, OracleError> {
-        let current_time = time();
-        
-        let (price_usd, change_24h, volume_24h) = match asset.symbol.as_str() {
-            "BTC" => (43_280.75, 1.7, 850_000_000.0),
-            "ETH" => (2247.25, 2.2, 420_000_000.0),
-            "SOL" => (104.55, 4.0, 85_000_000.0),
-            "MATIC" => (0.887, -1.0, 25_000_000.0),
-            "AVAX" => (38.25, 3.6, 18_000_000.0),
-            _ => return Err(OracleError::UnsupportedAsset(asset.symbol.clone())),
-        };
+        // The ticker quotes numbers as strings next to per-request ids and window times
+        let url = format!("{}/ticker/24hr?symbol={}", self.api_endpoint, pair);
+        let quote = fetch_quote(url, vec![
+            TransformStep::strip_headers(),
+            TransformStep::drop_fields(&["openTime", "closeTime", "firstId", "lastId", "count"]),
+            TransformStep::normalize_numbers(6, true),
+        ]).await?;
 
         Ok(Price {
             asset: asset.clone(),
-            price_usd,
-            change_24h_percentage: change_24h,
-            volume_24h_usd: volume_24h,
+            price_usd: quote_number(&quote, "lastPrice")?,
+            change_24h_percentage: quote_number(&quote, "priceChangePercent").unwrap_or(0.0),
+            volume_24h_usd: quote_number(&quote, "quoteVolume").unwrap_or(0.0),
             market_cap_usd: None, // Binance doesn't provide market cap
-            last_updated: current_time,
+            last_updated: time(),
             source: OracleProvider::Binance,
             confidence_score: 96.2,
         })
@@ -694,6 +690,31 @@ impl BinanceOracle {
     }
 }
 
+/// Fetch a JSON quote through an outcall whose response is reduced by `transform`
+async fn fetch_quote(url: String, transform: Vec<TransformStep>) -> Result<serde_json::Value, OracleError> {
+    let response = HttpClient::request(HttpRequest {
+        url,
+        method: HttpMethod::GET,
+        headers: vec![HttpHeader { name: "Accept".to_string(), value: "application/json".to_string() }],
+        body: None,
+        max_response_bytes: Some(QUOTE_MAX_RESPONSE_BYTES),
+        cycles: Some(outcall_cycles(0, QUOTE_MAX_RESPONSE_BYTES)),
+        transform,
+    }).await.map_err(OracleError::NetworkError)?;
+
+    match response.status {
+        200..=299 => parse_json_response(&response).map_err(OracleError::InvalidResponse),
+        429 => Err(OracleError::ApiRateLimited),
+        status => Err(OracleError::NetworkError(format!("Price API returned status {}: {}", status, response.body))),
+    }
+}
+
+fn quote_number(quote: &serde_json::Value, field: &str) -> Result<f64, OracleError> {
+    quote.get(field)
+        .and_then(serde_json::Value::as_f64)
+        .ok_or_else(|| OracleError::InvalidResponse(format!("Quote has no numeric {}", field)))
+}
+
 /// Price aggregator for combining multiple oracle sources
 #[derive(Debug, Clone)]
 pub struct PriceAggregator;
@@ -14,6 +14,20 @@ pub const SOL_RPC_CANISTER_ID: Principal = Principal::from_slice(&[
     0x00, 0x00, 0x00, 0x00, 0x02, 0x30, 0x00, 0xD3, 0x01, 0x01
 ]); // tghme-zyaaa-aaaar-qarca-cai
 
+/// Entry returned by `getSignaturesForAddress`
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureInfo {
+    pub signature: String,
+    pub slot: u64,
+    #[serde(default)]
+    pub err: Option<Value>,
+    #[serde(default)]
+    pub block_time: Option<i64>,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
 /// Pure ICP Solana service using only ICP built-in capabilities
 #[derive(Debug, Clone)]
 pub struct PureIcpSolanaService {
@@ -38,6 +52,11 @@ impl PureIcpSolanaService {
 
     /// Make HTTPS outcall to Solana RPC
     async fn make_rpc_call(&self, method: &str, params: Value) -> Result<Value, SolanaError> {
+        self.make_rpc_call_with_limit(method, params, 2048).await
+    }
+
+    /// Make HTTPS outcall to Solana RPC with an explicit response size limit
+    async fn make_rpc_call_with_limit(&self, method: &str, params: Value, max_response_bytes: u64) -> Result<Value, SolanaError> {
         let rpc_payload = json!({
             "jsonrpc": "2.0",
             "id": 1,
@@ -56,7 +75,7 @@ impl PureIcpSolanaService {
             url: self.get_rpc_endpoint().to_string(),
             method: HttpMethod::POST,
             body: Some(rpc_payload.to_string().into_bytes()),
-            max_response_bytes: Some(2048),
+            max_response_bytes: Some(max_response_bytes),
             transform: None,
             headers: request_headers,
         };
@@ -149,6 +168,39 @@ impl PureIcpSolanaService {
         }
     }
 
+    /// Get balance at a commitment level, surfacing RPC failures
+    pub async fn get_balance_at(&self, address: &str, commitment: &str) -> Result<u64, SolanaError> {
+        let params = json!([address, {"commitment": commitment}]);
+        let result = self.make_rpc_call("getBalance", params).await?;
+        result.get("value")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| SolanaError::RpcError("Invalid balance response".to_string()))
+    }
+
+    /// Get signatures for an address, newest first, bounded by `until` and `before`
+    pub async fn get_signatures_for_address(
+        &self,
+        address: &str,
+        until: Option<&str>,
+        before: Option<&str>,
+        limit: u32,
+        commitment: &str,
+    ) -> Result<Vec<SignatureInfo>, SolanaError> {
+        let mut config = json!({"limit": limit, "commitment": commitment});
+        if let Some(until) = until {
+            config["until"] = json!(until);
+        }
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+
+        // Each entry is roughly 200 bytes of JSON
+        let max_response_bytes = 1024 + limit as u64 * 256;
+        let result = self.make_rpc_call_with_limit("getSignaturesForAddress", json!([address, config]), max_response_bytes).await?;
+        serde_json::from_value(result)
+            .map_err(|e| SolanaError::RpcError(format!("Invalid signatures response: {}", e)))
+    }
+
     /// Generate mock balance for testing
     fn generate_mock_balance(&self, address: &str) -> u64 {
         use std::collections::hash_map::DefaultHasher;
@@ -2,9 +2,10 @@
 // Exposes the sophisticated backend strategy system via ICP canister APIs
 
 use super::automated_strategies::{
-    AutomatedStrategyManager, StrategyConfig, StrategyAnalytics, StrategyPerformanceSummary,
+    StrategyConfig, StrategyAnalytics, StrategyPerformanceSummary,
     StrategyError, ActiveStrategy, StrategyOpportunity,
 };
+use super::automated_strategy_api::{persist_strategy, persist_strategy_user, STRATEGY_MANAGER};
 use super::real_protocol_integrations::{
     RealProtocolIntegrationManager, RealYieldOpportunity, RealArbitrageOpportunity, IntegrationError,
 };
@@ -14,8 +15,8 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 
 // Global state for strategy management
+// Strategies live in the manager shared with automated_strategy_api
 thread_local! {
-    static STRATEGY_MANAGER: RefCell<AutomatedStrategyManager> = RefCell::new(AutomatedStrategyManager::new());
     static PROTOCOL_MANAGER: RefCell<RealProtocolIntegrationManager> = RefCell::new(RealProtocolIntegrationManager::new());
 }
 
@@ -123,9 +124,7 @@ pub struct StrategyPerformanceBreakdown {
 
 /// Initialize the strategy API system
 pub fn init_strategy_api() {
-    STRATEGY_MANAGER.with(|manager| {
-        manager.borrow_mut().initialize();
-    });
+    // The shared strategy manager is initialized by automated_strategy_api
     
     // Initialize protocol integrations (async initialization will happen on first call)
     
@@ -382,12 +381,14 @@ async fn create_and_execute_strategy(
         mgr.create_strategy(user_id.clone(), strategy_config.clone())
             .map_err(|e| format!("Strategy creation failed: {}", e))
     })?;
+    persist_strategy_user(&user_id);
 
     STRATEGY_MANAGER.with(|manager| {
         let mut mgr = manager.borrow_mut();
         mgr.activate_strategy(&strategy_id, capital_amount)
             .map_err(|e| format!("Strategy activation failed: {}", e))
     })?;
+    persist_strategy(&strategy_id);
 
     // Estimate APY based on strategy type and current market conditions
     let estimated_apy = estimate_strategy_apy(&strategy_config);
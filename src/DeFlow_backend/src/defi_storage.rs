@@ -0,0 +1,331 @@
+// Stable storage for DeFi state: automated strategies, portfolios and price alerts.
+//
+// The managers in `defi` keep serving reads from the heap. Every change to a
+// strategy, a user's strategy settings, a portfolio or a price alert is written
+// through to the maps below, and `defi::restore_persisted_state` rebuilds the
+// heap managers from them in `post_upgrade`.
+//
+// Records carry a schema header (see `schema.rs`); records written before it were
+// wrapped in a `V1` envelope, which the registered migrations unwrap.
+
+use crate::defi::automated_strategies::{ActiveStrategy, StrategyRiskLimits, UserPreferences, UserRiskLimits};
+use crate::defi::portfolio_manager::{AutoCompoundSettings, NotificationPreferences, UserPortfolio, WebhookEndpoint};
+use crate::defi::price_alert_service::PriceAlert;
+use crate::schema::{self, versioned};
+use crate::storage::MEMORY_MANAGER;
+use crate::types::StoreSchemaStatus;
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::memory_manager::{MemoryId, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, Storable};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+/// A strategy together with the risk limits set for it.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StrategyRecord {
+    pub strategy: ActiveStrategy,
+    pub risk_limits: Option<StrategyRiskLimits>,
+}
+
+/// Strategy settings of one user.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StrategyUserRecord {
+    pub preferences: Option<UserPreferences>,
+    pub risk_limits: Option<UserRiskLimits>,
+}
+
+/// Portfolio state of one user.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PortfolioRecord {
+    pub portfolio: Option<UserPortfolio>,
+    pub auto_compound: Option<AutoCompoundSettings>,
+    pub notification_preferences: Option<NotificationPreferences>,
+    pub webhook_endpoint: Option<WebhookEndpoint>,
+}
+
+// Envelope records were stored in before the schema header
+#[derive(CandidType, Deserialize)]
+enum LegacyEnvelope<T> {
+    V1(T),
+}
+
+pub(crate) fn unwrap_legacy_envelope<T: CandidType + DeserializeOwned>(payload: Vec<u8>) -> Result<Vec<u8>, String> {
+    let LegacyEnvelope::V1(record) = Decode!(&payload, LegacyEnvelope<T>).map_err(|e| e.to_string())?;
+    Encode!(&record).map_err(|e| e.to_string())
+}
+
+versioned!(
+    StrategyRecord => schema::STRATEGIES,
+    StrategyUserRecord => schema::STRATEGY_USERS,
+    PortfolioRecord => schema::PORTFOLIOS,
+    PriceAlert => schema::PRICE_ALERTS,
+);
+
+// Execution histories and position lists grow over time, so records are unbounded.
+// A record that no longer decodes traps, so a bad upgrade rolls back instead of dropping it.
+macro_rules! candid_storable {
+    ($($name:ty),*) => {$(
+        impl Storable for $name {
+            const BOUND: Bound = Bound::Unbounded;
+
+            fn to_bytes(&self) -> Cow<'_, [u8]> {
+                Cow::Owned(schema::encode(self).expect(concat!("Failed to encode ", stringify!($name))))
+            }
+
+            fn from_bytes(bytes: Cow<[u8]>) -> Self {
+                schema::decode(bytes.as_ref()).expect(concat!("Failed to decode ", stringify!($name)))
+            }
+        }
+    )*};
+}
+
+candid_storable!(StrategyRecord, StrategyUserRecord, PortfolioRecord, PriceAlert);
+
+thread_local! {
+    // Strategies keyed by strategy id
+    static STRATEGIES: RefCell<StableBTreeMap<String, StrategyRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18))),
+        )
+    );
+
+    // Strategy preferences and risk limits keyed by user
+    static STRATEGY_USERS: RefCell<StableBTreeMap<String, StrategyUserRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19))),
+        )
+    );
+
+    // Portfolios and their settings keyed by user
+    static PORTFOLIOS: RefCell<StableBTreeMap<String, PortfolioRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20))),
+        )
+    );
+
+    // Price alerts keyed by alert id
+    static PRICE_ALERTS: RefCell<StableBTreeMap<String, PriceAlert, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))),
+        )
+    );
+}
+
+/// Survey, and unless `dry_run` migrate, every map in this file.
+pub fn migrate_records(dry_run: bool) -> Vec<StoreSchemaStatus> {
+    vec![
+        STRATEGIES.with(|map| schema::migrate_map("strategies", map, dry_run)),
+        STRATEGY_USERS.with(|map| schema::migrate_map("strategy_users", map, dry_run)),
+        PORTFOLIOS.with(|map| schema::migrate_map("portfolios", map, dry_run)),
+        PRICE_ALERTS.with(|map| schema::migrate_map("price_alerts", map, dry_run)),
+    ]
+}
+
+pub fn save_strategy(record: StrategyRecord) {
+    STRATEGIES.with(|strategies| {
+        strategies.borrow_mut().insert(record.strategy.id.clone(), record);
+    });
+}
+
+pub fn remove_strategy(strategy_id: &str) {
+    STRATEGIES.with(|strategies| {
+        strategies.borrow_mut().remove(&strategy_id.to_string());
+    });
+}
+
+pub fn list_strategies() -> Vec<StrategyRecord> {
+    STRATEGIES.with(|strategies| {
+        strategies.borrow().iter()
+            .map(|(_, record)| record)
+            .collect()
+    })
+}
+
+pub fn save_strategy_user(user_id: String, record: StrategyUserRecord) {
+    STRATEGY_USERS.with(|users| {
+        users.borrow_mut().insert(user_id, record);
+    });
+}
+
+pub fn list_strategy_users() -> Vec<(String, StrategyUserRecord)> {
+    STRATEGY_USERS.with(|users| {
+        users.borrow().iter().collect()
+    })
+}
+
+pub fn save_portfolio(user_id: String, record: PortfolioRecord) {
+    PORTFOLIOS.with(|portfolios| {
+        portfolios.borrow_mut().insert(user_id, record);
+    });
+}
+
+pub fn list_portfolios() -> Vec<(String, PortfolioRecord)> {
+    PORTFOLIOS.with(|portfolios| {
+        portfolios.borrow().iter().collect()
+    })
+}
+
+pub fn save_price_alert(alert: PriceAlert) {
+    PRICE_ALERTS.with(|alerts| {
+        alerts.borrow_mut().insert(alert.id.clone(), alert);
+    });
+}
+
+pub fn list_price_alerts() -> Vec<PriceAlert> {
+    PRICE_ALERTS.with(|alerts| {
+        alerts.borrow().iter()
+            .map(|(_, record)| record)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defi::automated_strategies::{
+        AutomatedStrategyManager, StrategyConfig, StrategyPerformanceMetrics, StrategyRiskMetrics,
+        StrategyStatus, StrategyType, YieldFarmingConfig,
+    };
+    use crate::defi::automated_strategy_api::{persist_strategy, persist_strategy_user, STRATEGY_MANAGER};
+    use crate::defi::portfolio_api::{persist_portfolio, PORTFOLIO_MANAGER};
+    use crate::defi::portfolio_manager::{AdvancedPortfolioManager, PortfolioConfiguration};
+    use crate::defi::price_alert_service::{get_user_price_alerts, PriceAlertManager, PriceCondition, PRICE_ALERT_MANAGER};
+    use crate::defi::yield_farming::{ChainId, DeFiProtocol};
+
+    fn strategy() -> ActiveStrategy {
+        ActiveStrategy {
+            id: "strategy_1".to_string(),
+            user_id: "user_1".to_string(),
+            config: StrategyConfig {
+                name: "Stable yield".to_string(),
+                description: "USDC farming".to_string(),
+                strategy_type: StrategyType::YieldFarming(YieldFarmingConfig {
+                    min_apy_threshold: 5.0,
+                    preferred_tokens: vec!["USDC".to_string()],
+                    max_impermanent_loss_percentage: 5.0,
+                    auto_harvest_rewards: true,
+                }),
+                target_chains: vec![ChainId::Ethereum],
+                target_protocols: vec![DeFiProtocol::Aave],
+                risk_level: 3,
+                max_allocation_usd: 10000.0,
+                min_return_threshold: 5.0,
+                execution_interval_minutes: 1440,
+                gas_limit_usd: 50.0,
+                auto_compound: true,
+                stop_loss_percentage: Some(10.0),
+                take_profit_percentage: None,
+            },
+            status: StrategyStatus::Active,
+            allocated_capital: 5000.0,
+            performance_metrics: StrategyPerformanceMetrics::default(),
+            risk_metrics: StrategyRiskMetrics::default(),
+            execution_history: Vec::new(),
+            next_execution: Some(1_700_086_400),
+            created_at: 1_700_000_000,
+            last_updated: 1_700_000_000,
+            last_rebalance: None,
+        }
+    }
+
+    fn price_alert() -> PriceAlert {
+        PriceAlert {
+            id: "alert_1".to_string(),
+            user_id: "user_1".to_string(),
+            token_symbol: "ETH".to_string(),
+            condition: PriceCondition::Above(4000.0),
+            actions: Vec::new(),
+            social_config: None,
+            created_at: 1_700_000_000,
+            expires_at: None,
+            is_active: true,
+            triggered_count: 2,
+            max_triggers: Some(5),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_round_trip_restores_defi_state() {
+        STRATEGY_MANAGER.with(|manager| {
+            let mut manager = manager.borrow_mut();
+            manager.active_strategies.insert("strategy_1".to_string(), strategy());
+            manager.risk_manager.strategy_limits.insert("strategy_1".to_string(), StrategyRiskLimits {
+                max_risk_score: 5,
+                max_allocation: 5000.0,
+                stop_loss_percentage: 10.0,
+                take_profit_percentage: 25.0,
+                max_daily_executions: 4,
+            });
+            manager.risk_manager.user_limits.insert("user_1".to_string(), UserRiskLimits {
+                max_total_allocation: 20000.0,
+                max_single_strategy_allocation: 5000.0,
+                max_risk_score: 6,
+                max_strategies: 3,
+                emergency_stop_drawdown: 15.0,
+            });
+            manager.user_preferences.insert("user_1".to_string(), UserPreferences::default());
+        });
+        persist_strategy("strategy_1");
+        persist_strategy_user("user_1");
+
+        PORTFOLIO_MANAGER.with(|pm| {
+            pm.borrow_mut().create_portfolio("user_1".to_string(), PortfolioConfiguration::default()).unwrap();
+        });
+        persist_portfolio("user_1");
+
+        save_price_alert(price_alert());
+
+        let strategy_before = STRATEGY_MANAGER.with(|manager| {
+            let manager = manager.borrow();
+            format!("{:?} {:?} {:?} {:?}",
+                manager.active_strategies.get("strategy_1"),
+                manager.risk_manager.strategy_limits.get("strategy_1"),
+                manager.risk_manager.user_limits.get("user_1"),
+                manager.user_preferences.get("user_1"))
+        });
+        let portfolio_before = PORTFOLIO_MANAGER.with(|pm| format!("{:?}", pm.borrow().portfolios.get("user_1")));
+
+        // An upgrade drops the heap; a strategy that was never persisted must not survive either
+        STRATEGY_MANAGER.with(|manager| {
+            let mut fresh = AutomatedStrategyManager::new();
+            let mut stray = strategy();
+            stray.id = "strategy_unsaved".to_string();
+            fresh.active_strategies.insert(stray.id.clone(), stray);
+            *manager.borrow_mut() = fresh;
+        });
+        PORTFOLIO_MANAGER.with(|pm| *pm.borrow_mut() = AdvancedPortfolioManager::new());
+        PRICE_ALERT_MANAGER.with(|manager| *manager.borrow_mut() = PriceAlertManager::new());
+
+        crate::defi::restore_persisted_state();
+
+        let strategy_after = STRATEGY_MANAGER.with(|manager| {
+            let manager = manager.borrow();
+            assert_eq!(manager.active_strategies.len(), 1);
+            format!("{:?} {:?} {:?} {:?}",
+                manager.active_strategies.get("strategy_1"),
+                manager.risk_manager.strategy_limits.get("strategy_1"),
+                manager.risk_manager.user_limits.get("user_1"),
+                manager.user_preferences.get("user_1"))
+        });
+        assert_eq!(strategy_after, strategy_before);
+        assert_eq!(PORTFOLIO_MANAGER.with(|pm| format!("{:?}", pm.borrow().portfolios.get("user_1"))), portfolio_before);
+
+        let alerts = get_user_price_alerts("user_1");
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(format!("{:?}", alerts[0]), format!("{:?}", price_alert()));
+    }
+
+    #[test]
+    fn test_records_in_legacy_envelopes_still_decode() {
+        let legacy = Encode!(&LegacyEnvelope::V1(price_alert())).unwrap();
+        let alert: PriceAlert = schema::decode(&legacy).unwrap();
+        assert_eq!(format!("{:?}", alert), format!("{:?}", price_alert()));
+
+        let rewritten = schema::encode(&alert).unwrap();
+        assert_eq!(&rewritten[..3], schema::HEADER_MAGIC);
+    }
+}
@@ -1,74 +1,293 @@
 use crate::types::{
     Workflow, WorkflowExecution, ExecutionStatus, NodeExecution, ExecutionContext,
-    NodeOutput, ConfigValue, RetryPolicy, ExecutionGraph, WorkflowNode,
-    WorkflowRecovery, FallbackStrategy, EmergencyAction
+    NodeOutput, ConfigValue, RetryPolicy, ExecutionGraph, WorkflowNode, NodeConnection,
+    WorkflowRecovery, FallbackStrategy, EmergencyAction, ExecutionCheckpoint, NodeAttempt, NodeError,
+    ExecutionCancellation, WorkflowRole
 };
+use crate::acl;
 use crate::storage;
+use crate::system_events;
 use crate::workflow::generate_id;
-use crate::nodes::execute_node_internal;
-use ic_cdk::{api, update, query, spawn};
-use ic_cdk_timers::set_timer;
-use std::collections::HashMap;
+use crate::nodes::{execute_node_internal, delay_duration_ms};
+use crate::expressions::{evaluate_expression, values_equal};
+use futures::channel::oneshot;
+use futures::future::join_all;
+use ic_cdk::{api, caller, update, query, spawn};
+use ic_cdk_timers::{set_timer, clear_timer, TimerId};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
 use std::time::Duration;
 
+const MAX_SUB_WORKFLOW_DEPTH: u32 = 5;
+const ERROR_PORT: &str = "error";
+const CONDITION_PORTS: [&str; 2] = ["true", "false"];
+
+/// Cancel or pause request for a running execution, applied before its next node.
+#[derive(Clone, Debug)]
+enum ControlRequest {
+    Cancel { by: String, reason: Option<String> },
+    Pause,
+}
+
+thread_local! {
+    // Requests for executions that are mid-run; Waiting/Paused ones are updated directly
+    static CONTROL_REQUESTS: RefCell<HashMap<String, ControlRequest>> = RefCell::new(HashMap::new());
+    // Timers that will resume Waiting executions, so pause/cancel can clear them
+    static RESUME_TIMERS: RefCell<HashMap<String, TimerId>> = RefCell::new(HashMap::new());
+    // Executions with a loop running in this instance, mapped to their parent execution
+    static LIVE_RUNS: RefCell<HashMap<String, Option<String>>> = RefCell::new(HashMap::new());
+}
+
+/// Marks an execution as driven by a running loop until dropped.
+struct LiveRun(String);
+
+impl LiveRun {
+    fn start(execution_id: &str, parent_execution_id: Option<String>) -> Self {
+        LIVE_RUNS.with(|runs| runs.borrow_mut().insert(execution_id.to_string(), parent_execution_id));
+        LiveRun(execution_id.to_string())
+    }
+}
+
+impl Drop for LiveRun {
+    fn drop(&mut self) {
+        LIVE_RUNS.with(|runs| runs.borrow_mut().remove(&self.0));
+    }
+}
+
 #[update]
 pub async fn start_execution(workflow_id: String, trigger_data: Option<HashMap<String, ConfigValue>>) -> Result<String, String> {
-    let workflow = storage::get_workflow(&workflow_id)
+    acl::authorize(&workflow_id, WorkflowRole::Runner)?;
+    let execution_id = create_execution(&workflow_id, trigger_data, None, Some(caller().to_text()))?;
+    
+    spawn(execute_workflow(execution_id.clone()));
+    
+    Ok(execution_id)
+}
+
+/// Start a run on behalf of the workflow's owner. Used by schedules, events and webhooks,
+/// where the message caller is not the person the workflow acts for.
+pub fn trigger_execution(workflow_id: String, trigger_data: Option<HashMap<String, ConfigValue>>) -> Result<String, String> {
+    let execution_id = create_execution(&workflow_id, trigger_data, None, None)?;
+    
+    spawn(execute_workflow(execution_id.clone()));
+    
+    Ok(execution_id)
+}
+
+/// Store a new pending execution of `workflow_id`; the caller decides how to run it.
+/// Without an explicit initiator the run is attributed to the workflow owner.
+fn create_execution(
+    workflow_id: &str,
+    trigger_data: Option<HashMap<String, ConfigValue>>,
+    parent_execution_id: Option<String>,
+    initiated_by: Option<String>
+) -> Result<String, String> {
+    let workflow = storage::get_workflow(workflow_id)
         .ok_or_else(|| "Workflow not found".to_string())?;
     
     if !workflow.active {
         return Err("Workflow is not active".to_string());
     }
     
+    let initiated_by = initiated_by.or_else(|| workflow.owner.clone());
+    
     let execution_id = generate_id();
     let execution = WorkflowExecution {
         id: execution_id.clone(),
-        workflow_id: workflow_id.clone(),
+        workflow_id: workflow_id.to_string(),
         status: ExecutionStatus::Pending,
         started_at: api::time(),
         completed_at: None,
         trigger_data,
         node_executions: Vec::new(),
         error_message: None,
+        parent_execution_id,
+        checkpoint: None,
+        initiated_by,
+        variables: workflow.variables.clone(),
+        cancellation: None,
+        workflow_revision: workflow.revision,
     };
     
     storage::insert_execution(execution_id.clone(), execution);
     
-    spawn(execute_workflow(execution_id.clone()));
-    
     Ok(execution_id)
 }
 
+/// Number of parent executions above `execution_id` (0 for a top-level run).
+fn execution_depth(execution_id: &str) -> u32 {
+    let mut depth = 0;
+    let mut current = storage::get_execution(execution_id).and_then(|e| e.parent_execution_id);
+    
+    while let Some(parent_id) = current {
+        depth += 1;
+        if depth > MAX_SUB_WORKFLOW_DEPTH {
+            break;
+        }
+        current = storage::get_execution(&parent_id).and_then(|e| e.parent_execution_id);
+    }
+    
+    depth
+}
+
+/// Run another workflow as a child execution, wait for it to finish and expose the
+/// outputs of its terminal nodes (nodes without outgoing connections).
+pub async fn execute_sub_workflow_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let child_workflow_id = match node.configuration.parameters.get("workflow_id") {
+        Some(ConfigValue::String(id)) if !id.is_empty() => id.clone(),
+        _ => return Err("Missing workflow_id parameter".to_string()),
+    };
+    
+    if execution_depth(&context.execution_id) + 1 > MAX_SUB_WORKFLOW_DEPTH {
+        return Err(format!(
+            "Sub-workflow depth limit ({}) reached while calling workflow {}",
+            MAX_SUB_WORKFLOW_DEPTH, child_workflow_id
+        ));
+    }
+    
+    // The child runs as the parent's principal, who must be able to start it directly
+    let child_workflow = storage::get_workflow(&child_workflow_id)
+        .ok_or_else(|| "Workflow not found".to_string())?;
+    let role = acl::role_of(&child_workflow, storage::get_workflow_acl(&child_workflow_id).as_ref(), &context.user_id);
+    if role < Some(WorkflowRole::Runner) {
+        return Err(format!(
+            "Access denied: {} does not have Runner access to workflow {}",
+            context.user_id, child_workflow_id
+        ));
+    }
+    
+    // Typed inputs: each entry maps a child trigger field to an expression over this node's input
+    let trigger_data = match node.configuration.parameters.get("inputs") {
+        Some(ConfigValue::Object(mapping)) => {
+            let mut data = HashMap::new();
+            for (field, expr) in mapping {
+                let value = match expr {
+                    ConfigValue::String(expr) => evaluate_expression(expr, input)
+                        .map_err(|e| format!("Sub-workflow input '{}' error: {}", field, e))?,
+                    literal => literal.clone(),
+                };
+                data.insert(field.clone(), value);
+            }
+            data
+        }
+        _ => input.clone(),
+    };
+    
+    let child_id = create_execution(
+        &child_workflow_id,
+        Some(trigger_data),
+        Some(context.execution_id.clone()),
+        Some(context.user_id.clone())
+    )?;
+    execute_workflow(child_id.clone()).await;
+    
+    let child = storage::get_execution(&child_id)
+        .ok_or_else(|| format!("Child execution {} not found", child_id))?;
+    if let ExecutionStatus::Waiting { .. } = child.status {
+        // The parent fails here, so the child must not resume later and run its remaining nodes
+        let mut child = child;
+        clear_resume_timer(&child_id);
+        mark_cancelled(&mut child, context.user_id.clone(), Some(format!("parent execution {} cannot wait on a delay", context.execution_id)));
+        emit_finished(&child);
+        update_execution(&child_id, &child)?;
+        return Err(format!(
+            "Sub-workflow {} (execution {}) stopped on a delay and was cancelled; called workflows must run to completion",
+            child_workflow_id, child_id
+        ));
+    }
+    if !matches!(child.status, ExecutionStatus::Completed) {
+        return Err(format!(
+            "Sub-workflow {} (execution {}) did not complete: {}",
+            child_workflow_id, child_id, child.error_message.unwrap_or_default()
+        ));
+    }
+    
+    let mut terminal_ids: Vec<&String> = child_workflow.nodes.iter()
+        .map(|n| &n.id)
+        .filter(|id| !child_workflow.connections.iter().any(|c| &c.source_node_id == *id))
+        .collect();
+    terminal_ids.sort();
+    
+    let mut outputs = HashMap::new();
+    let mut data = HashMap::new();
+    for id in terminal_ids {
+        let output = child.node_executions.iter().rev()
+            .find(|ne| &ne.node_id == id && ne.iteration.is_none() && matches!(ne.status, ExecutionStatus::Completed))
+            .and_then(|ne| ne.output_data.clone());
+        if let Some(output) = output {
+            // Flatten terminal outputs so connections can read their fields directly
+            data.extend(output.clone());
+            outputs.insert(id.clone(), ConfigValue::Object(output));
+        }
+    }
+    
+    // Typed outputs: each entry maps an output field to an expression over the terminal outputs
+    if let Some(ConfigValue::Object(mapping)) = node.configuration.parameters.get("outputs") {
+        data.clear();
+        for (field, expr) in mapping {
+            let ConfigValue::String(expr) = expr else {
+                return Err(format!("Sub-workflow output '{}' must be an expression string", field));
+            };
+            let value = evaluate_expression(expr, &outputs)
+                .map_err(|e| format!("Sub-workflow output '{}' error: {}", field, e))?;
+            data.insert(field.clone(), value);
+        }
+    }
+    
+    data.insert("outputs".to_string(), ConfigValue::Object(outputs));
+    data.insert("execution_id".to_string(), ConfigValue::String(child_id));
+    
+    Ok(NodeOutput {
+        data,
+        next_nodes: Vec::new(),
+    })
+}
+
 #[query]
 pub fn get_execution(id: String) -> Result<WorkflowExecution, String> {
-    storage::get_execution(&id)
-        .ok_or_else(|| "Execution not found".to_string())
+    let execution = storage::get_execution(&id)
+        .ok_or_else(|| "Execution not found".to_string())?;
+    if !caller_initiated(&execution) {
+        acl::authorize(&execution.workflow_id, WorkflowRole::Viewer)?;
+    }
+    Ok(execution)
 }
 
+/// Executions of workflows the caller can view, plus runs the caller started.
 #[query]
 pub fn list_executions(workflow_id: Option<String>) -> Vec<WorkflowExecution> {
+    let mut visible: HashMap<String, bool> = HashMap::new();
     storage::EXECUTIONS.with(|executions| {
         executions.borrow()
             .iter()
-            .filter_map(|(_, storable)| {
-                let execution = &storable.0;
-                match &workflow_id {
-                    Some(wf_id) if execution.workflow_id == *wf_id => Some(execution.clone()),
-                    None => Some(execution.clone()),
-                    _ => None,
-                }
+            .map(|(_, storable)| storable.0)
+            .filter(|execution| workflow_id.as_ref().is_none_or(|wf_id| execution.workflow_id == *wf_id))
+            .filter(|execution| {
+                caller_initiated(execution) || *visible.entry(execution.workflow_id.clone()).or_insert_with(|| {
+                    storage::get_workflow(&execution.workflow_id)
+                        .is_some_and(|workflow| acl::caller_can(&workflow, WorkflowRole::Viewer))
+                })
             })
             .collect()
     })
 }
 
+fn caller_initiated(execution: &WorkflowExecution) -> bool {
+    execution.initiated_by.as_deref() == Some(caller().to_text().as_str())
+}
+
 #[update]
 pub async fn retry_failed_execution(execution_id: String, node_id: String) -> Result<(), String> {
     let execution = storage::get_execution(&execution_id)
         .ok_or("Execution not found")?;
     
-    let workflow = storage::get_workflow(&execution.workflow_id)
-        .ok_or("Workflow not found")?;
+    let workflow = acl::authorize(&execution.workflow_id, WorkflowRole::Runner)?;
     
     let node = workflow.nodes.iter()
         .find(|n| n.id == node_id)
@@ -84,15 +303,15 @@ pub async fn retry_failed_execution(execution_id: String, node_id: String) -> Re
         trigger_data: execution.trigger_data.clone(),
         node_executions: Vec::new(),
         error_message: None,
+        parent_execution_id: execution.parent_execution_id.clone(),
+        checkpoint: None,
+        initiated_by: execution.initiated_by.clone(),
+        variables: execution.variables.clone(),
+        cancellation: None,
+        workflow_revision: workflow.revision,
     };
     
-    let context = ExecutionContext {
-        workflow_id: workflow.id.clone(),
-        execution_id: retry_execution_id.clone(),
-        user_id: "retry".to_string(),
-        timestamp: api::time(),
-        global_variables: HashMap::new(),
-    };
+    let context = execution_context(&workflow, &retry_execution);
     
     let input_data = execution.node_executions
         .iter()
@@ -100,13 +319,9 @@ pub async fn retry_failed_execution(execution_id: String, node_id: String) -> Re
         .and_then(|ne| ne.input_data.clone())
         .unwrap_or_default();
     
-    let result = execute_single_node(
-        &retry_execution_id,
-        node,
-        input_data,
-        &context,
-        &mut retry_execution
-    ).await;
+    let mut record = begin_node_record(&node_id, &input_data, None);
+    let result = execute_single_node(node, input_data, &context, &mut record).await;
+    retry_execution.node_executions.push(record);
     
     match result {
         Ok(_) => {
@@ -116,7 +331,7 @@ pub async fn retry_failed_execution(execution_id: String, node_id: String) -> Re
         Err(error) => {
             retry_execution.status = ExecutionStatus::Failed;
             retry_execution.completed_at = Some(api::time());
-            retry_execution.error_message = Some(error);
+            retry_execution.error_message = Some(error.to_string());
         }
     }
     
@@ -125,12 +340,239 @@ pub async fn retry_failed_execution(execution_id: String, node_id: String) -> Re
     Ok(())
 }
 
+/// Whoever started the run and anyone with Runner access to the workflow may control it.
+fn authorize_execution_control(execution: &WorkflowExecution) -> Result<String, String> {
+    if !caller_initiated(execution) {
+        acl::authorize(&execution.workflow_id, WorkflowRole::Runner)?;
+    }
+    Ok(caller().to_text())
+}
+
+#[update]
+pub fn cancel_execution(execution_id: String, reason: Option<String>) -> Result<(), String> {
+    let mut execution = storage::get_execution(&execution_id)
+        .ok_or_else(|| "Execution not found".to_string())?;
+    let principal_id = authorize_execution_control(&execution)?;
+    
+    match execution.status {
+        ExecutionStatus::Pending | ExecutionStatus::Running if is_live(&execution_id) => {
+            request_cancel(&execution_id, principal_id, reason);
+            Ok(())
+        }
+        // Suspended runs, and runs whose loop was lost (e.g. to an upgrade), have nothing to apply a request
+        ExecutionStatus::Pending | ExecutionStatus::Running | ExecutionStatus::Waiting { .. } | ExecutionStatus::Paused => {
+            cancel_stranded_children(&execution_id, &principal_id);
+            clear_resume_timer(&execution_id);
+            mark_cancelled(&mut execution, principal_id, reason);
+            emit_finished(&execution);
+            update_execution(&execution_id, &execution)
+        }
+        _ => Err("Execution has already finished".to_string()),
+    }
+}
+
+fn is_live(execution_id: &str) -> bool {
+    LIVE_RUNS.with(|runs| runs.borrow().contains_key(execution_id))
+}
+
+/// Ask a running execution, and the sub-workflow runs it is waiting on, to stop before their next node.
+fn request_cancel(execution_id: &str, by: String, reason: Option<String>) {
+    let children: Vec<String> = LIVE_RUNS.with(|runs| {
+        runs.borrow()
+            .iter()
+            .filter(|(_, parent)| parent.as_deref() == Some(execution_id))
+            .map(|(id, _)| id.clone())
+            .collect()
+    });
+    for child_id in children {
+        request_cancel(&child_id, by.clone(), Some(format!("parent execution {} was cancelled", execution_id)));
+    }
+    
+    CONTROL_REQUESTS.with(|requests| {
+        requests.borrow_mut().insert(execution_id.to_string(), ControlRequest::Cancel { by, reason });
+    });
+}
+
+/// Cancel unfinished child executions of a run that no loop is driving.
+fn cancel_stranded_children(execution_id: &str, by: &str) {
+    let children: Vec<WorkflowExecution> = storage::EXECUTIONS.with(|executions| {
+        executions.borrow()
+            .iter()
+            .map(|(_, storable)| storable.0)
+            .filter(|child| child.parent_execution_id.as_deref() == Some(execution_id))
+            .filter(|child| matches!(
+                child.status,
+                ExecutionStatus::Pending | ExecutionStatus::Running | ExecutionStatus::Waiting { .. } | ExecutionStatus::Paused
+            ))
+            .collect()
+    });
+    
+    for mut child in children {
+        let child_id = child.id.clone();
+        if is_live(&child_id) {
+            request_cancel(&child_id, by.to_string(), Some(format!("parent execution {} was cancelled", execution_id)));
+            continue;
+        }
+        cancel_stranded_children(&child_id, by);
+        clear_resume_timer(&child_id);
+        mark_cancelled(&mut child, by.to_string(), Some(format!("parent execution {} was cancelled", execution_id)));
+        emit_finished(&child);
+        let _ = update_execution(&child_id, &child);
+    }
+}
+
+#[update]
+pub fn pause_execution(execution_id: String) -> Result<(), String> {
+    let mut execution = storage::get_execution(&execution_id)
+        .ok_or_else(|| "Execution not found".to_string())?;
+    authorize_execution_control(&execution)?;
+    
+    match execution.status {
+        ExecutionStatus::Pending | ExecutionStatus::Running => {
+            CONTROL_REQUESTS.with(|requests| {
+                let mut requests = requests.borrow_mut();
+                // A pending cancel wins over a later pause
+                if !matches!(requests.get(&execution_id), Some(ControlRequest::Cancel { .. })) {
+                    requests.insert(execution_id, ControlRequest::Pause);
+                }
+            });
+            Ok(())
+        }
+        ExecutionStatus::Waiting { resume_at } => {
+            clear_resume_timer(&execution_id);
+            if let Some(checkpoint) = execution.checkpoint.as_mut() {
+                checkpoint.resume_at = Some(resume_at);
+            }
+            execution.status = ExecutionStatus::Paused;
+            update_execution(&execution_id, &execution)
+        }
+        ExecutionStatus::Paused => Err("Execution is already paused".to_string()),
+        _ => Err("Execution has already finished".to_string()),
+    }
+}
+
+#[update]
+pub fn resume_execution(execution_id: String) -> Result<(), String> {
+    let mut execution = storage::get_execution(&execution_id)
+        .ok_or_else(|| "Execution not found".to_string())?;
+    authorize_execution_control(&execution)?;
+    
+    match execution.status {
+        ExecutionStatus::Paused => {
+            // Continue through the Waiting path; a delay that was pending keeps its deadline
+            let resume_at = execution.checkpoint.as_ref()
+                .and_then(|c| c.resume_at)
+                .unwrap_or(0)
+                .max(api::time());
+            execution.status = ExecutionStatus::Waiting { resume_at };
+            update_execution(&execution_id, &execution)?;
+            schedule_execution_resume(execution_id, resume_at);
+            Ok(())
+        }
+        ExecutionStatus::Pending | ExecutionStatus::Running => {
+            // Withdraw a pause that has not taken effect yet
+            let withdrawn = CONTROL_REQUESTS.with(|requests| {
+                let mut requests = requests.borrow_mut();
+                let is_pause = matches!(requests.get(&execution_id), Some(ControlRequest::Pause));
+                if is_pause {
+                    requests.remove(&execution_id);
+                }
+                is_pause
+            });
+            if withdrawn { Ok(()) } else { Err("Execution is not paused".to_string()) }
+        }
+        _ => Err("Execution is not paused".to_string()),
+    }
+}
+
+fn mark_cancelled(execution: &mut WorkflowExecution, cancelled_by: String, reason: Option<String>) {
+    let now = api::time();
+    execution.status = ExecutionStatus::Cancelled;
+    execution.completed_at = Some(now);
+    execution.checkpoint = None;
+    execution.error_message = Some(match &reason {
+        Some(reason) => format!("Cancelled by {}: {}", cancelled_by, reason),
+        None => format!("Cancelled by {}", cancelled_by),
+    });
+    execution.cancellation = Some(ExecutionCancellation {
+        cancelled_by,
+        reason,
+        cancelled_at: now,
+    });
+}
+
+fn take_control_request(execution_id: &str) -> Option<ControlRequest> {
+    CONTROL_REQUESTS.with(|requests| requests.borrow_mut().remove(execution_id))
+}
+
+fn cancel_requested(execution_id: &str) -> bool {
+    CONTROL_REQUESTS.with(|requests| {
+        matches!(requests.borrow().get(execution_id), Some(ControlRequest::Cancel { .. }))
+    })
+}
+
+/// Stop a run for a cancel or pause request; a paused run saves the nodes it has yet to run.
+fn apply_control_request(
+    request: ControlRequest,
+    execution: &mut WorkflowExecution,
+    state: &GraphState,
+    remaining_nodes: Vec<Vec<String>>
+) -> Result<(), String> {
+    match request {
+        ControlRequest::Cancel { by, reason } => {
+            mark_cancelled(execution, by, reason);
+            emit_finished(execution);
+        }
+        ControlRequest::Pause => {
+            execution.checkpoint = Some(ExecutionCheckpoint {
+                node_outputs: state.node_outputs.clone(),
+                node_branches: state.node_branches.clone(),
+                remaining_nodes,
+                resume_at: None,
+            });
+            execution.status = ExecutionStatus::Paused;
+        }
+    }
+    
+    let execution_id = execution.id.clone();
+    update_execution(&execution_id, execution)
+}
+
+/// Batches still to run from `position` in batch `batch_index` on, minus nodes handled elsewhere.
+fn remaining_nodes(
+    execution_order: &[Vec<String>],
+    batch_index: usize,
+    position: usize,
+    handled: &HashSet<String>
+) -> Vec<Vec<String>> {
+    std::iter::once(execution_order[batch_index][position..].to_vec())
+        .chain(execution_order[batch_index + 1..].iter().cloned())
+        .map(|batch| batch.into_iter().filter(|id| !handled.contains(id)).collect::<Vec<_>>())
+        .filter(|batch| !batch.is_empty())
+        .collect()
+}
+
 pub async fn execute_workflow(execution_id: String) {
+    let parent_execution_id = storage::get_execution(&execution_id).and_then(|e| e.parent_execution_id);
+    let _run = LiveRun::start(&execution_id, parent_execution_id);
     let result = execute_workflow_internal(execution_id.clone()).await;
+    finish_execution(&execution_id, result);
+}
+
+fn finish_execution(execution_id: &str, result: Result<(), String>) {
+    // Requests that arrived after the last node have nothing left to stop
+    take_control_request(execution_id);
     
-    if let Some(mut execution) = storage::get_execution(&execution_id) {
+    if let Some(mut execution) = storage::get_execution(execution_id) {
         match result {
             Ok(_) => {
+                // Suspended by a delay or pause (resumed later), or stopped by a cancel
+                if matches!(
+                    execution.status,
+                    ExecutionStatus::Waiting { .. } | ExecutionStatus::Paused | ExecutionStatus::Cancelled
+                ) {
+                    return;
+                }
                 execution.status = ExecutionStatus::Completed;
                 execution.completed_at = Some(api::time());
             }
@@ -140,7 +582,34 @@ pub async fn execute_workflow(execution_id: String) {
                 execution.error_message = Some(error);
             }
         }
-        storage::insert_execution(execution_id, execution);
+        emit_finished(&execution);
+        storage::insert_execution(execution_id.to_string(), execution);
+    }
+}
+
+/// Announce a finished execution to event listeners.
+fn emit_finished(execution: &WorkflowExecution) {
+    let workflow = storage::get_workflow(&execution.workflow_id);
+    let (workflow_name, owner) = match &workflow {
+        Some(workflow) => (Some(&workflow.name), workflow.owner.as_ref()),
+        None => (None, None),
+    };
+    if let Some((event_type, data)) = system_events::execution_finished(execution, workflow_name, owner) {
+        system_events::emit(event_type, data);
+    }
+}
+
+/// The workflow as it was when `execution` was created, even if it has been edited since.
+fn revision_of(execution: &WorkflowExecution) -> Result<Workflow, String> {
+    let workflow = storage::get_workflow(&execution.workflow_id)
+        .ok_or_else(|| "Workflow not found".to_string())?;
+    match execution.workflow_revision {
+        Some(revision) if workflow.revision != Some(revision) => {
+            storage::get_workflow_revision(&execution.workflow_id, revision)
+                .map(|snapshot| snapshot.workflow)
+                .ok_or_else(|| format!("Revision {} of workflow {} not found", revision, execution.workflow_id))
+        }
+        _ => Ok(workflow),
     }
 }
 
@@ -148,64 +617,503 @@ async fn execute_workflow_internal(execution_id: String) -> Result<(), String> {
     let mut execution = storage::get_execution(&execution_id)
         .ok_or_else(|| "Execution not found".to_string())?;
     
-    let workflow = storage::get_workflow(&execution.workflow_id)
-        .ok_or_else(|| "Workflow not found".to_string())?;
+    let workflow = revision_of(&execution)?;
     
     execution.status = ExecutionStatus::Running;
     update_execution(&execution_id, &execution)?;
     
-    let context = ExecutionContext {
-        workflow_id: workflow.id.clone(),
-        execution_id: execution_id.clone(),
-        user_id: "anonymous".to_string(),
-        timestamp: api::time(),
-        global_variables: HashMap::new(),
-    };
+    let context = execution_context(&workflow, &execution);
     
     let execution_graph = build_execution_graph(&workflow)?;
     let execution_order = topological_sort(&execution_graph)?;
     
-    let mut node_outputs: HashMap<String, HashMap<String, ConfigValue>> = HashMap::new();
+    run_graph(&workflow, execution_order, GraphState::default(), &context, &mut execution, None, true).await?;
+    
+    Ok(())
+}
+
+fn execution_context(workflow: &Workflow, execution: &WorkflowExecution) -> ExecutionContext {
+    let user_id = execution.initiated_by.clone()
+        .or_else(|| workflow.owner.clone())
+        .unwrap_or_else(|| "anonymous".to_string());
+    
+    ExecutionContext {
+        workflow_id: workflow.id.clone(),
+        execution_id: execution.id.clone(),
+        user_id,
+        timestamp: api::time(),
+        global_variables: execution.variables.clone().unwrap_or_default(),
+    }
+}
+
+/// Arm a one-shot timer that continues a Waiting execution at `resume_at`.
+pub fn schedule_execution_resume(execution_id: String, resume_at: u64) {
+    let delay = Duration::from_nanos(resume_at.saturating_sub(api::time()));
+    let timer_execution_id = execution_id.clone();
+    let timer_id = set_timer(delay, move || {
+        RESUME_TIMERS.with(|timers| timers.borrow_mut().remove(&timer_execution_id));
+        spawn(resume_waiting_execution(timer_execution_id));
+    });
     
-    for batch in execution_order {
-        let mut batch_results = Vec::new();
+    if let Some(previous) = RESUME_TIMERS.with(|timers| timers.borrow_mut().insert(execution_id, timer_id)) {
+        clear_timer(previous);
+    }
+}
+
+fn clear_resume_timer(execution_id: &str) {
+    if let Some(timer_id) = RESUME_TIMERS.with(|timers| timers.borrow_mut().remove(execution_id)) {
+        clear_timer(timer_id);
+    }
+}
+
+/// Re-arm resume timers for every Waiting execution (timers do not survive upgrades).
+pub fn restore_waiting_executions() {
+    let waiting: Vec<(String, u64)> = storage::EXECUTIONS.with(|executions| {
+        executions.borrow()
+            .iter()
+            .filter_map(|(id, storable)| match storable.0.status {
+                ExecutionStatus::Waiting { resume_at } => Some((id, resume_at)),
+                _ => None,
+            })
+            .collect()
+    });
+    
+    for (execution_id, resume_at) in waiting {
+        schedule_execution_resume(execution_id, resume_at);
+    }
+}
+
+async fn resume_waiting_execution(execution_id: String) {
+    // Only the first timer to fire for a checkpoint resumes it
+    let is_waiting = storage::get_execution(&execution_id)
+        .is_some_and(|e| matches!(e.status, ExecutionStatus::Waiting { .. }) && e.checkpoint.is_some());
+    if !is_waiting {
+        return;
+    }
+    
+    let _run = LiveRun::start(&execution_id, None);
+    let result = resume_execution_internal(execution_id.clone()).await;
+    finish_execution(&execution_id, result);
+}
+
+async fn resume_execution_internal(execution_id: String) -> Result<(), String> {
+    let mut execution = storage::get_execution(&execution_id)
+        .ok_or_else(|| "Execution not found".to_string())?;
+    
+    let workflow = revision_of(&execution)?;
+    
+    let checkpoint = execution.checkpoint.take()
+        .ok_or_else(|| "Execution has no checkpoint to resume from".to_string())?;
+    execution.status = ExecutionStatus::Running;
+    update_execution(&execution_id, &execution)?;
+    
+    let context = execution_context(&workflow, &execution);
+    let state = GraphState {
+        node_outputs: checkpoint.node_outputs,
+        node_branches: checkpoint.node_branches,
+    };
+    
+    run_graph(&workflow, checkpoint.remaining_nodes, state, &context, &mut execution, None, true).await?;
+    
+    Ok(())
+}
+
+/// Outputs produced so far within one pass over (part of) the workflow graph.
+#[derive(Clone, Default)]
+struct GraphState {
+    node_outputs: HashMap<String, HashMap<String, ConfigValue>>,
+    // Output ports each executed node activated (e.g. "true"/"false" for conditions)
+    node_branches: HashMap<String, Vec<String>>,
+}
+
+/// Execute the given topological batches. Independent nodes of a batch run concurrently, up
+/// to the workflow's concurrency cap, and are merged back in node id order. The top-level run
+/// persists progress as it goes; `for_each` iterations run on a scratch execution whose records
+/// the loop merges back.
+fn run_graph<'a>(
+    workflow: &'a Workflow,
+    execution_order: Vec<Vec<String>>,
+    mut state: GraphState,
+    context: &'a ExecutionContext,
+    execution: &'a mut WorkflowExecution,
+    iteration: Option<u32>,
+    persist: bool
+) -> Pin<Box<dyn Future<Output = Result<GraphState, String>> + 'a>> {
+    Box::pin(async move {
+        // Loop bodies are executed by their for_each node and alternative nodes by the
+        // node whose failure they stand in for, not by this pass
+        let mut handled: HashSet<String> = workflow.nodes.iter()
+            .filter_map(|n| match &n.on_error {
+                Some(FallbackStrategy::UseAlternativeNode { node_id }) => Some(node_id.clone()),
+                _ => None,
+            })
+            .collect();
         
-        for node_id in batch {
-            let node = workflow.nodes.iter()
-                .find(|n| n.id == node_id)
-                .ok_or_else(|| format!("Node {} not found", node_id))?;
-            
-            let input_data = prepare_node_input(&workflow, &node_id, &node_outputs)?;
-            
-            let result = execute_single_node(
-                &execution_id,
-                node,
-                input_data,
-                &context,
-                &mut execution
-            ).await;
+        let execution_order: Vec<Vec<String>> = execution_order.into_iter()
+            .map(|mut batch| {
+                batch.sort();
+                batch
+            })
+            .collect();
+        let concurrency = workflow_concurrency(workflow);
+        
+        for (batch_index, batch) in execution_order.iter().enumerate() {
+            let mut position = 0;
             
-            batch_results.push((node_id.clone(), result));
+            while position < batch.len() {
+                // Cancel and pause requests take effect between groups of the top-level run
+                if persist {
+                    if let Some(request) = take_control_request(&context.execution_id) {
+                        let remaining = remaining_nodes(&execution_order, batch_index, position, &handled);
+                        apply_control_request(request, execution, &state, remaining)?;
+                        return Ok(state);
+                    }
+                }
+                
+                // for_each nodes run on their own since they write into the execution directly
+                let mut group: Vec<&WorkflowNode> = Vec::new();
+                while position < batch.len() && group.len() < concurrency {
+                    let node_id = &batch[position];
+                    if handled.contains(node_id) {
+                        position += 1;
+                        continue;
+                    }
+                    
+                    let node = workflow.nodes.iter()
+                        .find(|n| &n.id == node_id)
+                        .ok_or_else(|| format!("Node {} not found", node_id))?;
+                    let is_loop = node.node_type == "for_each";
+                    if is_loop && !group.is_empty() {
+                        break;
+                    }
+                    
+                    group.push(node);
+                    position += 1;
+                    if is_loop {
+                        break;
+                    }
+                }
+                
+                let mut planned: Vec<(&WorkflowNode, usize, HashMap<String, ConfigValue>)> = Vec::new();
+                for node in group {
+                    if !should_execute_node(workflow, &node.id, &state.node_outputs, &state.node_branches) {
+                        mark_node_skipped(&node.id, execution, iteration);
+                        continue;
+                    }
+                    
+                    let mut input_data = prepare_node_input(workflow, &node.id, &state.node_outputs, &state.node_branches)?;
+                    if !workflow.connections.iter().any(|c| c.target_node_id == node.id) {
+                        // Entry nodes receive the trigger payload (webhook body, event data, sub-workflow inputs)
+                        if let Some(trigger_data) = &execution.trigger_data {
+                            for (key, value) in trigger_data {
+                                input_data.entry(key.clone()).or_insert_with(|| value.clone());
+                            }
+                        }
+                    }
+                    
+                    let record_index = execution.node_executions.len();
+                    execution.node_executions.push(begin_node_record(&node.id, &input_data, iteration));
+                    planned.push((node, record_index, input_data));
+                }
+                if persist {
+                    update_execution(&context.execution_id, execution)?;
+                }
+                
+                // Nodes see the variables as written by the groups before them
+                let node_context = ExecutionContext {
+                    global_variables: execution.variables.clone().unwrap_or_default(),
+                    ..context.clone()
+                };
+                
+                let results: Vec<Result<NodeOutput, NodeError>> = match planned.as_slice() {
+                    [(node, record_index, input_data)] if node.node_type == "for_each" => {
+                        handled.extend(loop_body_nodes(workflow, &node.id));
+                        let result = execute_for_each(workflow, node, input_data, &state, context, execution, persist).await
+                            .map_err(NodeError::from);
+                        finish_node_record(&mut execution.node_executions[*record_index], &result);
+                        vec![result]
+                    }
+                    _ => {
+                        let runs = planned.iter().map(|(node, record_index, input_data)| {
+                            let mut record = execution.node_executions[*record_index].clone();
+                            let node_context = &node_context;
+                            async move {
+                                let result = execute_single_node(node, input_data.clone(), node_context, &mut record).await;
+                                (record, result)
+                            }
+                        });
+                        let mut results = Vec::with_capacity(planned.len());
+                        for ((_, record_index, _), (record, result)) in planned.iter().zip(join_all(runs).await) {
+                            execution.node_executions[*record_index] = record;
+                            results.push(result);
+                        }
+                        results
+                    }
+                };
+                
+                // Merge in node id order so outputs, variables and records are deterministic
+                let mut delay_ms = 0;
+                for ((node, record_index, input_data), result) in planned.iter().zip(results) {
+                    // A node interrupted by a cancel (e.g. a for_each loop) is not a failure to handle
+                    if result.is_err() && persist && cancel_requested(&context.execution_id) {
+                        if let Some(request) = take_control_request(&context.execution_id) {
+                            apply_control_request(request, execution, &state, Vec::new())?;
+                            return Ok(state);
+                        }
+                    }
+                    
+                    let outcome = match result {
+                        Ok(output) => Ok(Some(output)),
+                        Err(error) => {
+                            let handled_output = handle_node_failure(workflow, node, input_data, error, &node_context).await;
+                            if let Ok(Some(output)) = &handled_output {
+                                // The record keeps the failure but shows what downstream nodes received
+                                execution.node_executions[*record_index].output_data = Some(output.data.clone());
+                            }
+                            handled_output
+                        }
+                    };
+                    
+                    let output = match outcome {
+                        Ok(output) => output,
+                        Err(error) => {
+                            if persist {
+                                update_execution(&context.execution_id, execution)?;
+                            }
+                            return Err(format!("Node {} failed: {}", node.id, error));
+                        }
+                    };
+                    
+                    if let Some(output) = output {
+                        if node.node_type == "set_variable" {
+                            if let (Some(ConfigValue::String(name)), Some(value)) = (output.data.get("name"), output.data.get("value")) {
+                                execution.variables.get_or_insert_with(HashMap::new).insert(name.clone(), value.clone());
+                            }
+                        }
+                        if node.node_type == "delay" {
+                            delay_ms = delay_ms.max(delay_duration_ms(&node.configuration)?);
+                        }
+                        
+                        state.node_outputs.insert(node.id.clone(), output.data);
+                        state.node_branches.insert(node.id.clone(), output.next_nodes);
+                    }
+                }
+                
+                if persist {
+                    update_execution(&context.execution_id, execution)?;
+                }
+                
+                if delay_ms > 0 {
+                    if !persist {
+                        return Err("Delay nodes cannot wait inside a for_each loop".to_string());
+                    }
+                    
+                    // Save the cursor and outputs so far, then hand over to a timer
+                    let remaining_nodes = remaining_nodes(&execution_order, batch_index, position, &handled);
+                    let resume_at = api::time() + delay_ms * 1_000_000;
+                    
+                    execution.checkpoint = Some(ExecutionCheckpoint {
+                        node_outputs: state.node_outputs.clone(),
+                        node_branches: state.node_branches.clone(),
+                        remaining_nodes,
+                        resume_at: None,
+                    });
+                    execution.status = ExecutionStatus::Waiting { resume_at };
+                    update_execution(&context.execution_id, execution)?;
+                    schedule_execution_resume(context.execution_id.clone(), resume_at);
+                    
+                    return Ok(state);
+                }
+            }
         }
         
-        for (node_id, result) in batch_results {
-            match result {
-                Ok(output) => {
-                    node_outputs.insert(node_id.clone(), output.data.clone());
-                    mark_node_completed(&execution_id, &node_id, Some(output), None)?;
+        Ok(state)
+    })
+}
+
+const DEFAULT_WORKFLOW_CONCURRENCY: usize = 5;
+const WORKFLOW_MAX_CONCURRENCY: usize = 10;
+
+/// Nodes of one batch that may run at once (`max_concurrency` on the workflow, 1-10).
+fn workflow_concurrency(workflow: &Workflow) -> usize {
+    workflow.max_concurrency
+        .map(|n| (n as usize).clamp(1, WORKFLOW_MAX_CONCURRENCY))
+        .unwrap_or(DEFAULT_WORKFLOW_CONCURRENCY)
+}
+
+const DEFAULT_FOR_EACH_MAX_ITERATIONS: usize = 100;
+const FOR_EACH_ITERATION_HARD_LIMIT: usize = 1000;
+const FOR_EACH_MAX_CONCURRENCY: usize = 10;
+
+/// Nodes reached through a for_each node's "item"/"index" outputs form its loop body.
+/// Anything also reachable from the loop's other outputs (e.g. "results") runs after the loop.
+fn loop_body_nodes(workflow: &Workflow, loop_node_id: &str) -> HashSet<String> {
+    let is_iteration_port = |output: &str| output == "item" || output == "index";
+    
+    let reachable = |seeds: Vec<String>, blocked: &HashSet<String>| {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut stack = seeds;
+        while let Some(current) = stack.pop() {
+            if blocked.contains(&current) || !seen.insert(current.clone()) {
+                continue;
+            }
+            for connection in &workflow.connections {
+                if connection.source_node_id == current {
+                    stack.push(connection.target_node_id.clone());
+                }
+            }
+        }
+        seen
+    };
+    
+    let outgoing = |iteration_ports: bool| -> Vec<String> {
+        workflow.connections.iter()
+            .filter(|c| c.source_node_id == loop_node_id && is_iteration_port(&c.source_output) == iteration_ports)
+            .map(|c| c.target_node_id.clone())
+            .collect()
+    };
+    
+    let blocked = HashSet::from([loop_node_id.to_string()]);
+    let after_loop = reachable(outgoing(false), &blocked);
+    
+    let mut blocked_for_body = after_loop;
+    blocked_for_body.insert(loop_node_id.to_string());
+    reachable(outgoing(true), &blocked_for_body)
+}
+
+fn config_number(node: &WorkflowNode, key: &str) -> Option<f64> {
+    match node.configuration.parameters.get(key) {
+        Some(ConfigValue::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Run the loop body once per element of the `items` array and collect each
+/// iteration's terminal node outputs into the `results` output.
+async fn execute_for_each(
+    workflow: &Workflow,
+    node: &WorkflowNode,
+    input_data: &HashMap<String, ConfigValue>,
+    state: &GraphState,
+    context: &ExecutionContext,
+    execution: &mut WorkflowExecution,
+    persist: bool
+) -> Result<NodeOutput, String> {
+    let items = match node.configuration.parameters.get("items") {
+        Some(ConfigValue::String(expr)) => evaluate_expression(expr, input_data)
+            .map_err(|e| format!("for_each items expression error: {}", e))?,
+        Some(ConfigValue::Array(items)) => ConfigValue::Array(items.clone()),
+        _ => input_data.get("items").cloned()
+            .ok_or("for_each node requires an 'items' input or parameter")?,
+    };
+    let ConfigValue::Array(items) = items else {
+        return Err("for_each items must be an array".to_string());
+    };
+    
+    let max_iterations = config_number(node, "max_iterations")
+        .map(|n| n.max(0.0) as usize)
+        .unwrap_or(DEFAULT_FOR_EACH_MAX_ITERATIONS)
+        .min(FOR_EACH_ITERATION_HARD_LIMIT);
+    if items.len() > max_iterations {
+        return Err(format!(
+            "for_each received {} items, exceeding max_iterations ({})",
+            items.len(), max_iterations
+        ));
+    }
+    
+    let concurrency = config_number(node, "concurrency")
+        .map(|n| n.max(1.0) as usize)
+        .unwrap_or(1)
+        .min(FOR_EACH_MAX_CONCURRENCY);
+    let continue_on_error = matches!(
+        node.configuration.parameters.get("continue_on_error"),
+        Some(ConfigValue::Boolean(true))
+    );
+    
+    let body = loop_body_nodes(workflow, &node.id);
+    let body_order: Vec<Vec<String>> = topological_sort(&build_execution_graph(workflow)?)?
+        .into_iter()
+        .map(|batch| batch.into_iter().filter(|id| body.contains(id)).collect::<Vec<_>>())
+        .filter(|batch| !batch.is_empty())
+        .collect();
+    // Terminal body nodes (no successors inside the body) provide each iteration's result
+    let terminal_nodes: Vec<String> = body.iter()
+        .filter(|id| !workflow.connections.iter()
+            .any(|c| &c.source_node_id == *id && body.contains(&c.target_node_id)))
+        .cloned()
+        .collect();
+    
+    let mut results = Vec::with_capacity(items.len());
+    let mut failures = 0u32;
+    
+    let indexed: Vec<(usize, ConfigValue)> = items.into_iter().enumerate().collect();
+    for chunk in indexed.chunks(concurrency) {
+        if cancel_requested(&context.execution_id) {
+            return Err("Execution cancelled".to_string());
+        }
+        
+        let variables_before = execution.variables.clone().unwrap_or_default();
+        let iterations = chunk.iter().map(|(index, item)| {
+            let mut seed = state.clone();
+            seed.node_outputs.insert(node.id.clone(), HashMap::from([
+                ("item".to_string(), item.clone()),
+                ("index".to_string(), ConfigValue::Number(*index as f64)),
+            ]));
+            seed.node_branches.insert(node.id.clone(), vec!["item".to_string()]);
+            
+            let mut scratch = WorkflowExecution {
+                node_executions: Vec::new(),
+                ..execution.clone()
+            };
+            let order = body_order.clone();
+            let index = *index as u32;
+            async move {
+                let outcome = run_graph(workflow, order, seed, context, &mut scratch, Some(index), false).await;
+                (outcome, scratch.node_executions, scratch.variables)
+            }
+        });
+        
+        for (outcome, records, variables) in join_all(iterations).await {
+            execution.node_executions.extend(records);
+            // Variable writes are applied in iteration order, so the last iteration wins
+            for (name, value) in variables.unwrap_or_default() {
+                if !variables_before.get(&name).is_some_and(|before| values_equal(before, &value)) {
+                    execution.variables.get_or_insert_with(HashMap::new).insert(name, value);
+                }
+            }
+            match outcome {
+                Ok(iteration_state) => {
+                    let result: HashMap<String, ConfigValue> = terminal_nodes.iter()
+                        .filter_map(|id| iteration_state.node_outputs.get(id)
+                            .map(|data| (id.clone(), ConfigValue::Object(data.clone()))))
+                        .collect();
+                    results.push(ConfigValue::Object(result));
+                }
+                Err(error) if continue_on_error => {
+                    failures += 1;
+                    results.push(ConfigValue::Object(HashMap::from([
+                        ("error".to_string(), ConfigValue::String(error)),
+                    ])));
                 }
                 Err(error) => {
-                    mark_node_failed(&execution_id, &node_id, &error)?;
-                    
-                    if is_critical_node(&workflow, &node_id) {
-                        return Err(format!("Critical node {} failed: {}", node_id, error));
-                    }
+                    return Err(format!("for_each iteration {} failed: {}", results.len(), error));
                 }
             }
         }
+        
+        if persist {
+            update_execution(&context.execution_id, execution)?;
+        }
     }
     
-    Ok(())
+    Ok(NodeOutput {
+        data: HashMap::from([
+            ("count".to_string(), ConfigValue::Number(results.len() as f64)),
+            ("failed".to_string(), ConfigValue::Number(failures as f64)),
+            ("results".to_string(), ConfigValue::Array(results)),
+        ]),
+        next_nodes: vec!["done".to_string()],
+    })
 }
 
 fn build_execution_graph(workflow: &Workflow) -> Result<ExecutionGraph, String> {
@@ -288,30 +1196,111 @@ fn validate_execution_graph(graph: &ExecutionGraph) -> Result<(), String> {
     Ok(())
 }
 
+/// A connection carries data only when its source node ran and, if that node
+/// selected output ports via `next_nodes`, the connection either names one of
+/// the selected ports or reads a plain data key from the output. A condition's
+/// data keys (e.g. `result`) follow its taken branch: they only reach nodes the
+/// taken port also reaches.
+fn is_connection_active(
+    workflow: &Workflow,
+    connection: &NodeConnection,
+    node_outputs: &HashMap<String, HashMap<String, ConfigValue>>,
+    node_branches: &HashMap<String, Vec<String>>
+) -> bool {
+    let Some(source_output) = node_outputs.get(&connection.source_node_id) else {
+        return false;
+    };
+    
+    // Error handlers only run when the source routed a failure to its "error" port
+    if connection.source_output == ERROR_PORT {
+        return node_branches.get(&connection.source_node_id)
+            .is_some_and(|ports| ports.iter().any(|port| port == ERROR_PORT));
+    }
+    
+    match node_branches.get(&connection.source_node_id) {
+        Some(ports) if !ports.is_empty() => {
+            if ports.contains(&connection.source_output) {
+                return true;
+            }
+            if !source_output.contains_key(&connection.source_output) {
+                return false;
+            }
+            let branched = ports.iter().any(|port| CONDITION_PORTS.contains(&port.as_str()));
+            !branched || workflow.connections.iter().any(|other| {
+                other.source_node_id == connection.source_node_id
+                    && other.target_node_id == connection.target_node_id
+                    && ports.contains(&other.source_output)
+            })
+        }
+        _ => true,
+    }
+}
+
+/// Entry nodes always run; every other node runs only if at least one of its
+/// incoming connections is active. Nodes downstream of an untaken branch are skipped.
+fn should_execute_node(
+    workflow: &Workflow,
+    node_id: &str,
+    node_outputs: &HashMap<String, HashMap<String, ConfigValue>>,
+    node_branches: &HashMap<String, Vec<String>>
+) -> bool {
+    let mut incoming = workflow.connections.iter()
+        .filter(|c| c.target_node_id == node_id)
+        .peekable();
+    
+    if incoming.peek().is_none() {
+        return true;
+    }
+    
+    incoming.any(|c| is_connection_active(workflow, c, node_outputs, node_branches))
+}
+
+fn mark_node_skipped(node_id: &str, execution: &mut WorkflowExecution, iteration: Option<u32>) {
+    let now = api::time();
+    execution.node_executions.push(NodeExecution {
+        node_id: node_id.to_string(),
+        status: ExecutionStatus::Skipped,
+        started_at: Some(now),
+        completed_at: Some(now),
+        input_data: None,
+        output_data: None,
+        error_message: None,
+        retry_count: 0,
+        iteration,
+        attempts: None,
+    });
+}
+
 fn prepare_node_input(
     workflow: &Workflow,
     node_id: &str,
-    node_outputs: &HashMap<String, HashMap<String, ConfigValue>>
+    node_outputs: &HashMap<String, HashMap<String, ConfigValue>>,
+    node_branches: &HashMap<String, Vec<String>>
 ) -> Result<HashMap<String, ConfigValue>, String> {
     let mut input_data = HashMap::new();
     
     for connection in &workflow.connections {
         if connection.target_node_id == node_id {
-            if let Some(source_output) = node_outputs.get(&connection.source_node_id) {
-                if let Some(value) = source_output.get(&connection.source_output) {
-                    input_data.insert(connection.target_input.clone(), value.clone());
-                    ic_cdk::println!("Using connection: output {} from node {}", 
-                                    connection.source_output, connection.source_node_id);
-                } else {
-                    return Err(format!(
-                        "Missing output {} from node {}", 
-                        connection.source_output, connection.source_node_id
-                    ));
+            // Sources that were skipped, or branches that were not taken, contribute nothing
+            if !is_connection_active(workflow, connection, node_outputs, node_branches) {
+                continue;
+            }
+            
+            let source_output = &node_outputs[&connection.source_node_id];
+            if let Some(value) = source_output.get(&connection.source_output) {
+                input_data.insert(connection.target_input.clone(), value.clone());
+                ic_cdk::println!("Using connection: output {} from node {}", 
+                                connection.source_output, connection.source_node_id);
+            } else if node_branches.get(&connection.source_node_id)
+                .is_some_and(|ports| ports.contains(&connection.source_output)) {
+                // Branch ports carry control flow; forward the source data as-is
+                for (key, value) in source_output {
+                    input_data.entry(key.clone()).or_insert_with(|| value.clone());
                 }
             } else {
                 return Err(format!(
-                    "Missing source node: {}", 
-                    connection.source_node_id
+                    "Missing output {} from node {}", 
+                    connection.source_output, connection.source_node_id
                 ));
             }
         }
@@ -321,26 +1310,11 @@ fn prepare_node_input(
 }
 
 async fn execute_single_node(
-    execution_id: &str,
     node: &WorkflowNode,
     input_data: HashMap<String, ConfigValue>,
     context: &ExecutionContext,
-    execution: &mut WorkflowExecution
-) -> Result<NodeOutput, String> {
-    let node_execution = NodeExecution {
-        node_id: node.id.clone(),
-        status: ExecutionStatus::Running,
-        started_at: Some(api::time()),
-        completed_at: None,
-        input_data: Some(input_data.clone()),
-        output_data: None,
-        error_message: None,
-        retry_count: 0,
-    };
-    
-    execution.node_executions.push(node_execution.clone());
-    update_execution(execution_id, execution)?;
-    
+    record: &mut NodeExecution
+) -> Result<NodeOutput, NodeError> {
     let retry_policy = get_retry_policy(&node.node_type);
     
     let result = execute_with_retry(
@@ -348,181 +1322,194 @@ async fn execute_single_node(
         &input_data,
         context,
         &retry_policy,
-        execution_id,
-        execution
+        record
     ).await;
     
-    if let Some(node_exec) = execution.node_executions.iter_mut()
-        .find(|ne| ne.node_id == node.id) {
-        match &result {
-            Ok(output) => {
-                node_exec.status = ExecutionStatus::Completed;
-                node_exec.completed_at = Some(api::time());
-                node_exec.output_data = Some(output.data.clone());
-            }
-            Err(error) => {
-                node_exec.status = ExecutionStatus::Failed;
-                node_exec.completed_at = Some(api::time());
-                node_exec.error_message = Some(error.clone());
-            }
-        }
-    }
-    
-    update_execution(execution_id, execution)?;
+    finish_node_record(record, &result);
     result
 }
 
-async fn execute_with_retry(
-    node: &WorkflowNode,
+fn begin_node_record(
+    node_id: &str,
     input_data: &HashMap<String, ConfigValue>,
-    context: &ExecutionContext,
-    _retry_policy: &RetryPolicy,
-    execution_id: &str,
-    execution: &mut WorkflowExecution
-) -> Result<NodeOutput, String> {
-    // Get recovery configuration for this node type
-    let recovery_config = get_recovery_config(&node.node_type);
-    
-    // Execute with self-healing recovery
-    execute_with_recovery(
-        node,
-        input_data,
-        context,
-        &recovery_config,
-        execution_id,
-        execution
-    ).await
+    iteration: Option<u32>
+) -> NodeExecution {
+    NodeExecution {
+        node_id: node_id.to_string(),
+        status: ExecutionStatus::Running,
+        started_at: Some(api::time()),
+        completed_at: None,
+        input_data: Some(input_data.clone()),
+        output_data: None,
+        error_message: None,
+        retry_count: 0,
+        iteration,
+        attempts: None,
+    }
 }
 
-async fn execute_with_recovery(
+fn finish_node_record(record: &mut NodeExecution, result: &Result<NodeOutput, NodeError>) {
+    match result {
+        Ok(output) => {
+            record.status = ExecutionStatus::Completed;
+            record.completed_at = Some(api::time());
+            record.output_data = Some(output.data.clone());
+        }
+        Err(error) => {
+            record.status = ExecutionStatus::Failed;
+            record.completed_at = Some(api::time());
+            record.error_message = Some(error.to_string());
+        }
+    }
+}
+
+async fn execute_with_retry(
     node: &WorkflowNode,
     input_data: &HashMap<String, ConfigValue>,
     context: &ExecutionContext,
-    recovery: &WorkflowRecovery,
-    execution_id: &str,
-    execution: &mut WorkflowExecution
-) -> Result<NodeOutput, String> {
-    let mut attempts = 0;
-    let mut last_error = String::new();
+    retry_policy: &RetryPolicy,
+    record: &mut NodeExecution
+) -> Result<NodeOutput, NodeError> {
+    let mut attempt = 0;
     
     loop {
+        attempt += 1;
+        let started_at = api::time();
         let result = execute_node_internal(node, input_data, context).await;
+        let attempts = record.attempts.get_or_insert_with(Vec::new);
+        record.retry_count = attempt - 1;
         
         match result {
             Ok(output) => {
-                // Success - update metrics and return
-                if let Some(node_exec) = execution.node_executions.iter_mut()
-                    .find(|ne| ne.node_id == node.id) {
-                    node_exec.retry_count = attempts;
-                }
-                update_execution(execution_id, execution).ok();
-                
+                attempts.push(NodeAttempt {
+                    attempt,
+                    started_at,
+                    completed_at: api::time(),
+                    error_message: None,
+                    error_class: None,
+                    retry_delay_ms: None,
+                });
                 return Ok(output);
             }
             Err(error) => {
-                attempts += 1;
-                last_error = error.clone();
+                let error_class = error.class();
+                let retry_delay_ms = (attempt <= retry_policy.max_retries
+                    && should_retry_error(error_class, &retry_policy.retry_on_errors))
+                    .then(|| calculate_retry_delay(attempt, retry_policy));
                 
-                // Log failure and update execution state
-                log_execution_failure(execution_id, &node.id, &error, attempts);
-                
-                if let Some(node_exec) = execution.node_executions.iter_mut()
-                    .find(|ne| ne.node_id == node.id) {
-                    node_exec.retry_count = attempts;
-                    node_exec.error_message = Some(error.clone());
-                }
-                update_execution(execution_id, execution).ok();
+                attempts.push(NodeAttempt {
+                    attempt,
+                    started_at,
+                    completed_at: api::time(),
+                    error_message: Some(error.to_string()),
+                    error_class: Some(error_class.to_string()),
+                    retry_delay_ms,
+                });
+                record.error_message = Some(error.to_string());
+                log_execution_failure(&context.execution_id, &node.id, &error.to_string(), attempt);
                 
-                if attempts < recovery.max_retries {
-                    // Exponential backoff delay
-                    let delay = recovery.retry_delay_ms * (2_u64.pow(attempts - 1));
-                    let delay_ns = Duration::from_millis(delay);
-                    
-                    ic_cdk::println!(
-                        "Node {} failed (attempt {}), retrying in {}ms: {}",
-                        node.id, attempts, delay, error
-                    );
-                    
-                    // Non-blocking delay using timer
-                    let _timer = set_timer(delay_ns, || {});
-                    
-                    // Try fallback strategy if available
-                    if let Some(ref fallback) = recovery.fallback_strategy {
-                        if let Ok(fallback_result) = execute_fallback_strategy(
-                            node, 
-                            input_data, 
-                            context, 
-                            fallback,
-                            execution_id,
-                            execution
-                        ).await {
-                            return Ok(fallback_result);
+                match retry_delay_ms {
+                    Some(delay_ms) => {
+                        ic_cdk::println!(
+                            "Node {} failed with {} (attempt {}), retrying in {}ms: {}",
+                            node.id, error_class, attempt, delay_ms, error
+                        );
+                        sleep(Duration::from_millis(delay_ms)).await;
+                        // A cancel that arrived during the backoff stops the run instead of another attempt
+                        if cancel_requested(&context.execution_id) {
+                            return Err(NodeError::ExecutionError("Execution cancelled".to_string()));
                         }
                     }
-                } else {
-                    // Max retries exceeded - execute emergency actions
-                    ic_cdk::println!(
-                        "Node {} max retries ({}) exceeded, executing emergency actions", 
-                        node.id, attempts
-                    );
-                    
-                    execute_emergency_actions(&recovery.emergency_actions, execution_id, &node.id).await;
-                    
-                    return Err(format!(
-                        "Node {} failed after {} attempts: {}", 
-                        node.id, attempts, last_error
-                    ));
+                    None => {
+                        let recovery_config = get_recovery_config(&node.node_type);
+                        return execute_with_recovery(node, &recovery_config, &context.execution_id, attempt, error).await;
+                    }
                 }
             }
         }
     }
 }
 
+/// Resolves once a one-shot timer fires, letting other messages run in between.
+async fn sleep(duration: Duration) {
+    let (sender, receiver) = oneshot::channel();
+    set_timer(duration, move || {
+        let _ = sender.send(());
+    });
+    let _ = receiver.await;
+}
+
+/// Runs the recovery configuration's emergency actions once a node has used up its retries.
+async fn execute_with_recovery(
+    node: &WorkflowNode,
+    recovery: &WorkflowRecovery,
+    execution_id: &str,
+    attempts: u32,
+    error: NodeError
+) -> Result<NodeOutput, NodeError> {
+    ic_cdk::println!(
+        "Node {} failed after {} attempts, executing emergency actions", 
+        node.id, attempts
+    );
+    
+    execute_emergency_actions(&recovery.emergency_actions, execution_id, &node.id).await;
+    
+    Err(error.map_message(|message| format!(
+        "Node {} failed after {} attempts: {}", 
+        node.id, attempts, message
+    )))
+}
+
 fn get_recovery_config(_node_type: &str) -> WorkflowRecovery {
     // In a real implementation, this would be configurable per node type
     // For now, return default recovery configuration
     WorkflowRecovery::default()
 }
 
-async fn execute_fallback_strategy(
+/// Apply the failed node's `on_error` strategy (falling back to its type's recovery config).
+/// `Ok(None)` continues the workflow without output from this node, so nodes that depend
+/// only on it are skipped; `Err` fails the workflow.
+async fn handle_node_failure(
+    workflow: &Workflow,
     node: &WorkflowNode,
-    _input_data: &HashMap<String, ConfigValue>,
-    _context: &ExecutionContext,
-    fallback: &FallbackStrategy,
-    execution_id: &str,
-    _execution: &mut WorkflowExecution
-) -> Result<NodeOutput, String> {
-    match fallback {
-        FallbackStrategy::UseAlternativeNode { node_id } => {
-            // In a real implementation, we would execute the alternative node
-            // For now, return a simulated success
-            Ok(NodeOutput {
-                data: [("fallback".to_string(), ConfigValue::Boolean(true))].into(),
-                next_nodes: vec![],
-            })
-        }
-        FallbackStrategy::SkipNode => {
-            Ok(NodeOutput {
-                data: [("skipped".to_string(), ConfigValue::Boolean(true))].into(),
-                next_nodes: vec![],
-            })
+    input_data: &HashMap<String, ConfigValue>,
+    error: NodeError,
+    context: &ExecutionContext
+) -> Result<Option<NodeOutput>, String> {
+    let strategy = node.on_error.clone()
+        .or_else(|| get_recovery_config(&node.node_type).fallback_strategy);
+    
+    let error_data = || HashMap::from([
+        ("error".to_string(), ConfigValue::String(error.to_string())),
+        ("error_type".to_string(), ConfigValue::String(error.class().to_string())),
+        ("failed_node_id".to_string(), ConfigValue::String(node.id.clone())),
+    ]);
+    
+    match strategy {
+        None | Some(FallbackStrategy::StopExecution) => Err(error.to_string()),
+        Some(FallbackStrategy::SkipNode) => Ok(None),
+        Some(FallbackStrategy::NotifyAndContinue) => {
+            send_failure_notification(&context.execution_id, &node.id).await;
+            Ok(None)
         }
-        FallbackStrategy::StopExecution => {
-            Err("Execution stopped by fallback strategy".to_string())
+        Some(FallbackStrategy::UseDefaultValue { value }) => {
+            let mut data = match value {
+                ConfigValue::Object(fields) => fields,
+                other => HashMap::from([("value".to_string(), other)]),
+            };
+            data.extend(error_data());
+            Ok(Some(NodeOutput { data, next_nodes: Vec::new() }))
         }
-        FallbackStrategy::UseDefaultValue { value } => {
-            Ok(NodeOutput {
-                data: [("default".to_string(), value.clone())].into(),
-                next_nodes: vec![],
-            })
+        Some(FallbackStrategy::RouteToErrorPort) => {
+            Ok(Some(NodeOutput { data: error_data(), next_nodes: vec![ERROR_PORT.to_string()] }))
         }
-        FallbackStrategy::NotifyAndContinue => {
-            // Send notification (simulated)
-            send_failure_notification(execution_id, &node.id).await;
-            Ok(NodeOutput {
-                data: [("notified".to_string(), ConfigValue::Boolean(true))].into(),
-                next_nodes: vec![],
-            })
+        Some(FallbackStrategy::UseAlternativeNode { node_id }) => {
+            let alternative = workflow.nodes.iter()
+                .find(|n| n.id == node_id)
+                .ok_or_else(|| format!("{} (alternative node {} not found)", error, node_id))?;
+            execute_node_internal(alternative, input_data, context).await
+                .map(Some)
+                .map_err(|alt_error| format!("{} (alternative node {} also failed: {})", error, node_id, alt_error))
         }
     }
 }
@@ -543,10 +1530,10 @@ async fn execute_emergency_actions(
             }
             EmergencyAction::ExecuteWorkflow { workflow_id } => {
                 // Execute emergency workflow
-                if let Ok(emergency_execution_id) = crate::execution::start_execution(
+                if let Ok(emergency_execution_id) = trigger_execution(
                     workflow_id.clone(), 
                     Some([("emergency".to_string(), ConfigValue::Boolean(true))].into())
-                ).await {
+                ) {
                 }
             }
             EmergencyAction::LiquidatePosition { asset, percentage } => {
@@ -625,15 +1612,13 @@ fn log_execution_failure(execution_id: &str, node_id: &str, error: &str, attempt
 
 fn get_retry_policy(node_type: &str) -> RetryPolicy {
     storage::get_retry_policy(node_type)
-        .unwrap_or_default()
+        .unwrap_or_else(|| RetryPolicy::default_for(node_type))
 }
 
-#[allow(dead_code)]
-fn should_retry_error(error: &str, retry_on_errors: &[String]) -> bool {
-    retry_on_errors.iter().any(|retry_error| error.contains(retry_error))
+fn should_retry_error(error_class: &str, retry_on_errors: &[String]) -> bool {
+    retry_on_errors.iter().any(|retry_class| retry_class == error_class)
 }
 
-#[allow(dead_code)]
 fn calculate_retry_delay(retry_count: u32, policy: &RetryPolicy) -> u64 {
     let delay = policy.initial_delay_ms as f64 * policy.backoff_multiplier.powi(retry_count as i32 - 1);
     let delay = delay as u64;
@@ -645,43 +1630,6 @@ fn update_execution(execution_id: &str, execution: &WorkflowExecution) -> Result
     Ok(())
 }
 
-fn mark_node_completed(
-    execution_id: &str,
-    node_id: &str,
-    output: Option<NodeOutput>,
-    _error: Option<String>
-) -> Result<(), String> {
-    if let Some(mut execution) = storage::get_execution(execution_id) {
-        if let Some(node_exec) = execution.node_executions.iter_mut()
-            .find(|ne| ne.node_id == node_id) {
-            node_exec.status = ExecutionStatus::Completed;
-            node_exec.completed_at = Some(api::time());
-            if let Some(output) = output {
-                node_exec.output_data = Some(output.data);
-            }
-        }
-        storage::insert_execution(execution_id.to_string(), execution);
-    }
-    Ok(())
-}
-
-fn mark_node_failed(execution_id: &str, node_id: &str, error: &str) -> Result<(), String> {
-    if let Some(mut execution) = storage::get_execution(execution_id) {
-        if let Some(node_exec) = execution.node_executions.iter_mut()
-            .find(|ne| ne.node_id == node_id) {
-            node_exec.status = ExecutionStatus::Failed;
-            node_exec.completed_at = Some(api::time());
-            node_exec.error_message = Some(error.to_string());
-        }
-        storage::insert_execution(execution_id.to_string(), execution);
-    }
-    Ok(())
-}
-
-fn is_critical_node(_workflow: &Workflow, _node_id: &str) -> bool {
-    true
-}
-
 // Zero-downtime workflow recovery
 pub fn resume_active_workflows() {
     use crate::storage::{get_workflow_state, update_workflow_state};
@@ -727,7 +1675,10 @@ pub fn resume_active_workflows() {
                     storage::insert_execution(execution.id.clone(), execution.clone());
                 }
             }
-            ExecutionStatus::Completed | ExecutionStatus::Cancelled => {
+            ExecutionStatus::Waiting { .. } | ExecutionStatus::Paused => {
+                // Resume timers are re-armed by restore_persistent_timers; paused runs wait for resume_execution
+            }
+            ExecutionStatus::Completed | ExecutionStatus::Cancelled | ExecutionStatus::Skipped => {
                 // Remove completed/cancelled executions from active list
                 state.active_workflows.retain(|(id, _)| id != &workflow_id);
             }
@@ -740,4 +1691,284 @@ pub fn resume_active_workflows() {
     
     update_workflow_state(state);
     
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{NodeConnection, WorkflowAcl, WorkflowShare};
+
+    fn connection(source: &str, output: &str, target: &str) -> NodeConnection {
+        NodeConnection {
+            id: format!("{}-{}", source, target),
+            source_node_id: source.to_string(),
+            source_output: output.to_string(),
+            target_node_id: target.to_string(),
+            target_input: "input".to_string(),
+        }
+    }
+
+    fn branching_workflow() -> Workflow {
+        let node = |id: &str| WorkflowNode { id: id.to_string(), ..Default::default() };
+        Workflow {
+            nodes: vec![node("check"), node("swap"), node("alert")],
+            connections: vec![
+                connection("check", "true", "swap"),
+                connection("check", "false", "alert"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_untaken_branch_is_skipped() {
+        let workflow = branching_workflow();
+        let outputs = HashMap::from([(
+            "check".to_string(),
+            HashMap::from([("result".to_string(), ConfigValue::Boolean(false))]),
+        )]);
+        let branches = HashMap::from([("check".to_string(), vec!["false".to_string()])]);
+
+        assert!(should_execute_node(&workflow, "check", &outputs, &branches));
+        assert!(!should_execute_node(&workflow, "swap", &outputs, &branches));
+        assert!(should_execute_node(&workflow, "alert", &outputs, &branches));
+    }
+
+    #[test]
+    fn test_branch_port_forwards_source_data() {
+        let workflow = branching_workflow();
+        let outputs = HashMap::from([(
+            "check".to_string(),
+            HashMap::from([("result".to_string(), ConfigValue::Boolean(true))]),
+        )]);
+        let branches = HashMap::from([("check".to_string(), vec!["true".to_string()])]);
+
+        let input = prepare_node_input(&workflow, "swap", &outputs, &branches).unwrap();
+        assert!(matches!(input.get("result"), Some(ConfigValue::Boolean(true))));
+    }
+
+    #[test]
+    fn test_condition_data_connections_follow_the_taken_branch() {
+        let mut workflow = branching_workflow();
+        workflow.connections.push(connection("check", "result", "swap"));
+        workflow.nodes.push(WorkflowNode { id: "log".to_string(), ..Default::default() });
+        workflow.connections.push(connection("check", "result", "log"));
+        let outputs = HashMap::from([(
+            "check".to_string(),
+            HashMap::from([("result".to_string(), ConfigValue::Boolean(false))]),
+        )]);
+        let branches = HashMap::from([("check".to_string(), vec!["false".to_string()])]);
+
+        assert!(!should_execute_node(&workflow, "swap", &outputs, &branches));
+        assert!(!should_execute_node(&workflow, "log", &outputs, &branches));
+
+        let branches = HashMap::from([("check".to_string(), vec!["true".to_string()])]);
+        assert!(should_execute_node(&workflow, "swap", &outputs, &branches));
+        assert!(!should_execute_node(&workflow, "log", &outputs, &branches));
+    }
+
+    #[test]
+    fn test_error_port_routes_failures_to_error_handlers() {
+        let node = |id: &str| WorkflowNode { id: id.to_string(), ..Default::default() };
+        let mut workflow = Workflow {
+            nodes: vec![node("fetch"), node("parse"), node("on_failure")],
+            connections: vec![
+                connection("fetch", "body", "parse"),
+                connection("fetch", "error", "on_failure"),
+            ],
+            ..Default::default()
+        };
+        workflow.nodes[0].on_error = Some(FallbackStrategy::RouteToErrorPort);
+        let context = ExecutionContext {
+            workflow_id: workflow.id.clone(),
+            execution_id: "exec".to_string(),
+            user_id: "anonymous".to_string(),
+            timestamp: 0,
+            global_variables: HashMap::new(),
+        };
+
+        let failure = futures::executor::block_on(handle_node_failure(
+            &workflow, &workflow.nodes[0], &HashMap::new(), NodeError::NetworkError("HTTP request failed: 503".to_string()), &context
+        )).unwrap().unwrap();
+        assert!(matches!(failure.data.get("error_type"), Some(ConfigValue::String(class)) if class == "NetworkError"));
+
+        let failed_outputs = HashMap::from([("fetch".to_string(), failure.data)]);
+        let failed_branches = HashMap::from([("fetch".to_string(), failure.next_nodes)]);
+        assert!(should_execute_node(&workflow, "on_failure", &failed_outputs, &failed_branches));
+        assert!(!should_execute_node(&workflow, "parse", &failed_outputs, &failed_branches));
+
+        let ok_outputs = HashMap::from([(
+            "fetch".to_string(),
+            HashMap::from([("body".to_string(), ConfigValue::String("{}".to_string()))]),
+        )]);
+        let ok_branches = HashMap::from([("fetch".to_string(), Vec::new())]);
+        assert!(should_execute_node(&workflow, "parse", &ok_outputs, &ok_branches));
+        assert!(!should_execute_node(&workflow, "on_failure", &ok_outputs, &ok_branches));
+    }
+
+    #[test]
+    fn test_downstream_of_skipped_node_is_skipped() {
+        let mut workflow = branching_workflow();
+        workflow.nodes.push(WorkflowNode { id: "notify".to_string(), ..Default::default() });
+        workflow.connections.push(connection("swap", "tx_hash", "notify"));
+        let outputs = HashMap::from([(
+            "check".to_string(),
+            HashMap::from([("result".to_string(), ConfigValue::Boolean(false))]),
+        )]);
+        let branches = HashMap::from([("check".to_string(), vec!["false".to_string()])]);
+
+        assert!(!should_execute_node(&workflow, "notify", &outputs, &branches));
+    }
+
+    #[test]
+    fn test_loop_body_excludes_nodes_after_the_loop() {
+        let node = |id: &str| WorkflowNode { id: id.to_string(), ..Default::default() };
+        let workflow = Workflow {
+            nodes: vec![node("wallets"), node("loop"), node("balance"), node("alert"), node("summary")],
+            connections: vec![
+                connection("wallets", "items", "loop"),
+                connection("loop", "item", "balance"),
+                connection("balance", "balance", "alert"),
+                connection("alert", "sent", "summary"),
+                connection("loop", "results", "summary"),
+            ],
+            ..Default::default()
+        };
+
+        let body = loop_body_nodes(&workflow, "loop");
+        assert_eq!(body, HashSet::from(["balance".to_string(), "alert".to_string()]));
+    }
+
+    #[test]
+    fn test_retry_policy_matches_error_classes_and_backs_off() {
+        let policy = RetryPolicy::default();
+        let retryable = |error: NodeError| should_retry_error(error.class(), &policy.retry_on_errors);
+
+        assert!(retryable(NodeError::NetworkError("HTTP request failed: connection reset".to_string())));
+        assert!(retryable(NodeError::TimeoutError));
+        assert!(!retryable(NodeError::ConfigurationError("Missing to_address parameter".to_string())));
+        assert!(!retryable(NodeError::ValidationError("Spending limit exceeded for BTC".to_string())));
+        // Messages are never inspected: unclassified failures are not retried, whatever they say
+        assert!(!retryable(NodeError::from("HTTP POST https://api.example.com returned status 400".to_string())));
+        assert!(!retryable(NodeError::from("Connection rejected: transaction rejected".to_string())));
+
+        assert_eq!(calculate_retry_delay(1, &policy), 1000);
+        assert_eq!(calculate_retry_delay(3, &policy), 4000);
+        assert_eq!(calculate_retry_delay(10, &policy), policy.max_delay_ms);
+    }
+
+    #[test]
+    fn test_pause_checkpoint_keeps_current_and_later_batches() {
+        let order = vec![
+            vec!["fetch".to_string()],
+            vec!["loop".to_string(), "price".to_string(), "body".to_string()],
+            vec!["swap".to_string()],
+        ];
+        let handled = HashSet::from(["body".to_string()]);
+
+        assert_eq!(
+            remaining_nodes(&order, 1, 1, &handled),
+            vec![vec!["price".to_string()], vec!["swap".to_string()]]
+        );
+        assert_eq!(remaining_nodes(&order, 2, 1, &handled), Vec::<Vec<String>>::new());
+    }
+
+    #[test]
+    fn test_cancel_request_survives_until_taken() {
+        CONTROL_REQUESTS.with(|requests| {
+            requests.borrow_mut().insert("exec-cancel".to_string(), ControlRequest::Cancel {
+                by: "owner".to_string(),
+                reason: Some("market moved".to_string()),
+            });
+        });
+
+        assert!(cancel_requested("exec-cancel"));
+        assert!(matches!(take_control_request("exec-cancel"), Some(ControlRequest::Cancel { .. })));
+        assert!(!cancel_requested("exec-cancel"));
+    }
+
+    #[test]
+    fn test_cancel_reaches_live_sub_workflow_runs() {
+        let parent = LiveRun::start("exec-parent", None);
+        let child = LiveRun::start("exec-child", Some("exec-parent".to_string()));
+        let _grandchild = LiveRun::start("exec-grandchild", Some("exec-child".to_string()));
+        let _other = LiveRun::start("exec-other", None);
+
+        request_cancel("exec-parent", "owner".to_string(), None);
+        assert!(cancel_requested("exec-parent"));
+        assert!(cancel_requested("exec-child"));
+        assert!(cancel_requested("exec-grandchild"));
+        assert!(!cancel_requested("exec-other"));
+        match take_control_request("exec-grandchild") {
+            Some(ControlRequest::Cancel { reason, .. }) => {
+                assert_eq!(reason.as_deref(), Some("parent execution exec-child was cancelled"));
+            }
+            _ => panic!("expected a cancel request"),
+        }
+
+        drop(child);
+        drop(parent);
+        assert!(!is_live("exec-child"));
+        assert!(is_live("exec-grandchild"));
+    }
+
+    #[test]
+    fn test_workflow_concurrency_is_capped() {
+        let mut workflow = Workflow::default();
+        assert_eq!(workflow_concurrency(&workflow), DEFAULT_WORKFLOW_CONCURRENCY);
+
+        workflow.max_concurrency = Some(0);
+        assert_eq!(workflow_concurrency(&workflow), 1);
+
+        workflow.max_concurrency = Some(64);
+        assert_eq!(workflow_concurrency(&workflow), WORKFLOW_MAX_CONCURRENCY);
+    }
+
+    #[test]
+    fn test_sub_workflow_requires_runner_access_to_the_child() {
+        let child = Workflow {
+            id: "wf-child-acl".to_string(),
+            owner: Some("alice".to_string()),
+            active: true,
+            ..Default::default()
+        };
+        storage::insert_workflow(child.id.clone(), child.clone());
+        storage::insert_workflow_acl(WorkflowAcl {
+            workflow_id: child.id.clone(),
+            shares: vec![WorkflowShare {
+                principal: "carol".to_string(),
+                role: WorkflowRole::Viewer,
+                granted_by: "alice".to_string(),
+                granted_at: 0,
+            }],
+        });
+        let mut node = WorkflowNode { id: "call".to_string(), ..Default::default() };
+        node.configuration.parameters.insert("workflow_id".to_string(), ConfigValue::String(child.id.clone()));
+
+        for user in ["bob", "carol"] {
+            let context = ExecutionContext {
+                workflow_id: "wf-parent".to_string(),
+                execution_id: "exec-acl-parent".to_string(),
+                user_id: user.to_string(),
+                timestamp: 0,
+                global_variables: HashMap::new(),
+            };
+            let error = futures::executor::block_on(execute_sub_workflow_node(&node, &HashMap::new(), &context)).unwrap_err();
+            assert!(error.starts_with("Access denied"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_execution_depth_follows_parent_chain() {
+        let ids = ["depth-root", "depth-child", "depth-grandchild"];
+        for (i, id) in ids.iter().enumerate() {
+            storage::insert_execution(id.to_string(), WorkflowExecution {
+                id: id.to_string(),
+                parent_execution_id: i.checked_sub(1).map(|p| ids[p].to_string()),
+                ..Default::default()
+            });
+        }
+
+        assert_eq!(execution_depth("depth-root"), 0);
+        assert_eq!(execution_depth("depth-grandchild"), 2);
+    }
+}
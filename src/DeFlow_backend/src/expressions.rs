@@ -0,0 +1,1060 @@
+// Sandboxed expression language used by condition and transform nodes.
+//
+// Expressions are evaluated over the node input (`HashMap<String, ConfigValue>`)
+// and can only read data: there are no assignments, loops, clocks or randomness,
+// so the same input always produces the same result on every replica.
+//
+//   price < 3000 && token in ["ETH", "WBTC"]
+//   body.data[0].price * 1.05
+//   starts_with(lower(from), "0xdead")
+//
+// Templates embed expressions in text as `{{ expression }}`, e.g.
+// `https://api.example.com/quotes/{{ lower(token) }}?limit={{ limit }}`.
+
+use crate::types::ConfigValue;
+use std::collections::HashMap;
+use std::fmt;
+
+const MAX_EXPRESSION_LENGTH: usize = 4096;
+const MAX_NESTING_DEPTH: usize = 64;
+const MAX_EVALUATION_STEPS: usize = 10_000;
+const MAX_STRING_LENGTH: usize = 64 * 1024;
+
+/// Parse or evaluation error with the character position it refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpressionError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl ExpressionError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        Self { message: message.into(), position }
+    }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position + 1)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    True,
+    False,
+    And,
+    Or,
+    Not,
+    In,
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Dot,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+            Token::And => write!(f, "and"),
+            Token::Or => write!(f, "or"),
+            Token::Not => write!(f, "not"),
+            Token::In => write!(f, "in"),
+            Token::Op(op) => write!(f, "{}", op),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+            Token::Comma => write!(f, ","),
+            Token::Dot => write!(f, "."),
+            Token::Eof => write!(f, "end of expression"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                // A dot only belongs to the number when a digit follows it
+                if chars[i] == '.' && !chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()) {
+                    break;
+                }
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text.parse::<f64>()
+                .map_err(|_| ExpressionError::new(format!("Invalid number '{}'", text), start))?;
+            tokens.push((Token::Number(number), start));
+            continue;
+        }
+
+        if c == '"' || c == '\'' {
+            i += 1;
+            let mut value = String::new();
+            loop {
+                match chars.get(i) {
+                    None => return Err(ExpressionError::new("Unterminated string literal", start)),
+                    Some(&ch) if ch == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some('\\') => {
+                        let escaped = match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(&other) => other,
+                            None => return Err(ExpressionError::new("Unterminated string literal", start)),
+                        };
+                        value.push(escaped);
+                        i += 2;
+                    }
+                    Some(&ch) => {
+                        value.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Token::Str(value), start));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' || c == '$' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let token = match word.as_str() {
+                "true" => Token::True,
+                "false" => Token::False,
+                "and" => Token::And,
+                "or" => Token::Or,
+                "not" => Token::Not,
+                "in" => Token::In,
+                _ => Token::Ident(word),
+            };
+            tokens.push((token, start));
+            continue;
+        }
+
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let token = match two.as_str() {
+            "==" => Some(Token::Op("==")),
+            "!=" => Some(Token::Op("!=")),
+            "<=" => Some(Token::Op("<=")),
+            ">=" => Some(Token::Op(">=")),
+            "&&" => Some(Token::And),
+            "||" => Some(Token::Or),
+            _ => None,
+        };
+        if let Some(token) = token {
+            tokens.push((token, start));
+            i += 2;
+            continue;
+        }
+
+        let token = match c {
+            '<' => Token::Op("<"),
+            '>' => Token::Op(">"),
+            '+' => Token::Op("+"),
+            '-' => Token::Op("-"),
+            '*' => Token::Op("*"),
+            '/' => Token::Op("/"),
+            '%' => Token::Op("%"),
+            '!' => Token::Not,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ',' => Token::Comma,
+            '.' => Token::Dot,
+            _ => return Err(ExpressionError::new(format!("Unexpected character '{}'", c), start)),
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+
+    tokens.push((Token::Eof, chars.len()));
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Literal(ConfigValue),
+    List(Vec<Expr>),
+    Identifier(String),
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Clone, Debug)]
+struct Expr {
+    node: Node,
+    position: usize,
+}
+
+/// A parsed expression, ready to be evaluated against any number of inputs.
+#[derive(Clone, Debug)]
+pub struct Expression {
+    root: Expr,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.index].1
+    }
+
+    fn advance(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.index].clone();
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ExpressionError> {
+        if *self.peek() == expected {
+            self.advance();
+            Ok(())
+        } else {
+            Err(ExpressionError::new(
+                format!("Expected '{}' but found '{}'", expected, self.peek()),
+                self.position(),
+            ))
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(ExpressionError::new("Expression is nested too deeply", self.position()));
+        }
+        Ok(())
+    }
+
+    fn parse_expression(&mut self) -> Result<Expr, ExpressionError> {
+        self.enter()?;
+        let expr = self.parse_or();
+        self.depth -= 1;
+        expr
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.parse_and()?;
+        while *self.peek() == Token::Or {
+            let (_, position) = self.advance();
+            let right = self.parse_and()?;
+            left = binary("||", left, right, position);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.parse_equality()?;
+        while *self.peek() == Token::And {
+            let (_, position) = self.advance();
+            let right = self.parse_equality()?;
+            left = binary("&&", left, right, position);
+        }
+        Ok(left)
+    }
+
+    fn parse_equality(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.parse_comparison()?;
+        while let Token::Op(op @ ("==" | "!=")) = *self.peek() {
+            let (_, position) = self.advance();
+            let right = self.parse_comparison()?;
+            left = binary(op, left, right, position);
+        }
+        Ok(left)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.parse_additive()?;
+        loop {
+            let op = match *self.peek() {
+                Token::Op(op @ ("<" | "<=" | ">" | ">=")) => op,
+                Token::In => "in",
+                _ => break,
+            };
+            let (_, position) = self.advance();
+            let right = self.parse_additive()?;
+            left = binary(op, left, right, position);
+        }
+        Ok(left)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.parse_multiplicative()?;
+        while let Token::Op(op @ ("+" | "-")) = *self.peek() {
+            let (_, position) = self.advance();
+            let right = self.parse_multiplicative()?;
+            left = binary(op, left, right, position);
+        }
+        Ok(left)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.parse_unary()?;
+        while let Token::Op(op @ ("*" | "/" | "%")) = *self.peek() {
+            let (_, position) = self.advance();
+            let right = self.parse_unary()?;
+            left = binary(op, left, right, position);
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ExpressionError> {
+        let op = match self.peek() {
+            Token::Not => "!",
+            Token::Op("-") => "-",
+            _ => return self.parse_postfix(),
+        };
+        let (_, position) = self.advance();
+        self.enter()?;
+        let operand = self.parse_unary();
+        self.depth -= 1;
+        Ok(Expr { node: Node::Unary(op, Box::new(operand?)), position })
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ExpressionError> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            match self.peek() {
+                Token::Dot => {
+                    let (_, position) = self.advance();
+                    match self.advance() {
+                        (Token::Ident(name), _) => {
+                            expr = Expr { node: Node::Member(Box::new(expr), name), position };
+                        }
+                        (token, position) => {
+                            return Err(ExpressionError::new(
+                                format!("Expected field name after '.' but found '{}'", token),
+                                position,
+                            ));
+                        }
+                    }
+                }
+                Token::LBracket => {
+                    let (_, position) = self.advance();
+                    let index = self.parse_expression()?;
+                    self.expect(Token::RBracket)?;
+                    expr = Expr { node: Node::Index(Box::new(expr), Box::new(index)), position };
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ExpressionError> {
+        let (token, position) = self.advance();
+        let node = match token {
+            Token::Number(n) => Node::Literal(ConfigValue::Number(n)),
+            Token::Str(s) => Node::Literal(ConfigValue::String(s)),
+            Token::True => Node::Literal(ConfigValue::Boolean(true)),
+            Token::False => Node::Literal(ConfigValue::Boolean(false)),
+            Token::Ident(name) => {
+                if *self.peek() == Token::LParen {
+                    self.advance();
+                    let args = self.parse_list(Token::RParen)?;
+                    check_function_arity(&name, args.len(), position)?;
+                    Node::Call(name, args)
+                } else {
+                    Node::Identifier(name)
+                }
+            }
+            Token::LParen => {
+                let inner = self.parse_expression()?;
+                self.expect(Token::RParen)?;
+                return Ok(inner);
+            }
+            Token::LBracket => Node::List(self.parse_list(Token::RBracket)?),
+            Token::Eof => return Err(ExpressionError::new("Unexpected end of expression", position)),
+            other => return Err(ExpressionError::new(format!("Unexpected token '{}'", other), position)),
+        };
+        Ok(Expr { node, position })
+    }
+
+    fn parse_list(&mut self, close: Token) -> Result<Vec<Expr>, ExpressionError> {
+        let mut items = Vec::new();
+        if *self.peek() == close {
+            self.advance();
+            return Ok(items);
+        }
+        loop {
+            items.push(self.parse_expression()?);
+            if *self.peek() == Token::Comma {
+                self.advance();
+            } else {
+                self.expect(close)?;
+                return Ok(items);
+            }
+        }
+    }
+}
+
+fn binary(op: &'static str, left: Expr, right: Expr, position: usize) -> Expr {
+    Expr { node: Node::Binary(op, Box::new(left), Box::new(right)), position }
+}
+
+/// (name, minimum arguments, maximum arguments)
+const FUNCTIONS: &[(&str, usize, usize)] = &[
+    ("len", 1, 1),
+    ("lower", 1, 1),
+    ("upper", 1, 1),
+    ("trim", 1, 1),
+    ("contains", 2, 2),
+    ("starts_with", 2, 2),
+    ("ends_with", 2, 2),
+    ("substring", 2, 3),
+    ("replace", 3, 3),
+    ("split", 2, 2),
+    ("join", 2, 2),
+    ("concat", 1, 16),
+    ("string", 1, 1),
+    ("number", 1, 1),
+    ("abs", 1, 1),
+    ("round", 1, 2),
+    ("floor", 1, 1),
+    ("ceil", 1, 1),
+    ("min", 1, 16),
+    ("max", 1, 16),
+    ("keys", 1, 1),
+    ("json_parse", 1, 1),
+    ("json_stringify", 1, 1),
+    // Lazily evaluated: tolerate missing fields in their arguments
+    ("exists", 1, 1),
+    ("default", 2, 2),
+];
+
+fn check_function_arity(name: &str, count: usize, position: usize) -> Result<(), ExpressionError> {
+    let (_, min, max) = FUNCTIONS.iter()
+        .find(|(function, _, _)| *function == name)
+        .ok_or_else(|| ExpressionError::new(format!("Unknown function '{}'", name), position))?;
+
+    if count < *min || count > *max {
+        let expected = if min == max { min.to_string() } else { format!("{} to {}", min, max) };
+        return Err(ExpressionError::new(
+            format!("Function '{}' expects {} argument(s), got {}", name, expected, count),
+            position,
+        ));
+    }
+    Ok(())
+}
+
+/// Parse an expression without evaluating it, e.g. to validate a workflow at save time.
+pub fn parse_expression(source: &str) -> Result<Expression, ExpressionError> {
+    if source.chars().count() > MAX_EXPRESSION_LENGTH {
+        return Err(ExpressionError::new(
+            format!("Expression exceeds {} characters", MAX_EXPRESSION_LENGTH),
+            MAX_EXPRESSION_LENGTH,
+        ));
+    }
+    if source.trim().is_empty() {
+        return Err(ExpressionError::new("Expression is empty", 0));
+    }
+
+    let mut parser = Parser { tokens: tokenize(source)?, index: 0, depth: 0 };
+    let root = parser.parse_expression()?;
+    if *parser.peek() != Token::Eof {
+        return Err(ExpressionError::new(
+            format!("Unexpected token '{}'", parser.peek()),
+            parser.position(),
+        ));
+    }
+    Ok(Expression { root })
+}
+
+/// Parse and evaluate an expression in one step.
+pub fn evaluate_expression(
+    source: &str,
+    scope: &HashMap<String, ConfigValue>,
+) -> Result<ConfigValue, ExpressionError> {
+    parse_expression(source)?.evaluate(scope)
+}
+
+/// Replace each `{{ expression }}` in `template` with its value over `scope`.
+/// Vault references (`{{credential:id}}`) are resolved before nodes run and are not expressions.
+pub fn render_template(template: &str, scope: &HashMap<String, ConfigValue>) -> Result<String, ExpressionError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    let mut offset = 0;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}")
+            .ok_or_else(|| ExpressionError::new("Unterminated '{{' in template", offset + start))?;
+        let value = evaluate_expression(&rest[start + 2..start + end], scope)
+            .map_err(|e| ExpressionError::new(e.message, offset + start + 2 + e.position))?;
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(&to_display_string(&value));
+        check_string_length(&rendered, offset + start)?;
+        rest = &rest[start + end + 2..];
+        offset += start + end + 2;
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Check that every `{{ expression }}` in `template` parses, e.g. when a workflow is saved.
+pub fn parse_template(template: &str) -> Result<(), ExpressionError> {
+    let mut offset = 0;
+    while let Some(start) = template[offset..].find("{{").map(|start| offset + start) {
+        let end = template[start..].find("}}").map(|end| start + end)
+            .ok_or_else(|| ExpressionError::new("Unterminated '{{' in template", start))?;
+        let inner = &template[start + 2..end];
+        if !inner.trim_start().starts_with("credential:") {
+            parse_expression(inner).map_err(|e| ExpressionError::new(e.message, start + 2 + e.position))?;
+        }
+        offset = end + 2;
+    }
+    Ok(())
+}
+
+/// Render every string in `value` as a template. A string that is a single `{{ expression }}`
+/// takes the expression's value, so numbers and objects keep their type.
+pub fn render_value(value: &ConfigValue, scope: &HashMap<String, ConfigValue>) -> Result<ConfigValue, ExpressionError> {
+    Ok(match value {
+        ConfigValue::String(text) => {
+            let trimmed = text.trim();
+            let whole = trimmed.strip_prefix("{{").and_then(|t| t.strip_suffix("}}"))
+                .filter(|inner| !inner.contains("{{") && !inner.contains("}}"));
+            match whole {
+                Some(expression) => evaluate_expression(expression, scope)?,
+                None => ConfigValue::String(render_template(text, scope)?),
+            }
+        }
+        ConfigValue::Array(items) => ConfigValue::Array(
+            items.iter().map(|item| render_value(item, scope)).collect::<Result<_, _>>()?
+        ),
+        ConfigValue::Object(fields) => ConfigValue::Object(
+            fields.iter()
+                .map(|(key, item)| Ok((key.clone(), render_value(item, scope)?)))
+                .collect::<Result<_, ExpressionError>>()?
+        ),
+        other => other.clone(),
+    })
+}
+
+/// Truthiness used by `!`, `&&`, `||` and condition results.
+pub fn is_truthy(value: &ConfigValue) -> bool {
+    match value {
+        ConfigValue::Boolean(b) => *b,
+        ConfigValue::Number(n) => *n != 0.0 && !n.is_nan(),
+        ConfigValue::String(s) => !s.is_empty(),
+        ConfigValue::Array(items) => !items.is_empty(),
+        ConfigValue::Object(fields) => !fields.is_empty(),
+    }
+}
+
+/// Structural equality; numbers compare with the same epsilon the rest of the codebase uses.
+pub fn values_equal(left: &ConfigValue, right: &ConfigValue) -> bool {
+    match (left, right) {
+        (ConfigValue::String(a), ConfigValue::String(b)) => a == b,
+        (ConfigValue::Number(a), ConfigValue::Number(b)) => (a - b).abs() < f64::EPSILON,
+        (ConfigValue::Boolean(a), ConfigValue::Boolean(b)) => a == b,
+        (ConfigValue::Array(a), ConfigValue::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| values_equal(x, y))
+        }
+        (ConfigValue::Object(a), ConfigValue::Object(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(key, x)| b.get(key).is_some_and(|y| values_equal(x, y)))
+        }
+        _ => false,
+    }
+}
+
+fn type_name(value: &ConfigValue) -> &'static str {
+    match value {
+        ConfigValue::String(_) => "string",
+        ConfigValue::Number(_) => "number",
+        ConfigValue::Boolean(_) => "boolean",
+        ConfigValue::Array(_) => "array",
+        ConfigValue::Object(_) => "object",
+    }
+}
+
+/// How a value reads when interpolated into text: strings as they are, whole numbers without a fraction.
+pub fn to_display_string(value: &ConfigValue) -> String {
+    match value {
+        ConfigValue::String(s) => s.clone(),
+        ConfigValue::Number(n) => {
+            if n.fract() == 0.0 && n.abs() < 1e15 {
+                format!("{}", *n as i64)
+            } else {
+                n.to_string()
+            }
+        }
+        ConfigValue::Boolean(b) => b.to_string(),
+        other => crate::http_client::config_value_to_json(other).to_string(),
+    }
+}
+
+struct Evaluator<'a> {
+    scope: &'a HashMap<String, ConfigValue>,
+    steps: usize,
+}
+
+impl Expression {
+    pub fn evaluate(&self, scope: &HashMap<String, ConfigValue>) -> Result<ConfigValue, ExpressionError> {
+        Evaluator { scope, steps: 0 }.eval(&self.root)
+    }
+
+    /// Evaluate and reduce the result to a boolean using [`is_truthy`].
+    pub fn evaluate_bool(&self, scope: &HashMap<String, ConfigValue>) -> Result<bool, ExpressionError> {
+        self.evaluate(scope).map(|value| is_truthy(&value))
+    }
+}
+
+impl Evaluator<'_> {
+    fn eval(&mut self, expr: &Expr) -> Result<ConfigValue, ExpressionError> {
+        self.steps += 1;
+        if self.steps > MAX_EVALUATION_STEPS {
+            return Err(ExpressionError::new("Expression evaluation limit exceeded", expr.position));
+        }
+
+        let at = expr.position;
+        match &expr.node {
+            Node::Literal(value) => Ok(value.clone()),
+            Node::List(items) => items.iter()
+                .map(|item| self.eval(item))
+                .collect::<Result<Vec<_>, _>>()
+                .map(ConfigValue::Array),
+            Node::Identifier(name) => match self.scope.get(name) {
+                Some(value) => Ok(value.clone()),
+                // `$` exposes the whole input, for keys that are not valid identifiers
+                None if name == "$" => Ok(ConfigValue::Object(self.scope.clone())),
+                None => Err(ExpressionError::new(format!("Unknown field '{}'", name), at)),
+            },
+            Node::Member(target, field) => match self.eval(target)? {
+                ConfigValue::Object(fields) => fields.get(field).cloned()
+                    .ok_or_else(|| ExpressionError::new(format!("Unknown field '{}'", field), at)),
+                ConfigValue::Array(items) if field == "length" => Ok(ConfigValue::Number(items.len() as f64)),
+                other => Err(ExpressionError::new(
+                    format!("Cannot read field '{}' of {}", field, type_name(&other)),
+                    at,
+                )),
+            },
+            Node::Index(target, index) => {
+                let target = self.eval(target)?;
+                let index = self.eval(index)?;
+                index_value(target, index, at)
+            }
+            Node::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                match (*op, value) {
+                    ("!", value) => Ok(ConfigValue::Boolean(!is_truthy(&value))),
+                    ("-", ConfigValue::Number(n)) => Ok(ConfigValue::Number(-n)),
+                    (op, value) => Err(ExpressionError::new(
+                        format!("Cannot apply '{}' to {}", op, type_name(&value)),
+                        at,
+                    )),
+                }
+            }
+            Node::Binary("&&", left, right) => {
+                if !is_truthy(&self.eval(left)?) {
+                    return Ok(ConfigValue::Boolean(false));
+                }
+                Ok(ConfigValue::Boolean(is_truthy(&self.eval(right)?)))
+            }
+            Node::Binary("||", left, right) => {
+                if is_truthy(&self.eval(left)?) {
+                    return Ok(ConfigValue::Boolean(true));
+                }
+                Ok(ConfigValue::Boolean(is_truthy(&self.eval(right)?)))
+            }
+            Node::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                apply_binary(op, left, right, at)
+            }
+            Node::Call(name, args) => self.call(name, args, at),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], at: usize) -> Result<ConfigValue, ExpressionError> {
+        match name {
+            "exists" => return Ok(ConfigValue::Boolean(self.eval(&args[0]).is_ok())),
+            "default" => return self.eval(&args[0]).or_else(|_| self.eval(&args[1])),
+            _ => {}
+        }
+
+        let values = args.iter()
+            .map(|arg| self.eval(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        call_function(name, values, at)
+    }
+}
+
+fn index_value(target: ConfigValue, index: ConfigValue, at: usize) -> Result<ConfigValue, ExpressionError> {
+    match (target, index) {
+        (ConfigValue::Array(items), ConfigValue::Number(n)) => {
+            let len = items.len() as f64;
+            let position = if n < 0.0 { len + n } else { n };
+            if position.fract() != 0.0 || position < 0.0 || position >= len {
+                return Err(ExpressionError::new(format!("Index {} out of bounds", n), at));
+            }
+            Ok(items[position as usize].clone())
+        }
+        (ConfigValue::Object(fields), ConfigValue::String(key)) => fields.get(&key).cloned()
+            .ok_or_else(|| ExpressionError::new(format!("Unknown field '{}'", key), at)),
+        (ConfigValue::String(s), ConfigValue::Number(n)) => {
+            let chars: Vec<char> = s.chars().collect();
+            if n.fract() != 0.0 || n < 0.0 || n as usize >= chars.len() {
+                return Err(ExpressionError::new(format!("Index {} out of bounds", n), at));
+            }
+            Ok(ConfigValue::String(chars[n as usize].to_string()))
+        }
+        (target, index) => Err(ExpressionError::new(
+            format!("Cannot index {} with {}", type_name(&target), type_name(&index)),
+            at,
+        )),
+    }
+}
+
+fn apply_binary(op: &str, left: ConfigValue, right: ConfigValue, at: usize) -> Result<ConfigValue, ExpressionError> {
+    use ConfigValue::{Array, Boolean, Number, Object, String as Str};
+
+    let result = match (op, &left, &right) {
+        ("==", _, _) => Boolean(values_equal(&left, &right)),
+        ("!=", _, _) => Boolean(!values_equal(&left, &right)),
+        ("<", Number(a), Number(b)) => Boolean(a < b),
+        ("<=", Number(a), Number(b)) => Boolean(a <= b),
+        (">", Number(a), Number(b)) => Boolean(a > b),
+        (">=", Number(a), Number(b)) => Boolean(a >= b),
+        ("<", Str(a), Str(b)) => Boolean(a < b),
+        ("<=", Str(a), Str(b)) => Boolean(a <= b),
+        (">", Str(a), Str(b)) => Boolean(a > b),
+        (">=", Str(a), Str(b)) => Boolean(a >= b),
+        ("in", _, Array(items)) => Boolean(items.iter().any(|item| values_equal(item, &left))),
+        ("in", Str(needle), Str(haystack)) => Boolean(haystack.contains(needle.as_str())),
+        ("in", Str(key), Object(fields)) => Boolean(fields.contains_key(key)),
+        ("+", Number(a), Number(b)) => Number(a + b),
+        ("+", Str(_), _) | ("+", _, Str(_)) => {
+            let joined = to_display_string(&left) + &to_display_string(&right);
+            check_string_length(&joined, at)?;
+            Str(joined)
+        }
+        ("+", Array(a), Array(b)) => Array(a.iter().chain(b).cloned().collect()),
+        ("-", Number(a), Number(b)) => Number(a - b),
+        ("*", Number(a), Number(b)) => Number(a * b),
+        ("/", Number(_), Number(b)) if *b == 0.0 => {
+            return Err(ExpressionError::new("Division by zero", at));
+        }
+        ("/", Number(a), Number(b)) => Number(a / b),
+        ("%", Number(_), Number(b)) if *b == 0.0 => {
+            return Err(ExpressionError::new("Division by zero", at));
+        }
+        ("%", Number(a), Number(b)) => Number(a % b),
+        _ => {
+            return Err(ExpressionError::new(
+                format!("Cannot apply '{}' to {} and {}", op, type_name(&left), type_name(&right)),
+                at,
+            ));
+        }
+    };
+    Ok(result)
+}
+
+fn check_string_length(value: &str, at: usize) -> Result<(), ExpressionError> {
+    if value.len() > MAX_STRING_LENGTH {
+        return Err(ExpressionError::new(
+            format!("String result exceeds {} bytes", MAX_STRING_LENGTH),
+            at,
+        ));
+    }
+    Ok(())
+}
+
+fn expect_string(name: &str, value: &ConfigValue, at: usize) -> Result<String, ExpressionError> {
+    match value {
+        ConfigValue::String(s) => Ok(s.clone()),
+        other => Err(ExpressionError::new(
+            format!("Function '{}' expects a string, got {}", name, type_name(other)),
+            at,
+        )),
+    }
+}
+
+fn expect_number(name: &str, value: &ConfigValue, at: usize) -> Result<f64, ExpressionError> {
+    match value {
+        ConfigValue::Number(n) => Ok(*n),
+        other => Err(ExpressionError::new(
+            format!("Function '{}' expects a number, got {}", name, type_name(other)),
+            at,
+        )),
+    }
+}
+
+fn call_function(name: &str, args: Vec<ConfigValue>, at: usize) -> Result<ConfigValue, ExpressionError> {
+    use ConfigValue::{Array, Boolean, Number, Object, String as Str};
+
+    let result = match name {
+        "len" => match &args[0] {
+            Str(s) => Number(s.chars().count() as f64),
+            Array(items) => Number(items.len() as f64),
+            Object(fields) => Number(fields.len() as f64),
+            other => {
+                return Err(ExpressionError::new(
+                    format!("Function 'len' cannot measure {}", type_name(other)),
+                    at,
+                ));
+            }
+        },
+        "lower" => Str(expect_string(name, &args[0], at)?.to_lowercase()),
+        "upper" => Str(expect_string(name, &args[0], at)?.to_uppercase()),
+        "trim" => Str(expect_string(name, &args[0], at)?.trim().to_string()),
+        "contains" => match &args[0] {
+            Array(items) => Boolean(items.iter().any(|item| values_equal(item, &args[1]))),
+            other => Boolean(expect_string(name, other, at)?.contains(&expect_string(name, &args[1], at)?)),
+        },
+        "starts_with" => Boolean(expect_string(name, &args[0], at)?.starts_with(&expect_string(name, &args[1], at)?)),
+        "ends_with" => Boolean(expect_string(name, &args[0], at)?.ends_with(&expect_string(name, &args[1], at)?)),
+        "substring" => {
+            let chars: Vec<char> = expect_string(name, &args[0], at)?.chars().collect();
+            let start = (expect_number(name, &args[1], at)?.max(0.0) as usize).min(chars.len());
+            let end = match args.get(2) {
+                Some(end) => (expect_number(name, end, at)?.max(0.0) as usize).clamp(start, chars.len()),
+                None => chars.len(),
+            };
+            Str(chars[start..end].iter().collect())
+        }
+        "replace" => {
+            let replaced = expect_string(name, &args[0], at)?
+                .replace(&expect_string(name, &args[1], at)?, &expect_string(name, &args[2], at)?);
+            check_string_length(&replaced, at)?;
+            Str(replaced)
+        }
+        "split" => {
+            let value = expect_string(name, &args[0], at)?;
+            let separator = expect_string(name, &args[1], at)?;
+            Array(value.split(separator.as_str()).map(|part| Str(part.to_string())).collect())
+        }
+        "join" => {
+            let separator = expect_string(name, &args[1], at)?;
+            match &args[0] {
+                Array(items) => {
+                    let joined = items.iter().map(to_display_string).collect::<Vec<_>>().join(&separator);
+                    check_string_length(&joined, at)?;
+                    Str(joined)
+                }
+                other => {
+                    return Err(ExpressionError::new(
+                        format!("Function 'join' expects an array, got {}", type_name(other)),
+                        at,
+                    ));
+                }
+            }
+        }
+        "concat" => {
+            let joined: String = args.iter().map(to_display_string).collect();
+            check_string_length(&joined, at)?;
+            Str(joined)
+        }
+        "string" => Str(to_display_string(&args[0])),
+        "number" => match &args[0] {
+            Number(n) => Number(*n),
+            Boolean(b) => Number(if *b { 1.0 } else { 0.0 }),
+            Str(s) => Number(s.trim().parse::<f64>().map_err(|_| {
+                ExpressionError::new(format!("Cannot convert '{}' to a number", s), at)
+            })?),
+            other => {
+                return Err(ExpressionError::new(
+                    format!("Cannot convert {} to a number", type_name(other)),
+                    at,
+                ));
+            }
+        },
+        "abs" => Number(expect_number(name, &args[0], at)?.abs()),
+        "round" => {
+            let value = expect_number(name, &args[0], at)?;
+            let digits = match args.get(1) {
+                Some(digits) => expect_number(name, digits, at)?.clamp(0.0, 15.0) as i32,
+                None => 0,
+            };
+            let factor = 10f64.powi(digits);
+            Number((value * factor).round() / factor)
+        }
+        "floor" => Number(expect_number(name, &args[0], at)?.floor()),
+        "ceil" => Number(expect_number(name, &args[0], at)?.ceil()),
+        "min" | "max" => {
+            let numbers = match args.as_slice() {
+                [Array(items)] => items.clone(),
+                _ => args.clone(),
+            };
+            let mut result: Option<f64> = None;
+            for value in &numbers {
+                let n = expect_number(name, value, at)?;
+                result = Some(match result {
+                    None => n,
+                    Some(current) if name == "min" => current.min(n),
+                    Some(current) => current.max(n),
+                });
+            }
+            Number(result.ok_or_else(|| {
+                ExpressionError::new(format!("Function '{}' needs at least one number", name), at)
+            })?)
+        }
+        "keys" => match &args[0] {
+            Object(fields) => {
+                let mut keys: Vec<&String> = fields.keys().collect();
+                keys.sort();
+                Array(keys.into_iter().map(|key| Str(key.clone())).collect())
+            }
+            other => {
+                return Err(ExpressionError::new(
+                    format!("Function 'keys' expects an object, got {}", type_name(other)),
+                    at,
+                ));
+            }
+        },
+        "json_parse" => {
+            let text = expect_string(name, &args[0], at)?;
+            let json: serde_json::Value = serde_json::from_str(&text)
+                .map_err(|e| ExpressionError::new(format!("Invalid JSON: {}", e), at))?;
+            crate::http_client::json_to_config_value(&json)
+        }
+        "json_stringify" => Str(crate::http_client::config_value_to_json(&args[0]).to_string()),
+        _ => return Err(ExpressionError::new(format!("Unknown function '{}'", name), at)),
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope() -> HashMap<String, ConfigValue> {
+        let quote = HashMap::from([("price".to_string(), ConfigValue::Number(2950.5))]);
+        let body = HashMap::from([(
+            "data".to_string(),
+            ConfigValue::Array(vec![ConfigValue::Object(quote)]),
+        )]);
+        HashMap::from([
+            ("price".to_string(), ConfigValue::Number(2950.5)),
+            ("token".to_string(), ConfigValue::String("ETH".to_string())),
+            ("from".to_string(), ConfigValue::String("0xDEADbeef".to_string())),
+            ("body".to_string(), ConfigValue::Object(body)),
+        ])
+    }
+
+    fn eval(source: &str) -> ConfigValue {
+        evaluate_expression(source, &scope()).unwrap()
+    }
+
+    #[test]
+    fn test_comparisons_and_combinators() {
+        assert!(matches!(eval("price < 3000 && token == 'ETH'"), ConfigValue::Boolean(true)));
+        assert!(matches!(eval("price >= 3000 or not (token != \"ETH\")"), ConfigValue::Boolean(true)));
+        assert!(matches!(eval("token in ['ETH', 'WBTC']"), ConfigValue::Boolean(true)));
+        assert!(matches!(eval("!(price > 1) || false"), ConfigValue::Boolean(false)));
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        assert!(matches!(eval("1 + 2 * 3 - 4 / 2"), ConfigValue::Number(n) if n == 5.0));
+        assert!(matches!(eval("-(2 + 3) % 4"), ConfigValue::Number(n) if n == -1.0));
+    }
+
+    #[test]
+    fn test_nested_field_access() {
+        assert!(matches!(eval("body.data[0].price"), ConfigValue::Number(n) if n == 2950.5));
+        assert!(matches!(eval("body['data'][-1].price > 2000"), ConfigValue::Boolean(true)));
+        assert!(matches!(eval("body.data.length"), ConfigValue::Number(n) if n == 1.0));
+    }
+
+    #[test]
+    fn test_string_functions() {
+        assert!(matches!(eval("starts_with(lower(from), '0xdead')"), ConfigValue::Boolean(true)));
+        assert!(matches!(eval("concat(token, '-', round(price))"), ConfigValue::String(s) if s == "ETH-2951"));
+        assert!(matches!(eval("len(split('a,b,c', ','))"), ConfigValue::Number(n) if n == 3.0));
+        assert!(matches!(eval("json_parse('{\"x\": [1, 2]}').x[1]"), ConfigValue::Number(n) if n == 2.0));
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        assert!(matches!(eval("exists(body.missing)"), ConfigValue::Boolean(false)));
+        assert!(matches!(eval("default(volume, 0) + 1"), ConfigValue::Number(n) if n == 1.0));
+
+        let error = evaluate_expression("price > volume", &scope()).unwrap_err();
+        assert_eq!(error.message, "Unknown field 'volume'");
+        assert_eq!(error.position, 8);
+    }
+
+    #[test]
+    fn test_parse_errors_are_positioned() {
+        let error = parse_expression("price <").unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected end of expression at position 8");
+
+        let error = parse_expression("price == 'ETH").unwrap_err();
+        assert_eq!(error.position, 9);
+
+        let error = parse_expression("upper(token, 1)").unwrap_err();
+        assert!(error.message.contains("expects 1 argument"));
+
+        assert!(parse_expression("price # 2").is_err());
+        assert!(parse_expression("(price").is_err());
+        assert!(parse_expression(&"(".repeat(200)).is_err());
+    }
+
+    #[test]
+    fn test_templates() {
+        let url = render_template("https://api.example.com/{{ lower(token) }}?min={{price * 2}}", &scope()).unwrap();
+        assert_eq!(url, "https://api.example.com/eth?min=5901");
+        assert_eq!(render_template("no placeholders", &scope()).unwrap(), "no placeholders");
+        assert_eq!(render_template("{{ volume }}", &scope()).unwrap_err().position, 3);
+        assert!(render_template("{{ price", &scope()).is_err());
+        assert!(parse_template("Bearer {{credential:api}} for {{ token }}").is_ok());
+        assert_eq!(parse_template("{{ price < }}").unwrap_err().position, 11);
+
+        let body = ConfigValue::Object(HashMap::from([
+            ("price".to_string(), ConfigValue::String("{{ body.data[0].price }}".to_string())),
+            ("label".to_string(), ConfigValue::String("{{token}} at {{price}}".to_string())),
+        ]));
+        let ConfigValue::Object(rendered) = render_value(&body, &scope()).unwrap() else { panic!("not an object") };
+        assert!(matches!(rendered.get("price"), Some(ConfigValue::Number(n)) if *n == 2950.5));
+        assert!(matches!(rendered.get("label"), Some(ConfigValue::String(s)) if s == "ETH at 2950.5"));
+    }
+
+    #[test]
+    fn test_runtime_type_errors() {
+        assert!(evaluate_expression("token * 2", &scope()).is_err());
+        assert!(evaluate_expression("price / 0", &scope()).is_err());
+        assert!(evaluate_expression("body.data[5]", &scope()).is_err());
+    }
+}
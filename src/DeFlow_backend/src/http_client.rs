@@ -1,12 +1,16 @@
+use crate::http_transforms::{self, TransformStep};
 use crate::types::ConfigValue;
-use ic_cdk::api::management_canister::http_request::{
-    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs, TransformContext
-};
+use ic_cdk::api::management_canister::http_request::{HttpHeader, HttpMethod, HttpResponse, TransformArgs};
 use serde_json::Value;
 use std::collections::HashMap;
 
 pub struct HttpClient;
 
+pub const DEFAULT_OUTCALL_CYCLES: u128 = 10_000_000_000;
+pub const MAX_OUTCALL_CYCLES: u128 = 100_000_000_000;
+pub const DEFAULT_MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+pub const MAX_RESPONSE_BYTES: u64 = 2_000_000; // Protocol limit for outcall responses
+
 #[derive(Debug, Clone)]
 pub struct HttpRequest {
     pub url: String,
@@ -14,6 +18,8 @@ pub struct HttpRequest {
     pub headers: Vec<HttpHeader>,
     pub body: Option<Vec<u8>>,
     pub max_response_bytes: Option<u64>,
+    pub cycles: Option<u128>, // Attached to the outcall; DEFAULT_OUTCALL_CYCLES when None
+    pub transform: Vec<TransformStep>, // Run on each replica's response; empty only redacts sensitive headers
 }
 
 #[derive(Debug, Clone)]
@@ -24,28 +30,35 @@ pub struct HttpClientResponse {
 }
 
 impl HttpClient {
-    pub async fn request(request: HttpRequest) -> Result<HttpClientResponse, String> {
+    pub async fn request(mut request: HttpRequest) -> Result<HttpClientResponse, String> {
+        request.cycles.get_or_insert(DEFAULT_OUTCALL_CYCLES);
+        http_transforms::validate(&request.transform)?;
+        Self::outcall(request).await
+    }
+
+    #[cfg(test)]
+    async fn outcall(request: HttpRequest) -> Result<HttpClientResponse, String> {
+        mock::handle(request)
+    }
+
+    #[cfg(not(test))]
+    async fn outcall(request: HttpRequest) -> Result<HttpClientResponse, String> {
+        use ic_cdk::api::management_canister::http_request::{http_request, CanisterHttpRequestArgument, TransformContext};
+
         let http_req = CanisterHttpRequestArgument {
             url: request.url.clone(),
             method: request.method,
             body: request.body,
             max_response_bytes: request.max_response_bytes,
-            transform: Some(TransformContext::from_name("transform_http_response".to_string(), vec![])),
+            transform: Some(TransformContext::from_name(
+                "transform_http_response".to_string(),
+                http_transforms::encode(&request.transform),
+            )),
             headers: request.headers,
         };
 
-        match http_request(http_req, 10_000_000_000).await {
-            Ok((response,)) => {
-                let headers = response.headers.iter()
-                    .map(|header| (header.name.clone(), header.value.clone()))
-                    .collect();
-
-                Ok(HttpClientResponse {
-                    status: 200u16, // Default to 200, TODO: parse actual status
-                    headers,
-                    body: String::from_utf8_lossy(&response.body).to_string(),
-                })
-            }
+        match http_request(http_req, request.cycles.unwrap_or_default()).await {
+            Ok((response,)) => Ok(client_response(response)),
             Err((rejection_code, msg)) => {
                 Err(format!("HTTP request failed: {:?} - {}", rejection_code, msg))
             }
@@ -64,7 +77,9 @@ impl HttpClient {
             method: HttpMethod::GET,
             headers: http_headers,
             body: None,
-            max_response_bytes: Some(1024 * 1024), // 1MB max
+            max_response_bytes: Some(DEFAULT_MAX_RESPONSE_BYTES),
+            cycles: None,
+            transform: Vec::new(),
         };
 
         Self::request(request).await
@@ -94,7 +109,9 @@ impl HttpClient {
             method: HttpMethod::POST,
             headers: http_headers,
             body: body.map(|b| b.into_bytes()),
-            max_response_bytes: Some(1024 * 1024), // 1MB max
+            max_response_bytes: Some(DEFAULT_MAX_RESPONSE_BYTES),
+            cycles: None,
+            transform: Vec::new(),
         };
 
         Self::request(request).await
@@ -123,7 +140,9 @@ impl HttpClient {
             method: HttpMethod::POST, // ICP doesn't support PUT, use POST
             headers: http_headers,
             body: body.map(|b| b.into_bytes()),
-            max_response_bytes: Some(1024 * 1024),
+            max_response_bytes: Some(DEFAULT_MAX_RESPONSE_BYTES),
+            cycles: None,
+            transform: Vec::new(),
         };
 
         Self::request(request).await
@@ -141,26 +160,29 @@ impl HttpClient {
             method: HttpMethod::GET, // ICP doesn't support DELETE, use GET
             headers: http_headers,
             body: None,
-            max_response_bytes: Some(1024 * 1024),
+            max_response_bytes: Some(DEFAULT_MAX_RESPONSE_BYTES),
+            cycles: None,
+            transform: Vec::new(),
         };
 
         Self::request(request).await
     }
 }
 
-// Transform function required for HTTP outcalls
+// Transform function required for HTTP outcalls; the context carries the request's pipeline
 #[ic_cdk::query]
 fn transform_http_response(args: TransformArgs) -> HttpResponse {
-    let mut response = args.response;
-    
-    // Remove sensitive headers
-    response.headers.retain(|header| {
-        !matches!(header.name.to_lowercase().as_str(), 
-            "authorization" | "cookie" | "set-cookie" | "x-api-key"
-        )
-    });
-    
-    response
+    http_transforms::apply(&args.context, args.response)
+}
+
+fn client_response(response: HttpResponse) -> HttpClientResponse {
+    use num_traits::ToPrimitive;
+
+    HttpClientResponse {
+        status: response.status.0.to_u16().unwrap_or(500),
+        headers: response.headers.into_iter().map(|header| (header.name, header.value)).collect(),
+        body: String::from_utf8_lossy(&response.body).to_string(),
+    }
 }
 
 // Helper functions for working with API responses
@@ -212,6 +234,60 @@ pub fn config_value_to_json(value: &ConfigValue) -> Value {
     }
 }
 
+/// `Authorization` header value for HTTP Basic authentication.
+pub fn basic_auth(username: &str, password: &str) -> String {
+    format!("Basic {}", base64_encode(format!("{}:{}", username, password).as_bytes()))
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Encode pairs as an `application/x-www-form-urlencoded` body.
+pub fn form_urlencode(pairs: &[(&str, &str)]) -> String {
+    fn encode(text: &str) -> String {
+        text.bytes().map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            b' ' => "+".to_string(),
+            _ => format!("%{:02X}", byte),
+        }).collect()
+    }
+    pairs.iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Cycles an outcall costs on a 13-node subnet, for a request of `request_bytes` that may
+/// return up to `max_response_bytes`.
+pub fn outcall_cycles(request_bytes: u64, max_response_bytes: u64) -> u128 {
+    const NODES: u128 = 13;
+    (3_000_000 + 60_000 * NODES) * NODES
+        + 400 * NODES * request_bytes as u128
+        + 800 * NODES * max_response_bytes as u128
+}
+
+/// Case-insensitive lookup of a response header.
+pub fn response_header<'a>(response: &'a HttpClientResponse, name: &str) -> Option<&'a str> {
+    response.headers.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
 // Utility function to merge headers
 #[allow(dead_code)]
 pub fn merge_headers(
@@ -270,4 +346,87 @@ impl ApiClient {
         let url = format!("{}/{}", self.base_url.trim_end_matches('/'), endpoint.trim_start_matches('/'));
         HttpClient::delete(&url, Some(self.default_headers.clone())).await
     }
-}
\ No newline at end of file
+}
+// In-process stand-in for HTTPS outcalls, so unit tests can exercise code that calls APIs.
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static ROUTES: RefCell<Vec<(String, HttpClientResponse, bool)>> = const { RefCell::new(Vec::new()) };
+        static REQUESTS: RefCell<Vec<HttpRequest>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Answer requests whose URL starts with `url_prefix`; later routes take precedence.
+    pub fn respond(url_prefix: &str, status: u16, body: &str) {
+        respond_with_headers(url_prefix, status, &[], body);
+    }
+
+    pub fn respond_with_headers(url_prefix: &str, status: u16, headers: &[(&str, &str)], body: &str) {
+        add_route(url_prefix, status, headers, body, false);
+    }
+
+    /// Answer only the next matching request, then fall back to the other routes.
+    pub fn respond_once(url_prefix: &str, status: u16, body: &str) {
+        add_route(url_prefix, status, &[], body, true);
+    }
+
+    fn add_route(url_prefix: &str, status: u16, headers: &[(&str, &str)], body: &str, once: bool) {
+        let response = HttpClientResponse {
+            status,
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body: body.to_string(),
+        };
+        ROUTES.with(|routes| routes.borrow_mut().push((url_prefix.to_string(), response, once)));
+    }
+
+    /// Requests made so far, oldest first.
+    pub fn requests() -> Vec<HttpRequest> {
+        REQUESTS.with(|requests| requests.borrow().clone())
+    }
+
+    pub(super) fn handle(request: HttpRequest) -> Result<HttpClientResponse, String> {
+        let response = ROUTES.with(|routes| {
+            let mut routes = routes.borrow_mut();
+            let index = routes.iter().rposition(|(prefix, ..)| request.url.starts_with(prefix.as_str()))?;
+            let (_, response, once) = routes[index].clone();
+            if once {
+                routes.remove(index);
+            }
+            Some(response)
+        });
+        let target = format!("{:?} {}", request.method, request.url);
+        let limit = request.max_response_bytes;
+        let context = http_transforms::encode(&request.transform);
+        REQUESTS.with(|requests| requests.borrow_mut().push(request));
+
+        let response = response.ok_or_else(|| format!("HTTP request failed: no mock response for {}", target))?;
+        // Like the replica, reject bodies above the request's response size limit
+        if limit.is_some_and(|limit| response.body.len() as u64 > limit) {
+            return Err(format!("HTTP request failed: response of {} exceeds max_response_bytes", target));
+        }
+        // and run the request's transform before handing the response back
+        let response = HttpResponse {
+            status: response.status.into(),
+            headers: response.headers.into_iter().map(|(name, value)| HttpHeader { name, value }).collect(),
+            body: response.body.into_bytes(),
+        };
+        Ok(client_response(http_transforms::apply(&context, response)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_auth_and_form_encoding() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(basic_auth("api", "key-123"), "Basic YXBpOmtleS0xMjM=");
+        assert_eq!(form_urlencode(&[("to", "a@b.io"), ("text", "Hi there & bye")]), "to=a%40b.io&text=Hi+there+%26+bye");
+    }
+}
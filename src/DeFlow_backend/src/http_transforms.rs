@@ -0,0 +1,461 @@
+// Deterministic response transforms for HTTPS outcalls.
+//
+// Every replica of the subnet makes the outcall and must agree on the response, so
+// anything that differs between them (dates, request ids, rotating headers, float
+// noise) has to be removed before consensus. Callers pick a pipeline of named
+// transforms per request; `HttpClient` sends it JSON-encoded as the transform context
+// and the `transform_http_response` query runs it on each replica's response.
+//
+// Sensitive headers are always removed first. A transform that cannot run (a body
+// that is not JSON, say) turns the response into a 502 naming the failure, which is
+// still the same on every replica.
+//
+// Pipelines are written as `[{"name": "json_extract", "paths": {"price": "$.eth.usd"}}]`
+// in node configuration, or with the `TransformStep` constructors in code.
+
+use crate::http_client::config_value_to_json;
+use crate::types::ConfigValue;
+use candid::Nat;
+use ic_cdk::api::management_canister::http_request::HttpResponse;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "cookie", "set-cookie", "x-api-key"];
+const MAX_DECIMALS: u32 = 18;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransformStep {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+impl TransformStep {
+    /// Drop every response header.
+    pub fn strip_headers() -> Self {
+        Self { name: "strip_headers".to_string(), params: Value::Null }
+    }
+
+    /// Drop every response header except these.
+    pub fn keep_headers(names: &[&str]) -> Self {
+        Self { name: "strip_headers".to_string(), params: serde_json::json!({ "keep": names }) }
+    }
+
+    /// Replace the body with an object of the values at each named JSONPath.
+    pub fn json_extract(paths: &[(&str, &str)]) -> Self {
+        let paths: Map<String, Value> = paths.iter()
+            .map(|(name, path)| (name.to_string(), Value::String(path.to_string())))
+            .collect();
+        Self { name: "json_extract".to_string(), params: serde_json::json!({ "paths": paths }) }
+    }
+
+    /// Remove object keys with these names at any depth of the body.
+    pub fn drop_fields(fields: &[&str]) -> Self {
+        Self { name: "drop_fields".to_string(), params: serde_json::json!({ "fields": fields }) }
+    }
+
+    /// Round numbers in the body to `decimals` places; with `strings`, numeric strings become numbers first.
+    pub fn normalize_numbers(decimals: u32, strings: bool) -> Self {
+        Self { name: "normalize_numbers".to_string(), params: serde_json::json!({ "decimals": decimals, "strings": strings }) }
+    }
+}
+
+struct Transform {
+    name: &'static str,
+    check: fn(&Value) -> Result<(), String>,
+    apply: fn(&Value, &mut HttpResponse) -> Result<(), String>,
+}
+
+const TRANSFORMS: &[Transform] = &[
+    Transform { name: "strip_headers", check: check::<StripHeaders>, apply: strip_headers },
+    Transform { name: "json_extract", check: check_json_extract, apply: json_extract },
+    Transform { name: "drop_fields", check: check::<DropFields>, apply: drop_fields },
+    Transform { name: "normalize_numbers", check: check_normalize_numbers, apply: normalize_numbers },
+];
+
+fn transform(name: &str) -> Result<&'static Transform, String> {
+    TRANSFORMS.iter().find(|transform| transform.name == name).ok_or_else(|| {
+        let names: Vec<&str> = TRANSFORMS.iter().map(|transform| transform.name).collect();
+        format!("Unknown response transform '{}': use {}", name, names.join(", "))
+    })
+}
+
+pub fn validate(steps: &[TransformStep]) -> Result<(), String> {
+    for step in steps {
+        (transform(&step.name)?.check)(&step.params).map_err(|e| format!("{}: {}", step.name, e))?;
+    }
+    Ok(())
+}
+
+/// Transform context bytes for a pipeline; empty when only the default redaction applies.
+pub fn encode(steps: &[TransformStep]) -> Vec<u8> {
+    if steps.is_empty() {
+        return Vec::new();
+    }
+    serde_json::to_vec(steps).unwrap_or_default()
+}
+
+/// Runs the pipeline encoded in `context` over a raw outcall response.
+pub fn apply(context: &[u8], mut response: HttpResponse) -> HttpResponse {
+    response.headers.retain(|header| !SENSITIVE_HEADERS.contains(&header.name.to_lowercase().as_str()));
+    if context.is_empty() {
+        return response;
+    }
+
+    let steps: Vec<TransformStep> = match serde_json::from_slice(context) {
+        Ok(steps) => steps,
+        Err(e) => return failure(format!("Invalid transform context: {}", e)),
+    };
+    for step in &steps {
+        let result = transform(&step.name).and_then(|transform| (transform.apply)(&step.params, &mut response));
+        if let Err(e) = result {
+            return failure(format!("Response transform {} failed: {}", step.name, e));
+        }
+    }
+    response
+}
+
+fn failure(error: String) -> HttpResponse {
+    HttpResponse {
+        status: Nat::from(502u16),
+        headers: Vec::new(),
+        body: serde_json::json!({ "error": error }).to_string().into_bytes(),
+    }
+}
+
+/// Pipeline from an `http_request` node's `transform` parameter: a transform name, a
+/// `{"name": ..., params...}` object, or a list of either.
+pub fn steps_from_config(value: &ConfigValue) -> Result<Vec<TransformStep>, String> {
+    let step = |value: &ConfigValue| match value {
+        ConfigValue::String(name) => Ok(TransformStep { name: name.clone(), params: Value::Null }),
+        ConfigValue::Object(fields) => {
+            let name = match fields.get("name") {
+                Some(ConfigValue::String(name)) => name.clone(),
+                _ => return Err("Each transform needs a name".to_string()),
+            };
+            let params: Map<String, Value> = fields.iter()
+                .filter(|(key, _)| key.as_str() != "name")
+                .map(|(key, value)| (key.clone(), config_value_to_json(value)))
+                .collect();
+            let params = if params.is_empty() { Value::Null } else { Value::Object(params) };
+            Ok(TransformStep { name, params })
+        }
+        _ => Err("Transforms are names or objects with a name".to_string()),
+    };
+    let steps = match value {
+        ConfigValue::Array(items) => items.iter().map(step).collect::<Result<Vec<_>, _>>()?,
+        value => vec![step(value)?],
+    };
+    validate(&steps)?;
+    Ok(steps)
+}
+
+fn params<T: DeserializeOwned>(params: &Value) -> Result<T, String> {
+    let params = match params {
+        Value::Null => Value::Object(Map::new()),
+        params => params.clone(),
+    };
+    serde_json::from_value(params).map_err(|e| e.to_string())
+}
+
+fn check<T: DeserializeOwned>(value: &Value) -> Result<(), String> {
+    params::<T>(value).map(|_| ())
+}
+
+fn edit_json(response: &mut HttpResponse, edit: impl FnOnce(Value) -> Value) -> Result<(), String> {
+    let body: Value = serde_json::from_slice(&response.body).map_err(|e| format!("body is not JSON: {}", e))?;
+    response.body = serde_json::to_vec(&edit(body)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// strip_headers
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StripHeaders {
+    #[serde(default)]
+    keep: Vec<String>, // Header names to keep, case-insensitive
+}
+
+fn strip_headers(value: &Value, response: &mut HttpResponse) -> Result<(), String> {
+    let StripHeaders { keep } = params(value)?;
+    response.headers.retain(|header| keep.iter().any(|name| name.eq_ignore_ascii_case(&header.name)));
+    response.headers.sort_by_key(|header| header.name.to_lowercase());
+    Ok(())
+}
+
+// json_extract
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonExtract {
+    path: Option<String>, // The body becomes the value at this path
+    #[serde(default)]
+    paths: BTreeMap<String, String>, // The body becomes an object of these names to values
+}
+
+enum Selection {
+    One(Vec<Segment>),
+    Named(Vec<(String, Vec<Segment>)>),
+}
+
+fn json_extract_params(value: &Value) -> Result<Selection, String> {
+    match params::<JsonExtract>(value)? {
+        JsonExtract { path: Some(path), paths } if paths.is_empty() => Ok(Selection::One(parse_path(&path)?)),
+        JsonExtract { path: None, paths } if !paths.is_empty() => paths.into_iter()
+            .map(|(name, path)| parse_path(&path).map(|segments| (name, segments)))
+            .collect::<Result<_, _>>()
+            .map(Selection::Named),
+        _ => Err("set either path or paths".to_string()),
+    }
+}
+
+fn check_json_extract(value: &Value) -> Result<(), String> {
+    json_extract_params(value).map(|_| ())
+}
+
+fn json_extract(value: &Value, response: &mut HttpResponse) -> Result<(), String> {
+    let selection = json_extract_params(value)?;
+    // Nothing to select from, e.g. a 201 or 204 without content
+    if response.body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(());
+    }
+    edit_json(response, |body| match selection {
+        Selection::One(path) => select(&body, &path),
+        Selection::Named(paths) => Value::Object(paths.into_iter()
+            .map(|(name, path)| (name, select(&body, &path)))
+            .collect()),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// Parses the JSONPath subset `$`, `.key`, `['key']`, `[0]`, `.*` and `[*]`.
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let invalid = |reason: &str| format!("invalid JSONPath '{}': {}", path, reason);
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(|| invalid("must start with $"))?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            segments.push(match key {
+                "" => return Err(invalid("empty key")),
+                "*" => Segment::Wildcard,
+                key => Segment::Key(key.to_string()),
+            });
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("unclosed ["))?;
+            let inner = after[..end].trim();
+            segments.push(if inner == "*" {
+                Segment::Wildcard
+            } else if let Some(key) = inner.strip_prefix('\'').and_then(|k| k.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|k| k.strip_suffix('"')))
+            {
+                Segment::Key(key.to_string())
+            } else {
+                Segment::Index(inner.parse().map_err(|_| invalid("indexes must be non-negative integers"))?)
+            });
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid("expected . or ["));
+        }
+    }
+    Ok(segments)
+}
+
+/// Value at `path`; missing values are null, and wildcards collect matches into an array.
+fn select(root: &Value, path: &[Segment]) -> Value {
+    let mut current = vec![root];
+    for segment in path {
+        current = current.into_iter()
+            .flat_map(|value| -> Vec<&Value> {
+                match (segment, value) {
+                    (Segment::Key(key), Value::Object(fields)) => fields.get(key).into_iter().collect(),
+                    (Segment::Index(index), Value::Array(items)) => items.get(*index).into_iter().collect(),
+                    (Segment::Wildcard, Value::Object(fields)) => fields.values().collect(),
+                    (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+    if path.contains(&Segment::Wildcard) {
+        Value::Array(current.into_iter().cloned().collect())
+    } else {
+        current.first().map_or(Value::Null, |value| (*value).clone())
+    }
+}
+
+// drop_fields
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DropFields {
+    fields: Vec<String>,
+}
+
+fn drop_fields(value: &Value, response: &mut HttpResponse) -> Result<(), String> {
+    fn strip(value: &mut Value, fields: &[String]) {
+        match value {
+            Value::Object(entries) => {
+                entries.retain(|key, _| !fields.contains(key));
+                entries.values_mut().for_each(|value| strip(value, fields));
+            }
+            Value::Array(items) => items.iter_mut().for_each(|value| strip(value, fields)),
+            _ => {}
+        }
+    }
+    let DropFields { fields } = params(value)?;
+    edit_json(response, |mut body| {
+        strip(&mut body, &fields);
+        body
+    })
+}
+
+// normalize_numbers
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NormalizeNumbers {
+    #[serde(default = "default_decimals")]
+    decimals: u32,
+    #[serde(default)]
+    strings: bool, // Also convert numeric strings such as "3010.25"
+}
+
+fn default_decimals() -> u32 {
+    8
+}
+
+fn normalize_numbers_params(value: &Value) -> Result<NormalizeNumbers, String> {
+    let normalize: NormalizeNumbers = params(value)?;
+    if normalize.decimals > MAX_DECIMALS {
+        return Err(format!("decimals must be at most {}", MAX_DECIMALS));
+    }
+    Ok(normalize)
+}
+
+fn check_normalize_numbers(value: &Value) -> Result<(), String> {
+    normalize_numbers_params(value).map(|_| ())
+}
+
+fn normalize_numbers(value: &Value, response: &mut HttpResponse) -> Result<(), String> {
+    fn round(number: f64, scale: f64) -> Value {
+        serde_json::Number::from_f64((number * scale).round() / scale).map_or(Value::Null, Value::Number)
+    }
+    fn normalize(value: &mut Value, scale: f64, strings: bool) {
+        match value {
+            Value::Number(number) if number.is_f64() => *value = round(number.as_f64().unwrap_or_default(), scale),
+            Value::String(text) if strings => {
+                if let Some(number) = text.trim().parse::<f64>().ok().filter(|n| n.is_finite()) {
+                    *value = round(number, scale);
+                }
+            }
+            Value::Object(entries) => entries.values_mut().for_each(|value| normalize(value, scale, strings)),
+            Value::Array(items) => items.iter_mut().for_each(|value| normalize(value, scale, strings)),
+            _ => {}
+        }
+    }
+    let NormalizeNumbers { decimals, strings } = normalize_numbers_params(value)?;
+    let scale = 10f64.powi(decimals as i32);
+    edit_json(response, |mut body| {
+        normalize(&mut body, scale, strings);
+        body
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_cdk::api::management_canister::http_request::HttpHeader;
+
+    fn response(body: &str) -> HttpResponse {
+        HttpResponse {
+            status: Nat::from(200u16),
+            headers: vec![
+                HttpHeader { name: "Date".to_string(), value: "Fri, 16 Oct 2026 09:00:00 GMT".to_string() },
+                HttpHeader { name: "Set-Cookie".to_string(), value: "session=1".to_string() },
+                HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string() },
+            ],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn body(response: &HttpResponse) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn test_pipeline_makes_responses_deterministic() {
+        let raw = r#"{"request_id":"a1","data":{"eth":{"usd":3010.123456789,"updated_at":17},"matic-network":{"usd":"0.5012345"}},"items":[{"id":1,"at":5},{"id":2,"at":6}]}"#;
+        let steps = vec![
+            TransformStep::strip_headers(),
+            TransformStep::drop_fields(&["updated_at", "request_id"]),
+            TransformStep::json_extract(&[("eth", "$.data.eth"), ("matic", "$.data['matic-network'].usd"), ("ids", "$.items[*].id"), ("missing", "$.nope[0]")]),
+            TransformStep::normalize_numbers(4, true),
+        ];
+        validate(&steps).unwrap();
+
+        let transformed = apply(&encode(&steps), response(raw));
+        assert_eq!(transformed.status, Nat::from(200u16));
+        assert!(transformed.headers.is_empty());
+        assert_eq!(body(&transformed), serde_json::json!({
+            "eth": { "usd": 3010.1235 },
+            "matic": 0.5012,
+            "ids": [1, 2],
+            "missing": null,
+        }));
+
+        // Without a context only sensitive headers go
+        let redacted = apply(&[], response(raw));
+        let names: Vec<&str> = redacted.headers.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(names, ["Date", "Content-Type"]);
+        assert_eq!(redacted.body, raw.as_bytes());
+
+        let kept = apply(&encode(&[TransformStep { name: "strip_headers".to_string(), params: serde_json::json!({ "keep": ["content-type"] }) }]), response(raw));
+        assert_eq!(kept.headers.len(), 1);
+
+        let single = TransformStep { name: "json_extract".to_string(), params: serde_json::json!({ "path": "$.items[1]" }) };
+        assert_eq!(body(&apply(&encode(&[single]), response(raw))), serde_json::json!({ "id": 2, "at": 6 }));
+
+        let failed = apply(&encode(&[TransformStep::drop_fields(&["id"])]), response("<html>"));
+        assert_eq!(failed.status, Nat::from(502u16));
+        assert!(body(&failed)["error"].as_str().unwrap().starts_with("Response transform drop_fields failed: body is not JSON"));
+    }
+
+    #[test]
+    fn test_steps_from_config_and_validation() {
+        let config = ConfigValue::Array(vec![
+            ConfigValue::String("strip_headers".to_string()),
+            ConfigValue::Object(std::collections::HashMap::from([
+                ("name".to_string(), ConfigValue::String("json_extract".to_string())),
+                ("path".to_string(), ConfigValue::String("$.result".to_string())),
+            ])),
+        ]);
+        let steps = steps_from_config(&config).unwrap();
+        assert_eq!(steps[0], TransformStep::strip_headers());
+        assert_eq!(steps[1].params, serde_json::json!({ "path": "$.result" }));
+
+        assert_eq!(
+            steps_from_config(&ConfigValue::String("gzip".to_string())).unwrap_err(),
+            "Unknown response transform 'gzip': use strip_headers, json_extract, drop_fields, normalize_numbers"
+        );
+        assert!(validate(&[TransformStep { name: "json_extract".to_string(), params: Value::Null }]).is_err());
+        assert!(validate(&[TransformStep::json_extract(&[("x", "data.x")])]).is_err());
+        assert!(validate(&[TransformStep::json_extract(&[("x", "$.data[")])]).is_err());
+        assert!(validate(&[TransformStep::normalize_numbers(30, false)]).is_err());
+        assert!(validate(&[TransformStep { name: "drop_fields".to_string(), params: serde_json::json!({ "field": ["x"] }) }]).is_err());
+        assert_eq!(parse_path("$.a[*]['b c'][2]").unwrap(), vec![
+            Segment::Key("a".to_string()), Segment::Wildcard, Segment::Key("b c".to_string()), Segment::Index(2),
+        ]);
+    }
+}
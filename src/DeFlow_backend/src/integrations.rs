@@ -0,0 +1,833 @@
+// Executors for the messaging, social and outbound webhook nodes.
+//
+// Each node calls its platform's HTTP API through `HttpClient`. Tokens come from the
+// node configuration or, when it leaves them out, from the token the workflow's owner
+// saved for that platform with `save_oauth_token`, read through `oauth` so expiring
+// tokens are refreshed. Platform responses are mapped onto the fields of the node's
+// `output_schema`.
+//
+// Every replica of the subnet makes each outcall, so one send reaches the platform once
+// per replica. Sends carry an idempotency key derived from the run, the node and the
+// content (Discord `nonce`, SendGrid `custom_args`, Mailgun `Message-Id`, an
+// `Idempotency-Key` header elsewhere) so platforms that support it keep one copy, and
+// responses are cut down to the fields the node reads so the replicas agree on them.
+// These nodes are not retried unless a retry policy is set for their type.
+
+use crate::credentials;
+use crate::http_client::{
+    basic_auth, config_value_to_json, form_urlencode, response_header, HttpClient, HttpClientResponse, HttpRequest,
+    DEFAULT_MAX_RESPONSE_BYTES,
+};
+use crate::http_transforms::TransformStep;
+use crate::oauth;
+use crate::types::{ConfigValue, ExecutionContext, NodeOutput, WorkflowNode};
+use ic_cdk::api::management_canister::http_request::{HttpHeader, HttpMethod};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+const DISCORD_API: &str = "https://discord.com/api/v10";
+const TWITTER_API: &str = "https://api.twitter.com/2";
+const LINKEDIN_API: &str = "https://api.linkedin.com";
+const LINKEDIN_VERSION: &str = "202405";
+const GRAPH_API: &str = "https://graph.facebook.com/v19.0";
+const SENDGRID_API: &str = "https://api.sendgrid.com/v3/mail/send";
+const MAILGUN_API: &str = "https://api.mailgun.net/v3";
+
+const MAX_TWEET_CHARS: usize = 280;
+// Discord rejects longer nonces
+const DISCORD_NONCE_CHARS: usize = 25;
+
+pub async fn execute_telegram_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let token = credential(node, context, "bot_token", "telegram").await?.token;
+    let mut body = json!({
+        "chat_id": required_config(node, "chat_id")?,
+        "text": text(node, input, "message")?,
+    });
+    if let Some(parse_mode) = config_string(node, "parse_mode") {
+        body["parse_mode"] = json!(parse_mode);
+    }
+
+    let url = format!("{}/bot{}/sendMessage", TELEGRAM_API, token);
+    let response = post_json(&url, &body, HashMap::new(), stable_reply(&[("id", "$.result.message_id")], &[])).await?;
+    let sent = platform_response("Telegram", &response)?;
+    output([("message_id", id_at("Telegram", &sent, &["id"])?)])
+}
+
+/// Posts through a channel webhook, or as a bot when only `channel_id` is configured.
+pub async fn execute_discord_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let mut body = json!({ "content": text(node, input, "message")? });
+
+    let response = match config_string(node, "webhook_url") {
+        Some(webhook_url) => {
+            // Without wait=true Discord answers 204 and does not return the message
+            let separator = if webhook_url.contains('?') { '&' } else { '?' };
+            let url = format!("{}{}wait=true", webhook_url, separator);
+            post_json(&url, &body, HashMap::new(), stable_reply(&[("id", "$.id")], &[])).await?
+        }
+        None => {
+            let channel_id = config_string(node, "channel_id")
+                .ok_or("Discord nodes need either a webhook_url or a channel_id")?;
+            let mut token = credential(node, context, "bot_token", "discord").await?;
+            // With enforce_nonce Discord returns the first message for repeats of the nonce
+            let mut nonce = idempotency_key(node, context, &body.to_string());
+            nonce.truncate(DISCORD_NONCE_CHARS);
+            body["nonce"] = json!(nonce);
+            body["enforce_nonce"] = json!(true);
+            let url = format!("{}/channels/{}/messages", DISCORD_API, channel_id);
+            let (url, body) = (&url, &body);
+            authorized(&mut token, context, |token| async move {
+                let headers = HashMap::from([("Authorization".to_string(), format!("Bot {}", token))]);
+                post_json(url, body, headers, stable_reply(&[("id", "$.id")], &[])).await
+            }).await?
+        }
+    };
+    let message = platform_response("Discord", &response)?;
+    output([("message_id", id_at("Discord", &message, &["id"])?)])
+}
+
+pub async fn execute_twitter_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let tweet_text = text(node, input, "tweet_text")?;
+    if tweet_text.chars().count() > MAX_TWEET_CHARS {
+        return Err(format!("Tweets are limited to {} characters", MAX_TWEET_CHARS));
+    }
+    let mut token = credential(node, context, "access_token", "twitter").await?;
+
+    let (url, body) = (&format!("{}/tweets", TWITTER_API), &json!({ "text": tweet_text }));
+    let response = authorized(&mut token, context, |token| async move {
+        post_json(url, body, bearer(&token), stable_reply(&[("id", "$.data.id")], &[])).await
+    }).await?;
+    let tweet = platform_response("Twitter", &response)?;
+    output([("tweet_id", id_at("Twitter", &tweet, &["id"])?)])
+}
+
+pub async fn execute_facebook_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let mut token = credential(node, context, "access_token", "facebook").await?;
+    let page_id = config_string(node, "page_id").unwrap_or_else(|| "me".to_string());
+    let body = json!({ "message": text(node, input, "message")? });
+
+    let (url, body) = (&format!("{}/{}/feed", GRAPH_API, page_id), &body);
+    let response = authorized(&mut token, context, |token| async move {
+        post_json(url, body, bearer(&token), stable_reply(&[("id", "$.id")], &[])).await
+    }).await?;
+    let post = platform_response("Facebook", &response)?;
+    output([("post_id", id_at("Facebook", &post, &["id"])?)])
+}
+
+/// Instagram publishing takes two calls: create a media container, then publish it.
+pub async fn execute_instagram_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let mut token = credential(node, context, "access_token", "instagram").await?;
+    let account_id = required_config(node, "instagram_account_id")?;
+    let mut container = json!({ "image_url": text(node, input, "image_url")? });
+    if let Ok(caption) = text(node, input, "caption") {
+        container["caption"] = json!(caption);
+    }
+
+    let (url, body) = (&format!("{}/{}/media", GRAPH_API, account_id), &container);
+    let response = authorized(&mut token, context, |token| async move {
+        post_json(url, body, bearer(&token), stable_reply(&[("id", "$.id")], &[])).await
+    }).await?;
+    let creation_id = id_at("Instagram", &platform_response("Instagram", &response)?, &["id"])?;
+
+    let (url, body) = (&format!("{}/{}/media_publish", GRAPH_API, account_id), &json!({ "creation_id": creation_id }));
+    let response = authorized(&mut token, context, |token| async move {
+        post_json(url, body, bearer(&token), stable_reply(&[("id", "$.id")], &[])).await
+    }).await?;
+    let media = platform_response("Instagram", &response)?;
+    output([("media_id", id_at("Instagram", &media, &["id"])?)])
+}
+
+/// Posts as the member owning the token unless `person_id` names one.
+pub async fn execute_linkedin_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let mut token = credential(node, context, "access_token", "linkedin").await?;
+    let person_id = match config_string(node, "person_id") {
+        Some(person_id) => person_id,
+        None => {
+            let url = &format!("{}/v2/userinfo", LINKEDIN_API);
+            let response = authorized(&mut token, context, |token| async move {
+                HttpClient::get(url, Some(bearer(&token))).await
+            }).await?;
+            id_at("LinkedIn", &platform_response("LinkedIn", &response)?, &["sub"])?
+        }
+    };
+    let author = if person_id.starts_with("urn:li:") { person_id } else { format!("urn:li:person:{}", person_id) };
+
+    let body = json!({
+        "author": author,
+        "commentary": text(node, input, "message")?,
+        "visibility": "PUBLIC",
+        "distribution": {
+            "feedDistribution": "MAIN_FEED",
+            "targetEntities": [],
+            "thirdPartyDistributionChannels": [],
+        },
+        "lifecycleState": "PUBLISHED",
+        "isReshareDisabledByAuthor": false,
+    });
+    let (url, body) = (&format!("{}/rest/posts", LINKEDIN_API), &body);
+    let response = authorized(&mut token, context, |token| async move {
+        let mut headers = bearer(&token);
+        headers.insert("LinkedIn-Version".to_string(), LINKEDIN_VERSION.to_string());
+        headers.insert("X-Restli-Protocol-Version".to_string(), "2.0.0".to_string());
+        post_json(url, body, headers, stable_reply(&[("id", "$.id")], &["x-restli-id"])).await
+    }).await?;
+    let post = platform_response("LinkedIn", &response)?;
+    // The post URN comes back in a header; the body is empty
+    let post_id = match response_header(&response, "x-restli-id") {
+        Some(urn) => urn.to_string(),
+        None => id_at("LinkedIn", &post, &["id"])?,
+    };
+    output([("post_id", post_id)])
+}
+
+/// Sends mail through SendGrid, Mailgun or a generic HTTP relay, chosen by `provider`.
+pub async fn execute_email_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let to = required_config(node, "to_email")?;
+    let from = required_config(node, "from_email")?;
+    let subject = text(node, input, "subject")?;
+    let body = text(node, input, "body")?;
+    let provider = config_string(node, "provider").unwrap_or_else(|| "sendgrid".to_string());
+    let key = idempotency_key(node, context, &[to.as_str(), &from, &subject, &body].join("\n"));
+
+    // Provider message ids differ between the replicas' copies, so SendGrid and Mailgun
+    // sends report the id the node attached instead
+    let message_id = match provider.as_str() {
+        "sendgrid" => {
+            let api_key = credential(node, context, "api_key", "sendgrid").await?.token;
+            let mail = json!({
+                "personalizations": [{ "to": [{ "email": to }] }],
+                "from": { "email": from },
+                "subject": subject,
+                "content": [{ "type": "text/plain", "value": body }],
+                "custom_args": { "idempotency_key": key },
+            });
+            let response = post_json(SENDGRID_API, &mail, bearer(&api_key), stable_reply(&[], &[])).await?;
+            platform_response("SendGrid", &response)?;
+            key
+        }
+        "mailgun" => {
+            let api_key = credential(node, context, "api_key", "mailgun").await?.token;
+            let domain = required_config(node, "domain")?;
+            let message_id = format!("<{}@{}>", key, domain);
+            let form = form_urlencode(&[
+                ("from", &from), ("to", &to), ("subject", &subject), ("text", &body), ("h:Message-Id", &message_id),
+            ]);
+            let headers = HashMap::from([
+                ("Authorization".to_string(), basic_auth("api", &api_key)),
+                ("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string()),
+            ]);
+            let url = format!("{}/{}/messages", MAILGUN_API, domain);
+            let response = post(&url, form, headers, stable_reply(&[], &[])).await?;
+            platform_response("Mailgun", &response)?;
+            message_id
+        }
+        "http" => {
+            let relay_url = required_config(node, "relay_url")?;
+            let mut headers = optional_credential(node, context, "api_key", "email").await?
+                .map(|api_key| bearer(&api_key.token))
+                .unwrap_or_default();
+            headers.insert("Idempotency-Key".to_string(), key);
+            let mail = json!({ "to": to, "from": from, "subject": subject, "body": body });
+            let reply = stable_reply(&[("message_id", "$.message_id"), ("id", "$.id")], &[]);
+            let response = post_json(&relay_url, &mail, headers, reply).await?;
+            let sent = platform_response("Email relay", &response)?;
+            id_at("Email relay", &sent, &["message_id"]).or_else(|_| id_at("Email relay", &sent, &["id"]))?
+        }
+        other => return Err(format!("Unsupported email provider '{}': use sendgrid, mailgun or http", other)),
+    };
+    output([("message_id", message_id)])
+}
+
+/// Sends `payload` (or the whole node input) to an external URL and fails on non-2xx answers.
+pub async fn execute_webhook_node(
+    node: &WorkflowNode,
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    let url = required_config(node, "url")?;
+    let method = config_string(node, "method").unwrap_or_else(|| "POST".to_string()).to_uppercase();
+    let mut headers = node.configuration.parameters.get("headers")
+        .map(string_map)
+        .unwrap_or_default();
+    let payload = input.get("payload")
+        .or_else(|| node.configuration.parameters.get("payload"))
+        .map(config_value_to_json)
+        .unwrap_or_else(|| config_value_to_json(&ConfigValue::Object(input.clone())));
+    if !headers.keys().any(|name| name.eq_ignore_ascii_case("idempotency-key")) {
+        headers.insert("Idempotency-Key".to_string(), idempotency_key(node, context, &payload.to_string()));
+    }
+
+    // Outcalls support GET and POST here; other methods are POSTs naming the method in an
+    // override header, which only servers that honour it understand, so callers opt in
+    let (outcall_method, body) = match method.as_str() {
+        "GET" => (HttpMethod::GET, None),
+        "POST" => (HttpMethod::POST, Some(payload.to_string())),
+        "PUT" | "PATCH" | "DELETE" => {
+            if !matches!(node.configuration.parameters.get("method_override"), Some(ConfigValue::Boolean(true))) {
+                return Err(format!(
+                    "HTTP outcalls support only GET and POST webhooks; set method_override to send {} as a POST with X-HTTP-Method-Override",
+                    method
+                ));
+            }
+            headers.insert("X-HTTP-Method-Override".to_string(), method.clone());
+            (HttpMethod::POST, (method != "DELETE").then(|| payload.to_string()))
+        }
+        _ => return Err(format!("Unsupported webhook method: {}", method)),
+    };
+    if body.is_some() {
+        headers = with_json_content_type(headers);
+    }
+    let response = send(&url, outcall_method, body, headers, vec![TransformStep::strip_headers()]).await?;
+    if !(200..300).contains(&response.status) {
+        return Err(format!("Webhook returned status {}: {}", response.status, truncate(&response.body)));
+    }
+
+    Ok(NodeOutput {
+        data: HashMap::from([
+            ("response_status".to_string(), ConfigValue::Number(response.status as f64)),
+            ("response_body".to_string(), ConfigValue::String(response.body)),
+        ]),
+        next_nodes: Vec::new(),
+    })
+}
+
+// Helpers
+
+fn config_string(node: &WorkflowNode, name: &str) -> Option<String> {
+    match node.configuration.parameters.get(name) {
+        Some(ConfigValue::String(value)) if !value.trim().is_empty() => Some(value.trim().to_string()),
+        Some(ConfigValue::Number(value)) => Some(format_number(*value)),
+        _ => None,
+    }
+}
+
+fn required_config(node: &WorkflowNode, name: &str) -> Result<String, String> {
+    config_string(node, name).ok_or_else(|| format!("Missing {} parameter", name))
+}
+
+/// A text field taken from the node input, falling back to the node configuration.
+fn text(node: &WorkflowNode, input: &HashMap<String, ConfigValue>, name: &str) -> Result<String, String> {
+    let value = input.get(name)
+        .or_else(|| node.configuration.parameters.get(name))
+        .ok_or_else(|| format!("Missing {} input", name))?;
+    Ok(match value {
+        ConfigValue::String(text) => text.clone(),
+        ConfigValue::Number(number) => format_number(*number),
+        ConfigValue::Boolean(flag) => flag.to_string(),
+        other => config_value_to_json(other).to_string(),
+    })
+}
+
+/// A token from the node configuration, or one the workflow's owner saved for the platform.
+struct Credential {
+    token: String,
+    platform: &'static str,
+    saved_by: Option<String>, // Set for saved tokens, which can be refreshed
+}
+
+async fn optional_credential(
+    node: &WorkflowNode,
+    context: &ExecutionContext,
+    parameter: &str,
+    platform: &'static str
+) -> Result<Option<Credential>, String> {
+    if let Some(token) = config_string(node, parameter) {
+        return Ok(Some(Credential { token, platform, saved_by: None }));
+    }
+    // Saved tokens belong to the workflow's owner, whoever started the run
+    let owner = credentials::workflow_owner(context).unwrap_or_else(|| context.user_id.clone());
+    credentials::check_owner_revision(context, &owner)?;
+    let token = oauth::access_token(&owner, platform, context.timestamp).await?;
+    Ok(token.map(|token| Credential { token, platform, saved_by: Some(owner) }))
+}
+
+async fn credential(
+    node: &WorkflowNode,
+    context: &ExecutionContext,
+    parameter: &str,
+    platform: &'static str
+) -> Result<Credential, String> {
+    optional_credential(node, context, parameter, platform).await?.ok_or_else(|| {
+        format!("No {} credentials: set {} on the node or save a {} token", platform, parameter, platform)
+    })
+}
+
+/// Send a request with `credential`. A saved token the platform rejects with 401 is
+/// refreshed and the request sent once more with the new token.
+async fn authorized<F, Fut>(credential: &mut Credential, context: &ExecutionContext, send: F) -> Result<HttpClientResponse, String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<HttpClientResponse, String>>,
+{
+    let response = send(credential.token.clone()).await?;
+    let Some(owner) = credential.saved_by.as_deref().filter(|_| response.status == 401) else {
+        return Ok(response);
+    };
+    match oauth::refresh_after_unauthorized(owner, credential.platform, &credential.token, context.timestamp).await? {
+        Some(token) => {
+            credential.token = token;
+            send(credential.token.clone()).await
+        }
+        None => Ok(response),
+    }
+}
+
+fn bearer(token: &str) -> HashMap<String, String> {
+    HashMap::from([("Authorization".to_string(), format!("Bearer {}", token))])
+}
+
+/// Key for one send of `node` in this run, the same on every replica and for any resend
+/// of the same content.
+fn idempotency_key(node: &WorkflowNode, context: &ExecutionContext, content: &str) -> String {
+    let digest = Sha256::digest([context.execution_id.as_str(), &node.id, content].join("\0"));
+    hex::encode(&digest[..16])
+}
+
+/// Transform keeping only the named values, the platform's error fields and the named
+/// headers, so the replicas' responses to a send can agree.
+fn stable_reply(values: &[(&str, &str)], headers: &[&str]) -> Vec<TransformStep> {
+    let mut paths = vec![
+        ("ok", "$.ok"),
+        ("description", "$.description"),
+        ("detail", "$.detail"),
+        ("message", "$.message"),
+        ("error", "$.error"),
+        ("errors", "$.errors"),
+    ];
+    paths.extend_from_slice(values);
+    vec![TransformStep::keep_headers(headers), TransformStep::json_extract(&paths)]
+}
+
+fn with_json_content_type(mut headers: HashMap<String, String>) -> HashMap<String, String> {
+    if !headers.keys().any(|name| name.eq_ignore_ascii_case("content-type")) {
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+    }
+    headers
+}
+
+async fn send(
+    url: &str,
+    method: HttpMethod,
+    body: Option<String>,
+    headers: HashMap<String, String>,
+    transform: Vec<TransformStep>
+) -> Result<HttpClientResponse, String> {
+    HttpClient::request(HttpRequest {
+        url: url.to_string(),
+        method,
+        headers: headers.into_iter().map(|(name, value)| HttpHeader { name, value }).collect(),
+        body: body.map(String::into_bytes),
+        max_response_bytes: Some(DEFAULT_MAX_RESPONSE_BYTES),
+        cycles: None,
+        transform,
+    }).await
+}
+
+async fn post(
+    url: &str,
+    body: String,
+    headers: HashMap<String, String>,
+    transform: Vec<TransformStep>
+) -> Result<HttpClientResponse, String> {
+    send(url, HttpMethod::POST, Some(body), headers, transform).await
+}
+
+async fn post_json(
+    url: &str,
+    body: &Value,
+    headers: HashMap<String, String>,
+    transform: Vec<TransformStep>
+) -> Result<HttpClientResponse, String> {
+    post(url, body.to_string(), with_json_content_type(headers), transform).await
+}
+
+/// The parsed response body, or the platform's error message for failed calls.
+fn platform_response(platform: &str, response: &HttpClientResponse) -> Result<Value, String> {
+    let body = if response.body.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(&response.body).unwrap_or_else(|_| Value::String(response.body.clone()))
+    };
+    // Telegram answers some failures with 200 and "ok": false
+    let failed = !(200..300).contains(&response.status) || body.get("ok") == Some(&Value::Bool(false));
+    if failed {
+        return Err(format!("{} API error ({}): {}", platform, response.status, error_message(&body)));
+    }
+    Ok(body)
+}
+
+fn error_message(body: &Value) -> String {
+    let candidates = [
+        body.get("description"),
+        body.pointer("/error/message"),
+        body.get("detail"),
+        body.get("message"),
+        body.pointer("/errors/0/message"),
+        body.get("error"),
+    ];
+    // Fields kept by `stable_reply` are null when the platform did not send them
+    match candidates.into_iter().flatten().find(|value| !value.is_null()) {
+        Some(Value::String(message)) => message.clone(),
+        Some(other) => truncate(&other.to_string()),
+        None => truncate(&body.to_string()),
+    }
+}
+
+/// A string or numeric id at `path` in a response body.
+fn id_at(platform: &str, body: &Value, path: &[&str]) -> Result<String, String> {
+    let value = path.iter().try_fold(body, |value, key| value.get(key));
+    match value {
+        Some(Value::String(id)) => Ok(id.clone()),
+        Some(Value::Number(id)) => Ok(id.to_string()),
+        _ => Err(format!("{} response has no {}: {}", platform, path.join("."), error_message(body))),
+    }
+}
+
+fn string_map(value: &ConfigValue) -> HashMap<String, String> {
+    match value {
+        ConfigValue::Object(entries) => entries.iter()
+            .filter_map(|(key, value)| match value {
+                ConfigValue::String(value) => Some((key.clone(), value.clone())),
+                _ => None,
+            })
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 { format!("{}", value as i64) } else { value.to_string() }
+}
+
+fn truncate(text: &str) -> String {
+    const MAX_CHARS: usize = 200;
+    if text.chars().count() > MAX_CHARS {
+        format!("{}...", text.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+fn output<const N: usize>(fields: [(&str, String); N]) -> Result<NodeOutput, String> {
+    Ok(NodeOutput {
+        data: fields.into_iter().map(|(name, value)| (name.to_string(), ConfigValue::String(value))).collect(),
+        next_nodes: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::mock;
+    use crate::stable_user_storage;
+    use crate::storage;
+    use crate::types::{NodeConfiguration, OAuthClient, OAuthToken};
+    use futures::executor::block_on;
+
+    const USER: &str = "2vxsx-fae";
+
+    fn node(node_type: &str, parameters: &[(&str, &str)]) -> WorkflowNode {
+        WorkflowNode {
+            id: "n1".to_string(),
+            node_type: node_type.to_string(),
+            configuration: NodeConfiguration {
+                parameters: parameters.iter()
+                    .map(|(k, v)| (k.to_string(), ConfigValue::String(v.to_string())))
+                    .collect(),
+            },
+            ..WorkflowNode::default()
+        }
+    }
+
+    fn context() -> ExecutionContext {
+        ExecutionContext {
+            workflow_id: "wf".to_string(),
+            execution_id: "exec".to_string(),
+            user_id: USER.to_string(),
+            timestamp: 0,
+            global_variables: HashMap::new(),
+        }
+    }
+
+    fn input(fields: &[(&str, &str)]) -> HashMap<String, ConfigValue> {
+        fields.iter().map(|(k, v)| (k.to_string(), ConfigValue::String(v.to_string()))).collect()
+    }
+
+    fn sent_json(index: usize) -> Value {
+        serde_json::from_slice(mock::requests()[index].body.as_ref().unwrap()).unwrap()
+    }
+
+    fn header(index: usize, name: &str) -> Option<String> {
+        mock::requests()[index].headers.iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.clone())
+    }
+
+    fn field(output: &NodeOutput, name: &str) -> String {
+        match output.data.get(name) {
+            Some(ConfigValue::String(value)) => value.clone(),
+            other => panic!("{} is {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn test_telegram_sends_message_and_reports_api_errors() {
+        mock::respond("https://api.telegram.org/bot123:abc/sendMessage", 200, r#"{"ok":true,"result":{"message_id":42,"chat":{"id":-100}}}"#);
+        let telegram = node("telegram", &[("bot_token", "123:abc"), ("chat_id", "-100")]);
+
+        let output = block_on(execute_telegram_node(&telegram, &input(&[("message", "Price alert")]), &context())).unwrap();
+        assert_eq!(field(&output, "message_id"), "42");
+        assert_eq!(sent_json(0), json!({ "chat_id": "-100", "text": "Price alert" }));
+
+        mock::respond("https://api.telegram.org/bot123:abc/sendMessage", 400, r#"{"ok":false,"error_code":400,"description":"Bad Request: chat not found"}"#);
+        let error = block_on(execute_telegram_node(&telegram, &input(&[("message", "hi")]), &context())).unwrap_err();
+        assert_eq!(error, "Telegram API error (400): Bad Request: chat not found");
+    }
+
+    #[test]
+    fn test_discord_uses_webhooks_or_the_saved_bot_token() {
+        mock::respond("https://discord.com/api/webhooks/1/t", 200, r#"{"id":"9001","channel_id":"7"}"#);
+        let webhook = node("discord", &[("webhook_url", "https://discord.com/api/webhooks/1/t")]);
+        let output = block_on(execute_discord_node(&webhook, &input(&[("message", "gm")]), &context())).unwrap();
+        assert_eq!(field(&output, "message_id"), "9001");
+        assert_eq!(mock::requests()[0].url, "https://discord.com/api/webhooks/1/t?wait=true");
+
+        let bot = node("discord", &[("channel_id", "7")]);
+        assert!(block_on(execute_discord_node(&bot, &input(&[("message", "gm")]), &context())).unwrap_err().contains("No discord credentials"));
+
+        credentials::install_test_vault_key();
+        stable_user_storage::insert_oauth_token(USER.to_string(), "discord".to_string(), OAuthToken {
+            user_principal: USER.to_string(),
+            platform: "discord".to_string(),
+            access_token: "bot-secret".to_string(),
+            refresh_token: None,
+            expires_at: 0,
+            scopes: Vec::new(),
+        }, 0).unwrap();
+        mock::respond("https://discord.com/api/v10/channels/7/messages", 200, r#"{"id":"9002"}"#);
+        let output = block_on(execute_discord_node(&bot, &input(&[("message", "gm")]), &context())).unwrap();
+        assert_eq!(field(&output, "message_id"), "9002");
+        assert_eq!(header(1, "authorization").as_deref(), Some("Bot bot-secret"));
+    }
+
+    #[test]
+    fn test_social_posts_parse_platform_ids() {
+        mock::respond("https://api.twitter.com/2/tweets", 201, r#"{"data":{"id":"1790","text":"gm"}}"#);
+        let twitter = node("twitter", &[("access_token", "tw")]);
+        let output = block_on(execute_twitter_node(&twitter, &input(&[("tweet_text", "gm")]), &context())).unwrap();
+        assert_eq!(field(&output, "tweet_id"), "1790");
+        assert_eq!(header(0, "authorization").as_deref(), Some("Bearer tw"));
+
+        mock::respond("https://graph.facebook.com/v19.0/ig1/media", 200, r#"{"id":"container"}"#);
+        mock::respond("https://graph.facebook.com/v19.0/ig1/media_publish", 200, r#"{"id":"media-5"}"#);
+        let instagram = node("instagram", &[("access_token", "fb"), ("instagram_account_id", "ig1")]);
+        let post = input(&[("image_url", "https://img.example/1.png"), ("caption", "New vault")]);
+        let output = block_on(execute_instagram_node(&instagram, &post, &context())).unwrap();
+        assert_eq!(field(&output, "media_id"), "media-5");
+        assert_eq!(sent_json(2), json!({ "creation_id": "container" }));
+
+        mock::respond("https://api.linkedin.com/v2/userinfo", 200, r#"{"sub":"abc"}"#);
+        mock::respond_with_headers("https://api.linkedin.com/rest/posts", 201, &[("X-RestLi-Id", "urn:li:share:7")], "");
+        let linkedin = node("linkedin", &[("access_token", "li")]);
+        let output = block_on(execute_linkedin_node(&linkedin, &input(&[("message", "Hello")]), &context())).unwrap();
+        assert_eq!(field(&output, "post_id"), "urn:li:share:7");
+        assert_eq!(sent_json(4)["author"], "urn:li:person:abc");
+
+        mock::respond("https://api.twitter.com/2/tweets", 403, r#"{"title":"Forbidden","detail":"You are not permitted to perform this action."}"#);
+        let error = block_on(execute_twitter_node(&twitter, &input(&[("tweet_text", "gm")]), &context())).unwrap_err();
+        assert_eq!(error, "Twitter API error (403): You are not permitted to perform this action.");
+    }
+
+    #[test]
+    fn test_saved_tokens_are_refreshed_after_a_401() {
+        storage::insert_oauth_client(OAuthClient {
+            provider: "twitter".to_string(),
+            client_id: "client-1".to_string(),
+            has_client_secret: false,
+            relay_url: Some("https://relay.example/exchange".to_string()),
+            configured_at: 0,
+        });
+        credentials::install_test_vault_key();
+        stable_user_storage::insert_oauth_token(USER.to_string(), "twitter".to_string(), OAuthToken {
+            user_principal: USER.to_string(),
+            platform: "twitter".to_string(),
+            access_token: "revoked-access".to_string(),
+            refresh_token: Some("refresh-1".to_string()),
+            expires_at: 0,
+            scopes: Vec::new(),
+        }, 0).unwrap();
+        mock::respond("https://api.twitter.com/2/tweets", 201, r#"{"data":{"id":"1791"}}"#);
+        mock::respond_once("https://api.twitter.com/2/tweets", 401, r#"{"title":"Unauthorized","status":401}"#);
+        mock::respond("https://relay.example/exchange", 200, r#"{"access_token":"fresh-access","expires_in":7200}"#);
+
+        let twitter = node("twitter", &[]);
+        let output = block_on(execute_twitter_node(&twitter, &input(&[("tweet_text", "gm")]), &context())).unwrap();
+        assert_eq!(field(&output, "tweet_id"), "1791");
+        assert_eq!(header(0, "authorization").as_deref(), Some("Bearer revoked-access"));
+        assert_eq!(mock::requests()[1].url, "https://relay.example/exchange");
+        assert_eq!(header(2, "authorization").as_deref(), Some("Bearer fresh-access"));
+        assert_eq!(stable_user_storage::get_oauth_token(USER, "twitter").unwrap().unwrap().access_token, "fresh-access");
+    }
+
+    #[test]
+    fn test_email_providers() {
+        mock::respond_with_headers("https://api.sendgrid.com/v3/mail/send", 202, &[("X-Message-Id", "sg-1")], "");
+        let sendgrid = node("email", &[("to_email", "a@b.io"), ("from_email", "bot@deflow.io"), ("api_key", "SG.key")]);
+        let mail = input(&[("subject", "Report"), ("body", "All good")]);
+        let output = block_on(execute_email_node(&sendgrid, &mail, &context())).unwrap();
+        let key = field(&output, "message_id");
+        assert_eq!(sent_json(0)["personalizations"][0]["to"][0]["email"], "a@b.io");
+        assert_eq!(sent_json(0)["custom_args"]["idempotency_key"], key.as_str());
+
+        mock::respond("https://api.mailgun.net/v3/mg.deflow.io/messages", 200, r#"{"id":"<mg-2@deflow.io>","message":"Queued. Thank you."}"#);
+        let mailgun = node("email", &[
+            ("provider", "mailgun"), ("domain", "mg.deflow.io"), ("to_email", "a@b.io"), ("from_email", "bot@deflow.io"), ("api_key", "key"),
+        ]);
+        let output = block_on(execute_email_node(&mailgun, &mail, &context())).unwrap();
+        assert_eq!(field(&output, "message_id"), format!("<{}@mg.deflow.io>", key));
+        assert_eq!(header(1, "authorization").as_deref(), Some("Basic YXBpOmtleQ=="));
+        let form = String::from_utf8(mock::requests()[1].body.clone().unwrap()).unwrap();
+        assert!(form.contains("subject=Report") && form.contains("text=All+good") && form.contains("h%3AMessage-Id="));
+    }
+
+    #[test]
+    fn test_webhook_sends_payload_and_fails_on_error_status() {
+        mock::respond("https://hooks.example/ok", 200, "accepted");
+        let mut webhook = node("webhook", &[("url", "https://hooks.example/ok")]);
+        let payload = HashMap::from([(
+            "payload".to_string(),
+            ConfigValue::Object(HashMap::from([("price".to_string(), ConfigValue::Number(61000.0))])),
+        )]);
+        let output = block_on(execute_webhook_node(&webhook, &payload, &context())).unwrap();
+        assert!(matches!(output.data.get("response_status"), Some(ConfigValue::Number(n)) if *n == 200.0));
+        assert_eq!(sent_json(0), json!({ "price": 61000.0 }));
+
+        mock::respond("https://hooks.example/down", 503, "unavailable");
+        webhook.configuration.parameters.insert("url".to_string(), ConfigValue::String("https://hooks.example/down".to_string()));
+        let error = block_on(execute_webhook_node(&webhook, &payload, &context())).unwrap_err();
+        assert_eq!(error, "Webhook returned status 503: unavailable");
+    }
+
+    #[test]
+    fn test_webhook_methods_on_the_wire() {
+        mock::respond("https://hooks.example/item", 200, "done");
+        let payload = input(&[("payload", "ping")]);
+        let send = |method: &str, method_override: bool| {
+            let mut webhook = node("webhook", &[("url", "https://hooks.example/item"), ("method", method)]);
+            webhook.configuration.parameters.insert("method_override".to_string(), ConfigValue::Boolean(method_override));
+            block_on(execute_webhook_node(&webhook, &payload, &context()))
+        };
+
+        // Methods outcalls lack are refused rather than sent as something else
+        for method in ["PUT", "PATCH", "DELETE"] {
+            assert!(send(method, false).unwrap_err().contains(&format!("set method_override to send {}", method)));
+        }
+        assert!(mock::requests().is_empty());
+
+        let cases = [
+            ("GET", false, HttpMethod::GET, None, false),
+            ("POST", false, HttpMethod::POST, None, true),
+            ("PUT", true, HttpMethod::POST, Some("PUT"), true),
+            ("PATCH", true, HttpMethod::POST, Some("PATCH"), true),
+            ("DELETE", true, HttpMethod::POST, Some("DELETE"), false),
+        ];
+        for (i, (method, method_override, on_wire, override_header, has_body)) in cases.into_iter().enumerate() {
+            send(method, method_override).unwrap();
+            let request = &mock::requests()[i];
+            assert_eq!(request.method, on_wire, "{}", method);
+            assert_eq!(header(i, "x-http-method-override").as_deref(), override_header, "{}", method);
+            assert_eq!(request.body.is_some(), has_body, "{}", method);
+            assert!(header(i, "idempotency-key").is_some(), "{}", method);
+            assert_eq!(request.transform, vec![TransformStep::strip_headers()], "{}", method);
+        }
+    }
+
+    #[test]
+    fn test_replicated_sends_are_one_logical_send() {
+        // Each replica makes the outcall; the platform answers the repeats of a nonce with
+        // the same message, but volatile fields and headers still differ between them
+        credentials::install_test_vault_key();
+        stable_user_storage::insert_oauth_token(USER.to_string(), "discord".to_string(), OAuthToken {
+            user_principal: USER.to_string(),
+            platform: "discord".to_string(),
+            access_token: "bot-secret".to_string(),
+            refresh_token: None,
+            expires_at: 0,
+            scopes: Vec::new(),
+        }, 0).unwrap();
+        let bot = node("discord", &[("channel_id", "7")]);
+        let message = input(&[("message", "gm")]);
+        let mut outputs = Vec::new();
+        for (replica, trace) in ["a1", "b2"].iter().enumerate() {
+            mock::respond_with_headers(
+                "https://discord.com/api/v10/channels/7/messages",
+                200,
+                &[("cf-ray", trace)],
+                &format!(r#"{{"id":"9100","channel_id":"7","timestamp":"2026-10-16T00:00:0{}Z"}}"#, replica),
+            );
+            outputs.push(block_on(execute_discord_node(&bot, &message, &context())).unwrap());
+        }
+
+        let requests = mock::requests();
+        assert_eq!(requests[0].body, requests[1].body);
+        assert!(sent_json(0)["nonce"].as_str().unwrap().len() <= DISCORD_NONCE_CHARS);
+        assert_eq!(sent_json(0)["enforce_nonce"], true);
+        assert_eq!(field(&outputs[0], "message_id"), field(&outputs[1], "message_id"));
+
+        // What consensus compares: each replica's response after the request's transform
+        let transformed: Vec<_> = ["a1", "b2"].iter().enumerate()
+            .map(|(replica, trace)| crate::http_transforms::apply(
+                &crate::http_transforms::encode(&requests[replica].transform),
+                ic_cdk::api::management_canister::http_request::HttpResponse {
+                    status: 200u16.into(),
+                    headers: vec![HttpHeader { name: "cf-ray".to_string(), value: trace.to_string() }],
+                    body: format!(r#"{{"id":"9100","timestamp":"2026-10-16T00:00:0{}Z"}}"#, replica).into_bytes(),
+                },
+            ))
+            .collect();
+        assert_eq!(transformed[0], transformed[1]);
+
+        // A different run sends a different key; webhooks carry it as a header
+        let webhook = node("webhook", &[("url", "https://hooks.example/ok")]);
+        mock::respond("https://hooks.example/ok", 200, "accepted");
+        let other_run = ExecutionContext { execution_id: "exec-2".to_string(), ..context() };
+        block_on(execute_webhook_node(&webhook, &message, &context())).unwrap();
+        block_on(execute_webhook_node(&webhook, &message, &context())).unwrap();
+        block_on(execute_webhook_node(&webhook, &message, &other_run)).unwrap();
+        assert_eq!(header(2, "idempotency-key"), header(3, "idempotency-key"));
+        assert_ne!(header(3, "idempotency-key"), header(4, "idempotency-key"));
+
+        assert_eq!(crate::types::RetryPolicy::default_for("discord").max_retries, 0);
+        assert_eq!(crate::types::RetryPolicy::default_for("http_request").max_retries, 3);
+    }
+}
@@ -1,12 +1,26 @@
 mod types;
 mod storage;
+mod schema;
 mod stable_user_storage;
 mod workflow;
+mod acl;
+mod revisions;
 mod execution;
 mod nodes;
+mod expressions;
+mod cron;
+mod conditions;
 mod events;
+mod system_events;
+mod webhooks;
+mod chain_watchers;
 mod http_client;
+mod http_transforms;
+mod credentials;
+mod oauth;
+mod integrations;
 mod defi;
+mod defi_storage;
 mod user_management;
 mod security;
 mod scheduler_service;
@@ -16,24 +30,38 @@ mod fee_collection;
 // Re-export types for external use
 pub use types::*;
 
-use ic_cdk::{init, post_upgrade, pre_upgrade, query, heartbeat, spawn, update};
+use ic_cdk::{init, post_upgrade, query, heartbeat, update};
 use candid::{CandidType, Deserialize, Principal};
 use serde::Serialize;
 use nodes::initialize_built_in_nodes;
-use events::restore_scheduled_workflows;
-use storage::{save_workflow_state_for_upgrade, restore_workflow_state_after_upgrade};
-use types::{InternalWorkflowState, SystemHealth as InternalSystemHealth, ExecutionStatus as InternalExecutionStatus};
+use types::{SystemHealth as InternalSystemHealth, ExecutionStatus as InternalExecutionStatus};
 use defi::api::get_defi_system_health;
 
 // Re-export all the API functions from modules
+pub use revisions::{list_workflow_revisions, get_workflow_revision, diff_workflow_revisions, rollback_workflow};
+pub use credentials::{save_credential, rotate_credential, delete_credential, list_credentials, rotate_vault_key};
+pub use oauth::{configure_oauth_client, list_oauth_providers};
+pub use acl::{share_workflow, revoke_workflow_access, list_workflow_shares, get_workflow_acl_audit_log};
 pub use workflow::{create_workflow, update_workflow, get_workflow, list_workflows, delete_workflow, validate_workflow_query, analyze_workflow_query, WorkflowAnalysis};
-pub use execution::{start_execution, get_execution, list_executions, retry_failed_execution, resume_active_workflows};
+pub use execution::{
+    start_execution, get_execution, list_executions, retry_failed_execution, resume_active_workflows,
+    cancel_execution, pause_execution, resume_execution
+};
 pub use nodes::{register_node, get_node_definition, list_node_types, list_nodes_by_category};
 pub use events::{
-    emit_event, register_event_listener, webhook_trigger, register_webhook,
+    emit_event, register_event_listener,
     schedule_workflow, unschedule_workflow, list_scheduled_workflows,
-    set_retry_policy, get_retry_policy_for_node,
-    schedule_workflow_execution, list_persistent_scheduled_executions, cancel_persistent_execution
+    set_retry_policy, get_retry_policy_for_node
+};
+pub use system_events::list_system_events;
+pub use schema::dry_run_schema_migrations;
+pub use chain_watchers::{
+    create_chain_watch, pause_chain_watch, resume_chain_watch, delete_chain_watch, list_chain_watches,
+    get_chain_watch, poll_chain_watch
+};
+pub use webhooks::{
+    http_request, http_request_update, webhook_trigger, register_webhook, configure_webhook,
+    unregister_webhook, list_webhooks
 };
 // DeFi functions are available as canister endpoints in defi::api module
 // Strategy API functions - Advanced DeFi strategy management
@@ -111,6 +139,8 @@ fn init(pool_canister_id: Option<String>) {
     };
     
     initialize_fee_collection(pool_id);
+    chain_watchers::start_polling();
+    start_cycles_monitoring();
     
     // Initialize DeFi system
     ic_cdk::spawn(async {
@@ -135,32 +165,21 @@ fn init(pool_canister_id: Option<String>) {
     
 }
 
-#[pre_upgrade]
-fn pre_upgrade() {
-    let state = save_workflow_state_for_upgrade();
-    ic_cdk::storage::stable_save((state,))
-        .expect("Failed to save state before upgrade");
-}
-
+// All state lives in stable structures, so there is no pre_upgrade hook: saving to raw
+// stable memory there would overwrite the memory manager's regions.
 #[post_upgrade]
 fn post_upgrade() {
-    // Try to restore the workflow state, but handle gracefully if it fails
-    match ic_cdk::storage::stable_restore::<(InternalWorkflowState,)>() {
-        Ok((saved_state,)) => {
-            restore_workflow_state_after_upgrade(saved_state);
-        }
-        Err(e) => {
-            
-            // Initialize with a fresh default state
-            let fresh_state = InternalWorkflowState::default();
-            restore_workflow_state_after_upgrade(fresh_state);
-        }
-    }
+    // Rewrite records stored under older schema versions before anything reads them
+    schema::migrate_stored_records();
     
     // Re-initialize components
     initialize_built_in_nodes();
-    restore_scheduled_workflows();
+    scheduler_service::restore_schedules();
+    execution::restore_waiting_executions();
     resume_active_workflows();
+    oauth::start_sealing_saved_tokens();
+    chain_watchers::start_polling();
+    start_cycles_monitoring();
     
     // Re-initialize DeFi system
     ic_cdk::spawn(async {
@@ -183,6 +202,8 @@ fn post_upgrade() {
     // Re-initialize workflow template system
     defi::simple_template_api::init_simple_workflow_template_system();
     
+    // Rebuild DeFi state from stable memory once the managers are initialized
+    defi::restore_persisted_state();
 }
 
 #[heartbeat]
@@ -196,16 +217,6 @@ async fn heartbeat() {
     // Update heartbeat timestamp
     state.system_health.last_heartbeat = current_time;
     
-    // Check for scheduled workflow executions
-    let due_workflows = get_due_workflows(current_time, &state.scheduled_executions);
-    
-    for workflow_id in due_workflows {
-        spawn(async move {
-            if let Ok(execution_id) = start_execution(workflow_id.clone(), None).await {
-            }
-        });
-    }
-    
     // Monitor active workflows for timeouts
     monitor_active_executions(&mut state).await;
     
@@ -219,14 +230,6 @@ async fn heartbeat() {
     update_workflow_state(state);
 }
 
-fn get_due_workflows(current_time: u64, scheduled_executions: &[(u64, String)]) -> Vec<String> {
-    scheduled_executions
-        .iter()
-        .filter(|(timestamp, _)| *timestamp <= current_time)
-        .map(|(_, workflow_id)| workflow_id.clone())
-        .collect()
-}
-
 async fn monitor_active_executions(state: &mut InternalWorkflowState) {
     let current_time = ic_cdk::api::time();
     let timeout_threshold = 30 * 60 * 1_000_000_000; // 30 minutes in nanoseconds
@@ -874,126 +877,76 @@ Price Monitor → Alert Trigger → DeFi Execution → JSON Formatting → Socia
 // SCHEDULER API ENDPOINTS
 // =============================================================================
 
-use scheduler_service::{SchedulerService, ScheduleResult, ScheduleInfo};
-use std::cell::RefCell;
+/// Create a one-shot, interval or cron schedule for a workflow
+#[update]
+fn create_schedule(request: ScheduleRequest) -> Result<ScheduledWorkflow, String> {
+    scheduler_service::create_schedule(request)
+}
 
-thread_local! {
-    static SCHEDULER: RefCell<SchedulerService> = RefCell::new(SchedulerService::new());
+/// Replace the timing of an existing schedule, keeping its run history
+#[update]
+fn update_schedule(schedule_id: String, request: ScheduleRequest) -> Result<ScheduledWorkflow, String> {
+    scheduler_service::update_schedule(&schedule_id, request)
 }
 
-/// Create a one-time schedule using universal date format (dd/mm/yy hh:mm:ss)
+/// Stop a schedule from firing until it is resumed
 #[update]
-async fn create_schedule(
-    datetime_string: String,
-    workflow_id: String,
-    node_id: String,
-    timezone: Option<String>,
-) -> Result<ScheduleResult, String> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow_mut().create_schedule_from_universal_format(
-            &datetime_string,
-            workflow_id,
-            node_id,
-            timezone,
-        )
-    })
+fn pause_schedule(schedule_id: String) -> Result<ScheduledWorkflow, String> {
+    scheduler_service::pause_schedule(&schedule_id)
 }
 
-/// Create a recurring schedule starting at specified time
+/// Resume a paused schedule, applying its missed-run policy
 #[update]
-async fn create_recurring_schedule(
-    start_datetime: String,
-    interval_seconds: u64,
-    workflow_id: String,
-    node_id: String,
-    max_executions: Option<u64>,
-    timezone: Option<String>,
-) -> Result<ScheduleResult, String> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow_mut().create_recurring_schedule(
-            &start_datetime,
-            interval_seconds,
-            workflow_id,
-            node_id,
-            max_executions,
-            timezone,
-        )
-    })
+fn resume_schedule(schedule_id: String) -> Result<ScheduledWorkflow, String> {
+    scheduler_service::resume_schedule(&schedule_id)
 }
 
-/// Create a schedule using cron expression
+/// Delete a schedule
 #[update]
-async fn create_cron_schedule(
-    cron_expression: String,
-    workflow_id: String,
-    node_id: String,
-    timezone: Option<String>,
-) -> Result<ScheduleResult, String> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow_mut().create_cron_schedule(
-            &cron_expression,
-            workflow_id,
-            node_id,
-            timezone,
-        )
-    })
+fn cancel_schedule(schedule_id: String) -> Result<(), String> {
+    scheduler_service::cancel_schedule(&schedule_id)
 }
 
-/// List all active schedules
+/// List all schedules, including paused and completed ones
 #[query]
-fn list_schedules() -> Vec<ScheduleInfo> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow().list_active_schedules()
-    })
+fn list_schedules() -> Vec<ScheduledWorkflow> {
+    scheduler_service::list_schedules()
 }
 
 /// Get details of a specific schedule
 #[query]
-fn get_schedule_details(schedule_id: String) -> Option<ScheduleInfo> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow().get_schedule(&schedule_id).cloned()
-    })
-}
-
-/// Cancel a schedule
-#[update]
-async fn cancel_schedule(schedule_id: String) -> Result<String, String> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow_mut().cancel_schedule(&schedule_id)
-    })
+fn get_schedule_details(schedule_id: String) -> Option<ScheduledWorkflow> {
+    scheduler_service::get_schedule(&schedule_id)
 }
 
-/// Update an existing schedule with new date/time
-#[update]
-async fn update_schedule(
-    schedule_id: String,
-    new_datetime: String,
-) -> Result<ScheduleResult, String> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow_mut().update_schedule(&schedule_id, &new_datetime)
-    })
+/// Recent runs of a schedule, oldest first
+#[query]
+fn get_schedule_history(schedule_id: String) -> Result<Vec<ScheduleRun>, String> {
+    scheduler_service::get_schedule_history(&schedule_id)
 }
 
 /// Get next upcoming executions
 #[query]
 fn get_upcoming_executions(limit: usize) -> Vec<(String, u64, String)> {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow().get_next_executions(limit)
-    })
+    scheduler_service::get_next_executions(limit)
+}
+
+/// Convert a universal-format date (dd/mm/yy hh:mm:ss) to nanoseconds since the epoch
+#[query]
+fn parse_schedule_datetime(datetime_string: String, timezone: Option<String>) -> Result<u64, String> {
+    scheduler_service::parse_universal_datetime(&datetime_string, timezone.as_deref())
 }
 
 /// Convert timestamp to universal format for display
 #[query]
 fn format_timestamp(timestamp_ns: u64) -> String {
-    SCHEDULER.with(|scheduler| {
-        scheduler.borrow().format_timestamp_to_universal(timestamp_ns)
-    })
+    scheduler_service::format_timestamp_to_universal(timestamp_ns)
 }
 
 /// Get scheduler usage examples and documentation
 #[query]
 fn get_scheduler_examples() -> String {
-    SchedulerService::example_usage()
+    scheduler_service::example_usage()
 }
 
 // =============================================================================
@@ -1001,11 +954,28 @@ fn get_scheduler_examples() -> String {
 // =============================================================================
 
 use cycles_monitor_service::{CyclesMonitorService, CyclesMonitorConfig, CyclesMonitorResult, CyclesData, CyclesStatistics};
+use std::cell::RefCell;
 
 thread_local! {
     static CYCLES_MONITOR: RefCell<CyclesMonitorService> = RefCell::new(CyclesMonitorService::new());
 }
 
+const CYCLES_CHECK_INTERVAL_SECONDS: u64 = 60 * 60;
+
+/// Check every cycles monitor hourly and raise `cycles.*` events for the alerts.
+fn start_cycles_monitoring() {
+    ic_cdk_timers::set_timer_interval(std::time::Duration::from_secs(CYCLES_CHECK_INTERVAL_SECONDS), || {
+        let alerts = CYCLES_MONITOR.with(|monitor| {
+            monitor.borrow_mut().check_all(ic_cdk::api::canister_balance128(), ic_cdk::api::time())
+        });
+        for (alert, owner) in alerts {
+            if let Some((event_type, data)) = system_events::cycles_alert(&alert, &owner) {
+                system_events::emit(event_type, data);
+            }
+        }
+    });
+}
+
 /// Create a new cycles monitor for a canister
 #[update]
 async fn create_cycles_monitor(
@@ -1024,7 +994,7 @@ async fn create_cycles_monitor(
         auto_topup,
         topup_amount,
         notification_channels,
-        owner: "current_user".to_string(), // In production, get from auth
+        owner: ic_cdk::caller().to_text(),
         created_at: ic_cdk::api::time(),
         last_alert_sent: None,
     };
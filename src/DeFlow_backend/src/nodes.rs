@@ -4,11 +4,14 @@ use crate::types::{
     ExecutionContext
 };
 use crate::storage;
+use crate::credentials;
+use crate::integrations;
+use crate::expressions::{parse_expression, parse_template, evaluate_expression, to_display_string};
 use crate::defi::{ChainId, Asset};
 use crate::defi::types::*;
 use crate::fee_collection::{FeeCollectionService, TransactionFeeRequest};
 use crate::security::spending_limits_enforcement::{SpendingLimitsEnforcement, SpendingError};
-use ic_cdk::{api, update, query, caller};
+use ic_cdk::{api, update, query};
 use candid::Principal;
 use std::collections::HashMap;
 
@@ -196,7 +199,18 @@ pub async fn execute_node_internal(
     node: &WorkflowNode,
     input_data: &HashMap<String, ConfigValue>,
     context: &ExecutionContext
-) -> Result<NodeOutput, String> {
+) -> Result<NodeOutput, NodeError> {
+    // Resolve `{{credential:id}}` references, and keep the secrets out of what gets recorded
+    let (node, secrets) = credentials::resolve_node(node, context).map_err(NodeError::ConfigurationError)?;
+    let result = execute_resolved_node(&node, input_data, context).await;
+    credentials::redact_result(result, &secrets)
+}
+
+async fn execute_resolved_node(
+    node: &WorkflowNode,
+    input_data: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, NodeError> {
     let timeout_ms = node.configuration.parameters
         .get("timeout")
         .and_then(|v| match v {
@@ -217,10 +231,9 @@ pub async fn execute_node_internal(
     // Collect fee before executing DeFi operations
     if is_defi_operation {
         let transaction_value = extract_transaction_value(node, input_data);
-        if transaction_value > 0 {
-            // Get user from context (for now use anonymous, in production get from context)
-            let user = caller(); // Get the actual caller
-            
+        // Fees are charged to the principal the execution runs for, not the message caller
+        let user = context_principal(context);
+        if let (true, Ok(user)) = (transaction_value > 0, user) {
             // Create a default asset (should be extracted from node config in production)
             let asset = Asset {
                 symbol: "USDC".to_string(),
@@ -237,19 +250,50 @@ pub async fn execute_node_internal(
     }
 
     let result = match node.node_type.as_str() {
+        // Executors that classify their own failures
+        "http_request" => execute_http_request_node(node, input_data).await,
+        _ => execute_node_type(node, input_data, context).await.map_err(NodeError::from),
+    };
+    
+    let elapsed_ms = (api::time() - start_time) / 1_000_000;
+    if elapsed_ms > timeout_ms {
+        return Err(NodeError::TimeoutError);
+    }
+    
+    result
+}
+
+async fn execute_node_type(
+    node: &WorkflowNode,
+    input_data: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
+) -> Result<NodeOutput, String> {
+    match node.node_type.as_str() {
         "delay" => execute_delay_node(node, input_data).await,
         "condition" => execute_condition_node(node, input_data).await,
         "transform" => execute_transform_node(node, input_data).await,
-        "http_request" => execute_http_request_node(node, input_data).await,
         "timer" => execute_timer_node(node, input_data).await,
+        "for_each" => Err("for_each nodes can only run as part of a workflow execution".to_string()),
+        "sub_workflow" => crate::execution::execute_sub_workflow_node(node, input_data, context).await,
+        "set_variable" => execute_set_variable_node(node, input_data).await,
+        "get_variable" => execute_get_variable_node(node, context).await,
+        // Messaging, social and outbound webhook nodes
+        "telegram" => integrations::execute_telegram_node(node, input_data, context).await,
+        "discord" => integrations::execute_discord_node(node, input_data, context).await,
+        "twitter" => integrations::execute_twitter_node(node, input_data, context).await,
+        "facebook" => integrations::execute_facebook_node(node, input_data, context).await,
+        "instagram" => integrations::execute_instagram_node(node, input_data, context).await,
+        "linkedin" => integrations::execute_linkedin_node(node, input_data, context).await,
+        "email" => integrations::execute_email_node(node, input_data, context).await,
+        "webhook" => integrations::execute_webhook_node(node, input_data, context).await,
         // Bitcoin DeFi nodes
         "bitcoin_portfolio" => execute_bitcoin_portfolio_node(node, input_data).await,
-        "bitcoin_send" => execute_bitcoin_send_node(node, input_data).await,
+        "bitcoin_send" => execute_bitcoin_send_node(node, input_data, context).await,
         "bitcoin_address" => execute_bitcoin_address_node(node, input_data).await,
         "bitcoin_balance" => execute_bitcoin_balance_node(node, input_data).await,
         // Ethereum & L2 DeFi nodes
         "ethereum_portfolio" => execute_ethereum_portfolio_node(node, input_data).await,
-        "ethereum_send" => execute_ethereum_send_node(node, input_data).await,
+        "ethereum_send" => execute_ethereum_send_node(node, input_data, context).await,
         "ethereum_address" => execute_ethereum_address_node(node, input_data).await,
         "ethereum_gas_estimate" => execute_ethereum_gas_estimate_node(node, input_data).await,
         "l2_optimization" => execute_l2_optimization_node(node, input_data).await,
@@ -283,35 +327,117 @@ pub async fn execute_node_internal(
         "content-optimizer" => execute_content_optimizer_node(node, input_data).await,
         "ai-responder" => execute_ai_responder_node(node, input_data).await,
         _ => execute_custom_node(node, input_data, context).await,
-    };
-    
-    let elapsed_ms = (api::time() - start_time) / 1_000_000;
-    if elapsed_ms > timeout_ms {
-        return Err("TimeoutError".to_string());
     }
-    
-    result
 }
 
 // Built-in node implementations
-pub async fn execute_delay_node(node: &WorkflowNode, _input: &HashMap<String, ConfigValue>) -> Result<NodeOutput, String> {
-    let delay_ms = node.configuration.parameters
-        .get("delay")
-        .and_then(|v| match v {
-            ConfigValue::Number(n) => Some(*n as u64),
-            _ => None,
-        })
-        .unwrap_or(1000);
+/// Principal an execution acts for (its initiator, or the workflow owner for triggered runs).
+fn context_principal(context: &ExecutionContext) -> Result<Principal, String> {
+    Principal::from_text(&context.user_id)
+        .map_err(|_| format!("Execution {} is not associated with a principal", context.execution_id))
+}
+
+fn variable_name(node: &WorkflowNode) -> Result<String, String> {
+    match node.configuration.parameters.get("name") {
+        Some(ConfigValue::String(name)) if !name.trim().is_empty() => Ok(name.trim().to_string()),
+        _ => Err("Missing variable name parameter".to_string()),
+    }
+}
+
+/// Evaluates `value` (an expression over the input, or a literal) and reports it; the
+/// execution engine stores it under `name` for the nodes that run afterwards.
+pub async fn execute_set_variable_node(node: &WorkflowNode, input: &HashMap<String, ConfigValue>) -> Result<NodeOutput, String> {
+    let name = variable_name(node)?;
+    let value = match node.configuration.parameters.get("value") {
+        Some(ConfigValue::String(expr)) => evaluate_expression(expr, input)
+            .map_err(|e| format!("Variable '{}' value error: {}", name, e))?,
+        Some(literal) => literal.clone(),
+        None => return Err("Missing value parameter".to_string()),
+    };
     
     Ok(NodeOutput {
         data: HashMap::from([
-            ("delayed".to_string(), ConfigValue::Boolean(true)),
-            ("delay_ms".to_string(), ConfigValue::Number(delay_ms as f64)),
+            ("name".to_string(), ConfigValue::String(name)),
+            ("value".to_string(), value),
         ]),
         next_nodes: Vec::new(),
     })
 }
 
+pub async fn execute_get_variable_node(node: &WorkflowNode, context: &ExecutionContext) -> Result<NodeOutput, String> {
+    let name = variable_name(node)?;
+    let current = context.global_variables.get(&name).cloned();
+    let exists = current.is_some();
+    let value = current
+        .or_else(|| node.configuration.parameters.get("default").cloned())
+        .unwrap_or(ConfigValue::String(String::new()));
+    
+    Ok(NodeOutput {
+        data: HashMap::from([
+            ("name".to_string(), ConfigValue::String(name)),
+            ("value".to_string(), value),
+            ("exists".to_string(), ConfigValue::Boolean(exists)),
+        ]),
+        next_nodes: Vec::new(),
+    })
+}
+
+const MAX_DELAY_MS: u64 = 30 * 24 * 60 * 60 * 1000; // 30 days
+
+/// Delay configured on a delay node, in milliseconds. Accepts `delay` (ms) or a
+/// `duration` string such as "90s", "15m", "24h" or "7d".
+pub fn delay_duration_ms(config: &NodeConfiguration) -> Result<u64, String> {
+    let delay_ms = match config.parameters.get("duration") {
+        Some(ConfigValue::String(duration)) => parse_duration_ms(duration)?,
+        _ => match config.parameters.get("delay") {
+            Some(ConfigValue::Number(n)) if *n >= 0.0 => *n as u64,
+            Some(_) => return Err("delay must be a non-negative number of milliseconds".to_string()),
+            None => 1000,
+        },
+    };
+    
+    if delay_ms > MAX_DELAY_MS {
+        return Err("Delay cannot exceed 30 days".to_string());
+    }
+    
+    Ok(delay_ms)
+}
+
+fn parse_duration_ms(duration: &str) -> Result<u64, String> {
+    let duration = duration.trim();
+    let split = duration.find(|c: char| !c.is_ascii_digit()).unwrap_or(duration.len());
+    let (amount, unit) = duration.split_at(split);
+    let amount: u64 = amount.parse()
+        .map_err(|_| format!("Invalid duration '{}'", duration))?;
+    
+    let unit_ms = match unit.trim() {
+        "ms" => 1,
+        "s" | "" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        other => return Err(format!("Unknown duration unit '{}' (use ms, s, m, h or d)", other)),
+    };
+    
+    amount.checked_mul(unit_ms).ok_or_else(|| format!("Duration '{}' is too large", duration))
+}
+
+/// Reports the configured delay; the execution engine suspends the workflow after this
+/// node and resumes it from a timer once the delay has elapsed.
+pub async fn execute_delay_node(node: &WorkflowNode, input: &HashMap<String, ConfigValue>) -> Result<NodeOutput, String> {
+    let delay_ms = delay_duration_ms(&node.configuration)?;
+    
+    // Pass the input through so nodes after the wait can keep using it
+    let mut data = input.clone();
+    data.insert("delayed".to_string(), ConfigValue::Boolean(delay_ms > 0));
+    data.insert("delay_ms".to_string(), ConfigValue::Number(delay_ms as f64));
+    
+    Ok(NodeOutput {
+        data,
+        next_nodes: Vec::new(),
+    })
+}
+
 pub async fn execute_condition_node(node: &WorkflowNode, input: &HashMap<String, ConfigValue>) -> Result<NodeOutput, String> {
     let condition = node.configuration.parameters
         .get("condition")
@@ -319,10 +445,12 @@ pub async fn execute_condition_node(node: &WorkflowNode, input: &HashMap<String,
     
     let result = evaluate_condition(condition, input)?;
     
+    // Pass the evaluated input through so the taken branch can keep using it
+    let mut data = input.clone();
+    data.insert("result".to_string(), ConfigValue::Boolean(result));
+    
     Ok(NodeOutput {
-        data: HashMap::from([
-            ("result".to_string(), ConfigValue::Boolean(result)),
-        ]),
+        data,
         next_nodes: if result { 
             vec!["true".to_string()]
         } else { 
@@ -345,6 +473,7 @@ pub async fn execute_transform_node(node: &WorkflowNode, input: &HashMap<String,
         "uppercase" => transform_strings_uppercase(input),
         "lowercase" => transform_strings_lowercase(input),
         "json_parse" => transform_json_parse(input)?,
+        "expression" => transform_expression(&node.configuration.parameters, input)?,
         _ => return Err(format!("Unknown transform type: {}", transform_type)),
     };
     
@@ -354,68 +483,38 @@ pub async fn execute_transform_node(node: &WorkflowNode, input: &HashMap<String,
     })
 }
 
-pub async fn execute_http_request_node(node: &WorkflowNode, input: &HashMap<String, ConfigValue>) -> Result<NodeOutput, String> {
-    use crate::http_client::{HttpClient, parse_json_response, json_to_config_value};
-    use std::collections::HashMap as StdHashMap;
-    
-    let url = node.configuration.parameters
-        .get("url")
-        .and_then(|v| match v {
-            ConfigValue::String(s) => Some(s.clone()),
-            _ => None,
-        })
-        .ok_or("Missing URL parameter")?;
-    
-    let method = node.configuration.parameters
-        .get("method")
-        .and_then(|v| match v {
-            ConfigValue::String(s) => Some(s.clone()),
-            _ => None,
-        })
-        .unwrap_or("GET".to_string());
-    
-    // Extract headers from configuration
-    let headers = node.configuration.parameters
-        .get("headers")
-        .and_then(|v| match v {
-            ConfigValue::Object(obj) => {
-                let mut headers = StdHashMap::new();
-                for (key, value) in obj {
-                    if let ConfigValue::String(val) = value {
-                        headers.insert(key.clone(), val.clone());
-                    }
-                }
-                Some(headers)
-            }
-            _ => None,
-        });
-    
-    // Extract body from configuration or input
-    let body = node.configuration.parameters
-        .get("body")
-        .or_else(|| input.get("body"))
-        .and_then(|v| match v {
-            ConfigValue::String(s) => Some(s.clone()),
-            ConfigValue::Object(_) | ConfigValue::Array(_) => {
-                // Convert to JSON string
-                let json_value = crate::http_client::config_value_to_json(v);
-                serde_json::to_string(&json_value).ok()
-            }
-            _ => None,
-        });
-    
-    // Make the HTTP request
-    let response = match method.to_uppercase().as_str() {
-        "GET" => HttpClient::get(&url, headers).await?,
-        "POST" => HttpClient::post(&url, body, headers).await?,
-        "PUT" => HttpClient::put(&url, body, headers).await?,
-        "DELETE" => HttpClient::delete(&url, headers).await?,
-        _ => return Err(format!("Unsupported HTTP method: {}", method)),
+/// Runs an `http_request` node. The URL, header values, query parameters and a configured body
+/// are templates over the node input (`{{ body.data[0].price }}`); `transform` names the
+/// response transforms replicas apply before agreeing on the response.
+pub async fn execute_http_request_node(node: &WorkflowNode, input: &HashMap<String, ConfigValue>) -> Result<NodeOutput, NodeError> {
+    use crate::http_client::{json_to_config_value, parse_json_response, HttpClient};
+
+    let (request, method) = build_http_request(node, input).map_err(NodeError::ConfigurationError)?;
+    let url = request.url.clone();
+    // Transport failures and transient statuses are retryable only for requests that are safe to repeat
+    let safe_to_repeat = matches!(method.as_str(), "GET" | "HEAD");
+    let transient = |message: String| if safe_to_repeat {
+        NodeError::NetworkError(message)
+    } else {
+        NodeError::ExecutionError(message)
     };
-    
+    let response = HttpClient::request(request).await.map_err(transient)?;
+
+    let ok = (200..300).contains(&response.status);
+    let fail_on_error = !matches!(node.configuration.parameters.get("fail_on_error"), Some(ConfigValue::Boolean(false)));
+    if !ok && fail_on_error {
+        let excerpt: String = response.body.chars().take(200).collect();
+        let message = format!("HTTP {} {} returned status {}: {}", method, url, response.status, excerpt);
+        return Err(match response.status {
+            408 | 429 | 500..=599 => transient(message),
+            _ => NodeError::ExecutionError(message),
+        });
+    }
+
     // Build response data
     let mut response_data = HashMap::new();
     response_data.insert("status".to_string(), ConfigValue::Number(response.status as f64));
+    response_data.insert("ok".to_string(), ConfigValue::Boolean(ok));
     response_data.insert("url".to_string(), ConfigValue::String(url));
     response_data.insert("method".to_string(), ConfigValue::String(method));
     
@@ -440,6 +539,173 @@ pub async fn execute_http_request_node(node: &WorkflowNode, input: &HashMap<Stri
     })
 }
 
+/// The outcall an `http_request` node makes, and the method it was configured with.
+fn build_http_request(node: &WorkflowNode, input: &HashMap<String, ConfigValue>) -> Result<(crate::http_client::HttpRequest, String), String> {
+    use crate::expressions::{render_template, render_value};
+    use crate::http_client::{
+        basic_auth, config_value_to_json, form_urlencode, outcall_cycles, HttpRequest,
+        DEFAULT_MAX_RESPONSE_BYTES, MAX_OUTCALL_CYCLES, MAX_RESPONSE_BYTES,
+    };
+    use crate::http_transforms::steps_from_config;
+    use ic_cdk::api::management_canister::http_request::{HttpHeader, HttpMethod};
+
+    let parameters = &node.configuration.parameters;
+    let template = |name: &str, text: &str| render_template(text, input).map_err(|e| format!("{} template error: {}", name, e));
+
+    let url = match parameters.get("url") {
+        Some(ConfigValue::String(url)) => template("url", url)?,
+        _ => return Err("Missing URL parameter".to_string()),
+    };
+    let method = http_parameter(parameters, "method").unwrap_or_else(|| "GET".to_string()).to_uppercase();
+
+    let mut headers: Vec<(String, String)> = match parameters.get("headers") {
+        Some(ConfigValue::Object(entries)) => entries.iter()
+            .filter_map(|(name, value)| match value {
+                ConfigValue::String(value) => Some(template(name, value).map(|value| (name.clone(), value))),
+                _ => None,
+            })
+            .collect::<Result<_, _>>()?,
+        _ => Vec::new(),
+    };
+    let mut query = match parameters.get("query") {
+        Some(query) => match render_value(query, input).map_err(|e| format!("query template error: {}", e))? {
+            ConfigValue::Object(entries) => query_pairs(entries),
+            _ => return Err("query must be an object of parameter names to values".to_string()),
+        },
+        None => Vec::new(),
+    };
+
+    // Authentication; the secret comes from the vault credential named by auth_credential
+    let secret = http_parameter(parameters, "auth_credential");
+    match http_parameter(parameters, "auth_type").as_deref().unwrap_or("none") {
+        "none" => {}
+        "basic" => {
+            let username = http_parameter(parameters, "auth_username").ok_or("Basic auth needs auth_username")?;
+            let password = secret.ok_or("Basic auth needs auth_credential")?;
+            headers.push(("Authorization".to_string(), basic_auth(&username, &password)));
+        }
+        "bearer" => {
+            let token = secret.ok_or("Bearer auth needs auth_credential")?;
+            headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+        }
+        "api_key" => {
+            let key = secret.ok_or("API key auth needs auth_credential")?;
+            let name = http_parameter(parameters, "api_key_name").unwrap_or_else(|| "X-API-Key".to_string());
+            match http_parameter(parameters, "api_key_in").as_deref().unwrap_or("header") {
+                "header" => headers.push((name, key)),
+                "query" => query.push((name, key)),
+                other => return Err(format!("api_key_in must be header or query, not '{}'", other)),
+            }
+        }
+        other => return Err(format!("Unsupported auth_type '{}': use none, basic, bearer or api_key", other)),
+    }
+
+    let body = if matches!(method.as_str(), "GET" | "HEAD") {
+        None
+    } else {
+        match parameters.get("body") {
+            Some(body) => Some(render_value(body, input).map_err(|e| format!("body template error: {}", e))?),
+            None => input.get("body").cloned(),
+        }
+    };
+    let body = match (http_parameter(parameters, "body_type").as_deref().unwrap_or("json"), body) {
+        (_, None) => None,
+        ("json", Some(ConfigValue::String(text))) => Some((text, "application/json")),
+        ("json", Some(value)) => Some((config_value_to_json(&value).to_string(), "application/json")),
+        ("form", Some(ConfigValue::Object(fields))) => {
+            let pairs = query_pairs(fields);
+            let pairs: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+            Some((form_urlencode(&pairs), "application/x-www-form-urlencoded"))
+        }
+        ("form", Some(_)) => return Err("Form bodies must be objects".to_string()),
+        ("text", Some(value)) => Some((to_display_string(&value), "text/plain")),
+        (other, Some(_)) => return Err(format!("Unsupported body_type '{}': use json, form or text", other)),
+    };
+    if let Some((_, content_type)) = &body {
+        if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type")) {
+            headers.push(("Content-Type".to_string(), content_type.to_string()));
+        }
+    }
+
+    // Outcalls support GET, HEAD and POST; other methods are sent as POSTs naming the method
+    // in an override header, which only servers that honour it understand, so callers opt in
+    let outcall_method = match method.as_str() {
+        "GET" => HttpMethod::GET,
+        "HEAD" => HttpMethod::HEAD,
+        "POST" => HttpMethod::POST,
+        "PUT" | "PATCH" | "DELETE" => {
+            if !matches!(parameters.get("method_override"), Some(ConfigValue::Boolean(true))) {
+                return Err(format!(
+                    "HTTP outcalls support only GET, HEAD and POST; set method_override to send {} as a POST with X-HTTP-Method-Override",
+                    method
+                ));
+            }
+            headers.push(("X-HTTP-Method-Override".to_string(), method.clone()));
+            HttpMethod::POST
+        }
+        _ => return Err(format!("Unsupported HTTP method: {}", method)),
+    };
+
+    let max_response_bytes = match parameters.get("max_response_bytes") {
+        Some(ConfigValue::Number(n)) if *n >= 1.0 && *n <= MAX_RESPONSE_BYTES as f64 => *n as u64,
+        Some(_) => return Err(format!("max_response_bytes must be between 1 and {}", MAX_RESPONSE_BYTES)),
+        None => DEFAULT_MAX_RESPONSE_BYTES,
+    };
+    let transform = match parameters.get("transform") {
+        Some(transform) => steps_from_config(transform).map_err(|e| format!("transform: {}", e))?,
+        None => Vec::new(),
+    };
+    let url = with_query(&url, &query);
+    let body = body.map(|(body, _)| body.into_bytes());
+    let request_bytes = url.len() + body.as_ref().map_or(0, Vec::len)
+        + headers.iter().map(|(name, value)| name.len() + value.len()).sum::<usize>();
+    let cycles = match parameters.get("cycles") {
+        Some(ConfigValue::Number(n)) if *n >= 1.0 && *n <= MAX_OUTCALL_CYCLES as f64 => *n as u128,
+        Some(_) => return Err(format!("cycles must be between 1 and {}", MAX_OUTCALL_CYCLES)),
+        None => outcall_cycles(request_bytes as u64, max_response_bytes),
+    };
+
+    let request = HttpRequest {
+        url,
+        method: outcall_method,
+        headers: headers.into_iter().map(|(name, value)| HttpHeader { name, value }).collect(),
+        body,
+        max_response_bytes: Some(max_response_bytes),
+        cycles: Some(cycles),
+        transform,
+    };
+    Ok((request, method))
+}
+
+
+fn http_parameter(parameters: &HashMap<String, ConfigValue>, name: &str) -> Option<String> {
+    match parameters.get(name) {
+        Some(ConfigValue::String(value)) if !value.trim().is_empty() => Some(value.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Name/value pairs of a query or form object, sorted by name; arrays repeat the name.
+fn query_pairs(fields: HashMap<String, ConfigValue>) -> Vec<(String, String)> {
+    let mut fields: Vec<_> = fields.into_iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+    fields.into_iter()
+        .flat_map(|(name, value)| match value {
+            ConfigValue::Array(items) => items.iter().map(|item| (name.clone(), to_display_string(item))).collect(),
+            value => vec![(name, to_display_string(&value))],
+        })
+        .collect()
+}
+
+fn with_query(url: &str, pairs: &[(String, String)]) -> String {
+    if pairs.is_empty() {
+        return url.to_string();
+    }
+    let pairs: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let separator = if !url.contains('?') { "?" } else if url.ends_with('?') || url.ends_with('&') { "" } else { "&" };
+    format!("{}{}{}", url, separator, crate::http_client::form_urlencode(&pairs))
+}
+
 pub async fn execute_timer_node(node: &WorkflowNode, _input: &HashMap<String, ConfigValue>) -> Result<NodeOutput, String> {
     let interval = node.configuration.parameters
         .get("interval")
@@ -471,40 +737,9 @@ fn evaluate_condition(condition: &ConfigValue, input: &HashMap<String, ConfigVal
     match condition {
         ConfigValue::Boolean(b) => Ok(*b),
         ConfigValue::String(expr) => {
-            if expr.contains("==") {
-                let parts: Vec<&str> = expr.split("==").collect();
-                if parts.len() == 2 {
-                    let left = parts[0].trim();
-                    let right = parts[1].trim();
-                    
-                    if let Some(value) = input.get(left) {
-                        match value {
-                            ConfigValue::String(s) => Ok(s == right),
-                            ConfigValue::Number(n) => {
-                                if let Ok(num) = right.parse::<f64>() {
-                                    Ok((n - num).abs() < f64::EPSILON)
-                                } else {
-                                    Ok(false)
-                                }
-                            }
-                            ConfigValue::Boolean(b) => {
-                                if let Ok(bool_val) = right.parse::<bool>() {
-                                    Ok(*b == bool_val)
-                                } else {
-                                    Ok(false)
-                                }
-                            }
-                            _ => Ok(false),
-                        }
-                    } else {
-                        Ok(false)
-                    }
-                } else {
-                    Err("Invalid condition format".to_string())
-                }
-            } else {
-                Err("Unsupported condition operator".to_string())
-            }
+            parse_expression(expr)
+                .and_then(|parsed| parsed.evaluate_bool(input))
+                .map_err(|e| format!("Condition error: {}", e))
         }
         _ => Err("Invalid condition type".to_string()),
     }
@@ -531,149 +766,658 @@ fn transform_strings_lowercase(input: &HashMap<String, ConfigValue>) -> HashMap<
 }
 
 fn transform_json_parse(input: &HashMap<String, ConfigValue>) -> Result<HashMap<String, ConfigValue>, String> {
-    Ok(input.clone())
+    use crate::http_client::json_to_config_value;
+    
+    input.iter().map(|(k, v)| {
+        let new_value = match v {
+            ConfigValue::String(s) => {
+                let json: serde_json::Value = serde_json::from_str(s)
+                    .map_err(|e| format!("Field '{}' is not valid JSON: {}", k, e))?;
+                json_to_config_value(&json)
+            }
+            _ => v.clone(),
+        };
+        Ok((k.clone(), new_value))
+    }).collect()
+}
+
+/// Evaluate the `expression` parameter into `result`, or each entry of the
+/// `mappings` object (output field -> expression) into its own output field.
+fn transform_expression(
+    parameters: &HashMap<String, ConfigValue>,
+    input: &HashMap<String, ConfigValue>
+) -> Result<HashMap<String, ConfigValue>, String> {
+    let mut output = HashMap::new();
+    
+    if let Some(ConfigValue::String(expr)) = parameters.get("expression") {
+        let value = evaluate_expression(expr, input)
+            .map_err(|e| format!("Expression error: {}", e))?;
+        output.insert("result".to_string(), value);
+    }
+    
+    if let Some(ConfigValue::Object(mappings)) = parameters.get("mappings") {
+        for (field, expr) in mappings {
+            let ConfigValue::String(expr) = expr else {
+                return Err(format!("Mapping '{}' must be an expression string", field));
+            };
+            let value = evaluate_expression(expr, input)
+                .map_err(|e| format!("Mapping '{}' error: {}", field, e))?;
+            output.insert(field.clone(), value);
+        }
+    }
+    
+    if output.is_empty() {
+        return Err("Expression transform requires an 'expression' or 'mappings' parameter".to_string());
+    }
+    
+    Ok(output)
+}
+
+/// Parse-check the expressions a condition or transform node carries so that
+/// syntax errors are reported when the workflow is saved rather than when it runs.
+pub fn validate_node_expressions(node_type: &str, config: &NodeConfiguration) -> Result<(), ValidationError> {
+    let check = |parameter: &str, expr: &str| {
+        parse_expression(expr).map(|_| ()).map_err(|e| {
+            ValidationError::InvalidParameterValue(format!("{}: {}", parameter, e))
+        })
+    };
+    
+    match node_type {
+        "condition" => {
+            if let Some(ConfigValue::String(expr)) = config.parameters.get("condition") {
+                check("condition", expr)?;
+            }
+        }
+        "set_variable" => {
+            if let Some(ConfigValue::String(expr)) = config.parameters.get("value") {
+                check("value", expr)?;
+            }
+        }
+        "delay" => {
+            delay_duration_ms(config).map_err(ValidationError::InvalidParameterValue)?;
+        }
+        "for_each" => {
+            if let Some(ConfigValue::String(expr)) = config.parameters.get("items") {
+                check("items", expr)?;
+            }
+        }
+        "sub_workflow" => {
+            if let Some(ConfigValue::Object(inputs)) = config.parameters.get("inputs") {
+                for (field, expr) in inputs {
+                    if let ConfigValue::String(expr) = expr {
+                        check(&format!("inputs.{}", field), expr)?;
+                    }
+                }
+            }
+            if let Some(ConfigValue::Object(outputs)) = config.parameters.get("outputs") {
+                for (field, expr) in outputs {
+                    match expr {
+                        ConfigValue::String(expr) => check(&format!("outputs.{}", field), expr)?,
+                        _ => return Err(ValidationError::InvalidParameterType {
+                            parameter: format!("outputs.{}", field),
+                            expected: "string".to_string(),
+                            got: "non-string".to_string(),
+                        }),
+                    }
+                }
+            }
+        }
+        "transform" => {
+            if let Some(ConfigValue::String(expr)) = config.parameters.get("expression") {
+                check("expression", expr)?;
+            }
+            if let Some(ConfigValue::Object(mappings)) = config.parameters.get("mappings") {
+                for (field, expr) in mappings {
+                    match expr {
+                        ConfigValue::String(expr) => check(&format!("mappings.{}", field), expr)?,
+                        _ => return Err(ValidationError::InvalidParameterType {
+                            parameter: format!("mappings.{}", field),
+                            expected: "string".to_string(),
+                            got: "non-string".to_string(),
+                        }),
+                    }
+                }
+            }
+        }
+        "http_request" => {
+            let mut templates = Vec::new();
+            for name in ["url", "headers", "query", "body"] {
+                if let Some(value) = config.parameters.get(name) {
+                    collect_templates(name, value, &mut templates);
+                }
+            }
+            for (parameter, template) in templates {
+                parse_template(&template).map_err(|e| {
+                    ValidationError::InvalidParameterValue(format!("{}: {}", parameter, e))
+                })?;
+            }
+            if let Some(transform) = config.parameters.get("transform") {
+                crate::http_transforms::steps_from_config(transform).map_err(|e| {
+                    ValidationError::InvalidParameterValue(format!("transform: {}", e))
+                })?;
+            }
+        }
+        _ => {}
+    }
+    
+    Ok(())
+}
+
+/// Every string in `value` with its dotted path.
+fn collect_templates(path: &str, value: &ConfigValue, templates: &mut Vec<(String, String)>) {
+    match value {
+        ConfigValue::String(text) => templates.push((path.to_string(), text.clone())),
+        ConfigValue::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_templates(&format!("{}[{}]", path, index), item, templates);
+            }
+        }
+        ConfigValue::Object(fields) => {
+            for (key, item) in fields {
+                collect_templates(&format!("{}.{}", path, key), item, templates);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Built-in node definitions
+pub fn create_delay_node_definition() -> NodeDefinition {
+    NodeDefinition {
+        node_type: "delay".to_string(),
+        name: "Delay".to_string(),
+        description: "Pauses the workflow for the specified time, then resumes with the next node".to_string(),
+        category: "utility".to_string(),
+        version: "1.0.0".to_string(),
+        input_schema: vec![],
+        output_schema: vec![
+            ParameterSchema {
+                name: "delayed".to_string(),
+                parameter_type: "boolean".to_string(),
+                required: true,
+                description: Some("Whether delay was applied".to_string()),
+                default_value: None,
+            }
+        ],
+        configuration_schema: vec![
+            ParameterSchema {
+                name: "delay".to_string(),
+                parameter_type: "number".to_string(),
+                required: false,
+                description: Some("Delay in milliseconds".to_string()),
+                default_value: Some(ConfigValue::Number(1000.0)),
+            },
+            ParameterSchema {
+                name: "duration".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("Delay as a duration such as 90s, 15m, 24h or 7d (up to 30 days); overrides delay".to_string()),
+                default_value: None,
+            }
+        ],
+    }
+}
+
+pub fn create_condition_node_definition() -> NodeDefinition {
+    NodeDefinition {
+        node_type: "condition".to_string(),
+        name: "Condition".to_string(),
+        description: "Evaluates conditions and routes execution through its \"true\" or \"false\" output; nodes wired only to its result key do not run".to_string(),
+        category: "logic".to_string(),
+        version: "1.1.0".to_string(),
+        input_schema: vec![],
+        output_schema: vec![
+            ParameterSchema {
+                name: "result".to_string(),
+                parameter_type: "boolean".to_string(),
+                required: true,
+                description: Some("Condition evaluation result; reaches only nodes the taken branch also reaches".to_string()),
+                default_value: None,
+            }
+        ],
+        configuration_schema: vec![
+            ParameterSchema {
+                name: "condition".to_string(),
+                parameter_type: "string".to_string(),
+                required: true,
+                description: Some("Expression to evaluate, e.g. price < 3000 && token in [\"ETH\", \"WBTC\"]".to_string()),
+                default_value: None,
+            }
+        ],
+    }
+}
+
+pub fn create_transform_node_definition() -> NodeDefinition {
+    NodeDefinition {
+        node_type: "transform".to_string(),
+        name: "Transform".to_string(),
+        description: "Transforms input data".to_string(),
+        category: "data".to_string(),
+        version: "1.0.0".to_string(),
+        input_schema: vec![],
+        output_schema: vec![],
+        configuration_schema: vec![
+            ParameterSchema {
+                name: "type".to_string(),
+                parameter_type: "string".to_string(),
+                required: true,
+                description: Some("Type of transformation: passthrough, uppercase, lowercase, json_parse or expression".to_string()),
+                default_value: Some(ConfigValue::String("passthrough".to_string())),
+            },
+            ParameterSchema {
+                name: "expression".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("Expression whose value becomes the 'result' output (type = expression)".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "mappings".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Output field to expression map, e.g. {\"usd\": \"body.data[0].price * amount\"} (type = expression)".to_string()),
+                default_value: None,
+            }
+        ],
+    }
+}
+
+pub fn create_http_request_node_definition() -> NodeDefinition {
+    NodeDefinition {
+        node_type: "http_request".to_string(),
+        name: "HTTP Request".to_string(),
+        description: "Makes HTTP requests; URL, headers, query and body may use {{ expression }} templates over the node input".to_string(),
+        category: "network".to_string(),
+        version: "1.3.0".to_string(),
+        input_schema: vec![
+            ParameterSchema {
+                name: "body".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Request body used when none is configured".to_string()),
+                default_value: None,
+            },
+        ],
+        output_schema: vec![
+            ParameterSchema {
+                name: "status".to_string(),
+                parameter_type: "number".to_string(),
+                required: true,
+                description: Some("HTTP status code".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "ok".to_string(),
+                parameter_type: "boolean".to_string(),
+                required: true,
+                description: Some("True for 2xx statuses".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "body".to_string(),
+                parameter_type: "object".to_string(),
+                required: true,
+                description: Some("Response body, parsed when it is JSON".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "headers".to_string(),
+                parameter_type: "object".to_string(),
+                required: true,
+                description: Some("Response headers".to_string()),
+                default_value: None,
+            },
+        ],
+        configuration_schema: vec![
+            ParameterSchema {
+                name: "url".to_string(),
+                parameter_type: "string".to_string(),
+                required: true,
+                description: Some("URL to request".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "method".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("GET, POST or HEAD; PUT, PATCH and DELETE need method_override".to_string()),
+                default_value: Some(ConfigValue::String("GET".to_string())),
+            },
+            ParameterSchema {
+                name: "method_override".to_string(),
+                parameter_type: "boolean".to_string(),
+                required: false,
+                description: Some("Send PUT, PATCH and DELETE as POST with X-HTTP-Method-Override, for servers that honour it".to_string()),
+                default_value: Some(ConfigValue::Boolean(false)),
+            },
+            ParameterSchema {
+                name: "headers".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Request headers".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "query".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Query parameters appended to the URL; arrays repeat the name".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "body".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Request body".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "body_type".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("json, form (x-www-form-urlencoded) or text".to_string()),
+                default_value: Some(ConfigValue::String("json".to_string())),
+            },
+            ParameterSchema {
+                name: "auth_type".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("none, basic, bearer or api_key".to_string()),
+                default_value: Some(ConfigValue::String("none".to_string())),
+            },
+            ParameterSchema {
+                name: "auth_credential".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("Id of the vault credential holding the password, token or API key".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "auth_username".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("Username for basic auth".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "api_key_name".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("Header or query parameter carrying the API key".to_string()),
+                default_value: Some(ConfigValue::String("X-API-Key".to_string())),
+            },
+            ParameterSchema {
+                name: "api_key_in".to_string(),
+                parameter_type: "string".to_string(),
+                required: false,
+                description: Some("header or query".to_string()),
+                default_value: Some(ConfigValue::String("header".to_string())),
+            },
+            ParameterSchema {
+                name: "fail_on_error".to_string(),
+                parameter_type: "boolean".to_string(),
+                required: false,
+                description: Some("Fail the node on non-2xx statuses".to_string()),
+                default_value: Some(ConfigValue::Boolean(true)),
+            },
+            ParameterSchema {
+                name: "max_response_bytes".to_string(),
+                parameter_type: "number".to_string(),
+                required: false,
+                description: Some("Largest response accepted, up to 2000000".to_string()),
+                default_value: Some(ConfigValue::Number(crate::http_client::DEFAULT_MAX_RESPONSE_BYTES as f64)),
+            },
+            ParameterSchema {
+                name: "cycles".to_string(),
+                parameter_type: "number".to_string(),
+                required: false,
+                description: Some("Cycles attached to the outcall; estimated from the request when unset".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "transform".to_string(),
+                parameter_type: "array".to_string(),
+                required: false,
+                description: Some("Response transforms run before consensus: strip_headers, json_extract, drop_fields, normalize_numbers".to_string()),
+                default_value: None,
+            },
+        ],
+    }
 }
 
-// Built-in node definitions
-pub fn create_delay_node_definition() -> NodeDefinition {
+pub fn create_timer_node_definition() -> NodeDefinition {
     NodeDefinition {
-        node_type: "delay".to_string(),
-        name: "Delay".to_string(),
-        description: "Delays execution for specified time".to_string(),
-        category: "utility".to_string(),
+        node_type: "timer".to_string(),
+        name: "Timer".to_string(),
+        description: "Triggers on timer intervals".to_string(),
+        category: "trigger".to_string(),
         version: "1.0.0".to_string(),
         input_schema: vec![],
         output_schema: vec![
             ParameterSchema {
-                name: "delayed".to_string(),
-                parameter_type: "boolean".to_string(),
+                name: "triggered_at".to_string(),
+                parameter_type: "number".to_string(),
                 required: true,
-                description: Some("Whether delay was applied".to_string()),
+                description: Some("Timestamp when triggered".to_string()),
                 default_value: None,
             }
         ],
         configuration_schema: vec![
             ParameterSchema {
-                name: "delay".to_string(),
+                name: "interval".to_string(),
                 parameter_type: "number".to_string(),
                 required: true,
-                description: Some("Delay in milliseconds".to_string()),
-                default_value: Some(ConfigValue::Number(1000.0)),
+                description: Some("Interval in milliseconds".to_string()),
+                default_value: Some(ConfigValue::Number(60000.0)),
             }
         ],
     }
 }
 
-pub fn create_condition_node_definition() -> NodeDefinition {
+pub fn create_for_each_node_definition() -> NodeDefinition {
     NodeDefinition {
-        node_type: "condition".to_string(),
-        name: "Condition".to_string(),
-        description: "Evaluates conditions and controls flow".to_string(),
+        node_type: "for_each".to_string(),
+        name: "For Each".to_string(),
+        description: "Runs the nodes connected to its \"item\" output once per array element".to_string(),
         category: "logic".to_string(),
         version: "1.0.0".to_string(),
-        input_schema: vec![],
+        input_schema: vec![
+            ParameterSchema {
+                name: "items".to_string(),
+                parameter_type: "array".to_string(),
+                required: false,
+                description: Some("Array to iterate over".to_string()),
+                default_value: None,
+            }
+        ],
         output_schema: vec![
             ParameterSchema {
-                name: "result".to_string(),
-                parameter_type: "boolean".to_string(),
+                name: "item".to_string(),
+                parameter_type: "any".to_string(),
+                required: true,
+                description: Some("Current element, available to the loop body".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "index".to_string(),
+                parameter_type: "number".to_string(),
                 required: true,
-                description: Some("Condition evaluation result".to_string()),
+                description: Some("Zero-based index of the current element".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "results".to_string(),
+                parameter_type: "array".to_string(),
+                required: true,
+                description: Some("Outputs of the loop body's final nodes, one entry per iteration".to_string()),
                 default_value: None,
             }
         ],
         configuration_schema: vec![
             ParameterSchema {
-                name: "condition".to_string(),
+                name: "items".to_string(),
                 parameter_type: "string".to_string(),
-                required: true,
-                description: Some("Condition expression to evaluate".to_string()),
+                required: false,
+                description: Some("Expression selecting the array, e.g. body.wallets (defaults to the 'items' input)".to_string()),
                 default_value: None,
+            },
+            ParameterSchema {
+                name: "max_iterations".to_string(),
+                parameter_type: "number".to_string(),
+                required: false,
+                description: Some("Fail if the array has more elements than this (hard limit 1000)".to_string()),
+                default_value: Some(ConfigValue::Number(100.0)),
+            },
+            ParameterSchema {
+                name: "concurrency".to_string(),
+                parameter_type: "number".to_string(),
+                required: false,
+                description: Some("Number of iterations to run at once (1-10)".to_string()),
+                default_value: Some(ConfigValue::Number(1.0)),
+            },
+            ParameterSchema {
+                name: "continue_on_error".to_string(),
+                parameter_type: "boolean".to_string(),
+                required: false,
+                description: Some("Record failed iterations in results instead of failing the loop".to_string()),
+                default_value: Some(ConfigValue::Boolean(false)),
             }
         ],
     }
 }
 
-pub fn create_transform_node_definition() -> NodeDefinition {
+pub fn create_sub_workflow_node_definition() -> NodeDefinition {
     NodeDefinition {
-        node_type: "transform".to_string(),
-        name: "Transform".to_string(),
-        description: "Transforms input data".to_string(),
-        category: "data".to_string(),
+        node_type: "sub_workflow".to_string(),
+        name: "Sub-workflow".to_string(),
+        description: "Runs another workflow, waits for it to finish and returns its final node outputs".to_string(),
+        category: "logic".to_string(),
         version: "1.0.0".to_string(),
-        input_schema: vec![],
-        output_schema: vec![],
+        input_schema: vec![
+            ParameterSchema {
+                name: "data".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Passed to the child workflow as trigger data when no inputs are configured".to_string()),
+                default_value: None,
+            }
+        ],
+        output_schema: vec![
+            ParameterSchema {
+                name: "outputs".to_string(),
+                parameter_type: "object".to_string(),
+                required: true,
+                description: Some("Outputs of the child's final nodes, keyed by node id".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "execution_id".to_string(),
+                parameter_type: "string".to_string(),
+                required: true,
+                description: Some("ID of the child execution".to_string()),
+                default_value: None,
+            }
+        ],
         configuration_schema: vec![
             ParameterSchema {
-                name: "type".to_string(),
+                name: "workflow_id".to_string(),
                 parameter_type: "string".to_string(),
                 required: true,
-                description: Some("Type of transformation".to_string()),
-                default_value: Some(ConfigValue::String("passthrough".to_string())),
+                description: Some("Workflow to run".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "inputs".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Child trigger fields mapped to expressions over this node's input".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "outputs".to_string(),
+                parameter_type: "object".to_string(),
+                required: false,
+                description: Some("Output fields mapped to expressions over the child outputs, e.g. notify.sent".to_string()),
+                default_value: None,
             }
         ],
     }
 }
 
-pub fn create_http_request_node_definition() -> NodeDefinition {
+pub fn create_set_variable_node_definition() -> NodeDefinition {
     NodeDefinition {
-        node_type: "http_request".to_string(),
-        name: "HTTP Request".to_string(),
-        description: "Makes HTTP requests".to_string(),
-        category: "network".to_string(),
+        node_type: "set_variable".to_string(),
+        name: "Set Variable".to_string(),
+        description: "Stores a value in a workflow variable for the nodes that run after it".to_string(),
+        category: "utility".to_string(),
         version: "1.0.0".to_string(),
         input_schema: vec![],
         output_schema: vec![
             ParameterSchema {
-                name: "status".to_string(),
-                parameter_type: "number".to_string(),
+                name: "value".to_string(),
+                parameter_type: "any".to_string(),
                 required: true,
-                description: Some("HTTP status code".to_string()),
+                description: Some("Value that was stored".to_string()),
                 default_value: None,
             }
         ],
         configuration_schema: vec![
             ParameterSchema {
-                name: "url".to_string(),
+                name: "name".to_string(),
                 parameter_type: "string".to_string(),
                 required: true,
-                description: Some("URL to request".to_string()),
+                description: Some("Variable name".to_string()),
                 default_value: None,
             },
             ParameterSchema {
-                name: "method".to_string(),
-                parameter_type: "string".to_string(),
-                required: false,
-                description: Some("HTTP method".to_string()),
-                default_value: Some(ConfigValue::String("GET".to_string())),
+                name: "value".to_string(),
+                parameter_type: "any".to_string(),
+                required: true,
+                description: Some("Expression over the node input (e.g. price * 2), or a literal value".to_string()),
+                default_value: None,
             }
         ],
     }
 }
 
-pub fn create_timer_node_definition() -> NodeDefinition {
+pub fn create_get_variable_node_definition() -> NodeDefinition {
     NodeDefinition {
-        node_type: "timer".to_string(),
-        name: "Timer".to_string(),
-        description: "Triggers on timer intervals".to_string(),
-        category: "trigger".to_string(),
+        node_type: "get_variable".to_string(),
+        name: "Get Variable".to_string(),
+        description: "Reads a workflow variable".to_string(),
+        category: "utility".to_string(),
         version: "1.0.0".to_string(),
         input_schema: vec![],
         output_schema: vec![
             ParameterSchema {
-                name: "triggered_at".to_string(),
-                parameter_type: "number".to_string(),
+                name: "value".to_string(),
+                parameter_type: "any".to_string(),
                 required: true,
-                description: Some("Timestamp when triggered".to_string()),
+                description: Some("Current value, or the default when the variable is unset".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "exists".to_string(),
+                parameter_type: "boolean".to_string(),
+                required: true,
+                description: Some("Whether the variable has been set".to_string()),
                 default_value: None,
             }
         ],
         configuration_schema: vec![
             ParameterSchema {
-                name: "interval".to_string(),
-                parameter_type: "number".to_string(),
+                name: "name".to_string(),
+                parameter_type: "string".to_string(),
                 required: true,
-                description: Some("Interval in milliseconds".to_string()),
-                default_value: Some(ConfigValue::Number(60000.0)),
+                description: Some("Variable name".to_string()),
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "default".to_string(),
+                parameter_type: "any".to_string(),
+                required: false,
+                description: Some("Value to return when the variable is unset".to_string()),
+                default_value: None,
             }
         ],
     }
@@ -686,6 +1430,10 @@ pub fn initialize_built_in_nodes() {
         create_transform_node_definition(),
         create_http_request_node_definition(),
         create_timer_node_definition(),
+        create_for_each_node_definition(),
+        create_sub_workflow_node_definition(),
+        create_set_variable_node_definition(),
+        create_get_variable_node_definition(),
         // Social Media Integration nodes - Simplified
         create_telegram_node_definition(),
         create_discord_node_definition(),
@@ -866,9 +1614,10 @@ fn create_bitcoin_send_node_definition() -> NodeDefinition {
 
 pub async fn execute_bitcoin_send_node(
     node: &WorkflowNode, 
-    input: &HashMap<String, ConfigValue>
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
 ) -> Result<NodeOutput, String> {
-    let user = caller();
+    let user = context_principal(context)?;
     
     // Extract parameters
     let to_address = input.get("to_address")
@@ -1218,11 +1967,12 @@ fn create_ethereum_send_node_definition() -> NodeDefinition {
 
 pub async fn execute_ethereum_send_node(
     node: &WorkflowNode, 
-    input: &HashMap<String, ConfigValue>
+    input: &HashMap<String, ConfigValue>,
+    context: &ExecutionContext
 ) -> Result<NodeOutput, String> {
     use crate::defi::ethereum::{EvmChain, GasPriority};
     
-    let user = caller();
+    let user = context_principal(context)?;
     
     // Extract parameters
     let to_address = input.get("to_address")
@@ -1838,8 +2588,8 @@ fn create_telegram_node_definition() -> NodeDefinition {
             ParameterSchema {
                 name: "bot_token".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("Telegram bot token".to_string()),
-                required: true,
+                description: Some("Telegram bot token (defaults to the saved telegram token)".to_string()),
+                required: false,
                 default_value: None,
             },
             ParameterSchema {
@@ -1849,6 +2599,13 @@ fn create_telegram_node_definition() -> NodeDefinition {
                 required: true,
                 default_value: None,
             },
+            ParameterSchema {
+                name: "parse_mode".to_string(),
+                parameter_type: "string".to_string(),
+                description: Some("Message formatting (MarkdownV2 or HTML)".to_string()),
+                required: false,
+                default_value: None,
+            },
         ],
     }
 }
@@ -1884,7 +2641,21 @@ fn create_discord_node_definition() -> NodeDefinition {
                 name: "webhook_url".to_string(),
                 parameter_type: "string".to_string(),
                 description: Some("Discord webhook URL".to_string()),
-                required: true,
+                required: false,
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "channel_id".to_string(),
+                parameter_type: "string".to_string(),
+                description: Some("Channel to post to as a bot when no webhook URL is set".to_string()),
+                required: false,
+                default_value: None,
+            },
+            ParameterSchema {
+                name: "bot_token".to_string(),
+                parameter_type: "string".to_string(),
+                description: Some("Discord bot token (defaults to the saved discord token)".to_string()),
+                required: false,
                 default_value: None,
             },
         ],
@@ -1918,32 +2689,11 @@ fn create_twitter_node_definition() -> NodeDefinition {
             },
         ],
         configuration_schema: vec![
-            ParameterSchema {
-                name: "api_key".to_string(),
-                parameter_type: "string".to_string(),
-                description: Some("Twitter API key".to_string()),
-                required: true,
-                default_value: None,
-            },
-            ParameterSchema {
-                name: "api_secret".to_string(),
-                parameter_type: "string".to_string(),
-                description: Some("Twitter API secret".to_string()),
-                required: true,
-                default_value: None,
-            },
             ParameterSchema {
                 name: "access_token".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("Twitter access token".to_string()),
-                required: true,
-                default_value: None,
-            },
-            ParameterSchema {
-                name: "access_token_secret".to_string(),
-                parameter_type: "string".to_string(),
-                description: Some("Twitter access token secret".to_string()),
-                required: true,
+                description: Some("OAuth 2.0 user access token with tweet.write (defaults to the saved twitter token)".to_string()),
+                required: false,
                 default_value: None,
             },
         ],
@@ -1980,8 +2730,8 @@ fn create_facebook_node_definition() -> NodeDefinition {
             ParameterSchema {
                 name: "access_token".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("Facebook access token".to_string()),
-                required: true,
+                description: Some("Facebook page access token (defaults to the saved facebook token)".to_string()),
+                required: false,
                 default_value: None,
             },
             ParameterSchema {
@@ -2000,7 +2750,7 @@ fn create_email_node_definition() -> NodeDefinition {
     NodeDefinition {
         node_type: "email".to_string(),
         name: "Email".to_string(),
-        description: "Send emails via SMTP".to_string(),
+        description: "Send emails through SendGrid, Mailgun or an HTTP mail relay".to_string(),
         category: "Communication".to_string(),
         version: "1.0.0".to_string(),
         input_schema: vec![
@@ -2044,31 +2794,31 @@ fn create_email_node_definition() -> NodeDefinition {
                 default_value: None,
             },
             ParameterSchema {
-                name: "smtp_server".to_string(),
+                name: "provider".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("SMTP server address".to_string()),
-                required: true,
-                default_value: None,
+                description: Some("sendgrid, mailgun, or http for an SMTP-over-HTTP relay".to_string()),
+                required: false,
+                default_value: Some(ConfigValue::String("sendgrid".to_string())),
             },
             ParameterSchema {
-                name: "smtp_port".to_string(),
-                parameter_type: "number".to_string(),
-                description: Some("SMTP server port".to_string()),
+                name: "api_key".to_string(),
+                parameter_type: "string".to_string(),
+                description: Some("Provider API key (defaults to the token saved for the provider)".to_string()),
                 required: false,
-                default_value: Some(ConfigValue::Number(587.0)),
+                default_value: None,
             },
             ParameterSchema {
-                name: "username".to_string(),
+                name: "domain".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("SMTP username".to_string()),
-                required: true,
+                description: Some("Mailgun sending domain".to_string()),
+                required: false,
                 default_value: None,
             },
             ParameterSchema {
-                name: "password".to_string(),
+                name: "relay_url".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("SMTP password".to_string()),
-                required: true,
+                description: Some("Endpoint of the HTTP mail relay".to_string()),
+                required: false,
                 default_value: None,
             },
         ],
@@ -2105,14 +2855,14 @@ fn create_linkedin_node_definition() -> NodeDefinition {
             ParameterSchema {
                 name: "access_token".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("LinkedIn access token".to_string()),
-                required: true,
+                description: Some("LinkedIn access token with w_member_social (defaults to the saved linkedin token)".to_string()),
+                required: false,
                 default_value: None,
             },
             ParameterSchema {
                 name: "person_id".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("LinkedIn person ID".to_string()),
+                description: Some("LinkedIn person ID (defaults to the token's member)".to_string()),
                 required: false,
                 default_value: None,
             },
@@ -2157,8 +2907,8 @@ fn create_instagram_node_definition() -> NodeDefinition {
             ParameterSchema {
                 name: "access_token".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("Instagram access token".to_string()),
-                required: true,
+                description: Some("Instagram access token (defaults to the saved instagram token)".to_string()),
+                required: false,
                 default_value: None,
             },
             ParameterSchema {
@@ -2179,7 +2929,7 @@ fn create_webhook_node_definition() -> NodeDefinition {
         name: "Webhook".to_string(),
         description: "Send HTTP webhooks to external services".to_string(),
         category: "Integration".to_string(),
-        version: "1.0.0".to_string(),
+        version: "1.1.0".to_string(),
         input_schema: vec![
             ParameterSchema {
                 name: "payload".to_string(),
@@ -2216,10 +2966,17 @@ fn create_webhook_node_definition() -> NodeDefinition {
             ParameterSchema {
                 name: "method".to_string(),
                 parameter_type: "string".to_string(),
-                description: Some("HTTP method (GET, POST, PUT, DELETE)".to_string()),
+                description: Some("GET or POST; PUT, PATCH and DELETE need method_override".to_string()),
                 required: false,
                 default_value: Some(ConfigValue::String("POST".to_string())),
             },
+            ParameterSchema {
+                name: "method_override".to_string(),
+                parameter_type: "boolean".to_string(),
+                description: Some("Send PUT, PATCH and DELETE as POST with X-HTTP-Method-Override, for servers that honour it".to_string()),
+                required: false,
+                default_value: Some(ConfigValue::Boolean(false)),
+            },
             ParameterSchema {
                 name: "headers".to_string(),
                 parameter_type: "object".to_string(),
@@ -4324,3 +5081,153 @@ async fn execute_ai_responder_node(
         next_nodes: vec![],
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delay_config(key: &str, value: ConfigValue) -> NodeConfiguration {
+        NodeConfiguration {
+            parameters: HashMap::from([(key.to_string(), value)]),
+        }
+    }
+
+    #[test]
+    fn test_delay_duration_units() {
+        let hours = delay_config("duration", ConfigValue::String("24h".to_string()));
+        assert_eq!(delay_duration_ms(&hours), Ok(24 * 60 * 60 * 1000));
+
+        let millis = delay_config("delay", ConfigValue::Number(1500.0));
+        assert_eq!(delay_duration_ms(&millis), Ok(1500));
+
+        let too_long = delay_config("duration", ConfigValue::String("31d".to_string()));
+        assert!(delay_duration_ms(&too_long).is_err());
+
+        let bad_unit = delay_config("duration", ConfigValue::String("3w".to_string()));
+        assert!(delay_duration_ms(&bad_unit).is_err());
+    }
+
+    #[test]
+    fn test_set_variable_evaluates_value_against_input() {
+        let node = WorkflowNode {
+            node_type: "set_variable".to_string(),
+            configuration: NodeConfiguration {
+                parameters: HashMap::from([
+                    ("name".to_string(), ConfigValue::String("threshold".to_string())),
+                    ("value".to_string(), ConfigValue::String("price * 2".to_string())),
+                ]),
+            },
+            ..Default::default()
+        };
+        let input = HashMap::from([("price".to_string(), ConfigValue::Number(21.0))]);
+
+        let output = futures::executor::block_on(execute_set_variable_node(&node, &input)).unwrap();
+        assert!(matches!(output.data.get("name"), Some(ConfigValue::String(name)) if name == "threshold"));
+        assert!(matches!(output.data.get("value"), Some(ConfigValue::Number(n)) if *n == 42.0));
+    }
+
+    fn http_node(parameters: Vec<(&str, ConfigValue)>) -> WorkflowNode {
+        WorkflowNode {
+            node_type: "http_request".to_string(),
+            configuration: NodeConfiguration {
+                parameters: parameters.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn text(value: &str) -> ConfigValue {
+        ConfigValue::String(value.to_string())
+    }
+
+    #[test]
+    fn test_http_request_templates_query_auth_and_form_bodies() {
+        use crate::http_client::mock;
+        mock::respond("https://api.example.com/quotes/eth", 200, r#"{"price":3010.5}"#);
+
+        let node = http_node(vec![
+            ("url", text("https://api.example.com/quotes/{{ lower(token) }}")),
+            ("method", text("PATCH")),
+            ("method_override", ConfigValue::Boolean(true)),
+            ("query", ConfigValue::Object(HashMap::from([
+                ("limit".to_string(), ConfigValue::Number(5.0)),
+                ("fields".to_string(), ConfigValue::Array(vec![text("price"), text("volume")])),
+            ]))),
+            ("auth_type", text("api_key")),
+            ("api_key_in", text("query")),
+            ("api_key_name", text("key")),
+            ("auth_credential", text("k-123")),
+            ("body_type", text("form")),
+            ("body", ConfigValue::Object(HashMap::from([("note".to_string(), text("{{ token }} at {{ price }}"))]))),
+            ("max_response_bytes", ConfigValue::Number(4096.0)),
+            ("transform", ConfigValue::Array(vec![
+                text("strip_headers"),
+                ConfigValue::Object(HashMap::from([("name".to_string(), text("json_extract")), ("path".to_string(), text("$.price"))])),
+            ])),
+        ]);
+        let input = HashMap::from([
+            ("token".to_string(), text("ETH")),
+            ("price".to_string(), ConfigValue::Number(3000.0)),
+        ]);
+
+        let output = futures::executor::block_on(execute_http_request_node(&node, &input)).unwrap();
+        assert!(matches!(output.data.get("ok"), Some(ConfigValue::Boolean(true))));
+        assert!(matches!(output.data.get("body"), Some(ConfigValue::Number(n)) if *n == 3010.5));
+        let request = &mock::requests()[0];
+        assert_eq!(request.transform.len(), 2);
+        assert_eq!(request.url, "https://api.example.com/quotes/eth?fields=price&fields=volume&limit=5&key=k-123");
+        assert_eq!(request.body.as_deref(), Some("note=ETH+at+3000".as_bytes()));
+        assert_eq!(request.max_response_bytes, Some(4096));
+        assert!(request.cycles.is_some_and(|cycles| cycles > 0));
+        let header = |name: &str| request.headers.iter().find(|h| h.name == name).map(|h| h.value.as_str());
+        assert_eq!(header("X-HTTP-Method-Override"), Some("PATCH"));
+        assert_eq!(header("Content-Type"), Some("application/x-www-form-urlencoded"));
+    }
+
+    #[test]
+    fn test_http_request_statuses_and_validation() {
+        use crate::http_client::mock;
+        mock::respond("https://api.example.com/missing", 404, r#"{"error":"not found"}"#);
+
+        let mut node = http_node(vec![
+            ("url", text("https://api.example.com/missing")),
+            ("auth_type", text("bearer")),
+            ("auth_credential", text("tok")),
+        ]);
+        let error = futures::executor::block_on(execute_http_request_node(&node, &HashMap::new())).unwrap_err();
+        assert_eq!(error.class(), "ExecutionError");
+        assert_eq!(error.to_string(), r#"HTTP GET https://api.example.com/missing returned status 404: {"error":"not found"}"#);
+        assert_eq!(mock::requests()[0].headers[0].value, "Bearer tok");
+
+        // A 503 is only classified as retryable when resending cannot repeat a side effect
+        mock::respond("https://api.example.com/busy", 503, "busy");
+        node.configuration.parameters.insert("url".to_string(), text("https://api.example.com/busy"));
+        let error = futures::executor::block_on(execute_http_request_node(&node, &HashMap::new())).unwrap_err();
+        assert_eq!(error.class(), "NetworkError");
+        node.configuration.parameters.insert("method".to_string(), text("POST"));
+        let error = futures::executor::block_on(execute_http_request_node(&node, &HashMap::new())).unwrap_err();
+        assert_eq!(error.class(), "ExecutionError");
+        node.configuration.parameters.insert("method".to_string(), text("GET"));
+        node.configuration.parameters.insert("url".to_string(), text("https://api.example.com/missing"));
+
+        // Methods outcalls lack are refused unless the override header is asked for
+        let requests = mock::requests().len();
+        node.configuration.parameters.insert("method".to_string(), text("DELETE"));
+        let error = futures::executor::block_on(execute_http_request_node(&node, &HashMap::new())).unwrap_err();
+        assert!(error.to_string().contains("set method_override to send DELETE"));
+        assert_eq!(mock::requests().len(), requests);
+        node.configuration.parameters.insert("method".to_string(), text("GET"));
+
+        node.configuration.parameters.insert("fail_on_error".to_string(), ConfigValue::Boolean(false));
+        let output = futures::executor::block_on(execute_http_request_node(&node, &HashMap::new())).unwrap();
+        assert!(matches!(output.data.get("status"), Some(ConfigValue::Number(n)) if *n == 404.0));
+        assert!(matches!(output.data.get("ok"), Some(ConfigValue::Boolean(false))));
+
+        node.configuration.parameters.insert("url".to_string(), text("https://api.example.com/{{ path }"));
+        assert!(validate_node_expressions("http_request", &node.configuration).is_err());
+        node.configuration.parameters.insert("url".to_string(), text("https://api.example.com/{{ path }}?k={{credential:key}}"));
+        assert!(validate_node_expressions("http_request", &node.configuration).is_ok());
+        node.configuration.parameters.insert("transform".to_string(), text("gzip"));
+        assert!(validate_node_expressions("http_request", &node.configuration).is_err());
+    }
+}
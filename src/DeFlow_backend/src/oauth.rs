@@ -0,0 +1,450 @@
+// OAuth2 token lifecycle for the social integrations.
+//
+// Nodes read saved tokens through `access_token`, which refreshes a token at its
+// provider's token endpoint shortly before it expires, and through
+// `refresh_after_unauthorized` when a platform answers 401. Refreshed tokens replace
+// the saved ones. When a provider refuses the refresh token itself (`invalid_grant`)
+// the user has revoked access: the refresh token is dropped and `oauth.token_revoked`
+// is emitted so a workflow can tell them to reconnect.
+//
+// Every replica makes each outcall, and a refresh token can be redeemed only once, so
+// the exchange goes through the token relay configured for the provider. The relay
+// runs the request once per `Idempotency-Key` and answers every copy with the same
+// provider response, which the replicas then agree on. Without a relay tokens are not
+// refreshed. An outcall that does not reach consensus fails before its answer is read,
+// so only an agreed `invalid_grant` is treated as revocation.
+//
+// Controllers register each provider's client and relay with `configure_oauth_client`;
+// client secrets, like the saved tokens themselves, are sealed in the credential vault.
+
+use crate::credentials;
+use crate::http_client::{basic_auth, form_urlencode, HttpClient, HttpRequest, DEFAULT_MAX_RESPONSE_BYTES};
+use crate::http_transforms::TransformStep;
+use crate::stable_user_storage;
+use crate::storage;
+use crate::system_events;
+use crate::types::{OAuthClient, OAuthProviderInfo, OAuthToken};
+use ic_cdk::api::management_canister::http_request::{HttpHeader, HttpMethod};
+use ic_cdk::{api, caller, query, update};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+// Vault owner of client secrets; not a principal, so nodes cannot reference them
+const CLIENT_SECRET_OWNER: &str = "oauth-client";
+const REFRESH_MARGIN_NS: u64 = 5 * 60 * 1_000_000_000;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+// Keeps a stored client within its 1 KiB bound
+const MAX_RELAY_URL_BYTES: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ClientAuth {
+    Basic, // Client credentials in an Authorization header
+    Body,  // Client credentials as form fields
+}
+
+struct Provider {
+    name: &'static str,
+    platforms: &'static [&'static str],
+    token_url: &'static str,
+    client_auth: ClientAuth,
+}
+
+const PROVIDERS: &[Provider] = &[
+    Provider {
+        name: "twitter",
+        platforms: &["twitter", "x"],
+        token_url: "https://api.twitter.com/2/oauth2/token",
+        client_auth: ClientAuth::Basic,
+    },
+    Provider {
+        name: "linkedin",
+        platforms: &["linkedin"],
+        token_url: "https://www.linkedin.com/oauth/v2/accessToken",
+        client_auth: ClientAuth::Body,
+    },
+    Provider {
+        name: "discord",
+        platforms: &["discord"],
+        token_url: "https://discord.com/api/oauth2/token",
+        client_auth: ClientAuth::Basic,
+    },
+    Provider {
+        name: "google",
+        platforms: &["google", "gmail", "youtube"],
+        token_url: "https://oauth2.googleapis.com/token",
+        client_auth: ClientAuth::Body,
+    },
+];
+
+thread_local! {
+    // principal:platform pairs with a refresh in flight
+    static REFRESHING: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+fn provider_for(platform: &str) -> Option<&'static Provider> {
+    let platform = platform.to_lowercase();
+    PROVIDERS.iter().find(|provider| provider.platforms.contains(&platform.as_str()))
+}
+
+/// `expires_at` in nanoseconds; tokens saved with an expiry in seconds or milliseconds are scaled. 0 never expires.
+pub fn expiry_ns(expires_at: u64) -> u64 {
+    match expires_at {
+        0 => 0,
+        seconds if seconds < 10_000_000_000 => seconds.saturating_mul(NANOS_PER_SECOND),
+        millis if millis < 10_000_000_000_000 => millis.saturating_mul(1_000_000),
+        nanos => nanos,
+    }
+}
+
+fn expires_soon(token: &OAuthToken, now: u64) -> bool {
+    let expiry = expiry_ns(token.expires_at);
+    expiry != 0 && now.saturating_add(REFRESH_MARGIN_NS) >= expiry
+}
+
+fn expired(token: &OAuthToken, now: u64) -> bool {
+    let expiry = expiry_ns(token.expires_at);
+    expiry != 0 && now >= expiry
+}
+
+#[derive(Debug, PartialEq)]
+enum RefreshError {
+    Revoked(String),
+    Failed(String),
+}
+
+/// `token` updated from a token endpoint answer.
+fn apply_refresh(token: &OAuthToken, status: u16, body: &str, now: u64) -> Result<OAuthToken, RefreshError> {
+    let body: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    let error = body.get("error").and_then(Value::as_str);
+    // Providers refuse a dead refresh token with 400 (some with 401); other statuses come from the relay or a proxy
+    if error == Some("invalid_grant") && matches!(status, 400 | 401) {
+        let description = body.get("error_description").and_then(Value::as_str).unwrap_or("invalid_grant");
+        return Err(RefreshError::Revoked(description.to_string()));
+    }
+    let access_token = body.get("access_token").and_then(Value::as_str).filter(|_| (200..300).contains(&status));
+    let Some(access_token) = access_token else {
+        let reason = body.get("error_description").and_then(Value::as_str).or(error).unwrap_or("no access_token returned");
+        return Err(RefreshError::Failed(format!("Token refresh failed ({}): {}", status, reason)));
+    };
+
+    let scopes = body.get("scope").and_then(Value::as_str)
+        .map(|scope| scope.split([' ', ',']).filter(|s| !s.is_empty()).map(str::to_string).collect())
+        .unwrap_or_else(|| token.scopes.clone());
+    Ok(OAuthToken {
+        access_token: access_token.to_string(),
+        // Providers that rotate refresh tokens return a new one; the others keep the old one valid
+        refresh_token: body.get("refresh_token").and_then(Value::as_str).map(str::to_string)
+            .or_else(|| token.refresh_token.clone()),
+        expires_at: body.get("expires_in").and_then(Value::as_u64)
+            .map_or(0, |seconds| now.saturating_add(seconds.saturating_mul(NANOS_PER_SECOND))),
+        scopes,
+        ..token.clone()
+    })
+}
+
+/// Key the relay runs an exchange once under: each refresh token is redeemed at most once.
+fn exchange_key(client: &OAuthClient, refresh_token: &str) -> String {
+    let digest = Sha256::digest([client.provider.as_str(), &client.client_id, refresh_token].join("\0"));
+    hex::encode(&digest[..16])
+}
+
+/// Transform keeping only the token endpoint fields `apply_refresh` reads.
+fn token_reply() -> Vec<TransformStep> {
+    vec![
+        TransformStep::strip_headers(),
+        TransformStep::json_extract(&[
+            ("access_token", "$.access_token"),
+            ("refresh_token", "$.refresh_token"),
+            ("expires_in", "$.expires_in"),
+            ("scope", "$.scope"),
+            ("error", "$.error"),
+            ("error_description", "$.error_description"),
+        ]),
+    ]
+}
+
+struct RefreshGuard(String);
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        REFRESHING.with(|refreshing| refreshing.borrow_mut().remove(&self.0));
+    }
+}
+
+/// Exchange the refresh token of `token` for a new access token and save it.
+async fn refresh(principal: &str, token: OAuthToken, now: u64) -> Result<OAuthToken, String> {
+    let platform = token.platform.clone();
+    let provider = provider_for(&platform)
+        .ok_or_else(|| format!("{} tokens cannot be refreshed; reconnect the account", platform))?;
+    let refresh_token = token.refresh_token.clone()
+        .ok_or_else(|| format!("The {} token has expired and has no refresh token; reconnect the account", platform))?;
+    let client = storage::get_oauth_client(provider.name)
+        .ok_or_else(|| format!("No OAuth client is configured for {}", provider.name))?;
+    let relay_url = client.relay_url.clone()
+        .ok_or_else(|| format!("No token relay is configured for {}, so its tokens cannot be refreshed", provider.name))?;
+    let client_secret = credentials::internal_secret(CLIENT_SECRET_OWNER, provider.name)?;
+
+    let key = format!("{}:{}", principal, platform);
+    if !REFRESHING.with(|refreshing| refreshing.borrow_mut().insert(key.clone())) {
+        return Err(format!("The {} token is being refreshed; try again shortly", platform));
+    }
+    let _guard = RefreshGuard(key);
+
+    let mut fields = vec![("grant_type", "refresh_token"), ("refresh_token", refresh_token.as_str())];
+    let mut headers = HashMap::from([
+        ("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string()),
+        ("Accept".to_string(), "application/json".to_string()),
+    ]);
+    match (&client_secret, provider.client_auth) {
+        (Some(secret), ClientAuth::Basic) => {
+            headers.insert("Authorization".to_string(), basic_auth(&client.client_id, secret));
+        }
+        (Some(secret), ClientAuth::Body) => {
+            fields.push(("client_id", &client.client_id));
+            fields.push(("client_secret", secret));
+        }
+        // Public clients identify themselves by id only
+        (None, _) => fields.push(("client_id", &client.client_id)),
+    }
+
+    let exchange = json!({ "url": provider.token_url, "headers": headers, "body": form_urlencode(&fields) });
+    let response = HttpClient::request(HttpRequest {
+        url: relay_url,
+        method: HttpMethod::POST,
+        headers: vec![
+            HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string() },
+            HttpHeader { name: "Idempotency-Key".to_string(), value: exchange_key(&client, &refresh_token) },
+        ],
+        body: Some(exchange.to_string().into_bytes()),
+        max_response_bytes: Some(DEFAULT_MAX_RESPONSE_BYTES),
+        cycles: None,
+        transform: token_reply(),
+    }).await?;
+
+    match apply_refresh(&token, response.status, &response.body, now) {
+        Ok(refreshed) => {
+            stable_user_storage::insert_oauth_token(principal.to_string(), platform, refreshed.clone(), now)?;
+            Ok(refreshed)
+        }
+        Err(RefreshError::Revoked(reason)) => {
+            // A token saved while this exchange was in flight is not the one the provider refused
+            let saved = stable_user_storage::get_oauth_token(principal, &platform)?;
+            if saved.as_ref().and_then(|saved| saved.refresh_token.as_deref()) != Some(refresh_token.as_str()) {
+                return Err(format!("The {} token was replaced while it was being refreshed; try again", platform));
+            }
+            // Keep the access token for the record but stop retrying a dead refresh token
+            let revoked = OAuthToken { refresh_token: None, ..token };
+            stable_user_storage::insert_oauth_token(principal.to_string(), platform.clone(), revoked, now)?;
+            system_events::emit(
+                system_events::OAUTH_TOKEN_REVOKED,
+                system_events::oauth_token_revoked(principal, &platform, provider.name, &reason),
+            );
+            Err(format!("{} access was revoked ({}); reconnect the account", platform, reason))
+        }
+        Err(RefreshError::Failed(reason)) => Err(reason),
+    }
+}
+
+/// Access token `principal` saved for `platform`, refreshed first when it is about to expire.
+/// None when they have not saved one.
+pub async fn access_token(principal: &str, platform: &str, now: u64) -> Result<Option<String>, String> {
+    let Some(token) = stable_user_storage::get_oauth_token(principal, platform)? else {
+        return Ok(None);
+    };
+    if token.access_token.is_empty() {
+        return Ok(None);
+    }
+    if !expires_soon(&token, now) {
+        return Ok(Some(token.access_token));
+    }
+    let still_valid = !expired(&token, now);
+    match refresh(principal, token.clone(), now).await {
+        Ok(refreshed) => Ok(Some(refreshed.access_token)),
+        // Within the margin the old token still works
+        Err(_) if still_valid => Ok(Some(token.access_token)),
+        Err(error) => Err(error),
+    }
+}
+
+/// A new access token after a platform rejected `rejected` with 401, or None when it cannot be refreshed.
+pub async fn refresh_after_unauthorized(principal: &str, platform: &str, rejected: &str, now: u64) -> Result<Option<String>, String> {
+    let Some(token) = stable_user_storage::get_oauth_token(principal, platform)? else {
+        return Ok(None);
+    };
+    // Another execution already refreshed it
+    if token.access_token != rejected {
+        return Ok(Some(token.access_token));
+    }
+    if token.refresh_token.is_none() || provider_for(platform).is_none() {
+        return Ok(None);
+    }
+    refresh(principal, token, now).await.map(|refreshed| Some(refreshed.access_token))
+}
+
+/// Seal tokens saved in plaintext before the vault held them, once the vault has a key.
+pub fn start_sealing_saved_tokens() {
+    ic_cdk_timers::set_timer(std::time::Duration::ZERO, || {
+        ic_cdk::spawn(async {
+            let sealed = match credentials::ensure_vault_key().await {
+                Ok(()) => stable_user_storage::seal_plaintext_oauth_tokens(api::time()),
+                Err(error) => Err(error),
+            };
+            if let Err(error) = sealed {
+                ic_cdk::println!("WARNING: Failed to seal saved OAuth tokens: {}", error);
+            }
+        });
+    });
+}
+
+/// Register the OAuth client used to refresh tokens of `provider`. Omit `client_secret` for public (PKCE) clients.
+/// `relay_url` is the token relay the exchange is sent through; see the module comment for its contract.
+#[update]
+pub async fn configure_oauth_client(
+    provider: String,
+    client_id: String,
+    client_secret: Option<String>,
+    relay_url: Option<String>
+) -> Result<OAuthProviderInfo, String> {
+    if !api::is_controller(&caller()) {
+        return Err("Only controllers can configure OAuth clients".to_string());
+    }
+    let provider = PROVIDERS.iter().find(|p| p.name == provider.to_lowercase())
+        .ok_or_else(|| format!("Unknown OAuth provider '{}'", provider))?;
+    let client_id = client_id.trim().to_string();
+    if client_id.is_empty() {
+        return Err("client_id is required".to_string());
+    }
+    let relay_url = relay_url.map(|url| url.trim().to_string()).filter(|url| !url.is_empty());
+    if let Some(url) = &relay_url {
+        if !url.starts_with("https://") || url.len() > MAX_RELAY_URL_BYTES {
+            return Err(format!("relay_url must be an https URL of at most {} bytes", MAX_RELAY_URL_BYTES));
+        }
+    }
+
+    let now = api::time();
+    match &client_secret {
+        Some(secret) => {
+            credentials::ensure_vault_key().await?;
+            credentials::store_internal_secret(CLIENT_SECRET_OWNER, provider.name, secret, now)?;
+        }
+        None => {
+            storage::remove_credential(CLIENT_SECRET_OWNER, provider.name);
+        }
+    }
+    storage::insert_oauth_client(OAuthClient {
+        provider: provider.name.to_string(),
+        client_id,
+        has_client_secret: client_secret.is_some(),
+        relay_url,
+        configured_at: now,
+    });
+    Ok(provider_info(provider))
+}
+
+fn provider_info(provider: &Provider) -> OAuthProviderInfo {
+    OAuthProviderInfo {
+        provider: provider.name.to_string(),
+        platforms: provider.platforms.iter().map(|platform| platform.to_string()).collect(),
+        token_url: provider.token_url.to_string(),
+        client: storage::get_oauth_client(provider.name),
+    }
+}
+
+#[query]
+pub fn list_oauth_providers() -> Vec<OAuthProviderInfo> {
+    PROVIDERS.iter().map(provider_info).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::mock;
+    use futures::executor::block_on;
+
+    const USER: &str = "oauth-user";
+    const NOW: u64 = 1_700_000_000 * NANOS_PER_SECOND;
+
+    fn token(platform: &str, expires_at: u64, refresh_token: Option<&str>) -> OAuthToken {
+        OAuthToken {
+            user_principal: USER.to_string(),
+            platform: platform.to_string(),
+            access_token: "old-access".to_string(),
+            refresh_token: refresh_token.map(str::to_string),
+            expires_at,
+            scopes: vec!["tweet.write".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_expiry_units_and_refresh_responses() {
+        assert_eq!(expiry_ns(0), 0);
+        assert_eq!(expiry_ns(1_700_000_000), NOW);
+        assert_eq!(expiry_ns(1_700_000_000_000), NOW);
+        assert_eq!(expiry_ns(NOW), NOW);
+        assert!(expires_soon(&token("twitter", 1_700_000_100, None), NOW));
+        assert!(!expires_soon(&token("twitter", 1_700_003_600, None), NOW));
+        assert!(provider_for("X").is_some_and(|p| p.name == "twitter"));
+        assert!(provider_for("facebook").is_none());
+
+        let saved = token("twitter", 1, Some("refresh-1"));
+        let refreshed = apply_refresh(&saved, 200, r#"{"access_token":"new","expires_in":7200,"scope":"tweet.write users.read"}"#, NOW).unwrap();
+        assert_eq!(refreshed.access_token, "new");
+        assert_eq!(refreshed.refresh_token.as_deref(), Some("refresh-1"));
+        assert_eq!(refreshed.expires_at, NOW + 7200 * NANOS_PER_SECOND);
+        assert_eq!(refreshed.scopes, vec!["tweet.write", "users.read"]);
+
+        assert_eq!(
+            apply_refresh(&saved, 400, r#"{"error":"invalid_grant","error_description":"Token has been revoked."}"#, NOW).unwrap_err(),
+            RefreshError::Revoked("Token has been revoked.".to_string())
+        );
+        assert!(matches!(apply_refresh(&saved, 500, "upstream error", NOW), Err(RefreshError::Failed(_))));
+        // invalid_grant is only the provider's answer when it comes with the provider's status
+        assert!(matches!(apply_refresh(&saved, 502, r#"{"error":"invalid_grant"}"#, NOW), Err(RefreshError::Failed(_))));
+    }
+
+    #[test]
+    fn test_expiring_tokens_are_refreshed_through_the_relay() {
+        credentials::install_test_vault_key();
+        let mut client = OAuthClient {
+            provider: "linkedin".to_string(),
+            client_id: "client-1".to_string(),
+            has_client_secret: false,
+            relay_url: None,
+            configured_at: 0,
+        };
+        storage::insert_oauth_client(client.clone());
+        stable_user_storage::insert_oauth_token(USER.to_string(), "linkedin".to_string(), token("linkedin", 1_700_000_060, Some("refresh-1")), NOW).unwrap();
+
+        // Without a relay every replica would redeem the refresh token, so none does
+        let saved = stable_user_storage::get_oauth_token(USER, "linkedin").unwrap().unwrap();
+        assert!(block_on(refresh(USER, saved, NOW)).unwrap_err().contains("No token relay"));
+        assert_eq!(block_on(access_token(USER, "linkedin", NOW)).unwrap().as_deref(), Some("old-access"));
+        assert!(mock::requests().is_empty());
+
+        client.relay_url = Some("https://relay.example/exchange".to_string());
+        storage::insert_oauth_client(client.clone());
+        mock::respond("https://relay.example/exchange", 200, r#"{"access_token":"fresh","expires_in":3600,"refresh_token":"refresh-2"}"#);
+
+        let access = block_on(access_token(USER, "linkedin", NOW)).unwrap();
+        assert_eq!(access.as_deref(), Some("fresh"));
+        let request = &mock::requests()[0];
+        let sent: Value = serde_json::from_slice(request.body.as_ref().unwrap()).unwrap();
+        assert_eq!(sent["url"], "https://www.linkedin.com/oauth/v2/accessToken");
+        assert_eq!(sent["body"], "grant_type=refresh_token&refresh_token=refresh-1&client_id=client-1");
+        let key = request.headers.iter().find(|h| h.name == "Idempotency-Key").unwrap();
+        assert_eq!(key.value, exchange_key(&client, "refresh-1"));
+        assert_ne!(key.value, exchange_key(&client, "refresh-2"));
+
+        let saved = stable_user_storage::get_oauth_token(USER, "linkedin").unwrap().unwrap();
+        assert_eq!(saved.refresh_token.as_deref(), Some("refresh-2"));
+        assert_eq!(saved.expires_at, NOW + 3600 * NANOS_PER_SECOND);
+
+        // A fresh token is used as it is, and a 401 on a token someone already replaced reuses theirs
+        assert_eq!(block_on(access_token(USER, "linkedin", NOW)).unwrap().as_deref(), Some("fresh"));
+        assert_eq!(block_on(refresh_after_unauthorized(USER, "linkedin", "stale", NOW)).unwrap().as_deref(), Some("fresh"));
+        assert_eq!(mock::requests().len(), 1);
+        assert_eq!(block_on(access_token(USER, "twitter", NOW)).unwrap(), None);
+    }
+}
@@ -0,0 +1,297 @@
+// Workflow revision history.
+//
+// Every save of a workflow appends an immutable `WorkflowRevision` snapshot and stamps
+// the stored workflow with its revision number, which executions record when they
+// start. Rolling back copies an old snapshot's content into a new revision, so the
+// log itself is never rewritten.
+
+use crate::acl;
+use crate::expressions::values_equal;
+use crate::storage;
+use crate::types::{
+    ConfigChange, ConfigValue, NodeConnection, NodeDiff, Workflow, WorkflowDiff, WorkflowNode, WorkflowRevision,
+    WorkflowRevisionSummary, WorkflowRole
+};
+use crate::workflow::validate_workflow;
+use ic_cdk::{api, caller, query, update};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+/// Record `workflow` as its next revision and store it as the current version.
+pub fn save(mut workflow: Workflow, author: String, now: u64, rolled_back_from: Option<u64>) -> Workflow {
+    let latest = storage::latest_workflow_revision(&workflow.id).or_else(|| record_baseline(&workflow.id));
+    let revision = latest.map_or(1, |latest| latest + 1);
+
+    workflow.revision = Some(revision);
+    storage::insert_workflow_revision(WorkflowRevision {
+        workflow_id: workflow.id.clone(),
+        revision,
+        workflow: workflow.clone(),
+        author,
+        created_at: now,
+        rolled_back_from,
+    });
+    storage::insert_workflow(workflow.id.clone(), workflow.clone());
+    workflow
+}
+
+/// Workflows stored before revisions existed keep their current state as revision 1.
+fn record_baseline(workflow_id: &str) -> Option<u64> {
+    let mut existing = storage::get_workflow(workflow_id)?;
+    existing.revision = Some(1);
+    storage::insert_workflow_revision(WorkflowRevision {
+        workflow_id: workflow_id.to_string(),
+        revision: 1,
+        author: existing.owner.clone().unwrap_or_default(),
+        created_at: existing.updated_at,
+        workflow: existing,
+        rolled_back_from: None,
+    });
+    Some(1)
+}
+
+/// `snapshot`'s content with the identity and lifecycle of `current`.
+fn restore(current: &Workflow, snapshot: Workflow) -> Workflow {
+    Workflow {
+        id: current.id.clone(),
+        owner: current.owner.clone(),
+        created_at: current.created_at,
+        state: current.state.clone(),
+        active: current.active,
+        metadata: current.metadata.clone(),
+        ..snapshot
+    }
+}
+
+pub fn diff_workflows(before: &Workflow, after: &Workflow) -> WorkflowDiff {
+    let fields = [
+        ("name", same(&before.name, &after.name)),
+        ("description", same(&before.description, &after.description)),
+        ("triggers", same(&before.triggers, &after.triggers)),
+        ("tags", same(&before.tags, &after.tags)),
+        ("max_concurrency", before.max_concurrency == after.max_concurrency),
+        ("state", before.state == after.state),
+        ("active", before.active == after.active),
+    ];
+    let mut variable_changes = Vec::new();
+    diff_maps(
+        before.variables.as_ref().unwrap_or(&HashMap::new()),
+        after.variables.as_ref().unwrap_or(&HashMap::new()),
+        None,
+        &mut variable_changes,
+    );
+
+    let find = |nodes: &[WorkflowNode], id: &str| nodes.iter().find(|node| node.id == id).cloned();
+    let nodes_changed = after.nodes.iter()
+        .filter_map(|node| find(&before.nodes, &node.id).map(|old| diff_node(&old, node)))
+        .filter(|diff| !diff.fields_changed.is_empty() || !diff.config_changes.is_empty())
+        .collect();
+
+    WorkflowDiff {
+        workflow_id: after.id.clone(),
+        from_revision: before.revision.unwrap_or(0),
+        to_revision: after.revision.unwrap_or(0),
+        fields_changed: fields.iter().filter(|(_, same)| !same).map(|(name, _)| name.to_string()).collect(),
+        variable_changes,
+        nodes_added: after.nodes.iter().filter(|node| find(&before.nodes, &node.id).is_none()).cloned().collect(),
+        nodes_removed: before.nodes.iter().filter(|node| find(&after.nodes, &node.id).is_none()).cloned().collect(),
+        nodes_changed,
+        connections_added: missing_connections(&after.connections, &before.connections),
+        connections_removed: missing_connections(&before.connections, &after.connections),
+    }
+}
+
+fn diff_node(before: &WorkflowNode, after: &WorkflowNode) -> NodeDiff {
+    let fields = [
+        ("node_type", before.node_type == after.node_type),
+        ("position", same(&before.position, &after.position)),
+        ("metadata", same(&before.metadata, &after.metadata)),
+        ("on_error", same(&before.on_error, &after.on_error)),
+    ];
+    let mut config_changes = Vec::new();
+    diff_maps(&before.configuration.parameters, &after.configuration.parameters, None, &mut config_changes);
+
+    NodeDiff {
+        node_id: after.id.clone(),
+        fields_changed: fields.iter().filter(|(_, same)| !same).map(|(name, _)| name.to_string()).collect(),
+        config_changes,
+    }
+}
+
+fn diff_maps(
+    before: &HashMap<String, ConfigValue>,
+    after: &HashMap<String, ConfigValue>,
+    prefix: Option<&str>,
+    changes: &mut Vec<ConfigChange>,
+) {
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        let path = prefix.map_or_else(|| key.clone(), |prefix| format!("{}.{}", prefix, key));
+        match (before.get(key), after.get(key)) {
+            (Some(ConfigValue::Object(old)), Some(ConfigValue::Object(new))) => diff_maps(old, new, Some(&path), changes),
+            (Some(old), Some(new)) if values_equal(old, new) => {}
+            (old, new) => changes.push(ConfigChange { path, before: old.cloned(), after: new.cloned() }),
+        }
+    }
+}
+
+/// Connections in `connections` with no connection between the same ports in `other`.
+fn missing_connections(connections: &[NodeConnection], other: &[NodeConnection]) -> Vec<NodeConnection> {
+    let ports = |c: &NodeConnection| (c.source_node_id.clone(), c.source_output.clone(), c.target_node_id.clone(), c.target_input.clone());
+    let other: BTreeSet<_> = other.iter().map(ports).collect();
+    connections.iter().filter(|c| !other.contains(&ports(c))).cloned().collect()
+}
+
+// Compares through JSON, whose maps are ordered, for types without PartialEq
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+fn load_revision(workflow_id: &str, revision: u64) -> Result<WorkflowRevision, String> {
+    storage::get_workflow_revision(workflow_id, revision)
+        .ok_or_else(|| format!("Revision {} of workflow {} not found", revision, workflow_id))
+}
+
+/// Revisions of a workflow, oldest first.
+#[query]
+pub fn list_workflow_revisions(workflow_id: String) -> Result<Vec<WorkflowRevisionSummary>, String> {
+    acl::authorize(&workflow_id, WorkflowRole::Viewer)?;
+    Ok(storage::list_workflow_revisions(&workflow_id)
+        .into_iter()
+        .map(|revision| WorkflowRevisionSummary {
+            revision: revision.revision,
+            author: revision.author,
+            created_at: revision.created_at,
+            rolled_back_from: revision.rolled_back_from,
+            node_count: revision.workflow.nodes.len() as u32,
+        })
+        .collect())
+}
+
+#[query]
+pub fn get_workflow_revision(workflow_id: String, revision: u64) -> Result<WorkflowRevision, String> {
+    acl::authorize(&workflow_id, WorkflowRole::Viewer)?;
+    load_revision(&workflow_id, revision)
+}
+
+/// What changed going from revision `from` to revision `to`.
+#[query]
+pub fn diff_workflow_revisions(workflow_id: String, from: u64, to: u64) -> Result<WorkflowDiff, String> {
+    acl::authorize(&workflow_id, WorkflowRole::Viewer)?;
+    let before = load_revision(&workflow_id, from)?;
+    let after = load_revision(&workflow_id, to)?;
+    Ok(diff_workflows(&before.workflow, &after.workflow))
+}
+
+/// Restore the content of an earlier revision as a new revision.
+#[update]
+pub fn rollback_workflow(workflow_id: String, revision: u64) -> Result<Workflow, String> {
+    let current = acl::authorize(&workflow_id, WorkflowRole::Editor)?;
+    if current.revision == Some(revision) {
+        return Err(format!("Workflow {} is already at revision {}", workflow_id, revision));
+    }
+    let snapshot = load_revision(&workflow_id, revision)?;
+
+    let now = api::time();
+    let mut restored = restore(&current, snapshot.workflow);
+    restored.updated_at = now;
+    validate_workflow(&restored)
+        .map_err(|e| format!("Revision {} is no longer valid: {:?}", revision, e))?;
+
+    Ok(save(restored, caller().to_text(), now, Some(revision)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{NodeConfiguration, WorkflowState};
+
+    fn node(id: &str, parameters: Vec<(&str, ConfigValue)>) -> WorkflowNode {
+        WorkflowNode {
+            id: id.to_string(),
+            node_type: "http_request".to_string(),
+            configuration: NodeConfiguration {
+                parameters: parameters.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            },
+            ..WorkflowNode::default()
+        }
+    }
+
+    fn connection(source: &str, target: &str) -> NodeConnection {
+        NodeConnection {
+            id: format!("{}-{}", source, target),
+            source_node_id: source.to_string(),
+            source_output: "output".to_string(),
+            target_node_id: target.to_string(),
+            target_input: "input".to_string(),
+        }
+    }
+
+    fn text(value: &str) -> ConfigValue {
+        ConfigValue::String(value.to_string())
+    }
+
+    #[test]
+    fn test_diff_reports_nodes_connections_and_nested_config() {
+        let headers = |token: &str| ConfigValue::Object(HashMap::from([("Authorization".to_string(), text(token))]));
+        let before = Workflow {
+            id: "wf_1".to_string(),
+            revision: Some(1),
+            nodes: vec![
+                node("fetch", vec![("url", text("https://a.example")), ("headers", headers("old")), ("timeout", ConfigValue::Number(5.0))]),
+                node("notify", vec![]),
+            ],
+            connections: vec![connection("fetch", "notify")],
+            ..Workflow::default()
+        };
+        let after = Workflow {
+            name: "Renamed".to_string(),
+            revision: Some(2),
+            nodes: vec![
+                node("fetch", vec![("url", text("https://a.example")), ("headers", headers("new")), ("method", text("POST"))]),
+                node("swap", vec![]),
+            ],
+            connections: vec![connection("fetch", "swap")],
+            ..before.clone()
+        };
+
+        let diff = diff_workflows(&before, &after);
+        assert_eq!((diff.from_revision, diff.to_revision), (1, 2));
+        assert_eq!(diff.fields_changed, vec!["name"]);
+        assert_eq!(diff.nodes_added.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), vec!["swap"]);
+        assert_eq!(diff.nodes_removed.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), vec!["notify"]);
+        assert_eq!(diff.connections_added[0].target_node_id, "swap");
+        assert_eq!(diff.connections_removed[0].target_node_id, "notify");
+
+        assert_eq!(diff.nodes_changed.len(), 1);
+        let changes: Vec<(&str, bool, bool)> = diff.nodes_changed[0].config_changes.iter()
+            .map(|c| (c.path.as_str(), c.before.is_some(), c.after.is_some()))
+            .collect();
+        assert_eq!(changes, vec![("headers.Authorization", true, true), ("method", false, true), ("timeout", true, false)]);
+        assert!(diff_workflows(&after, &after).nodes_changed.is_empty());
+    }
+
+    #[test]
+    fn test_saves_append_revisions_after_a_baseline() {
+        // Stored before revisions existed
+        let legacy = Workflow { id: "wf_revisions".to_string(), owner: Some("owner".to_string()), ..Workflow::default() };
+        storage::insert_workflow(legacy.id.clone(), legacy.clone());
+
+        let saved = save(Workflow { name: "v2".to_string(), ..legacy.clone() }, "editor".to_string(), 10, None);
+        assert_eq!(saved.revision, Some(2));
+        assert_eq!(storage::get_workflow("wf_revisions").unwrap().revision, Some(2));
+
+        let revisions = storage::list_workflow_revisions("wf_revisions");
+        assert_eq!(revisions.iter().map(|r| (r.revision, r.author.as_str())).collect::<Vec<_>>(), vec![(1, "owner"), (2, "editor")]);
+        assert_eq!(revisions[0].workflow.name, legacy.name);
+
+        // A rollback keeps lifecycle fields and logs where it came from
+        let published = Workflow { state: WorkflowState::Published, active: true, ..saved };
+        let restored = save(restore(&published, revisions[0].workflow.clone()), "editor".to_string(), 20, Some(1));
+        assert_eq!((restored.name.as_str(), restored.active, restored.revision), (legacy.name.as_str(), true, Some(3)));
+        assert_eq!(storage::get_workflow_revision("wf_revisions", 3).unwrap().rolled_back_from, Some(1));
+
+        storage::remove_workflow_revisions("wf_revisions");
+        assert!(storage::list_workflow_revisions("wf_revisions").is_empty());
+    }
+}
@@ -1,573 +1,734 @@
-// Enhanced Scheduler Service with Universal Date Format Support
-// Supports both cron expressions and user-friendly dd/mm/yy hh:mm:ss format
-
-use candid::{CandidType, Deserialize};
-use serde::Serialize;
-use std::collections::HashMap;
+// Persistent workflow scheduler.
+//
+// Every schedule - one-shot, fixed interval or cron - is a `ScheduledWorkflow`
+// record in the stable `SCHEDULED_WORKFLOWS` map, so schedules, their run
+// counters and their history survive upgrades. Timers live on the heap and are
+// re-armed from the stored `next_execution` by `restore_schedules`; fire times
+// that passed in the meantime are handled by the schedule's `MissedRunPolicy`.
+//
+// Date strings in the universal format (dd/mm/yy hh:mm:ss, dd/mm/yyyy hh:mm:ss,
+// yyyy-mm-dd hh:mm:ss) are converted with `parse_universal_datetime`.
+
+use crate::acl;
+use crate::cron::{self, Timezone};
+use crate::execution::trigger_execution;
+use crate::storage;
+use crate::types::{
+    ConfigValue, MissedRunPolicy, ScheduleRequest, ScheduleRun, ScheduleRunStatus, ScheduleSpec,
+    ScheduleType, ScheduledWorkflow, WorkflowRole
+};
+use crate::workflow::generate_id;
 use ic_cdk::api::time;
-use ic_cdk_timers::{set_timer, set_timer_interval, clear_timer};
-
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
-pub struct SchedulerService {
-    active_schedules: HashMap<String, ScheduleInfo>,
-    schedule_counter: u64,
+use ic_cdk_timers::{clear_timer, set_timer, TimerId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const MIN_INTERVAL_SECONDS: u64 = 60;
+const MAX_SCHEDULE_HISTORY: usize = 20;
+// A fire time counts as missed when its timer runs this much later than planned.
+const MISSED_RUN_GRACE_NS: u64 = 60 * NANOS_PER_SECOND;
+// Bounds the work done when a schedule wakes up after a long gap.
+const MAX_MISSED_SCAN: usize = 1000;
+const MAX_CATCH_UP_RUNS: usize = 10;
+
+thread_local! {
+    static SCHEDULE_TIMERS: RefCell<HashMap<String, TimerId>> = RefCell::new(HashMap::new());
 }
 
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
-pub struct ScheduleInfo {
-    pub id: String,
-    pub schedule_type: ScheduleType,
-    pub schedule_config: ScheduleConfig,
-    pub next_execution: u64, // Nanoseconds since epoch
-    pub timer_id: Option<u64>, // Store timer ID for cancellation
-    pub is_active: bool,
-    pub workflow_id: String,
-    pub node_id: String,
-    pub created_at: u64,
-    pub execution_count: u64,
-    pub last_execution: Option<u64>,
+// =============================================================================
+// SCHEDULE MANAGEMENT
+// =============================================================================
+
+pub fn create_schedule(request: ScheduleRequest) -> Result<ScheduledWorkflow, String> {
+    acl::authorize(&request.workflow_id, WorkflowRole::Editor)?;
+
+    let schedule = new_schedule(generate_id(), request, time())?;
+    storage::insert_scheduled_workflow(schedule.id.clone(), schedule.clone());
+    arm_timer(&schedule);
+    Ok(schedule)
 }
 
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
-pub enum ScheduleType {
-    OneTime,
-    Recurring,
-    Interval,
-    Cron,
+/// Replace the timing of an existing schedule, keeping its run count and history.
+pub fn update_schedule(schedule_id: &str, request: ScheduleRequest) -> Result<ScheduledWorkflow, String> {
+    let existing = load_editable_schedule(schedule_id)?;
+    acl::authorize(&request.workflow_id, WorkflowRole::Editor)?;
+
+    let mut schedule = new_schedule(existing.id.clone(), request, time())?;
+    schedule.run_count = existing.run_count;
+    schedule.last_run_at = existing.last_run_at;
+    schedule.history = existing.history;
+    schedule.created_at = existing.created_at;
+
+    storage::insert_scheduled_workflow(schedule.id.clone(), schedule.clone());
+    arm_timer(&schedule);
+    Ok(schedule)
 }
 
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
-pub struct ScheduleConfig {
-    // Universal format: dd/mm/yy hh:mm:ss or dd/mm/yyyy hh:mm:ss
-    pub datetime_string: Option<String>,
-    
-    // Traditional cron expression
-    pub cron_expression: Option<String>,
-    
-    // Interval in seconds for recurring schedules
-    pub interval_seconds: Option<u64>,
-    
-    // Timezone support
-    pub timezone: String, // Default: "UTC"
-    
-    // Execution limits
-    pub max_executions: Option<u64>,
-    pub end_date: Option<String>, // dd/mm/yy hh:mm:ss format
-    
-    // Advanced options
-    pub skip_weekends: bool,
-    pub skip_holidays: bool,
-    pub retry_on_failure: bool,
-    pub retry_attempts: u32,
+pub fn pause_schedule(schedule_id: &str) -> Result<ScheduledWorkflow, String> {
+    let mut schedule = load_editable_schedule(schedule_id)?;
+    if !schedule.active {
+        return Err(format!("Schedule {} is not active", schedule_id));
+    }
+
+    schedule.active = false;
+    clear_schedule_timer(schedule_id);
+    storage::insert_scheduled_workflow(schedule.id.clone(), schedule.clone());
+    Ok(schedule)
 }
 
-#[derive(Debug, Clone, CandidType, Serialize, Deserialize)]
-pub struct ScheduleResult {
-    pub success: bool,
-    pub message: String,
-    pub schedule_id: Option<String>,
-    pub next_execution: Option<u64>,
+/// Resume a paused schedule. Fire times that passed while it was paused are
+/// handled by its missed-run policy as soon as the timer fires.
+pub fn resume_schedule(schedule_id: &str) -> Result<ScheduledWorkflow, String> {
+    let mut schedule = load_editable_schedule(schedule_id)?;
+    if schedule.active {
+        return Err(format!("Schedule {} is already active", schedule_id));
+    }
+    if schedule.is_complete() {
+        return Err(format!("Schedule {} has no further runs", schedule_id));
+    }
+
+    schedule.active = true;
+    storage::insert_scheduled_workflow(schedule.id.clone(), schedule.clone());
+    arm_timer(&schedule);
+    Ok(schedule)
 }
 
-impl SchedulerService {
-    pub fn new() -> Self {
-        SchedulerService {
-            active_schedules: HashMap::new(),
-            schedule_counter: 0,
+pub fn cancel_schedule(schedule_id: &str) -> Result<(), String> {
+    if let Some(schedule) = storage::get_scheduled_workflow(schedule_id) {
+        // Schedules of deleted workflows can be cleaned up by anyone
+        if storage::get_workflow(&schedule.workflow_id).is_some() {
+            acl::authorize(&schedule.workflow_id, WorkflowRole::Editor)?;
         }
     }
+    clear_schedule_timer(schedule_id);
+    storage::remove_scheduled_workflow(schedule_id)
+        .map(|_| ())
+        .ok_or_else(|| format!("Schedule {} not found", schedule_id))
+}
 
-    // =============================================================================
-    // SCHEDULE CREATION WITH UNIVERSAL FORMAT
-    // =============================================================================
-
-    pub fn create_schedule_from_universal_format(
-        &mut self,
-        datetime_string: &str,
-        workflow_id: String,
-        node_id: String,
-        timezone: Option<String>,
-    ) -> Result<ScheduleResult, String> {
-        // Parse universal format: dd/mm/yy hh:mm:ss or dd/mm/yyyy hh:mm:ss
-        let execution_time = self.parse_universal_datetime(datetime_string)?;
-        
-        self.schedule_counter += 1;
-        let schedule_id = format!("schedule_{}", self.schedule_counter);
-        
-        let schedule_info = ScheduleInfo {
-            id: schedule_id.clone(),
-            schedule_type: ScheduleType::OneTime,
-            schedule_config: ScheduleConfig {
-                datetime_string: Some(datetime_string.to_string()),
-                cron_expression: None,
-                interval_seconds: None,
-                timezone: timezone.unwrap_or_else(|| "UTC".to_string()),
-                max_executions: Some(1),
-                end_date: None,
-                skip_weekends: false,
-                skip_holidays: false,
-                retry_on_failure: true,
-                retry_attempts: 3,
-            },
-            next_execution: execution_time,
-            timer_id: None,
-            is_active: true,
-            workflow_id,
-            node_id,
-            created_at: time(),
-            execution_count: 0,
-            last_execution: None,
-        };
+/// Schedules of workflows the caller can view.
+pub fn list_schedules() -> Vec<ScheduledWorkflow> {
+    let mut visible: HashMap<String, bool> = HashMap::new();
+    storage::list_scheduled_workflows()
+        .into_iter()
+        .filter(|schedule| *visible.entry(schedule.workflow_id.clone()).or_insert_with(|| {
+            acl::authorize(&schedule.workflow_id, WorkflowRole::Viewer).is_ok()
+        }))
+        .collect()
+}
 
-        // Set the timer
-        let timer_result = self.set_execution_timer(&schedule_info)?;
-        let mut final_schedule = schedule_info;
-        final_schedule.timer_id = Some(timer_result);
-
-        self.active_schedules.insert(schedule_id.clone(), final_schedule);
-
-        Ok(ScheduleResult {
-            success: true,
-            message: format!("Schedule created successfully for {}", datetime_string),
-            schedule_id: Some(schedule_id),
-            next_execution: Some(execution_time),
-        })
-    }
-
-    pub fn create_recurring_schedule(
-        &mut self,
-        datetime_string: &str,
-        interval_seconds: u64,
-        workflow_id: String,
-        node_id: String,
-        max_executions: Option<u64>,
-        timezone: Option<String>,
-    ) -> Result<ScheduleResult, String> {
-        let first_execution = self.parse_universal_datetime(datetime_string)?;
-        
-        self.schedule_counter += 1;
-        let schedule_id = format!("recurring_{}", self.schedule_counter);
-        
-        let schedule_info = ScheduleInfo {
-            id: schedule_id.clone(),
-            schedule_type: ScheduleType::Recurring,
-            schedule_config: ScheduleConfig {
-                datetime_string: Some(datetime_string.to_string()),
-                cron_expression: None,
-                interval_seconds: Some(interval_seconds),
-                timezone: timezone.unwrap_or_else(|| "UTC".to_string()),
-                max_executions,
-                end_date: None,
-                skip_weekends: false,
-                skip_holidays: false,
-                retry_on_failure: true,
-                retry_attempts: 3,
-            },
-            next_execution: first_execution,
-            timer_id: None,
-            is_active: true,
-            workflow_id,
-            node_id,
-            created_at: time(),
-            execution_count: 0,
-            last_execution: None,
-        };
+pub fn get_schedule(schedule_id: &str) -> Option<ScheduledWorkflow> {
+    storage::get_scheduled_workflow(schedule_id)
+        .filter(|schedule| acl::authorize(&schedule.workflow_id, WorkflowRole::Viewer).is_ok())
+}
 
-        // Set interval timer
-        let timer_result = self.set_interval_timer(&schedule_info)?;
-        let mut final_schedule = schedule_info;
-        final_schedule.timer_id = Some(timer_result);
-
-        self.active_schedules.insert(schedule_id.clone(), final_schedule);
-
-        Ok(ScheduleResult {
-            success: true,
-            message: format!("Recurring schedule created starting at {}", datetime_string),
-            schedule_id: Some(schedule_id),
-            next_execution: Some(first_execution),
-        })
-    }
-
-    pub fn create_cron_schedule(
-        &mut self,
-        cron_expression: &str,
-        workflow_id: String,
-        node_id: String,
-        timezone: Option<String>,
-    ) -> Result<ScheduleResult, String> {
-        // Validate cron expression
-        self.validate_cron_expression(cron_expression)?;
-        
-        let next_execution = self.calculate_next_cron_execution(cron_expression)?;
-        
-        self.schedule_counter += 1;
-        let schedule_id = format!("cron_{}", self.schedule_counter);
-        
-        let schedule_info = ScheduleInfo {
-            id: schedule_id.clone(),
-            schedule_type: ScheduleType::Cron,
-            schedule_config: ScheduleConfig {
-                datetime_string: None,
-                cron_expression: Some(cron_expression.to_string()),
-                interval_seconds: None,
-                timezone: timezone.unwrap_or_else(|| "UTC".to_string()),
-                max_executions: None,
-                end_date: None,
-                skip_weekends: false,
-                skip_holidays: false,
-                retry_on_failure: true,
-                retry_attempts: 3,
-            },
-            next_execution,
-            timer_id: None,
-            is_active: true,
-            workflow_id,
-            node_id,
-            created_at: time(),
-            execution_count: 0,
-            last_execution: None,
-        };
+pub fn get_schedule_history(schedule_id: &str) -> Result<Vec<ScheduleRun>, String> {
+    let schedule = load_schedule(schedule_id)?;
+    acl::authorize(&schedule.workflow_id, WorkflowRole::Viewer)?;
+    Ok(schedule.history.unwrap_or_default())
+}
 
-        self.active_schedules.insert(schedule_id.clone(), schedule_info);
-
-        Ok(ScheduleResult {
-            success: true,
-            message: format!("Cron schedule created: {}", cron_expression),
-            schedule_id: Some(schedule_id),
-            next_execution: Some(next_execution),
-        })
-    }
-
-    // =============================================================================
-    // DATE/TIME PARSING
-    // =============================================================================
-
-    fn parse_universal_datetime(&self, datetime_str: &str) -> Result<u64, String> {
-        // Support formats:
-        // dd/mm/yy hh:mm:ss
-        // dd/mm/yyyy hh:mm:ss
-        // dd-mm-yy hh:mm:ss
-        // dd-mm-yyyy hh:mm:ss
-        // yyyy-mm-dd hh:mm:ss (ISO format)
-
-        let datetime_str = datetime_str.trim();
-        
-        // Split date and time parts
-        let parts: Vec<&str> = datetime_str.split_whitespace().collect();
-        if parts.len() != 2 {
-            return Err("Invalid datetime format. Expected: 'dd/mm/yy hh:mm:ss' or 'dd/mm/yyyy hh:mm:ss'".to_string());
-        }
+/// (schedule id, next fire time, workflow id) of the next active schedules.
+pub fn get_next_executions(limit: usize) -> Vec<(String, u64, String)> {
+    let mut schedules: Vec<_> = list_schedules().into_iter()
+        .filter(|s| s.active)
+        .map(|s| (s.id, s.next_execution, s.workflow_id))
+        .collect();
 
-        let date_part = parts[0];
-        let time_part = parts[1];
-
-        // Parse date part (handle different separators)
-        let date_components = if date_part.contains('/') {
-            date_part.split('/').collect::<Vec<&str>>()
-        } else if date_part.contains('-') {
-            // Check if it's ISO format (yyyy-mm-dd) or dd-mm-yy
-            let dash_parts = date_part.split('-').collect::<Vec<&str>>();
-            if dash_parts.len() == 3 && dash_parts[0].len() == 4 {
-                // ISO format: yyyy-mm-dd
-                vec![dash_parts[2], dash_parts[1], dash_parts[0]] // Reorder to dd-mm-yyyy
-            } else {
-                dash_parts
-            }
-        } else {
-            return Err("Invalid date separator. Use '/' or '-'".to_string());
-        };
+    schedules.sort_by_key(|&(_, time, _)| time);
+    schedules.truncate(limit);
+    schedules
+}
 
-        if date_components.len() != 3 {
-            return Err("Invalid date format. Expected: dd/mm/yy or dd/mm/yyyy".to_string());
-        }
+fn load_schedule(schedule_id: &str) -> Result<ScheduledWorkflow, String> {
+    storage::get_scheduled_workflow(schedule_id)
+        .ok_or_else(|| format!("Schedule {} not found", schedule_id))
+}
 
-        // Parse time part
-        let time_components: Vec<&str> = time_part.split(':').collect();
-        if time_components.len() != 3 {
-            return Err("Invalid time format. Expected: hh:mm:ss".to_string());
-        }
+fn load_editable_schedule(schedule_id: &str) -> Result<ScheduledWorkflow, String> {
+    let schedule = load_schedule(schedule_id)?;
+    acl::authorize(&schedule.workflow_id, WorkflowRole::Editor)?;
+    Ok(schedule)
+}
 
-        // Extract components
-        let day: u32 = date_components[0].parse()
-            .map_err(|_| "Invalid day")?;
-        let month: u32 = date_components[1].parse()
-            .map_err(|_| "Invalid month")?;
-        let mut year: u32 = date_components[2].parse()
-            .map_err(|_| "Invalid year")?;
-
-        let hour: u32 = time_components[0].parse()
-            .map_err(|_| "Invalid hour")?;
-        let minute: u32 = time_components[1].parse()
-            .map_err(|_| "Invalid minute")?;
-        let second: u32 = time_components[2].parse()
-            .map_err(|_| "Invalid second")?;
-
-        // Handle 2-digit years (assume 20xx for yy < 50, 19xx for yy >= 50)
-        if year < 100 {
-            year = if year < 50 { 2000 + year } else { 1900 + year };
-        }
+fn new_schedule(id: String, request: ScheduleRequest, now: u64) -> Result<ScheduledWorkflow, String> {
+    validate_spec(&request.spec)?;
+    let next_execution = first_fire_time(&request.spec, now)?;
 
-        // Validate ranges
-        if month < 1 || month > 12 {
-            return Err("Month must be between 1 and 12".to_string());
-        }
-        if day < 1 || day > 31 {
-            return Err("Day must be between 1 and 31".to_string());
+    if request.max_runs == Some(0) {
+        return Err("max_runs must be at least 1".to_string());
+    }
+    if request.end_at.is_some_and(|end_at| end_at < next_execution) {
+        return Err("Schedule ends before its first run".to_string());
+    }
+
+    Ok(ScheduledWorkflow {
+        id,
+        workflow_id: request.workflow_id,
+        cron_expression: match &request.spec {
+            ScheduleSpec::Cron { expression, .. } => expression.clone(),
+            _ => String::new(),
+        },
+        next_execution,
+        active: true,
+        timer_id: None,
+        spec: Some(request.spec),
+        missed_run_policy: Some(request.missed_run_policy.unwrap_or_default()),
+        max_runs: request.max_runs,
+        end_at: request.end_at,
+        run_count: Some(0),
+        last_run_at: None,
+        history: Some(Vec::new()),
+        created_at: Some(now),
+    })
+}
+
+fn validate_spec(spec: &ScheduleSpec) -> Result<(), String> {
+    match spec {
+        ScheduleSpec::Once { .. } => Ok(()),
+        ScheduleSpec::Interval { seconds, .. } if *seconds < MIN_INTERVAL_SECONDS => {
+            Err(format!("Interval must be at least {} seconds", MIN_INTERVAL_SECONDS))
         }
-        if hour > 23 {
-            return Err("Hour must be between 0 and 23".to_string());
+        ScheduleSpec::Interval { .. } => Ok(()),
+        ScheduleSpec::Cron { expression, timezone } => cron::validate_expression(expression, timezone.as_deref()),
+    }
+}
+
+fn first_fire_time(spec: &ScheduleSpec, now: u64) -> Result<u64, String> {
+    match spec {
+        ScheduleSpec::Once { at } if *at <= now => Err("Scheduled time is in the past".to_string()),
+        ScheduleSpec::Once { at } => Ok(*at),
+        ScheduleSpec::Interval { start_at: Some(start), .. } if *start <= now => {
+            Err("Interval start time is in the past".to_string())
         }
-        if minute > 59 {
-            return Err("Minute must be between 0 and 59".to_string());
+        ScheduleSpec::Interval { start_at: Some(start), .. } => Ok(*start),
+        ScheduleSpec::Interval { seconds, start_at: None } => Ok(now + seconds * NANOS_PER_SECOND),
+        ScheduleSpec::Cron { expression, timezone } => cron::next_fire_time(expression, timezone.as_deref(), now),
+    }
+}
+
+/// First fire time after `after`. Intervals stay aligned to `previous` so a late
+/// timer does not shift the cadence.
+fn next_fire_after(spec: &ScheduleSpec, previous: u64, after: u64) -> Option<u64> {
+    match spec {
+        ScheduleSpec::Once { .. } => None,
+        ScheduleSpec::Interval { seconds, .. } => {
+            let step = seconds.saturating_mul(NANOS_PER_SECOND).max(1);
+            let steps = after.saturating_sub(previous) / step + 1;
+            Some(previous.saturating_add(steps.saturating_mul(step)))
         }
-        if second > 59 {
-            return Err("Second must be between 0 and 59".to_string());
+        ScheduleSpec::Cron { expression, timezone } => {
+            cron::next_fire_time(expression, timezone.as_deref(), after).ok()
         }
+    }
+}
 
-        // Convert to timestamp (simplified - in production would use proper datetime library)
-        let timestamp = self.datetime_to_timestamp(year, month, day, hour, minute, second)?;
-        
-        // Convert to nanoseconds (IC time format)
-        Ok(timestamp * 1_000_000_000)
-    }
-
-    fn datetime_to_timestamp(&self, year: u32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Result<u64, String> {
-        // Simplified timestamp calculation
-        // In production, would use a proper datetime library like chrono
-        
-        // Days since Unix epoch (1970-01-01)
-        let mut days = 0u64;
-        
-        // Add days for years
-        for y in 1970..year {
-            if self.is_leap_year(y) {
-                days += 366;
-            } else {
-                days += 365;
-            }
+// =============================================================================
+// FIRING
+// =============================================================================
+
+#[derive(Debug, PartialEq)]
+struct RunPlan {
+    run: Vec<u64>,
+    skipped: Vec<u64>,
+    last_due: u64,
+}
+
+/// Decide which due fire times to run at `now` according to the missed-run policy.
+fn plan_runs(schedule: &ScheduledWorkflow, now: u64) -> RunPlan {
+    let spec = schedule.spec();
+    let mut due = vec![schedule.next_execution];
+    while due.len() < MAX_MISSED_SCAN {
+        let previous = due[due.len() - 1];
+        match next_fire_after(&spec, previous, previous) {
+            Some(next) if next <= now => due.push(next),
+            _ => break,
         }
-        
-        // Add days for months in current year
-        let days_in_month = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
-        for m in 1..month {
-            days += days_in_month[(m - 1) as usize] as u64;
-            if m == 2 && self.is_leap_year(year) {
-                days += 1; // February has 29 days in leap years
+    }
+    if let Some(end_at) = schedule.end_at {
+        due.retain(|at| *at <= end_at);
+    }
+    let last_due = due.last().copied().unwrap_or(schedule.next_execution);
+
+    let missed = now.saturating_sub(schedule.next_execution) > MISSED_RUN_GRACE_NS;
+    let (mut run, mut skipped) = if !missed {
+        (due, Vec::new())
+    } else {
+        match schedule.missed_run_policy() {
+            MissedRunPolicy::Skip => (Vec::new(), due),
+            MissedRunPolicy::RunOnce => {
+                let latest = due.split_off(due.len().saturating_sub(1));
+                (latest, due)
+            }
+            MissedRunPolicy::CatchUp => {
+                let recent = due.split_off(due.len().saturating_sub(MAX_CATCH_UP_RUNS));
+                (recent, due)
             }
         }
-        
-        // Add remaining days
-        days += (day - 1) as u64;
-        
-        // Convert to seconds
-        let timestamp = days * 24 * 60 * 60 + 
-                       hour as u64 * 60 * 60 + 
-                       minute as u64 * 60 + 
-                       second as u64;
-        
-        Ok(timestamp)
-    }
-
-    fn is_leap_year(&self, year: u32) -> bool {
-        (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
-    }
-
-    // =============================================================================
-    // TIMER MANAGEMENT
-    // =============================================================================
-
-    fn set_execution_timer(&self, schedule: &ScheduleInfo) -> Result<u64, String> {
-        let current_time = time();
-        if schedule.next_execution <= current_time {
-            return Err("Scheduled time is in the past".to_string());
+    };
+
+    if let Some(max_runs) = schedule.max_runs {
+        let remaining = max_runs.saturating_sub(schedule.run_count.unwrap_or(0)) as usize;
+        if run.len() > remaining {
+            skipped.extend(run.split_off(remaining));
         }
-        
-        let delay_ns = schedule.next_execution - current_time;
-        let delay_seconds = delay_ns / 1_000_000_000;
-        
-        // Set timer (this is a simplified version - actual implementation would call workflow execution)
-        let _timer_id = set_timer(
-            std::time::Duration::from_secs(delay_seconds),
-            || {
-                // This would trigger workflow execution
-            }
-        );
-        
-        // Store timer_id as a simple counter for demo purposes
-        // In production, you'd need a proper mapping system
-        Ok(delay_seconds) // Return a unique identifier
-    }
-
-    fn set_interval_timer(&self, schedule: &ScheduleInfo) -> Result<u64, String> {
-        let interval_seconds = schedule.schedule_config.interval_seconds
-            .ok_or("Interval not specified for recurring schedule")?;
-        
-        let _timer_id = set_timer_interval(
-            std::time::Duration::from_secs(interval_seconds),
-            || {
-                // This would trigger workflow execution
-            }
-        );
-        
-        Ok(interval_seconds) // Return a unique identifier
+    }
+    skipped.sort_unstable();
+
+    RunPlan { run, skipped, last_due }
+}
+
+fn fire_schedule(schedule_id: &str) {
+    let Some(mut schedule) = storage::get_scheduled_workflow(schedule_id) else {
+        return;
+    };
+    if !schedule.active {
+        return;
+    }
+
+    let now = time();
+    let plan = plan_runs(&schedule, now);
+
+    for scheduled_for in &plan.skipped {
+        record_run(&mut schedule, ScheduleRun {
+            scheduled_for: *scheduled_for,
+            fired_at: now,
+            status: ScheduleRunStatus::Skipped,
+            execution_id: None,
+            error: None,
+        });
     }
 
-    // =============================================================================
-    // CRON SUPPORT
-    // =============================================================================
+    for scheduled_for in plan.run {
+        let trigger_data = schedule_trigger_data(&schedule, scheduled_for, now);
+        let run = match trigger_execution(schedule.workflow_id.clone(), Some(trigger_data)) {
+            Ok(execution_id) => ScheduleRun {
+                scheduled_for,
+                fired_at: now,
+                status: ScheduleRunStatus::Started,
+                execution_id: Some(execution_id),
+                error: None,
+            },
+            Err(error) => ScheduleRun {
+                scheduled_for,
+                fired_at: now,
+                status: ScheduleRunStatus::Failed,
+                execution_id: None,
+                error: Some(error),
+            },
+        };
+        schedule.run_count = Some(schedule.run_count.unwrap_or(0) + 1);
+        schedule.last_run_at = Some(now);
+        record_run(&mut schedule, run);
+    }
 
-    fn validate_cron_expression(&self, cron_expr: &str) -> Result<(), String> {
-        let parts: Vec<&str> = cron_expr.split_whitespace().collect();
-        if parts.len() != 5 {
-            return Err("Cron expression must have 5 fields: minute hour day month weekday".to_string());
+    advance_schedule(&mut schedule, plan.last_due, now);
+    storage::insert_scheduled_workflow(schedule.id.clone(), schedule.clone());
+    arm_timer(&schedule);
+}
+
+/// Move the schedule to its next fire time, or deactivate it when there is none.
+fn advance_schedule(schedule: &mut ScheduledWorkflow, last_due: u64, now: u64) {
+    let next = if schedule.is_complete() {
+        None
+    } else {
+        next_fire_after(&schedule.spec(), last_due, now)
+    };
+
+    match next.filter(|next| schedule.end_at.is_none_or(|end_at| *next <= end_at)) {
+        Some(next) => schedule.next_execution = next,
+        None => {
+            schedule.next_execution = 0;
+            schedule.active = false;
         }
-        
-        // Basic validation (in production, would use a proper cron library)
-        Ok(())
     }
+}
 
-    fn calculate_next_cron_execution(&self, _cron_expr: &str) -> Result<u64, String> {
-        // Simplified cron calculation - in production would use proper cron library
-        let current_time = time();
-        Ok(current_time + 3600 * 1_000_000_000) // Next hour as example
+fn record_run(schedule: &mut ScheduledWorkflow, run: ScheduleRun) {
+    let history = schedule.history.get_or_insert_with(Vec::new);
+    history.push(run);
+    if history.len() > MAX_SCHEDULE_HISTORY {
+        let excess = history.len() - MAX_SCHEDULE_HISTORY;
+        history.drain(..excess);
     }
+}
 
-    // =============================================================================
-    // SCHEDULE MANAGEMENT
-    // =============================================================================
+fn schedule_trigger_data(schedule: &ScheduledWorkflow, scheduled_for: u64, now: u64) -> HashMap<String, ConfigValue> {
+    let mut data = HashMap::new();
+    data.insert("schedule_id".to_string(), ConfigValue::String(schedule.id.clone()));
+    data.insert("scheduled_for_ms".to_string(), ConfigValue::Number((scheduled_for / 1_000_000) as f64));
+    data.insert("missed".to_string(), ConfigValue::Boolean(now.saturating_sub(scheduled_for) > MISSED_RUN_GRACE_NS));
+    data
+}
 
-    pub fn list_active_schedules(&self) -> Vec<ScheduleInfo> {
-        self.active_schedules.values()
-            .filter(|schedule| schedule.is_active)
-            .cloned()
-            .collect()
+fn arm_timer(schedule: &ScheduledWorkflow) {
+    clear_schedule_timer(&schedule.id);
+    if !schedule.active {
+        return;
     }
 
-    pub fn get_schedule(&self, schedule_id: &str) -> Option<&ScheduleInfo> {
-        self.active_schedules.get(schedule_id)
+    let delay = Duration::from_nanos(schedule.next_execution.saturating_sub(time()));
+    let schedule_id = schedule.id.clone();
+    let timer_id = set_timer(delay, move || {
+        SCHEDULE_TIMERS.with(|timers| timers.borrow_mut().remove(&schedule_id));
+        fire_schedule(&schedule_id);
+    });
+    SCHEDULE_TIMERS.with(|timers| timers.borrow_mut().insert(schedule.id.clone(), timer_id));
+}
+
+fn clear_schedule_timer(schedule_id: &str) {
+    if let Some(timer_id) = SCHEDULE_TIMERS.with(|timers| timers.borrow_mut().remove(schedule_id)) {
+        clear_timer(timer_id);
     }
+}
 
-    pub fn cancel_schedule(&mut self, schedule_id: &str) -> Result<String, String> {
-        if let Some(schedule) = self.active_schedules.get_mut(schedule_id) {
-            schedule.is_active = false;
-            
-            // Cancel the timer if it exists - simplified for demo
-            if let Some(_timer_id) = schedule.timer_id {
-                // In production, would properly cancel the timer using the actual TimerId
-                // clear_timer(actual_timer_id);
-            }
-            
-            Ok(format!("Schedule {} cancelled successfully", schedule_id))
-        } else {
-            Err(format!("Schedule {} not found", schedule_id))
+// =============================================================================
+// UPGRADES
+// =============================================================================
+
+/// Re-arm every active schedule (timers do not survive upgrades) after folding in
+/// schedules created by the older persistent-timer and heartbeat schedulers.
+pub fn restore_schedules() {
+    migrate_legacy_schedules(time());
+
+    // Every stored schedule, not just the ones the (upgrade-time) caller may list
+    for schedule in storage::list_scheduled_workflows() {
+        if schedule.active {
+            arm_timer(&schedule);
         }
     }
+}
 
-    pub fn update_schedule(&mut self, schedule_id: &str, new_datetime: &str) -> Result<ScheduleResult, String> {
-        // Parse the datetime first to avoid borrow checker issues
-        let new_execution_time = self.parse_universal_datetime(new_datetime)?;
-        
-        if let Some(schedule) = self.active_schedules.get_mut(schedule_id) {
-            // Cancel existing timer - simplified for demo
-            if let Some(_timer_id) = schedule.timer_id {
-                // In production, would properly cancel the timer using the actual TimerId
-                // clear_timer(actual_timer_id);
-            }
-            
-            // Update schedule
-            schedule.next_execution = new_execution_time;
-            schedule.schedule_config.datetime_string = Some(new_datetime.to_string());
-            
-            // Calculate new timer delay for demo purposes
-            let current_time = time();
-            let delay_seconds = if new_execution_time > current_time {
-                (new_execution_time - current_time) / 1_000_000_000
-            } else {
-                1 // Default to 1 second if in the past
-            };
-            
-            schedule.timer_id = Some(delay_seconds);
-            
-            Ok(ScheduleResult {
-                success: true,
-                message: format!("Schedule {} updated to {}", schedule_id, new_datetime),
-                schedule_id: Some(schedule_id.to_string()),
-                next_execution: Some(new_execution_time),
-            })
+fn migrate_legacy_schedules(now: u64) {
+    for (key, legacy) in storage::list_all_scheduled_executions() {
+        storage::remove_scheduled_execution(&key);
+        let spec = match legacy.schedule_type {
+            ScheduleType::Once => ScheduleSpec::Once { at: legacy.next_execution },
+            ScheduleType::Interval { seconds } => ScheduleSpec::Interval { seconds, start_at: Some(legacy.next_execution) },
+            ScheduleType::Cron { expression } => ScheduleSpec::Cron { expression, timezone: None },
+            // Heartbeat entries were never fired by anything
+            ScheduleType::Heartbeat => continue,
+        };
+        let schedule = migrated_schedule(generate_id(), legacy.workflow_id, spec, legacy.next_execution, now);
+        storage::insert_scheduled_workflow(schedule.id.clone(), schedule);
+    }
+
+    let mut state = storage::get_workflow_state();
+    if !state.scheduled_executions.is_empty() {
+        for (at, workflow_id) in std::mem::take(&mut state.scheduled_executions) {
+            let schedule = migrated_schedule(generate_id(), workflow_id, ScheduleSpec::Once { at }, at, now);
+            storage::insert_scheduled_workflow(schedule.id.clone(), schedule);
+        }
+        storage::update_workflow_state(state);
+    }
+}
+
+fn migrated_schedule(id: String, workflow_id: String, spec: ScheduleSpec, next_execution: u64, now: u64) -> ScheduledWorkflow {
+    ScheduledWorkflow {
+        id,
+        workflow_id,
+        cron_expression: match &spec {
+            ScheduleSpec::Cron { expression, .. } => expression.clone(),
+            _ => String::new(),
+        },
+        next_execution,
+        active: true,
+        timer_id: None,
+        spec: Some(spec),
+        missed_run_policy: Some(MissedRunPolicy::RunOnce),
+        max_runs: None,
+        end_at: None,
+        run_count: Some(0),
+        last_run_at: None,
+        history: Some(Vec::new()),
+        created_at: Some(now),
+    }
+}
+
+// =============================================================================
+// DATE/TIME PARSING
+// =============================================================================
+
+/// Parse a universal-format date string as wall-clock time in `timezone` (UTC by default).
+pub fn parse_universal_datetime(datetime_str: &str, timezone: Option<&str>) -> Result<u64, String> {
+    // Support formats:
+    // dd/mm/yy hh:mm:ss
+    // dd/mm/yyyy hh:mm:ss
+    // dd-mm-yy hh:mm:ss
+    // dd-mm-yyyy hh:mm:ss
+    // yyyy-mm-dd hh:mm:ss (ISO format)
+
+    let datetime_str = datetime_str.trim();
+
+    // Split date and time parts
+    let parts: Vec<&str> = datetime_str.split_whitespace().collect();
+    if parts.len() != 2 {
+        return Err("Invalid datetime format. Expected: 'dd/mm/yy hh:mm:ss' or 'dd/mm/yyyy hh:mm:ss'".to_string());
+    }
+
+    let date_part = parts[0];
+    let time_part = parts[1];
+
+    // Parse date part (handle different separators)
+    let date_components = if date_part.contains('/') {
+        date_part.split('/').collect::<Vec<&str>>()
+    } else if date_part.contains('-') {
+        // Check if it's ISO format (yyyy-mm-dd) or dd-mm-yy
+        let dash_parts = date_part.split('-').collect::<Vec<&str>>();
+        if dash_parts.len() == 3 && dash_parts[0].len() == 4 {
+            // ISO format: yyyy-mm-dd
+            vec![dash_parts[2], dash_parts[1], dash_parts[0]] // Reorder to dd-mm-yyyy
         } else {
-            Err(format!("Schedule {} not found", schedule_id))
+            dash_parts
         }
+    } else {
+        return Err("Invalid date separator. Use '/' or '-'".to_string());
+    };
+
+    if date_components.len() != 3 {
+        return Err("Invalid date format. Expected: dd/mm/yy or dd/mm/yyyy".to_string());
+    }
+
+    // Parse time part
+    let time_components: Vec<&str> = time_part.split(':').collect();
+    if time_components.len() != 3 {
+        return Err("Invalid time format. Expected: hh:mm:ss".to_string());
+    }
+
+    // Extract components
+    let day: u32 = date_components[0].parse()
+        .map_err(|_| "Invalid day")?;
+    let month: u32 = date_components[1].parse()
+        .map_err(|_| "Invalid month")?;
+    let mut year: i64 = date_components[2].parse()
+        .map_err(|_| "Invalid year")?;
+
+    let hour: i64 = time_components[0].parse()
+        .map_err(|_| "Invalid hour")?;
+    let minute: i64 = time_components[1].parse()
+        .map_err(|_| "Invalid minute")?;
+    let second: i64 = time_components[2].parse()
+        .map_err(|_| "Invalid second")?;
+
+    // Handle 2-digit years (assume 20xx for yy < 50, 19xx for yy >= 50)
+    if year < 100 {
+        year = if year < 50 { 2000 + year } else { 1900 + year };
+    }
+
+    // Validate ranges
+    if !(1..=12).contains(&month) {
+        return Err("Month must be between 1 and 12".to_string());
+    }
+    let days = cron::days_from_civil(year, month, day);
+    if day == 0 || cron::civil_from_days(days) != (year, month, day) {
+        return Err(format!("Day {} does not exist in {:02}/{}", day, month, year));
+    }
+    if hour > 23 {
+        return Err("Hour must be between 0 and 23".to_string());
+    }
+    if minute > 59 {
+        return Err("Minute must be between 0 and 59".to_string());
+    }
+    if second > 59 {
+        return Err("Second must be between 0 and 59".to_string());
     }
 
-    pub fn get_next_executions(&self, limit: usize) -> Vec<(String, u64, String)> {
-        let mut schedules: Vec<_> = self.active_schedules.values()
-            .filter(|s| s.is_active)
-            .map(|s| (s.id.clone(), s.next_execution, s.workflow_id.clone()))
-            .collect();
-        
-        schedules.sort_by_key(|&(_, time, _)| time);
-        schedules.truncate(limit);
-        schedules
-    }
-
-    // =============================================================================
-    // UTILITY FUNCTIONS
-    // =============================================================================
-
-    pub fn format_timestamp_to_universal(&self, timestamp_ns: u64) -> String {
-        // Convert nanoseconds back to dd/mm/yyyy hh:mm:ss format
-        // Simplified implementation - in production would use proper datetime library
-        let timestamp_s = timestamp_ns / 1_000_000_000;
-        
-        // Basic conversion (placeholder)
-        let days_since_epoch = timestamp_s / (24 * 60 * 60);
-        let remaining_seconds = timestamp_s % (24 * 60 * 60);
-        
-        let hours = remaining_seconds / 3600;
-        let minutes = (remaining_seconds % 3600) / 60;
-        let seconds = remaining_seconds % 60;
-        
-        // Simplified date calculation (would be more complex in reality)
-        let year = 1970 + (days_since_epoch / 365) as u32;
-        let month = 1; // Simplified
-        let day = 1; // Simplified
-        
-        format!("{:02}/{:02}/{} {:02}:{:02}:{:02}", day, month, year, hours, minutes, seconds)
+    let zone = match timezone {
+        Some(name) => Timezone::parse(name)?,
+        None => Timezone::utc(),
+    };
+    let utc = zone.local_to_utc(days * 86_400 + hour * 3_600 + minute * 60 + second);
+    if utc < 0 {
+        return Err("Dates before 1970 are not supported".to_string());
     }
+
+    // Convert to nanoseconds (IC time format)
+    Ok(utc as u64 * NANOS_PER_SECOND)
+}
+
+/// Format nanoseconds since the epoch as dd/mm/yyyy hh:mm:ss (UTC).
+pub fn format_timestamp_to_universal(timestamp_ns: u64) -> String {
+    let timestamp_s = (timestamp_ns / NANOS_PER_SECOND) as i64;
+    let (year, month, day) = cron::civil_from_days(timestamp_s.div_euclid(86_400));
+    let remaining_seconds = timestamp_s.rem_euclid(86_400);
+
+    let hours = remaining_seconds / 3600;
+    let minutes = (remaining_seconds % 3600) / 60;
+    let seconds = remaining_seconds % 60;
+
+    format!("{:02}/{:02}/{} {:02}:{:02}:{:02}", day, month, year, hours, minutes, seconds)
 }
 
 // =============================================================================
 // EXAMPLES AND USAGE PATTERNS
 // =============================================================================
 
-impl SchedulerService {
-    pub fn example_usage() -> String {
-        format!(r#"
-SCHEDULER SERVICE EXAMPLES:
+pub fn example_usage() -> String {
+    r#"
+SCHEDULER EXAMPLES:
 
 1. One-time execution:
-   // Logging temporarily disabled
-   // Logging temporarily disabled
-
-2. Recurring schedule:
-   Start: "26/08/24 10:00:00"
-   Interval: 3600 seconds (every hour)
-   
-3. Cron expressions:
-   // Logging temporarily disabled
-   // Logging temporarily disabled
-
-4. Supported formats:
+   create_schedule({ workflow_id; spec = variant { Once = record { at = <ns> } } })
+   Convert "26/08/24 10:00:00" to nanoseconds with parse_schedule_datetime.
+
+2. Interval schedule:
+   spec = variant { Interval = record { seconds = 3600; start_at = null } }
+
+3. Cron schedule:
+   spec = variant { Cron = record { expression = "0 9 * * mon-fri"; timezone = opt "Europe/London" } }
+   Aliases: @hourly, @daily, @weekly, @monthly, @yearly
+
+4. Missed runs (paused schedules, upgrades):
+   Skip     - drop every missed run
+   RunOnce  - run once for the latest missed time (default)
+   CatchUp  - run each missed time, up to 10
+
+5. Supported date formats:
    - dd/mm/yy hh:mm:ss
    - dd/mm/yyyy hh:mm:ss
    - dd-mm-yy hh:mm:ss
    - yyyy-mm-dd hh:mm:ss (ISO)
 
-5. Timezone support:
-   - UTC (default)
+6. Timezone support:
+   - UTC (default) and offsets such as +05:30 or UTC-8
    - America/New_York
    - Europe/London
    - Asia/Tokyo
-        "#)
+    "#.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: u64 = 60 * NANOS_PER_SECOND;
+    const NOW: u64 = 1_800_000_000 * NANOS_PER_SECOND;
+
+    fn request(spec: ScheduleSpec, policy: MissedRunPolicy) -> ScheduleRequest {
+        ScheduleRequest {
+            workflow_id: "wf".to_string(),
+            spec,
+            missed_run_policy: Some(policy),
+            max_runs: None,
+            end_at: None,
+        }
     }
-}
\ No newline at end of file
+
+    fn interval_schedule(policy: MissedRunPolicy) -> ScheduledWorkflow {
+        let spec = ScheduleSpec::Interval { seconds: 600, start_at: None };
+        new_schedule("s1".to_string(), request(spec, policy), NOW).unwrap()
+    }
+
+    #[test]
+    fn new_schedules_validate_their_timing() {
+        let once = |at| request(ScheduleSpec::Once { at }, MissedRunPolicy::RunOnce);
+        assert!(new_schedule("s".to_string(), once(NOW - 1), NOW).is_err());
+        assert_eq!(new_schedule("s".to_string(), once(NOW + MINUTE), NOW).unwrap().next_execution, NOW + MINUTE);
+
+        let short = request(ScheduleSpec::Interval { seconds: 5, start_at: None }, MissedRunPolicy::RunOnce);
+        assert!(new_schedule("s".to_string(), short, NOW).is_err());
+
+        let bad_cron = request(ScheduleSpec::Cron { expression: "0 0 31 2 *".to_string(), timezone: None }, MissedRunPolicy::RunOnce);
+        assert!(new_schedule("s".to_string(), bad_cron, NOW).is_err());
+
+        let mut ends_early = request(ScheduleSpec::Interval { seconds: 600, start_at: None }, MissedRunPolicy::RunOnce);
+        ends_early.end_at = Some(NOW + MINUTE);
+        assert!(new_schedule("s".to_string(), ends_early, NOW).is_err());
+    }
+
+    #[test]
+    fn on_time_timer_runs_the_due_fire_time() {
+        let schedule = interval_schedule(MissedRunPolicy::Skip);
+        let at = schedule.next_execution;
+        let plan = plan_runs(&schedule, at + NANOS_PER_SECOND);
+        assert_eq!(plan, RunPlan { run: vec![at], skipped: vec![], last_due: at });
+    }
+
+    #[test]
+    fn missed_run_policies() {
+        // Woken 35 minutes late: fire times at +0, +10, +20 and +30 minutes have passed
+        let first = interval_schedule(MissedRunPolicy::Skip).next_execution;
+        let due: Vec<u64> = (0..4).map(|i| first + i * 10 * MINUTE).collect();
+        let now = first + 35 * MINUTE;
+
+        let skip = plan_runs(&interval_schedule(MissedRunPolicy::Skip), now);
+        assert_eq!(skip.run, Vec::<u64>::new());
+        assert_eq!(skip.skipped, due);
+
+        let once = plan_runs(&interval_schedule(MissedRunPolicy::RunOnce), now);
+        assert_eq!(once.run, vec![due[3]]);
+        assert_eq!(once.skipped, due[..3].to_vec());
+
+        let catch_up = plan_runs(&interval_schedule(MissedRunPolicy::CatchUp), now);
+        assert_eq!(catch_up.run, due);
+        assert_eq!(catch_up.last_due, due[3]);
+
+        let mut limited = interval_schedule(MissedRunPolicy::CatchUp);
+        limited.max_runs = Some(3);
+        limited.run_count = Some(1);
+        assert_eq!(plan_runs(&limited, now).run, due[..2].to_vec());
+    }
+
+    #[test]
+    fn advancing_keeps_interval_cadence_and_completes() {
+        let mut schedule = interval_schedule(MissedRunPolicy::RunOnce);
+        let first = schedule.next_execution;
+        advance_schedule(&mut schedule, first, first + 3 * MINUTE);
+        assert_eq!(schedule.next_execution, first + 10 * MINUTE);
+        assert!(schedule.active);
+
+        schedule.end_at = Some(first + 15 * MINUTE);
+        advance_schedule(&mut schedule, first + 10 * MINUTE, first + 10 * MINUTE);
+        assert!(!schedule.active);
+        assert!(schedule.is_complete());
+
+        let mut once = new_schedule("s2".to_string(), request(ScheduleSpec::Once { at: NOW + MINUTE }, MissedRunPolicy::RunOnce), NOW).unwrap();
+        once.run_count = Some(1);
+        advance_schedule(&mut once, NOW + MINUTE, NOW + MINUTE);
+        assert!(!once.active);
+        assert!(once.is_complete());
+    }
+
+    #[test]
+    fn history_keeps_the_latest_runs() {
+        let mut schedule = interval_schedule(MissedRunPolicy::RunOnce);
+        for i in 0..25u64 {
+            record_run(&mut schedule, ScheduleRun {
+                scheduled_for: i,
+                fired_at: i,
+                status: ScheduleRunStatus::Started,
+                execution_id: None,
+                error: None,
+            });
+        }
+        let history = schedule.history.unwrap();
+        assert_eq!(history.len(), MAX_SCHEDULE_HISTORY);
+        assert_eq!(history[0].scheduled_for, 5);
+    }
+
+    #[test]
+    fn legacy_cron_records_keep_working() {
+        let legacy = ScheduledWorkflow {
+            id: "old".to_string(),
+            workflow_id: "wf".to_string(),
+            cron_expression: "*/5 * * * *".to_string(),
+            next_execution: NOW,
+            active: true,
+            ..ScheduledWorkflow::default()
+        };
+        assert_eq!(legacy.spec(), ScheduleSpec::Cron { expression: "*/5 * * * *".to_string(), timezone: None });
+        assert_eq!(legacy.missed_run_policy(), MissedRunPolicy::RunOnce);
+        assert!(!legacy.is_complete());
+    }
+
+    #[test]
+    fn universal_dates_respect_timezones() {
+        let utc = parse_universal_datetime("26/08/24 10:00:00", None).unwrap();
+        assert_eq!(format_timestamp_to_universal(utc), "26/08/2024 10:00:00");
+        assert_eq!(parse_universal_datetime("2024-08-26 10:00:00", None).unwrap(), utc);
+        assert_eq!(parse_universal_datetime("26/08/2024 12:00:00", Some("Europe/Berlin")).unwrap(), utc);
+        assert!(parse_universal_datetime("31/02/2024 10:00:00", None).is_err());
+    }
+}
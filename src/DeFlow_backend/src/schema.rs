@@ -0,0 +1,404 @@
+// Schema versions for values kept in stable memory.
+//
+// Every stored value is written as a header (`DFS` followed by a little-endian u16
+// schema version) and the candid encoding of the value. Values written before the
+// header existed start directly with candid's `DIDL` magic and are read as version 0.
+//
+// To change the layout of a stored type, register a migration from the collection's
+// current version below; the current version is one past the last registered
+// migration. Old records are upgraded when read, and `migrate_stored_records`
+// rewrites them at the current version in `post_upgrade`.
+
+use crate::types::{MigrationStep, SchemaMigrationReport, StoreSchemaStatus};
+use candid::{CandidType, Decode, Encode};
+use ic_cdk::{api, caller, query};
+use ic_stable_structures::{Memory, StableBTreeMap, Storable};
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+
+pub const HEADER_MAGIC: &[u8; 3] = b"DFS";
+pub const HEADER_LEN: u32 = 5;
+const CANDID_MAGIC: &[u8; 4] = b"DIDL";
+
+// storage.rs
+pub const WORKFLOWS: &str = "workflows";
+pub const EXECUTIONS: &str = "executions";
+pub const NODE_DEFINITIONS: &str = "node_definitions";
+pub const EVENT_LISTENERS: &str = "event_listeners";
+pub const SCHEDULED_WORKFLOWS: &str = "scheduled_workflows";
+pub const RETRY_POLICIES: &str = "retry_policies";
+pub const WORKFLOW_STATE: &str = "workflow_state";
+pub const SCHEDULED_EXECUTIONS: &str = "scheduled_executions";
+pub const WEBHOOKS: &str = "webhooks";
+pub const CHAIN_WATCHES: &str = "chain_watches";
+pub const WORKFLOW_ACLS: &str = "workflow_acls";
+pub const ACL_AUDIT_LOG: &str = "acl_audit_log";
+pub const WORKFLOW_REVISIONS: &str = "workflow_revisions";
+pub const CREDENTIALS: &str = "credentials";
+pub const VAULT_KEYS: &str = "vault_keys";
+pub const OAUTH_CLIENTS: &str = "oauth_clients";
+// stable_user_storage.rs
+pub const USER_PROFILES: &str = "user_profiles";
+pub const USER_SUBSCRIPTIONS: &str = "user_subscriptions";
+pub const INTEGRATION_CREDENTIALS: &str = "integration_credentials";
+pub const OAUTH_TOKENS: &str = "oauth_tokens";
+pub const API_CONNECTIONS: &str = "api_connections";
+pub const WORKFLOW_TEMPLATES: &str = "workflow_templates";
+pub const USER_SETTINGS: &str = "user_settings";
+// defi_storage.rs
+pub const STRATEGIES: &str = "strategies";
+pub const STRATEGY_USERS: &str = "strategy_users";
+pub const PORTFOLIOS: &str = "portfolios";
+pub const PRICE_ALERTS: &str = "price_alerts";
+
+/// Rewrites the candid payload of a record from `from_version` to the next version.
+pub type MigrateFn = fn(Vec<u8>) -> Result<Vec<u8>, String>;
+
+pub struct Migration {
+    pub collection: &'static str,
+    pub from_version: u16,
+    pub description: &'static str,
+    pub migrate: MigrateFn,
+}
+
+const ADD_HEADER: &str = "Add the schema version header";
+
+fn unchanged(payload: Vec<u8>) -> Result<Vec<u8>, String> {
+    Ok(payload)
+}
+
+const fn add_header(collection: &'static str) -> Migration {
+    Migration { collection, from_version: 0, description: ADD_HEADER, migrate: unchanged }
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    add_header(WORKFLOWS),
+    add_header(EXECUTIONS),
+    add_header(NODE_DEFINITIONS),
+    add_header(EVENT_LISTENERS),
+    add_header(SCHEDULED_WORKFLOWS),
+    add_header(RETRY_POLICIES),
+    add_header(WORKFLOW_STATE),
+    add_header(SCHEDULED_EXECUTIONS),
+    add_header(WEBHOOKS),
+    add_header(CHAIN_WATCHES),
+    add_header(USER_PROFILES),
+    add_header(USER_SUBSCRIPTIONS),
+    add_header(INTEGRATION_CREDENTIALS),
+    add_header(OAUTH_TOKENS),
+    add_header(API_CONNECTIONS),
+    add_header(WORKFLOW_TEMPLATES),
+    add_header(USER_SETTINGS),
+    Migration {
+        collection: STRATEGIES,
+        from_version: 0,
+        description: "Unwrap the V1 envelope and add the schema version header",
+        migrate: crate::defi_storage::unwrap_legacy_envelope::<crate::defi_storage::StrategyRecord>,
+    },
+    Migration {
+        collection: STRATEGY_USERS,
+        from_version: 0,
+        description: "Unwrap the V1 envelope and add the schema version header",
+        migrate: crate::defi_storage::unwrap_legacy_envelope::<crate::defi_storage::StrategyUserRecord>,
+    },
+    Migration {
+        collection: PORTFOLIOS,
+        from_version: 0,
+        description: "Unwrap the V1 envelope and add the schema version header",
+        migrate: crate::defi_storage::unwrap_legacy_envelope::<crate::defi_storage::PortfolioRecord>,
+    },
+    Migration {
+        collection: PRICE_ALERTS,
+        from_version: 0,
+        description: "Unwrap the V1 envelope and add the schema version header",
+        migrate: crate::defi_storage::unwrap_legacy_envelope::<crate::defi::price_alert_service::PriceAlert>,
+    },
+];
+
+/// A type stored in stable memory under a versioned collection.
+pub trait Versioned {
+    const COLLECTION: &'static str;
+}
+
+macro_rules! versioned {
+    ($($ty:ty => $collection:expr),* $(,)?) => {$(
+        impl $crate::schema::Versioned for $ty {
+            const COLLECTION: &'static str = $collection;
+        }
+    )*};
+}
+pub(crate) use versioned;
+
+pub fn current_version(collection: &str) -> u16 {
+    MIGRATIONS.iter()
+        .filter(|migration| migration.collection == collection)
+        .map(|migration| migration.from_version + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// How a record decoded: the version it was stored under, or that it could not be read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Observed {
+    Version(u16),
+    Unreadable,
+}
+
+thread_local! {
+    // Outcomes of `decode` calls made while `observe` is running
+    static OBSERVED: RefCell<Option<Vec<Observed>>> = const { RefCell::new(None) };
+}
+
+fn observe<R>(f: impl FnOnce() -> R) -> (R, Vec<Observed>) {
+    OBSERVED.with(|observed| *observed.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let seen = OBSERVED.with(|observed| observed.borrow_mut().take()).unwrap_or_default();
+    (result, seen)
+}
+
+fn record(outcome: Observed) {
+    OBSERVED.with(|observed| {
+        if let Some(seen) = observed.borrow_mut().as_mut() {
+            seen.push(outcome);
+        }
+    });
+}
+
+pub fn encode<T: CandidType + Versioned>(value: &T) -> Result<Vec<u8>, String> {
+    let payload = Encode!(value).map_err(|e| format!("Failed to encode {}: {}", T::COLLECTION, e))?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN as usize + payload.len());
+    bytes.extend_from_slice(HEADER_MAGIC);
+    bytes.extend_from_slice(&current_version(T::COLLECTION).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Decode a stored value, running any migrations between its stored and current version.
+pub fn decode<T: CandidType + DeserializeOwned + Versioned>(bytes: &[u8]) -> Result<T, String> {
+    let result = split_header(bytes).and_then(|(version, payload)| {
+        let payload = upgrade(T::COLLECTION, version, payload.to_vec())?;
+        let value = Decode!(&payload, T).map_err(|e| format!("Failed to decode {}: {}", T::COLLECTION, e))?;
+        Ok((version, value))
+    });
+    match result {
+        Ok((version, value)) => {
+            record(Observed::Version(version));
+            Ok(value)
+        }
+        Err(e) => {
+            record(Observed::Unreadable);
+            Err(e)
+        }
+    }
+}
+
+fn split_header(bytes: &[u8]) -> Result<(u16, &[u8]), String> {
+    if let Some(rest) = bytes.strip_prefix(HEADER_MAGIC) {
+        let (version, payload) = rest.split_first_chunk::<2>()
+            .ok_or("Truncated schema header")?;
+        Ok((u16::from_le_bytes(*version), payload))
+    } else if bytes.starts_with(CANDID_MAGIC) {
+        Ok((0, bytes))
+    } else {
+        Err("Stored value has neither a schema header nor candid data".to_string())
+    }
+}
+
+fn upgrade(collection: &str, version: u16, mut payload: Vec<u8>) -> Result<Vec<u8>, String> {
+    let current = current_version(collection);
+    if version > current {
+        return Err(format!(
+            "{} record has schema version {} but this build only reads up to {}",
+            collection, version, current
+        ));
+    }
+    for from_version in version..current {
+        let migration = MIGRATIONS.iter()
+            .find(|m| m.collection == collection && m.from_version == from_version)
+            .ok_or_else(|| format!("No {} migration from version {}", collection, from_version))?;
+        payload = (migration.migrate)(payload)
+            .map_err(|e| format!("{} migration from version {} failed: {}", collection, from_version, e))?;
+    }
+    Ok(payload)
+}
+
+/// Survey one map and, unless `dry_run`, rewrite its outdated records at the current version.
+/// Unreadable records are counted and left untouched.
+pub fn migrate_map<K, V, M>(store: &str, map: &RefCell<StableBTreeMap<K, V, M>>, dry_run: bool) -> StoreSchemaStatus
+where
+    K: Storable + Ord + Clone,
+    V: Storable + Versioned,
+    M: Memory,
+{
+    let current = current_version(V::COLLECTION);
+    let (keys, seen) = observe(|| map.borrow().iter().map(|(key, _)| key).collect::<Vec<_>>());
+
+    let mut status = StoreSchemaStatus {
+        store: store.to_string(),
+        collection: V::COLLECTION.to_string(),
+        current_version: current,
+        records_by_version: Vec::new(),
+        unreadable: 0,
+        migrated: 0,
+    };
+    for (key, outcome) in keys.into_iter().zip(seen) {
+        match outcome {
+            Observed::Unreadable => status.unreadable += 1,
+            Observed::Version(version) => {
+                match status.records_by_version.iter_mut().find(|(v, _)| *v == version) {
+                    Some((_, count)) => *count += 1,
+                    None => status.records_by_version.push((version, 1)),
+                }
+                if version < current && !dry_run {
+                    let mut map = map.borrow_mut();
+                    if let Some(value) = map.get(&key) {
+                        map.insert(key, value);
+                        status.migrated += 1;
+                    }
+                }
+            }
+        }
+    }
+    status.records_by_version.sort();
+    status
+}
+
+fn build_report(dry_run: bool, stores: Vec<StoreSchemaStatus>) -> SchemaMigrationReport {
+    let migrations = MIGRATIONS.iter()
+        .map(|migration| MigrationStep {
+            collection: migration.collection.to_string(),
+            from_version: migration.from_version,
+            to_version: migration.from_version + 1,
+            description: migration.description.to_string(),
+            records: stores.iter()
+                .filter(|store| store.collection == migration.collection)
+                .flat_map(|store| store.records_by_version.iter())
+                .filter(|(version, _)| *version <= migration.from_version)
+                .map(|(_, count)| count)
+                .sum(),
+        })
+        .collect();
+    SchemaMigrationReport { dry_run, stores, migrations }
+}
+
+fn survey_all(dry_run: bool) -> SchemaMigrationReport {
+    let mut stores = crate::storage::migrate_records(dry_run);
+    stores.extend(crate::stable_user_storage::migrate_records(dry_run));
+    stores.extend(crate::defi_storage::migrate_records(dry_run));
+    build_report(dry_run, stores)
+}
+
+/// Rewrite every outdated record at its current schema version. Called from `post_upgrade`.
+pub fn migrate_stored_records() -> SchemaMigrationReport {
+    survey_all(false)
+}
+
+/// Report how many records each registered migration would touch, without writing anything.
+#[query]
+pub fn dry_run_schema_migrations() -> Result<SchemaMigrationReport, String> {
+    if !api::is_controller(&caller()) {
+        return Err("Only canister controllers can inspect stored schemas".to_string());
+    }
+    Ok(survey_all(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorableWorkflow;
+    use crate::types::Workflow;
+    use ic_stable_structures::storable::Bound;
+    use ic_stable_structures::DefaultMemoryImpl;
+    use std::borrow::Cow;
+
+    fn workflow(id: &str) -> StorableWorkflow {
+        StorableWorkflow(Workflow { id: id.to_string(), ..Workflow::default() })
+    }
+
+    // Writes headerless bytes while `legacy` is set, as builds before the header did
+    #[derive(Clone)]
+    struct Probe {
+        workflow: StorableWorkflow,
+        legacy: bool,
+    }
+
+    impl Storable for Probe {
+        const BOUND: Bound = Bound::Unbounded;
+
+        fn to_bytes(&self) -> Cow<'_, [u8]> {
+            if self.legacy {
+                Cow::Owned(Encode!(&self.workflow).unwrap())
+            } else {
+                Cow::Owned(encode(&self.workflow).unwrap())
+            }
+        }
+
+        fn from_bytes(bytes: Cow<[u8]>) -> Self {
+            let workflow = decode(bytes.as_ref()).unwrap_or_else(|_| workflow("unreadable"));
+            Probe { workflow, legacy: false }
+        }
+    }
+
+    versioned!(Probe => WORKFLOWS);
+
+    #[test]
+    fn test_every_collection_has_a_contiguous_migration_chain() {
+        for migration in MIGRATIONS {
+            let current = current_version(migration.collection);
+            for from_version in 0..current {
+                let steps = MIGRATIONS.iter()
+                    .filter(|m| m.collection == migration.collection && m.from_version == from_version)
+                    .count();
+                assert_eq!(steps, 1, "{} needs exactly one migration from v{}", migration.collection, from_version);
+            }
+        }
+    }
+
+    #[test]
+    fn test_legacy_values_decode_as_version_zero_and_are_written_with_a_header() {
+        let legacy = Encode!(&workflow("wf_1")).unwrap();
+        let (decoded, seen) = observe(|| decode::<StorableWorkflow>(&legacy).unwrap());
+        assert_eq!(decoded.0.id, "wf_1");
+        assert_eq!(seen, vec![Observed::Version(0)]);
+
+        let bytes = encode(&decoded).unwrap();
+        assert_eq!(&bytes[..3], HEADER_MAGIC);
+        assert_eq!(u16::from_le_bytes([bytes[3], bytes[4]]), current_version(WORKFLOWS));
+        assert_eq!(decode::<StorableWorkflow>(&bytes).unwrap().0.id, "wf_1");
+    }
+
+    #[test]
+    fn test_unknown_versions_and_garbage_are_rejected() {
+        let mut newer = encode(&workflow("wf_1")).unwrap();
+        newer[3..5].copy_from_slice(&(current_version(WORKFLOWS) + 1).to_le_bytes());
+        let error = decode::<StorableWorkflow>(&newer).err().unwrap();
+        assert!(error.contains("only reads up to"));
+
+        let (result, seen) = observe(|| decode::<StorableWorkflow>(b"not candid"));
+        assert!(result.is_err());
+        assert_eq!(seen, vec![Observed::Unreadable]);
+    }
+
+    #[test]
+    fn test_dry_run_counts_outdated_records_and_migration_rewrites_them() {
+        let map = RefCell::new(StableBTreeMap::<String, Probe, _>::init(DefaultMemoryImpl::default()));
+        for (id, legacy) in [("wf_1", true), ("wf_2", true), ("wf_3", false)] {
+            map.borrow_mut().insert(id.to_string(), Probe { workflow: workflow(id), legacy });
+        }
+
+        let dry = migrate_map("probes", &map, true);
+        assert_eq!(dry.records_by_version, vec![(0, 2), (1, 1)]);
+        assert_eq!(dry.migrated, 0);
+        let report = build_report(true, vec![dry]);
+        let header_step = report.migrations.iter()
+            .find(|m| m.collection == WORKFLOWS && m.from_version == 0)
+            .unwrap();
+        assert_eq!(header_step.records, 2);
+
+        let applied = migrate_map("probes", &map, false);
+        assert_eq!(applied.migrated, 2);
+        let after = migrate_map("probes", &map, true);
+        assert_eq!(after.records_by_version, vec![(1, 3)]);
+        assert_eq!(map.borrow().get(&"wf_2".to_string()).unwrap().workflow.0.id, "wf_2");
+    }
+}
@@ -89,6 +89,23 @@ impl std::fmt::Display for SpendingError {
     }
 }
 
+impl SpendingError {
+    /// Stable reason code used in `spending_limit.blocked` events
+    pub fn code(&self) -> &'static str {
+        match self {
+            SpendingError::NoApprovalFound(_) => "no_approval",
+            SpendingError::ExceedsDailyLimit { .. } => "daily_limit",
+            SpendingError::ExceedsTotalLimit { .. } => "total_limit",
+            SpendingError::OperationNotAllowed { .. } => "operation_not_allowed",
+            SpendingError::ApprovalExpired { .. } => "expired",
+            SpendingError::ApprovalNotActive(_) => "inactive",
+            SpendingError::InvalidAmount => "invalid_amount",
+            SpendingError::UserNotFound => "user_not_found",
+            SpendingError::InternalError(_) => "internal",
+        }
+    }
+}
+
 pub type SpendingResult<T> = Result<T, SpendingError>;
 
 // =============================================================================
@@ -105,6 +122,22 @@ impl SpendingLimitsEnforcement {
         token_symbol: &str,
         amount: u64,
         operation: &str,
+    ) -> SpendingResult<()> {
+        let result = Self::check_spending_request(user, token_symbol, amount, operation);
+        if let Err(error) = &result {
+            crate::system_events::emit(
+                crate::system_events::SPENDING_BLOCKED,
+                crate::system_events::spending_blocked(&user.to_text(), token_symbol, amount, operation, error),
+            );
+        }
+        result
+    }
+
+    fn check_spending_request(
+        user: Principal,
+        token_symbol: &str,
+        amount: u64,
+        operation: &str,
     ) -> SpendingResult<()> {
         // Get user's spending limits
         let mut user_limits = Self::get_user_spending_limits(user)?;
@@ -188,8 +221,12 @@ impl SpendingLimitsEnforcement {
         approval.remaining_daily = approval.remaining_daily.saturating_sub(amount);
         
         // Deactivate approval if fully spent
-        if approval.remaining_amount == 0 {
+        if approval.remaining_amount == 0 && approval.is_active {
             approval.is_active = false;
+            crate::system_events::emit(
+                crate::system_events::SPENDING_EXHAUSTED,
+                crate::system_events::spending_exhausted(&user.to_text(), token_symbol, approval.approved_amount, operation),
+            );
         }
         
         // Record the spending
@@ -6,7 +6,10 @@ use crate::types::{
 use ic_stable_structures::memory_manager::{MemoryId, VirtualMemory};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
 use std::cell::RefCell;
-use candid::{CandidType, Deserialize, Encode, Decode};
+use crate::credentials;
+use crate::schema::{self, versioned};
+use crate::types::StoreSchemaStatus;
+use candid::{CandidType, Deserialize};
 use serde::Serialize;
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
@@ -33,109 +36,119 @@ pub struct StorableTemplate(pub WorkflowTemplate);
 #[derive(CandidType, Deserialize, Serialize, Clone)]
 pub struct StorableUserSettings(pub UserSettings);
 
+versioned!(
+    StorableUserProfile => schema::USER_PROFILES,
+    StorableUserSubscriptionInfo => schema::USER_SUBSCRIPTIONS,
+    StorableIntegrationCredentials => schema::INTEGRATION_CREDENTIALS,
+    StorableOAuthToken => schema::OAUTH_TOKENS,
+    StorableAPIConnection => schema::API_CONNECTIONS,
+    StorableTemplate => schema::WORKFLOW_TEMPLATES,
+    StorableUserSettings => schema::USER_SETTINGS,
+);
+
 // Implement Storable traits for all wrapper types
 impl ic_stable_structures::Storable for StorableUserProfile {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 2048,
+        max_size: 2048 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        std::borrow::Cow::Owned(Encode!(self).unwrap())
+        std::borrow::Cow::Owned(schema::encode(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
 impl ic_stable_structures::Storable for StorableUserSubscriptionInfo {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 8192,
+        max_size: 8192 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        std::borrow::Cow::Owned(Encode!(self).unwrap())
+        std::borrow::Cow::Owned(schema::encode(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
 impl ic_stable_structures::Storable for StorableIntegrationCredentials {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 4096,
+        max_size: 4096 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        std::borrow::Cow::Owned(Encode!(self).unwrap())
+        std::borrow::Cow::Owned(schema::encode(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
 impl ic_stable_structures::Storable for StorableOAuthToken {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 2048,
+        max_size: 2048 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        std::borrow::Cow::Owned(Encode!(self).unwrap())
+        std::borrow::Cow::Owned(schema::encode(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
 impl ic_stable_structures::Storable for StorableAPIConnection {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 4096,
+        max_size: 4096 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        std::borrow::Cow::Owned(Encode!(self).unwrap())
+        std::borrow::Cow::Owned(schema::encode(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
 impl ic_stable_structures::Storable for StorableTemplate {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 16384,
+        max_size: 16384 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        std::borrow::Cow::Owned(Encode!(self).unwrap())
+        std::borrow::Cow::Owned(schema::encode(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
 impl ic_stable_structures::Storable for StorableUserSettings {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 4096,
+        max_size: 4096 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        std::borrow::Cow::Owned(Encode!(self).unwrap())
+        std::borrow::Cow::Owned(schema::encode(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
@@ -196,6 +209,20 @@ thread_local! {
     );
 }
 
+/// Survey, and unless `dry_run` migrate, every map in this file.
+pub fn migrate_records(dry_run: bool) -> Vec<StoreSchemaStatus> {
+    vec![
+        USER_PROFILES.with(|map| schema::migrate_map("user_profiles", map, dry_run)),
+        USER_SUBSCRIPTION_INFO.with(|map| schema::migrate_map("user_subscription_info", map, dry_run)),
+        USER_INTEGRATIONS.with(|map| schema::migrate_map("user_integrations", map, dry_run)),
+        OAUTH_TOKENS.with(|map| schema::migrate_map("oauth_tokens", map, dry_run)),
+        API_CONNECTIONS.with(|map| schema::migrate_map("api_connections", map, dry_run)),
+        GLOBAL_TEMPLATES.with(|map| schema::migrate_map("global_templates", map, dry_run)),
+        USER_TEMPLATES.with(|map| schema::migrate_map("user_templates", map, dry_run)),
+        USER_SETTINGS.with(|map| schema::migrate_map("user_settings", map, dry_run)),
+    ]
+}
+
 // Helper functions for user data management
 pub fn get_user_profile(principal: &str) -> Option<User> {
     USER_PROFILES.with(|profiles| {
@@ -235,18 +262,37 @@ pub fn insert_user_integration_credentials(user_principal: String, integration_t
     });
 }
 
-pub fn get_oauth_token(user_principal: &str, platform: &str) -> Option<OAuthToken> {
+pub fn get_oauth_token(user_principal: &str, platform: &str) -> Result<Option<OAuthToken>, String> {
     let key = format!("{}:{}", user_principal, platform);
-    OAUTH_TOKENS.with(|tokens| {
-        tokens.borrow().get(&key).map(|storable| storable.0)
-    })
+    OAUTH_TOKENS.with(|tokens| tokens.borrow().get(&key))
+        .map(|storable| credentials::open_oauth_token(user_principal, storable.0))
+        .transpose()
 }
 
-pub fn insert_oauth_token(user_principal: String, platform: String, token: OAuthToken) {
+/// Save `token` with its access and refresh tokens sealed in the credential vault.
+pub fn insert_oauth_token(user_principal: String, platform: String, token: OAuthToken, now: u64) -> Result<(), String> {
+    let sealed = credentials::seal_oauth_token(&user_principal, token, now)?;
     let key = format!("{}:{}", user_principal, platform);
     OAUTH_TOKENS.with(|tokens| {
-        tokens.borrow_mut().insert(key, StorableOAuthToken(token));
+        tokens.borrow_mut().insert(key, StorableOAuthToken(sealed));
     });
+    Ok(())
+}
+
+/// Seal tokens saved in plaintext before the vault held them. Returns the number sealed.
+pub fn seal_plaintext_oauth_tokens(now: u64) -> Result<usize, String> {
+    let plaintext: Vec<(String, OAuthToken)> = OAUTH_TOKENS.with(|tokens| {
+        tokens.borrow().iter()
+            .filter(|(_, token)| credentials::is_plaintext_oauth_token(&token.0))
+            .map(|(key, token)| (key, token.0))
+            .collect()
+    });
+    let count = plaintext.len();
+    for (key, token) in plaintext {
+        let Some((principal, platform)) = key.split_once(':') else { continue };
+        insert_oauth_token(principal.to_string(), platform.to_string(), token, now)?;
+    }
+    Ok(count)
 }
 
 pub fn get_user_templates(user_principal: &str) -> Vec<WorkflowTemplate> {
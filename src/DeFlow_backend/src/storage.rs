@@ -1,12 +1,14 @@
 use crate::types::{
     Workflow, WorkflowExecution, NodeDefinition, EventListener, 
-    ScheduledWorkflow, RetryPolicy, InternalWorkflowState, ScheduledExecution
+    ScheduledWorkflow, RetryPolicy, InternalWorkflowState, ScheduledExecution, WebhookConfig,
+    ChainWatch, WorkflowAcl, AclAuditEntry, WorkflowRevision, StoredCredential, VaultKey, OAuthClient
 };
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
 use std::cell::RefCell;
-use std::collections::HashMap;
-use candid::{CandidType, Deserialize, Encode, Decode};
+use crate::schema::{self, versioned};
+use crate::types::StoreSchemaStatus;
+use candid::{CandidType, Deserialize};
 use serde::Serialize;
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
@@ -36,15 +38,58 @@ pub struct StorableWorkflowState(pub InternalWorkflowState);
 #[derive(CandidType, Deserialize, Serialize, Clone)]
 pub struct StorableScheduledExecution(pub ScheduledExecution);
 
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableWebhookConfig(pub WebhookConfig);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableChainWatch(pub ChainWatch);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableWorkflowAcl(pub WorkflowAcl);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableAclAuditEntry(pub AclAuditEntry);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableWorkflowRevision(pub WorkflowRevision);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableCredential(pub StoredCredential);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableVaultKey(pub VaultKey);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StorableOAuthClient(pub OAuthClient);
+
+versioned!(
+    StorableWorkflow => schema::WORKFLOWS,
+    StorableExecution => schema::EXECUTIONS,
+    StorableNodeDefinition => schema::NODE_DEFINITIONS,
+    StorableEventListeners => schema::EVENT_LISTENERS,
+    StorableScheduledWorkflow => schema::SCHEDULED_WORKFLOWS,
+    StorableRetryPolicy => schema::RETRY_POLICIES,
+    StorableWorkflowState => schema::WORKFLOW_STATE,
+    StorableScheduledExecution => schema::SCHEDULED_EXECUTIONS,
+    StorableWebhookConfig => schema::WEBHOOKS,
+    StorableChainWatch => schema::CHAIN_WATCHES,
+    StorableWorkflowAcl => schema::WORKFLOW_ACLS,
+    StorableAclAuditEntry => schema::ACL_AUDIT_LOG,
+    StorableWorkflowRevision => schema::WORKFLOW_REVISIONS,
+    StorableCredential => schema::CREDENTIALS,
+    StorableVaultKey => schema::VAULT_KEYS,
+    StorableOAuthClient => schema::OAUTH_CLIENTS,
+);
+
 // Implement Storable trait for our wrapper types
 impl ic_stable_structures::Storable for StorableWorkflow {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 8192,
+        max_size: 8192 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -54,7 +99,7 @@ impl ic_stable_structures::Storable for StorableWorkflow {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to default workflow to prevent canister crash
@@ -66,12 +111,12 @@ impl ic_stable_structures::Storable for StorableWorkflow {
 
 impl ic_stable_structures::Storable for StorableExecution {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 16384,
+        max_size: 16384 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -81,7 +126,7 @@ impl ic_stable_structures::Storable for StorableExecution {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to default execution to prevent canister crash
@@ -93,12 +138,12 @@ impl ic_stable_structures::Storable for StorableExecution {
 
 impl ic_stable_structures::Storable for StorableNodeDefinition {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 4096,
+        max_size: 4096 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -108,7 +153,7 @@ impl ic_stable_structures::Storable for StorableNodeDefinition {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to default node definition to prevent canister crash
@@ -120,12 +165,12 @@ impl ic_stable_structures::Storable for StorableNodeDefinition {
 
 impl ic_stable_structures::Storable for StorableEventListeners {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 8192,
+        max_size: 8192 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -135,7 +180,7 @@ impl ic_stable_structures::Storable for StorableEventListeners {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to empty listeners to prevent canister crash
@@ -147,12 +192,12 @@ impl ic_stable_structures::Storable for StorableEventListeners {
 
 impl ic_stable_structures::Storable for StorableScheduledWorkflow {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 2048,
+        max_size: 8192 + schema::HEADER_LEN, // Includes the bounded run history
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -162,7 +207,7 @@ impl ic_stable_structures::Storable for StorableScheduledWorkflow {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to default scheduled workflow to prevent canister crash
@@ -174,12 +219,12 @@ impl ic_stable_structures::Storable for StorableScheduledWorkflow {
 
 impl ic_stable_structures::Storable for StorableRetryPolicy {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 1024,
+        max_size: 1024 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -189,7 +234,7 @@ impl ic_stable_structures::Storable for StorableRetryPolicy {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to default retry policy to prevent canister crash
@@ -201,12 +246,12 @@ impl ic_stable_structures::Storable for StorableRetryPolicy {
 
 impl ic_stable_structures::Storable for StorableWorkflowState {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 65536, // Large size for comprehensive state
+        max_size: 65536 + schema::HEADER_LEN, // Large size for comprehensive state
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -216,7 +261,7 @@ impl ic_stable_structures::Storable for StorableWorkflowState {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to default workflow state to prevent canister crash
@@ -228,12 +273,12 @@ impl ic_stable_structures::Storable for StorableWorkflowState {
 
 impl ic_stable_structures::Storable for StorableScheduledExecution {
     const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
-        max_size: 4096,
+        max_size: 4096 + schema::HEADER_LEN,
         is_fixed_size: false,
     };
 
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        match Encode!(self) {
+        match schema::encode(self) {
             Ok(bytes) => std::borrow::Cow::Owned(bytes),
             Err(_) => {
                 // DEMO: Fallback to empty bytes to prevent canister crash
@@ -243,7 +288,7 @@ impl ic_stable_structures::Storable for StorableScheduledExecution {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        match Decode!(bytes.as_ref(), Self) {
+        match schema::decode(bytes.as_ref()) {
             Ok(data) => data,
             Err(_) => {
                 // DEMO: Fallback to default scheduled execution to prevent canister crash
@@ -253,6 +298,230 @@ impl ic_stable_structures::Storable for StorableScheduledExecution {
     }
 }
 
+impl ic_stable_structures::Storable for StorableWebhookConfig {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 2048 + schema::HEADER_LEN,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to default webhook to prevent canister crash
+                StorableWebhookConfig(WebhookConfig::default())
+            }
+        }
+    }
+}
+
+impl ic_stable_structures::Storable for StorableChainWatch {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 32768 + schema::HEADER_LEN,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to an inactive watch to prevent canister crash
+                StorableChainWatch(ChainWatch::default())
+            }
+        }
+    }
+}
+
+impl ic_stable_structures::Storable for StorableWorkflowAcl {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 16384 + schema::HEADER_LEN, // Bounded by acl::MAX_SHARES_PER_WORKFLOW
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to an empty ACL, leaving only the owner with access
+                StorableWorkflowAcl(WorkflowAcl::default())
+            }
+        }
+    }
+}
+
+impl ic_stable_structures::Storable for StorableAclAuditEntry {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 1024 + schema::HEADER_LEN,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to default audit entry to prevent canister crash
+                StorableAclAuditEntry(AclAuditEntry::default())
+            }
+        }
+    }
+}
+
+impl ic_stable_structures::Storable for StorableWorkflowRevision {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 8192 + 512 + schema::HEADER_LEN, // A workflow plus the revision details
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to an empty revision to prevent canister crash
+                StorableWorkflowRevision(WorkflowRevision {
+                    workflow_id: "default".to_string(),
+                    revision: 0,
+                    workflow: Workflow::default(),
+                    author: "default".to_string(),
+                    created_at: 0,
+                    rolled_back_from: None,
+                })
+            }
+        }
+    }
+}
+
+impl ic_stable_structures::Storable for StorableCredential {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 8192 + schema::HEADER_LEN, // Bounded by credentials::MAX_SECRET_BYTES
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to an empty credential, which fails to decrypt
+                StorableCredential(StoredCredential::default())
+            }
+        }
+    }
+}
+
+impl ic_stable_structures::Storable for StorableVaultKey {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 256 + schema::HEADER_LEN,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to an empty key, which fails to decrypt
+                StorableVaultKey(VaultKey::default())
+            }
+        }
+    }
+}
+
+impl ic_stable_structures::Storable for StorableOAuthClient {
+    const BOUND: ic_stable_structures::storable::Bound = ic_stable_structures::storable::Bound::Bounded {
+        max_size: 1024 + schema::HEADER_LEN,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        match schema::encode(self) {
+            Ok(bytes) => std::borrow::Cow::Owned(bytes),
+            Err(_) => {
+                // DEMO: Fallback to empty bytes to prevent canister crash
+                std::borrow::Cow::Owned(vec![])
+            }
+        }
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match schema::decode(bytes.as_ref()) {
+            Ok(data) => data,
+            Err(_) => {
+                // DEMO: Fallback to an unconfigured client
+                StorableOAuthClient(OAuthClient::default())
+            }
+        }
+    }
+}
+
+// MemoryIds 8-15 are used by stable_user_storage.rs and 18-21 by defi_storage.rs, which share this manager
 thread_local! {
     pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
@@ -307,9 +576,83 @@ thread_local! {
         )
     );
 
-    // Keep these as thread-local for temporary data
-    pub static TIMERS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
-    pub static WEBHOOK_ENDPOINTS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    // Inbound webhook routes keyed by path
+    pub static WEBHOOKS: RefCell<StableBTreeMap<String, StorableWebhookConfig, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))),
+        )
+    );
+
+    // On-chain watches with their cursors, keyed by watch id
+    pub static CHAIN_WATCHES: RefCell<StableBTreeMap<String, StorableChainWatch, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))),
+        )
+    );
+
+    // Sharing settings keyed by workflow id
+    pub static WORKFLOW_ACLS: RefCell<StableBTreeMap<String, StorableWorkflowAcl, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22))),
+        )
+    );
+
+    // Append-only record of ACL changes keyed by sequence number
+    pub static ACL_AUDIT_LOG: RefCell<StableBTreeMap<u64, StorableAclAuditEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))),
+        )
+    );
+
+    // Workflow snapshots keyed by `revision_key`, so each workflow's revisions sort together
+    pub static WORKFLOW_REVISIONS: RefCell<StableBTreeMap<String, StorableWorkflowRevision, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))),
+        )
+    );
+
+    // Vault secrets keyed by `credential_key`
+    pub static CREDENTIALS: RefCell<StableBTreeMap<String, StorableCredential, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))),
+        )
+    );
+
+    // Vault encryption keys by version; the highest version seals new secrets
+    pub static VAULT_KEYS: RefCell<StableBTreeMap<u32, StorableVaultKey, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26))),
+        )
+    );
+
+    // OAuth clients by provider name
+    pub static OAUTH_CLIENTS: RefCell<StableBTreeMap<String, StorableOAuthClient, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27))),
+        )
+    );
+}
+
+/// Survey, and unless `dry_run` migrate, every map in this file.
+pub fn migrate_records(dry_run: bool) -> Vec<StoreSchemaStatus> {
+    vec![
+        WORKFLOWS.with(|map| schema::migrate_map("workflows", map, dry_run)),
+        EXECUTIONS.with(|map| schema::migrate_map("executions", map, dry_run)),
+        NODE_REGISTRY.with(|map| schema::migrate_map("node_registry", map, dry_run)),
+        EVENT_LISTENERS.with(|map| schema::migrate_map("event_listeners", map, dry_run)),
+        SCHEDULED_WORKFLOWS.with(|map| schema::migrate_map("scheduled_workflows", map, dry_run)),
+        RETRY_POLICIES.with(|map| schema::migrate_map("retry_policies", map, dry_run)),
+        WORKFLOW_STATE.with(|map| schema::migrate_map("workflow_state", map, dry_run)),
+        SCHEDULED_EXECUTIONS.with(|map| schema::migrate_map("scheduled_executions", map, dry_run)),
+        WEBHOOKS.with(|map| schema::migrate_map("webhooks", map, dry_run)),
+        CHAIN_WATCHES.with(|map| schema::migrate_map("chain_watches", map, dry_run)),
+        WORKFLOW_ACLS.with(|map| schema::migrate_map("workflow_acls", map, dry_run)),
+        ACL_AUDIT_LOG.with(|map| schema::migrate_map("acl_audit_log", map, dry_run)),
+        WORKFLOW_REVISIONS.with(|map| schema::migrate_map("workflow_revisions", map, dry_run)),
+        CREDENTIALS.with(|map| schema::migrate_map("credentials", map, dry_run)),
+        VAULT_KEYS.with(|map| schema::migrate_map("vault_keys", map, dry_run)),
+        OAUTH_CLIENTS.with(|map| schema::migrate_map("oauth_clients", map, dry_run)),
+    ]
 }
 
 // Helper functions for accessing stable storage
@@ -392,6 +735,219 @@ pub fn remove_scheduled_workflow(id: &str) -> Option<ScheduledWorkflow> {
     })
 }
 
+pub fn list_scheduled_workflows() -> Vec<ScheduledWorkflow> {
+    SCHEDULED_WORKFLOWS.with(|schedules| {
+        schedules.borrow().iter()
+            .map(|(_, storable)| storable.0)
+            .collect()
+    })
+}
+
+pub fn get_webhook(path: &str) -> Option<WebhookConfig> {
+    WEBHOOKS.with(|webhooks| {
+        webhooks.borrow().get(&path.to_string()).map(|storable| storable.0)
+    })
+}
+
+pub fn insert_webhook(path: String, webhook: WebhookConfig) {
+    WEBHOOKS.with(|webhooks| {
+        webhooks.borrow_mut().insert(path, StorableWebhookConfig(webhook));
+    });
+}
+
+pub fn remove_webhook(path: &str) -> Option<WebhookConfig> {
+    WEBHOOKS.with(|webhooks| {
+        webhooks.borrow_mut().remove(&path.to_string()).map(|storable| storable.0)
+    })
+}
+
+pub fn list_webhooks() -> Vec<WebhookConfig> {
+    WEBHOOKS.with(|webhooks| {
+        webhooks.borrow().iter()
+            .map(|(_, storable)| storable.0)
+            .collect()
+    })
+}
+
+pub fn get_chain_watch(id: &str) -> Option<ChainWatch> {
+    CHAIN_WATCHES.with(|watches| {
+        watches.borrow().get(&id.to_string()).map(|storable| storable.0)
+    })
+}
+
+pub fn insert_chain_watch(watch: ChainWatch) {
+    CHAIN_WATCHES.with(|watches| {
+        watches.borrow_mut().insert(watch.id.clone(), StorableChainWatch(watch));
+    });
+}
+
+pub fn remove_chain_watch(id: &str) -> Option<ChainWatch> {
+    CHAIN_WATCHES.with(|watches| {
+        watches.borrow_mut().remove(&id.to_string()).map(|storable| storable.0)
+    })
+}
+
+pub fn list_chain_watches() -> Vec<ChainWatch> {
+    CHAIN_WATCHES.with(|watches| {
+        watches.borrow().iter()
+            .map(|(_, storable)| storable.0)
+            .collect()
+    })
+}
+
+pub fn get_workflow_acl(workflow_id: &str) -> Option<WorkflowAcl> {
+    WORKFLOW_ACLS.with(|acls| {
+        acls.borrow().get(&workflow_id.to_string()).map(|storable| storable.0)
+    })
+}
+
+pub fn insert_workflow_acl(acl: WorkflowAcl) {
+    WORKFLOW_ACLS.with(|acls| {
+        acls.borrow_mut().insert(acl.workflow_id.clone(), StorableWorkflowAcl(acl));
+    });
+}
+
+pub fn remove_workflow_acl(workflow_id: &str) -> Option<WorkflowAcl> {
+    WORKFLOW_ACLS.with(|acls| {
+        acls.borrow_mut().remove(&workflow_id.to_string()).map(|storable| storable.0)
+    })
+}
+
+/// Append an entry to the ACL audit log, assigning it the next sequence number.
+pub fn append_acl_audit_entry(mut entry: AclAuditEntry) -> u64 {
+    ACL_AUDIT_LOG.with(|log| {
+        let mut log = log.borrow_mut();
+        entry.id = log.last_key_value().map(|(id, _)| id + 1).unwrap_or(0);
+        let id = entry.id;
+        log.insert(id, StorableAclAuditEntry(entry));
+        id
+    })
+}
+
+pub fn list_acl_audit_entries(workflow_id: &str) -> Vec<AclAuditEntry> {
+    ACL_AUDIT_LOG.with(|log| {
+        log.borrow().iter()
+            .map(|(_, storable)| storable.0)
+            .filter(|entry| entry.workflow_id == workflow_id)
+            .collect()
+    })
+}
+
+fn revision_key(workflow_id: &str, revision: u64) -> String {
+    format!("{}#{:020}", workflow_id, revision)
+}
+
+pub fn insert_workflow_revision(revision: WorkflowRevision) {
+    WORKFLOW_REVISIONS.with(|revisions| {
+        let key = revision_key(&revision.workflow_id, revision.revision);
+        revisions.borrow_mut().insert(key, StorableWorkflowRevision(revision));
+    });
+}
+
+pub fn get_workflow_revision(workflow_id: &str, revision: u64) -> Option<WorkflowRevision> {
+    WORKFLOW_REVISIONS.with(|revisions| {
+        revisions.borrow().get(&revision_key(workflow_id, revision)).map(|storable| storable.0)
+    })
+}
+
+/// All revisions of a workflow, oldest first.
+pub fn list_workflow_revisions(workflow_id: &str) -> Vec<WorkflowRevision> {
+    let prefix = format!("{}#", workflow_id);
+    WORKFLOW_REVISIONS.with(|revisions| {
+        revisions.borrow().range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, storable)| storable.0)
+            // Ids that themselves contain '#' share the prefix
+            .filter(|revision| revision.workflow_id == workflow_id)
+            .collect()
+    })
+}
+
+pub fn latest_workflow_revision(workflow_id: &str) -> Option<u64> {
+    list_workflow_revisions(workflow_id).last().map(|revision| revision.revision)
+}
+
+pub fn remove_workflow_revisions(workflow_id: &str) {
+    let keys: Vec<String> = list_workflow_revisions(workflow_id).iter()
+        .map(|revision| revision_key(workflow_id, revision.revision))
+        .collect();
+    WORKFLOW_REVISIONS.with(|revisions| {
+        let mut revisions = revisions.borrow_mut();
+        for key in keys {
+            revisions.remove(&key);
+        }
+    });
+}
+
+fn credential_key(owner: &str, id: &str) -> String {
+    format!("{}/{}", owner, id)
+}
+
+pub fn get_credential(owner: &str, id: &str) -> Option<StoredCredential> {
+    CREDENTIALS.with(|credentials| {
+        credentials.borrow().get(&credential_key(owner, id)).map(|storable| storable.0)
+    })
+}
+
+pub fn insert_credential(credential: StoredCredential) {
+    CREDENTIALS.with(|credentials| {
+        let key = credential_key(&credential.owner, &credential.id);
+        credentials.borrow_mut().insert(key, StorableCredential(credential));
+    });
+}
+
+pub fn remove_credential(owner: &str, id: &str) -> Option<StoredCredential> {
+    CREDENTIALS.with(|credentials| {
+        credentials.borrow_mut().remove(&credential_key(owner, id)).map(|storable| storable.0)
+    })
+}
+
+pub fn list_credentials(owner: &str) -> Vec<StoredCredential> {
+    let prefix = credential_key(owner, "");
+    CREDENTIALS.with(|credentials| {
+        credentials.borrow().range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, storable)| storable.0)
+            .collect()
+    })
+}
+
+pub fn list_all_credentials() -> Vec<StoredCredential> {
+    CREDENTIALS.with(|credentials| {
+        credentials.borrow().iter().map(|(_, storable)| storable.0).collect()
+    })
+}
+
+pub fn get_vault_key(version: u32) -> Option<VaultKey> {
+    VAULT_KEYS.with(|keys| keys.borrow().get(&version).map(|storable| storable.0))
+}
+
+pub fn current_vault_key() -> Option<VaultKey> {
+    VAULT_KEYS.with(|keys| keys.borrow().last_key_value().map(|(_, storable)| storable.0))
+}
+
+pub fn insert_vault_key(key: VaultKey) {
+    VAULT_KEYS.with(|keys| {
+        keys.borrow_mut().insert(key.version, StorableVaultKey(key));
+    });
+}
+
+pub fn remove_vault_key(version: u32) {
+    VAULT_KEYS.with(|keys| {
+        keys.borrow_mut().remove(&version);
+    });
+}
+
+pub fn get_oauth_client(provider: &str) -> Option<OAuthClient> {
+    OAUTH_CLIENTS.with(|clients| clients.borrow().get(&provider.to_string()).map(|storable| storable.0))
+}
+
+pub fn insert_oauth_client(client: OAuthClient) {
+    OAUTH_CLIENTS.with(|clients| {
+        clients.borrow_mut().insert(client.provider.clone(), StorableOAuthClient(client));
+    });
+}
+
 pub fn get_retry_policy(node_type: &str) -> Option<RetryPolicy> {
     RETRY_POLICIES.with(|policies| {
         policies.borrow().get(&node_type.to_string()).map(|storable| storable.0)
@@ -419,28 +975,7 @@ pub fn update_workflow_state(new_state: InternalWorkflowState) {
     });
 }
 
-pub fn save_workflow_state_for_upgrade() -> InternalWorkflowState {
-    get_workflow_state()
-}
-
-pub fn restore_workflow_state_after_upgrade(state: InternalWorkflowState) {
-    update_workflow_state(state);
-}
-
-// Persistent scheduled execution management
-pub fn get_scheduled_execution(workflow_id: &str) -> Option<ScheduledExecution> {
-    SCHEDULED_EXECUTIONS.with(|executions| {
-        executions.borrow().get(&workflow_id.to_string())
-            .map(|storable| storable.0)
-    })
-}
-
-pub fn insert_scheduled_execution(workflow_id: String, execution: ScheduledExecution) {
-    SCHEDULED_EXECUTIONS.with(|executions| {
-        executions.borrow_mut().insert(workflow_id, StorableScheduledExecution(execution));
-    });
-}
-
+// Legacy persistent scheduled executions, drained by the scheduler on upgrade
 pub fn remove_scheduled_execution(workflow_id: &str) -> Option<ScheduledExecution> {
     SCHEDULED_EXECUTIONS.with(|executions| {
         executions.borrow_mut().remove(&workflow_id.to_string())
@@ -455,13 +990,3 @@ pub fn list_all_scheduled_executions() -> Vec<(String, ScheduledExecution)> {
             .collect()
     })
 }
-
-#[allow(dead_code)]
-pub fn get_due_scheduled_executions(current_time: u64) -> Vec<(String, ScheduledExecution)> {
-    SCHEDULED_EXECUTIONS.with(|executions| {
-        executions.borrow().iter()
-            .filter(|(_, storable)| storable.0.next_execution <= current_time)
-            .map(|(id, storable)| (id, storable.0))
-            .collect()
-    })
-}
\ No newline at end of file
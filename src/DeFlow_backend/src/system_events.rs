@@ -0,0 +1,743 @@
+// System events
+//
+// Events the canister raises itself, so workflows can be chained to other
+// workflows, price alerts, strategies, cycles monitors, spending limits,
+// on-chain activity seen by chain watches and revoked OAuth tokens through
+// `register_event_listener` or a `WorkflowTrigger::Event` trigger.
+// The catalogue below documents each payload; `list_system_events` serves it.
+//
+// Every triggered execution receives the event payload plus an `_event` object
+// ({ id, event_type, chain_depth }). Loop protection:
+// - an execution event carries the chain depth of the event that started the
+//   run plus one, and events deeper than MAX_EVENT_CHAIN_DEPTH are dropped;
+// - each event type is rate limited per workflow, or per principal for events
+//   not raised by a workflow, over a one minute window (chain events are
+//   bounded per poll by `chain_watchers` instead);
+// - dispatch happens on a zero-delay timer, never inside the emitting call;
+// - callers cannot `emit_event` a system event type;
+// - a system event only reaches workflows owned by the principal its payload
+//   names (`owner`, `user_id` or `user`).
+
+use crate::chain_watchers::{EvmObservation, UtxoObservation};
+use crate::cycles_monitor_service::{AlertType, CyclesAlert};
+use crate::defi::solana::pure_icp::SignatureInfo;
+use crate::defi::automated_strategies::{ActiveStrategy, StrategyExecutionResult};
+use crate::defi::price_alert_service::{PriceAlert, TokenPrice};
+use crate::security::spending_limits_enforcement::SpendingError;
+use crate::types::{
+    ChainWatch, ConfigValue, EventPayloadField, ExecutionStatus, SystemEventDefinition, WatchSource, WorkflowEvent,
+    WorkflowExecution,
+};
+use ic_cdk::query;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const EXECUTION_COMPLETED: &str = "workflow.execution.completed";
+pub const EXECUTION_FAILED: &str = "workflow.execution.failed";
+pub const EXECUTION_CANCELLED: &str = "workflow.execution.cancelled";
+pub const PRICE_ALERT_TRIGGERED: &str = "price_alert.triggered";
+pub const STRATEGY_EXECUTED: &str = "strategy.executed";
+pub const STRATEGY_FAILED: &str = "strategy.execution_failed";
+pub const STRATEGY_STOP_LOSS: &str = "strategy.stop_loss";
+pub const STRATEGY_TAKE_PROFIT: &str = "strategy.take_profit";
+pub const CYCLES_LOW: &str = "cycles.low";
+pub const CYCLES_TOPUP_REQUESTED: &str = "cycles.topup_requested";
+pub const SPENDING_BLOCKED: &str = "spending_limit.blocked";
+pub const SPENDING_EXHAUSTED: &str = "spending_limit.exhausted";
+pub const CHAIN_EVM_LOG: &str = "chain.evm_log";
+pub const CHAIN_BTC_UTXO: &str = "chain.btc_utxo";
+pub const CHAIN_SOL_TRANSACTION: &str = "chain.sol_transaction";
+pub const CHAIN_SOL_BALANCE_CHANGED: &str = "chain.sol_balance_changed";
+pub const OAUTH_TOKEN_REVOKED: &str = "oauth.token_revoked";
+
+pub const EVENT_METADATA_KEY: &str = "_event";
+const MAX_EVENT_CHAIN_DEPTH: u32 = 5;
+const RATE_WINDOW_NS: u64 = 60 * 1_000_000_000;
+const MAX_EVENTS_PER_WINDOW: u32 = 30;
+
+// (name, type, required, description)
+type Field = (&'static str, &'static str, bool, &'static str);
+
+const EXECUTION_FIELDS: &[Field] = &[
+    ("workflow_id", "string", true, "Workflow that ran"),
+    ("workflow_name", "string", false, "Name of the workflow"),
+    ("owner", "string", false, "Owner of the workflow"),
+    ("execution_id", "string", true, "Execution that finished"),
+    ("started_at_ms", "number", true, "Start time, Unix milliseconds"),
+    ("completed_at_ms", "number", false, "Finish time, Unix milliseconds"),
+    ("duration_ms", "number", false, "Run time in milliseconds"),
+    ("nodes_executed", "number", true, "Node runs recorded, including loop iterations"),
+    ("error", "string", false, "Failure or cancellation message"),
+    ("failed_node_id", "string", false, "First node that failed"),
+    ("initiated_by", "string", false, "Principal the run is attributed to"),
+    ("parent_execution_id", "string", false, "Set for sub-workflow runs"),
+    ("chain_depth", "number", true, "Event hops that led to this run"),
+];
+
+const PRICE_ALERT_FIELDS: &[Field] = &[
+    ("alert_id", "string", true, "Alert that fired"),
+    ("user_id", "string", true, "Owner of the alert"),
+    ("token_symbol", "string", true, "Token being watched"),
+    ("price_usd", "number", true, "Price that met the condition"),
+    ("change_24h", "number", true, "24h change in percent"),
+    ("condition", "string", true, "Condition that was met, e.g. \"above $2000.0000\""),
+    ("triggered_count", "number", true, "Times the alert has fired, including this one"),
+    ("deactivated", "boolean", true, "True when the alert reached max_triggers"),
+];
+
+const STRATEGY_EXECUTED_FIELDS: &[Field] = &[
+    ("strategy_id", "string", true, "Strategy that ran"),
+    ("user_id", "string", true, "Owner of the strategy"),
+    ("strategy_name", "string", true, "Name of the strategy"),
+    ("execution_id", "string", true, "Strategy execution id"),
+    ("action_type", "string", true, "Action taken, e.g. yield_farming"),
+    ("amount_usd", "number", true, "Capital used"),
+    ("actual_return", "number", true, "Return of this execution in USD"),
+    ("gas_cost_usd", "number", true, "Gas spent"),
+    ("roi_percentage", "number", true, "Strategy ROI after this execution"),
+    ("total_pnl", "number", true, "Strategy PnL after this execution"),
+];
+
+const STRATEGY_FAILED_FIELDS: &[Field] = &[
+    ("strategy_id", "string", true, "Strategy that failed"),
+    ("user_id", "string", true, "Owner of the strategy"),
+    ("strategy_name", "string", true, "Name of the strategy"),
+    ("error", "string", true, "Failure message"),
+];
+
+const STRATEGY_EXIT_FIELDS: &[Field] = &[
+    ("strategy_id", "string", true, "Strategy that was stopped"),
+    ("user_id", "string", true, "Owner of the strategy"),
+    ("strategy_name", "string", true, "Name of the strategy"),
+    ("roi_percentage", "number", true, "ROI that crossed the threshold"),
+    ("threshold_percentage", "number", true, "Configured stop-loss or take-profit percentage"),
+    ("total_pnl", "number", true, "Strategy PnL in USD"),
+    ("allocated_capital", "number", true, "Capital allocated to the strategy"),
+];
+
+const CYCLES_LOW_FIELDS: &[Field] = &[
+    ("monitor_id", "string", true, "Monitor that raised the alert"),
+    ("canister_id", "string", true, "Canister being monitored"),
+    ("owner", "string", true, "Owner of the monitor"),
+    ("level", "string", true, "warning or critical"),
+    ("current_cycles", "number", true, "Cycles balance"),
+    ("threshold", "number", true, "Threshold that was crossed"),
+];
+
+const CYCLES_TOPUP_FIELDS: &[Field] = &[
+    ("monitor_id", "string", true, "Monitor that requested the top-up"),
+    ("canister_id", "string", true, "Canister being topped up"),
+    ("owner", "string", true, "Owner of the monitor"),
+    ("current_cycles", "number", true, "Cycles balance before the top-up"),
+    ("topup_amount", "number", true, "Cycles requested"),
+];
+
+const SPENDING_BLOCKED_FIELDS: &[Field] = &[
+    ("user", "string", true, "Principal whose spending was refused"),
+    ("token_symbol", "string", true, "Token of the request"),
+    ("amount", "number", true, "Requested amount in smallest units"),
+    ("operation", "string", true, "Requested operation, e.g. swap"),
+    ("reason", "string", true, "no_approval, daily_limit, total_limit, operation_not_allowed, expired, inactive, invalid_amount, user_not_found or internal"),
+    ("message", "string", true, "Human readable reason"),
+];
+
+const SPENDING_EXHAUSTED_FIELDS: &[Field] = &[
+    ("user", "string", true, "Principal whose approval ran out"),
+    ("token_symbol", "string", true, "Token of the approval"),
+    ("approved_amount", "number", true, "Total amount that was approved"),
+    ("operation", "string", true, "Operation that used the last of the approval"),
+];
+
+const EVM_LOG_FIELDS: &[Field] = &[
+    ("watch_id", "string", true, "Chain watch that matched"),
+    ("owner", "string", true, "Owner of the watch"),
+    ("chain", "string", true, "EVM chain, e.g. ethereum"),
+    ("address", "string", true, "Contract that emitted the log"),
+    ("block_number", "number", true, "Block containing the log"),
+    ("block_hash", "string", true, "Hash of that block"),
+    ("transaction_hash", "string", true, "Transaction that emitted the log"),
+    ("log_index", "number", true, "Position of the log in the block"),
+    ("topic0", "string", false, "Event signature hash"),
+    ("topic1", "string", false, "First indexed argument"),
+    ("topic2", "string", false, "Second indexed argument"),
+    ("topic3", "string", false, "Third indexed argument"),
+    ("data", "string", true, "Hex encoded non-indexed arguments"),
+    ("confirmations", "number", true, "Confirmations when the log was emitted"),
+];
+
+const BTC_UTXO_FIELDS: &[Field] = &[
+    ("watch_id", "string", true, "Chain watch that matched"),
+    ("owner", "string", true, "Owner of the watch"),
+    ("network", "string", true, "mainnet, testnet or regtest"),
+    ("address", "string", true, "Watched address"),
+    ("txid", "string", true, "Transaction that created the output"),
+    ("vout", "number", true, "Output index"),
+    ("value_satoshis", "number", true, "Output value"),
+    ("height", "number", true, "Block height of the transaction"),
+    ("confirmations", "number", true, "Confirmations when the output was emitted"),
+];
+
+const SOL_TRANSACTION_FIELDS: &[Field] = &[
+    ("watch_id", "string", true, "Chain watch that matched"),
+    ("owner", "string", true, "Owner of the watch"),
+    ("network", "string", true, "mainnet, devnet or testnet"),
+    ("address", "string", true, "Watched account"),
+    ("signature", "string", true, "Transaction signature"),
+    ("slot", "number", true, "Slot of the transaction"),
+    ("block_time", "number", false, "Unix seconds, when the node knows it"),
+    ("success", "boolean", true, "False when the transaction failed"),
+    ("error", "string", false, "Transaction error as JSON"),
+    ("memo", "string", false, "Memo attached to the transaction"),
+];
+
+const SOL_BALANCE_FIELDS: &[Field] = &[
+    ("watch_id", "string", true, "Chain watch that matched"),
+    ("owner", "string", true, "Owner of the watch"),
+    ("network", "string", true, "mainnet, devnet or testnet"),
+    ("address", "string", true, "Watched account"),
+    ("previous_lamports", "number", true, "Balance at the previous poll"),
+    ("balance_lamports", "number", true, "Current balance"),
+    ("change_lamports", "number", true, "Signed difference"),
+];
+
+const OAUTH_REVOKED_FIELDS: &[Field] = &[
+    ("user", "string", true, "Principal whose saved token stopped working"),
+    ("platform", "string", true, "Platform the token was saved for, e.g. twitter"),
+    ("provider", "string", true, "OAuth provider that refused the refresh token"),
+    ("error", "string", true, "Error returned by the provider"),
+];
+
+// (event_type, source, description, payload)
+const CATALOGUE: &[(&str, &str, &str, &[Field])] = &[
+    (EXECUTION_COMPLETED, "execute_workflow", "A workflow execution completed", EXECUTION_FIELDS),
+    (EXECUTION_FAILED, "execute_workflow", "A workflow execution failed", EXECUTION_FIELDS),
+    (EXECUTION_CANCELLED, "execute_workflow", "A workflow execution was cancelled", EXECUTION_FIELDS),
+    (PRICE_ALERT_TRIGGERED, "price_alert_service", "A price alert condition was met", PRICE_ALERT_FIELDS),
+    (STRATEGY_EXECUTED, "AutomatedStrategyManager", "An automated strategy executed", STRATEGY_EXECUTED_FIELDS),
+    (STRATEGY_FAILED, "AutomatedStrategyManager", "An automated strategy execution failed", STRATEGY_FAILED_FIELDS),
+    (STRATEGY_STOP_LOSS, "AutomatedStrategyManager", "A strategy hit its stop-loss and was stopped", STRATEGY_EXIT_FIELDS),
+    (STRATEGY_TAKE_PROFIT, "AutomatedStrategyManager", "A strategy hit its take-profit and was stopped", STRATEGY_EXIT_FIELDS),
+    (CYCLES_LOW, "CyclesMonitorService", "A monitored canister fell below a cycles threshold", CYCLES_LOW_FIELDS),
+    (CYCLES_TOPUP_REQUESTED, "CyclesMonitorService", "An automatic cycles top-up was requested", CYCLES_TOPUP_FIELDS),
+    (SPENDING_BLOCKED, "SpendingLimitsEnforcement", "A spending request was refused", SPENDING_BLOCKED_FIELDS),
+    (SPENDING_EXHAUSTED, "SpendingLimitsEnforcement", "A token approval was fully spent and deactivated", SPENDING_EXHAUSTED_FIELDS),
+    (CHAIN_EVM_LOG, "chain_watchers", "A watched contract emitted a matching log", EVM_LOG_FIELDS),
+    (CHAIN_BTC_UTXO, "chain_watchers", "A watched Bitcoin address received an output", BTC_UTXO_FIELDS),
+    (CHAIN_SOL_TRANSACTION, "chain_watchers", "A transaction touched a watched Solana account", SOL_TRANSACTION_FIELDS),
+    (CHAIN_SOL_BALANCE_CHANGED, "chain_watchers", "The balance of a watched Solana account changed", SOL_BALANCE_FIELDS),
+    (OAUTH_TOKEN_REVOKED, "oauth", "A saved OAuth token can no longer be refreshed; the account must be reconnected", OAUTH_REVOKED_FIELDS),
+];
+
+thread_local! {
+    static EMIT_WINDOWS: RefCell<HashMap<String, (u64, u32)>> = RefCell::new(HashMap::new());
+    static EVENT_SEQUENCE: RefCell<u64> = const { RefCell::new(0) };
+}
+
+#[query]
+pub fn list_system_events() -> Vec<SystemEventDefinition> {
+    CATALOGUE
+        .iter()
+        .map(|(event_type, source, description, payload)| SystemEventDefinition {
+            event_type: event_type.to_string(),
+            source: source.to_string(),
+            description: description.to_string(),
+            payload: payload
+                .iter()
+                .map(|(name, field_type, required, description)| EventPayloadField {
+                    name: name.to_string(),
+                    field_type: field_type.to_string(),
+                    required: *required,
+                    description: description.to_string(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+pub fn is_system_event(event_type: &str) -> bool {
+    CATALOGUE.iter().any(|(name, ..)| *name == event_type)
+}
+
+/// Queue a system event for dispatch to listeners and event-triggered workflows.
+pub fn emit(event_type: &str, data: HashMap<String, ConfigValue>) {
+    let now = ic_cdk::api::time();
+    let depth = chain_depth(&data);
+    let workflow_id = string_field(&data, "workflow_id");
+
+    if depth > MAX_EVENT_CHAIN_DEPTH {
+        ic_cdk::println!("Dropped {} from workflow {:?}: event chain deeper than {}", event_type, workflow_id, MAX_EVENT_CHAIN_DEPTH);
+        return;
+    }
+    let scope = workflow_id.clone().or_else(|| tenant_of(&data));
+    let key = format!("{}:{}", event_type, scope.as_deref().unwrap_or_default());
+    if !EMIT_WINDOWS.with(|windows| allow_event(&mut windows.borrow_mut(), key, now)) {
+        ic_cdk::println!("Dropped {} from workflow {:?}: more than {} per minute", event_type, workflow_id, MAX_EVENTS_PER_WINDOW);
+        return;
+    }
+    queue(event_type, data, workflow_id, now);
+}
+
+/// Queue an observation whose volume the caller already bounds, skipping the rate limit.
+pub fn emit_observed(event_type: &str, data: HashMap<String, ConfigValue>) {
+    queue(event_type, data, None, ic_cdk::api::time());
+}
+
+fn queue(event_type: &str, data: HashMap<String, ConfigValue>, workflow_id: Option<String>, now: u64) {
+    let sequence = EVENT_SEQUENCE.with(|sequence| {
+        let mut sequence = sequence.borrow_mut();
+        *sequence += 1;
+        *sequence
+    });
+    let event = WorkflowEvent {
+        id: format!("sysevt_{:x}_{}", now, sequence),
+        event_type: event_type.to_string(),
+        workflow_id,
+        execution_id: string_field(&data, "execution_id"),
+        data,
+        timestamp: now,
+    };
+
+    ic_cdk_timers::set_timer(Duration::ZERO, move || {
+        crate::events::dispatch_event(&event);
+    });
+}
+
+/// Principal a system event concerns: the `owner`, `user_id` or `user` of its payload.
+fn tenant_of(data: &HashMap<String, ConfigValue>) -> Option<String> {
+    ["owner", "user_id", "user"].iter().find_map(|key| string_field(data, key))
+}
+
+/// Whether a workflow owned by `owner` may be started by `event`. System events
+/// carry another principal's activity, so they only reach that principal's workflows.
+pub fn may_receive(event: &WorkflowEvent, owner: Option<&String>) -> bool {
+    if !is_system_event(&event.event_type) {
+        return true;
+    }
+    tenant_of(&event.data).is_some_and(|tenant| owner == Some(&tenant))
+}
+
+/// Trigger data for a run started by `event`: the payload plus `_event` metadata.
+pub fn trigger_data_for(event: &WorkflowEvent) -> HashMap<String, ConfigValue> {
+    let mut metadata = HashMap::new();
+    metadata.insert("id".to_string(), ConfigValue::String(event.id.clone()));
+    metadata.insert("event_type".to_string(), ConfigValue::String(event.event_type.clone()));
+    metadata.insert("chain_depth".to_string(), ConfigValue::Number(chain_depth(&event.data) as f64));
+
+    let mut data = event.data.clone();
+    data.insert(EVENT_METADATA_KEY.to_string(), ConfigValue::Object(metadata));
+    data
+}
+
+fn chain_depth(data: &HashMap<String, ConfigValue>) -> u32 {
+    match data.get("chain_depth") {
+        Some(ConfigValue::Number(depth)) if *depth > 0.0 => *depth as u32,
+        _ => 0,
+    }
+}
+
+/// Depth of the event that started a run, or None when no event started it.
+fn origin_depth(trigger_data: Option<&HashMap<String, ConfigValue>>) -> Option<u32> {
+    match trigger_data?.get(EVENT_METADATA_KEY)? {
+        ConfigValue::Object(metadata) => Some(chain_depth(metadata)),
+        _ => None,
+    }
+}
+
+fn allow_event(windows: &mut HashMap<String, (u64, u32)>, key: String, now: u64) -> bool {
+    if windows.len() > 1000 {
+        windows.retain(|_, (started, _)| now.saturating_sub(*started) < RATE_WINDOW_NS);
+    }
+    let window = windows.entry(key).or_insert((now, 0));
+    if now.saturating_sub(window.0) >= RATE_WINDOW_NS {
+        *window = (now, 0);
+    }
+    if window.1 >= MAX_EVENTS_PER_WINDOW {
+        return false;
+    }
+    window.1 += 1;
+    true
+}
+
+fn string_field(data: &HashMap<String, ConfigValue>, key: &str) -> Option<String> {
+    match data.get(key) {
+        Some(ConfigValue::String(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+struct Payload(HashMap<String, ConfigValue>);
+
+impl Payload {
+    fn new() -> Self {
+        Payload(HashMap::new())
+    }
+
+    fn text(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.0.insert(key.to_string(), ConfigValue::String(value.into()));
+        self
+    }
+
+    fn number(mut self, key: &str, value: f64) -> Self {
+        self.0.insert(key.to_string(), ConfigValue::Number(value));
+        self
+    }
+
+    fn flag(mut self, key: &str, value: bool) -> Self {
+        self.0.insert(key.to_string(), ConfigValue::Boolean(value));
+        self
+    }
+
+    fn maybe_text(self, key: &str, value: Option<&String>) -> Self {
+        match value {
+            Some(value) => self.text(key, value.clone()),
+            None => self,
+        }
+    }
+
+    fn maybe_number(self, key: &str, value: Option<f64>) -> Self {
+        match value {
+            Some(value) => self.number(key, value),
+            None => self,
+        }
+    }
+}
+
+fn ms(nanos: u64) -> f64 {
+    (nanos / 1_000_000) as f64
+}
+
+/// Payload for a finished execution; None while the execution can still run.
+pub fn execution_finished(
+    execution: &WorkflowExecution,
+    workflow_name: Option<&String>,
+    owner: Option<&String>,
+) -> Option<(&'static str, HashMap<String, ConfigValue>)> {
+    let event_type = match execution.status {
+        ExecutionStatus::Completed => EXECUTION_COMPLETED,
+        ExecutionStatus::Failed => EXECUTION_FAILED,
+        ExecutionStatus::Cancelled => EXECUTION_CANCELLED,
+        _ => return None,
+    };
+    let failed_node = execution
+        .node_executions
+        .iter()
+        .find(|node| matches!(node.status, ExecutionStatus::Failed))
+        .map(|node| &node.node_id);
+    let depth = origin_depth(execution.trigger_data.as_ref()).map_or(0, |depth| depth + 1);
+
+    let payload = Payload::new()
+        .text("workflow_id", execution.workflow_id.clone())
+        .maybe_text("workflow_name", workflow_name)
+        .maybe_text("owner", owner)
+        .text("execution_id", execution.id.clone())
+        .number("started_at_ms", ms(execution.started_at))
+        .maybe_number("completed_at_ms", execution.completed_at.map(ms))
+        .maybe_number(
+            "duration_ms",
+            execution.completed_at.map(|completed| ms(completed.saturating_sub(execution.started_at))),
+        )
+        .number("nodes_executed", execution.node_executions.len() as f64)
+        .maybe_text("error", execution.error_message.as_ref())
+        .maybe_text("failed_node_id", failed_node)
+        .maybe_text("initiated_by", execution.initiated_by.as_ref())
+        .maybe_text("parent_execution_id", execution.parent_execution_id.as_ref())
+        .number("chain_depth", depth as f64);
+    Some((event_type, payload.0))
+}
+
+pub fn price_alert_triggered(alert: &PriceAlert, price: &TokenPrice, condition: &str) -> HashMap<String, ConfigValue> {
+    let deactivated = alert.max_triggers.is_some_and(|max| alert.triggered_count >= max);
+    Payload::new()
+        .text("alert_id", alert.id.clone())
+        .text("user_id", alert.user_id.clone())
+        .text("token_symbol", alert.token_symbol.clone())
+        .number("price_usd", price.price_usd)
+        .number("change_24h", price.change_24h)
+        .text("condition", condition)
+        .number("triggered_count", alert.triggered_count as f64)
+        .flag("deactivated", deactivated)
+        .0
+}
+
+fn strategy_payload(strategy: &ActiveStrategy) -> Payload {
+    Payload::new()
+        .text("strategy_id", strategy.id.clone())
+        .text("user_id", strategy.user_id.clone())
+        .text("strategy_name", strategy.config.name.clone())
+}
+
+pub fn strategy_executed(strategy: &ActiveStrategy, result: &StrategyExecutionResult) -> HashMap<String, ConfigValue> {
+    strategy_payload(strategy)
+        .text("execution_id", result.execution_id.clone())
+        .text("action_type", result.action_type.clone())
+        .number("amount_usd", result.amount_usd)
+        .number("actual_return", result.actual_return)
+        .number("gas_cost_usd", result.gas_cost_usd)
+        .number("roi_percentage", strategy.performance_metrics.roi_percentage)
+        .number("total_pnl", strategy.performance_metrics.total_pnl)
+        .0
+}
+
+pub fn strategy_failed(strategy: &ActiveStrategy, error: &str) -> HashMap<String, ConfigValue> {
+    strategy_payload(strategy).text("error", error).0
+}
+
+pub fn strategy_exit(strategy: &ActiveStrategy, threshold_percentage: f64) -> HashMap<String, ConfigValue> {
+    strategy_payload(strategy)
+        .number("roi_percentage", strategy.performance_metrics.roi_percentage)
+        .number("threshold_percentage", threshold_percentage)
+        .number("total_pnl", strategy.performance_metrics.total_pnl)
+        .number("allocated_capital", strategy.allocated_capital)
+        .0
+}
+
+/// Payload for a cycles alert; top-up completions are not events.
+pub fn cycles_alert(alert: &CyclesAlert, owner: &str) -> Option<(&'static str, HashMap<String, ConfigValue>)> {
+    let payload = Payload::new()
+        .text("monitor_id", alert.monitor_id.clone())
+        .text("canister_id", alert.canister_id.clone())
+        .text("owner", owner)
+        .number("current_cycles", alert.current_cycles as f64);
+    let (event_type, payload) = match alert.alert_type {
+        AlertType::Warning => (CYCLES_LOW, payload.text("level", "warning").number("threshold", alert.threshold as f64)),
+        AlertType::Critical => (CYCLES_LOW, payload.text("level", "critical").number("threshold", alert.threshold as f64)),
+        AlertType::TopupRequested => (CYCLES_TOPUP_REQUESTED, payload.number("topup_amount", alert.threshold as f64)),
+        AlertType::TopupCompleted => return None,
+    };
+    Some((event_type, payload.0))
+}
+
+pub fn spending_blocked(user: &str, token_symbol: &str, amount: u64, operation: &str, error: &SpendingError) -> HashMap<String, ConfigValue> {
+    Payload::new()
+        .text("user", user)
+        .text("token_symbol", token_symbol)
+        .number("amount", amount as f64)
+        .text("operation", operation)
+        .text("reason", error.code())
+        .text("message", error.to_string())
+        .0
+}
+
+pub fn spending_exhausted(user: &str, token_symbol: &str, approved_amount: u64, operation: &str) -> HashMap<String, ConfigValue> {
+    Payload::new()
+        .text("user", user)
+        .text("token_symbol", token_symbol)
+        .number("approved_amount", approved_amount as f64)
+        .text("operation", operation)
+        .0
+}
+
+pub fn oauth_token_revoked(user: &str, platform: &str, provider: &str, error: &str) -> HashMap<String, ConfigValue> {
+    Payload::new()
+        .text("user", user)
+        .text("platform", platform)
+        .text("provider", provider)
+        .text("error", error)
+        .0
+}
+
+/// watch_id, owner and where the watch looks; EVM logs add their own contract address.
+fn watch_payload(watch: &ChainWatch) -> Payload {
+    let payload = Payload::new()
+        .text("watch_id", watch.id.clone())
+        .text("owner", watch.owner.clone());
+    match &watch.source {
+        WatchSource::EvmLogs { chain, .. } => payload.text("chain", chain.clone()),
+        WatchSource::BitcoinUtxos { network, address } | WatchSource::SolanaAccount { network, address } => {
+            payload.text("network", network.clone()).text("address", address.clone())
+        }
+    }
+}
+
+pub fn evm_log(watch: &ChainWatch, log: &EvmObservation, confirmations: u64) -> HashMap<String, ConfigValue> {
+    let mut payload = watch_payload(watch)
+        .text("address", log.address.clone())
+        .number("block_number", log.block_number as f64)
+        .text("block_hash", log.block_hash.clone())
+        .text("transaction_hash", log.transaction_hash.clone())
+        .number("log_index", log.log_index as f64)
+        .text("data", log.data.clone())
+        .number("confirmations", confirmations as f64);
+    for (index, topic) in log.topics.iter().take(4).enumerate() {
+        payload = payload.text(&format!("topic{}", index), topic.clone());
+    }
+    payload.0
+}
+
+pub fn btc_utxo(watch: &ChainWatch, utxo: &UtxoObservation, confirmations: u32) -> HashMap<String, ConfigValue> {
+    watch_payload(watch)
+        .text("txid", utxo.txid.clone())
+        .number("vout", utxo.vout as f64)
+        .number("value_satoshis", utxo.value_satoshis as f64)
+        .number("height", utxo.height as f64)
+        .number("confirmations", confirmations as f64)
+        .0
+}
+
+pub fn sol_transaction(watch: &ChainWatch, info: &SignatureInfo) -> HashMap<String, ConfigValue> {
+    watch_payload(watch)
+        .text("signature", info.signature.clone())
+        .number("slot", info.slot as f64)
+        .maybe_number("block_time", info.block_time.map(|time| time as f64))
+        .flag("success", info.err.is_none())
+        .maybe_text("error", info.err.as_ref().map(|err| err.to_string()).as_ref())
+        .maybe_text("memo", info.memo.as_ref())
+        .0
+}
+
+pub fn sol_balance_changed(watch: &ChainWatch, previous: u64, current: u64) -> HashMap<String, ConfigValue> {
+    watch_payload(watch)
+        .number("previous_lamports", previous as f64)
+        .number("balance_lamports", current as f64)
+        .number("change_lamports", current as f64 - previous as f64)
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NodeExecution;
+
+    /// Check a payload against the catalogue entry for its event type.
+    fn assert_conforms(event_type: &str, data: &HashMap<String, ConfigValue>) {
+        let (.., fields) = CATALOGUE.iter().find(|(name, ..)| *name == event_type).expect("event type in catalogue");
+        for (key, value) in data {
+            let (_, field_type, ..) = fields.iter().find(|(name, ..)| name == key)
+                .unwrap_or_else(|| panic!("{} has undocumented field {}", event_type, key));
+            let actual_type = match value {
+                ConfigValue::String(_) => "string",
+                ConfigValue::Number(_) => "number",
+                ConfigValue::Boolean(_) => "boolean",
+                _ => "structured",
+            };
+            assert_eq!(*field_type, actual_type, "{}.{}", event_type, key);
+        }
+        for (name, _, required, _) in fields.iter() {
+            assert!(!required || data.contains_key(*name), "{} is missing {}", event_type, name);
+        }
+    }
+
+    fn execution(status: ExecutionStatus, trigger_data: Option<HashMap<String, ConfigValue>>) -> WorkflowExecution {
+        WorkflowExecution {
+            id: "exec_1".to_string(),
+            workflow_id: "wf_1".to_string(),
+            status,
+            started_at: 1_700_000_000_000_000_000,
+            completed_at: Some(1_700_000_002_500_000_000),
+            trigger_data,
+            node_executions: vec![NodeExecution {
+                node_id: "http".to_string(),
+                status: ExecutionStatus::Failed,
+                started_at: Some(1_700_000_000_000_000_000),
+                completed_at: Some(1_700_000_002_000_000_000),
+                input_data: None,
+                output_data: None,
+                error_message: Some("timeout".to_string()),
+                retry_count: 0,
+                iteration: None,
+                attempts: None,
+            }],
+            error_message: Some("timeout".to_string()),
+            parent_execution_id: None,
+            checkpoint: None,
+            initiated_by: Some("aaaaa-aa".to_string()),
+            variables: None,
+            cancellation: None,
+            workflow_revision: None,
+        }
+    }
+
+    #[test]
+    fn catalogue_covers_every_builder() {
+        assert_eq!(list_system_events().len(), CATALOGUE.len());
+        assert!(is_system_event(CYCLES_LOW));
+        assert!(!is_system_event("user.signup"));
+
+        let (event_type, data) = execution_finished(&execution(ExecutionStatus::Failed, None), Some(&"Sync".to_string()), Some(&"aaaaa-aa".to_string())).unwrap();
+        assert_eq!(event_type, EXECUTION_FAILED);
+        assert_conforms(event_type, &data);
+        assert!(matches!(data.get("duration_ms"), Some(ConfigValue::Number(ms)) if *ms == 2500.0));
+        assert!(matches!(data.get("failed_node_id"), Some(ConfigValue::String(id)) if id == "http"));
+        assert!(execution_finished(&execution(ExecutionStatus::Running, None), None, None).is_none());
+
+        let alert = CyclesAlert {
+            monitor_id: "m1".to_string(),
+            canister_id: "current".to_string(),
+            alert_type: AlertType::Critical,
+            current_cycles: 500_000_000_000,
+            threshold: 1_000_000_000_000,
+            timestamp: 0,
+            notification_sent: false,
+        };
+        let (event_type, data) = cycles_alert(&alert, "owner").unwrap();
+        assert_conforms(event_type, &data);
+        let topup = CyclesAlert { alert_type: AlertType::TopupRequested, ..alert };
+        let (event_type, data) = cycles_alert(&topup, "owner").unwrap();
+        assert_conforms(event_type, &data);
+
+        let error = SpendingError::ExceedsDailyLimit { requested: 10, remaining: 5 };
+        assert_conforms(SPENDING_BLOCKED, &spending_blocked("user", "ETH", 10, "swap", &error));
+        assert_conforms(SPENDING_EXHAUSTED, &spending_exhausted("user", "ETH", 100, "swap"));
+    }
+
+    #[test]
+    fn execution_events_count_chain_depth() {
+        let (_, root) = execution_finished(&execution(ExecutionStatus::Completed, None), None, None).unwrap();
+        assert_eq!(chain_depth(&root), 0);
+
+        // A run started by the root event reports one more hop, and so on down the chain
+        let mut data = root;
+        for expected in 1..=3 {
+            let event = WorkflowEvent {
+                id: format!("evt_{}", expected),
+                event_type: EXECUTION_COMPLETED.to_string(),
+                workflow_id: None,
+                execution_id: None,
+                data,
+                timestamp: 0,
+            };
+            let triggered = execution(ExecutionStatus::Completed, Some(trigger_data_for(&event)));
+            data = execution_finished(&triggered, None, None).unwrap().1;
+            assert_eq!(chain_depth(&data), expected);
+        }
+    }
+
+    #[test]
+    fn system_events_only_reach_the_owners_workflows() {
+        let event = |event_type: &str, data: HashMap<String, ConfigValue>| WorkflowEvent {
+            id: "evt".to_string(),
+            event_type: event_type.to_string(),
+            workflow_id: None,
+            execution_id: None,
+            data,
+            timestamp: 0,
+        };
+        let alice = "alice".to_string();
+        let bob = "bob".to_string();
+
+        let spending = event(SPENDING_EXHAUSTED, spending_exhausted("alice", "ETH", 100, "swap"));
+        assert!(may_receive(&spending, Some(&alice)));
+        assert!(!may_receive(&spending, Some(&bob)));
+        assert!(!may_receive(&spending, None));
+
+        let (event_type, data) = execution_finished(&execution(ExecutionStatus::Completed, None), None, Some(&bob)).unwrap();
+        assert!(may_receive(&event(event_type, data), Some(&bob)));
+        let (event_type, data) = execution_finished(&execution(ExecutionStatus::Completed, None), None, None).unwrap();
+        assert!(!may_receive(&event(event_type, data), Some(&bob)));
+
+        // User events are not scoped to a principal
+        assert!(may_receive(&event("user.signup", HashMap::new()), None));
+    }
+
+    #[test]
+    fn rate_limit_resets_after_the_window() {
+        let mut windows = HashMap::new();
+        for _ in 0..MAX_EVENTS_PER_WINDOW {
+            assert!(allow_event(&mut windows, "cycles.low:".to_string(), 1_000));
+        }
+        assert!(!allow_event(&mut windows, "cycles.low:".to_string(), 2_000));
+        assert!(allow_event(&mut windows, "cycles.low:wf_2".to_string(), 2_000));
+        assert!(allow_event(&mut windows, "cycles.low:".to_string(), 1_000 + RATE_WINDOW_NS));
+    }
+}
@@ -18,6 +18,9 @@ pub struct Workflow {
     pub tags: Option<Vec<String>>,
     pub version: Option<String>,
     pub metadata: Option<WorkflowMetadata>,
+    pub variables: Option<HashMap<String, ConfigValue>>, // Initial values of workflow variables
+    pub max_concurrency: Option<u32>, // Nodes of one batch that may run at once (default 5, max 10)
+    pub revision: Option<u64>, // Latest entry in the workflow's revision log; set by the backend
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
@@ -81,6 +84,9 @@ impl Default for Workflow {
             tags: None,
             version: None,
             metadata: None,
+            variables: None,
+            max_concurrency: None,
+            revision: None,
         }
     }
 }
@@ -93,6 +99,7 @@ impl Default for WorkflowNode {
             position: NodePosition::default(),
             configuration: NodeConfiguration::default(),
             metadata: NodeMetadata::default(),
+            on_error: None,
         }
     }
 }
@@ -128,6 +135,7 @@ pub struct WorkflowNode {
     pub position: NodePosition,
     pub configuration: NodeConfiguration,
     pub metadata: NodeMetadata,
+    pub on_error: Option<FallbackStrategy>, // What happens when the node fails after its retries (default: stop)
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -171,7 +179,33 @@ pub enum WorkflowTrigger {
     Manual,
     Schedule { cron: String },
     Webhook { path: String },
-    Event { event_type: String, conditions: HashMap<String, ConfigValue> },
+    Event { event_type: String, conditions: HashMap<String, ConfigValue>, condition: Option<EventCondition> },
+}
+
+/// Structured condition over event data. Paths use dots and indices (`tx.logs[0].topics[1]`).
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub enum EventCondition {
+    Compare { path: String, operator: ConditionOperator, value: Option<ConfigValue> },
+    All(Vec<EventCondition>),
+    Any(Vec<EventCondition>),
+    Not(Box<EventCondition>),
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum ConditionOperator {
+    Equals,
+    NotEquals,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    In,
+    NotIn,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Exists,
+    NotExists,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -184,6 +218,12 @@ pub struct WorkflowExecution {
     pub trigger_data: Option<HashMap<String, ConfigValue>>,
     pub node_executions: Vec<NodeExecution>,
     pub error_message: Option<String>,
+    pub parent_execution_id: Option<String>, // Set when started by a sub_workflow node
+    pub checkpoint: Option<ExecutionCheckpoint>, // Saved while the execution is Waiting
+    pub initiated_by: Option<String>, // Principal that started the run (owner for scheduled/event triggers)
+    pub variables: Option<HashMap<String, ConfigValue>>, // Current workflow variables
+    pub cancellation: Option<ExecutionCancellation>, // Who cancelled the execution and why
+    pub workflow_revision: Option<u64>, // Revision of the workflow the execution ran
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -193,6 +233,25 @@ pub enum ExecutionStatus {
     Completed,
     Failed,
     Cancelled,
+    Skipped, // Node sat on a branch that was not taken
+    Waiting { resume_at: u64 }, // Suspended by a delay node until resume_at (ns)
+    Paused, // Stopped by pause_execution; continues from its checkpoint on resume_execution
+}
+
+/// Graph state saved while an execution is suspended so it can continue where it stopped.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct ExecutionCheckpoint {
+    pub node_outputs: HashMap<String, HashMap<String, ConfigValue>>,
+    pub node_branches: HashMap<String, Vec<String>>,
+    pub remaining_nodes: Vec<Vec<String>>, // Topological batches still to run
+    pub resume_at: Option<u64>, // End of the delay that was pending when the execution was paused
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ExecutionCancellation {
+    pub cancelled_by: String, // Principal ID
+    pub reason: Option<String>,
+    pub cancelled_at: u64,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -205,6 +264,18 @@ pub struct NodeExecution {
     pub output_data: Option<HashMap<String, ConfigValue>>,
     pub error_message: Option<String>,
     pub retry_count: u32,
+    pub iteration: Option<u32>, // Loop index when executed inside a for_each body
+    pub attempts: Option<Vec<NodeAttempt>>, // One entry per try, including retries
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct NodeAttempt {
+    pub attempt: u32,
+    pub started_at: u64,
+    pub completed_at: u64,
+    pub error_message: Option<String>,
+    pub error_class: Option<String>, // NodeError class of a failed attempt
+    pub retry_delay_ms: Option<u64>, // Backoff before the next attempt, if one was scheduled
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -258,6 +329,54 @@ pub enum NodeError {
     TimeoutError,
 }
 
+impl NodeError {
+    pub const CLASSES: [&'static str; 5] = [
+        "ConfigurationError", "ExecutionError", "NetworkError", "ValidationError", "TimeoutError",
+    ];
+    
+    /// Class name matched against `RetryPolicy::retry_on_errors`.
+    pub fn class(&self) -> &'static str {
+        match self {
+            NodeError::ConfigurationError(_) => "ConfigurationError",
+            NodeError::ExecutionError(_) => "ExecutionError",
+            NodeError::NetworkError(_) => "NetworkError",
+            NodeError::ValidationError(_) => "ValidationError",
+            NodeError::TimeoutError => "TimeoutError",
+        }
+    }
+    
+    /// The same class with a different message (timeouts carry none).
+    pub fn map_message(self, f: impl FnOnce(String) -> String) -> Self {
+        match self {
+            NodeError::ConfigurationError(message) => NodeError::ConfigurationError(f(message)),
+            NodeError::ExecutionError(message) => NodeError::ExecutionError(f(message)),
+            NodeError::NetworkError(message) => NodeError::NetworkError(f(message)),
+            NodeError::ValidationError(message) => NodeError::ValidationError(f(message)),
+            NodeError::TimeoutError => NodeError::TimeoutError,
+        }
+    }
+}
+
+impl std::fmt::Display for NodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeError::ConfigurationError(message)
+            | NodeError::ExecutionError(message)
+            | NodeError::NetworkError(message)
+            | NodeError::ValidationError(message) => write!(f, "{}", message),
+            NodeError::TimeoutError => write!(f, "Node timed out"),
+        }
+    }
+}
+
+/// Executors that report plain messages have not classified the failure, so it is an
+/// ExecutionError, which retry policies skip unless they list it.
+impl From<String> for NodeError {
+    fn from(message: String) -> Self {
+        NodeError::ExecutionError(message)
+    }
+}
+
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub enum ValidationError {
     MissingRequiredParameter(String),
@@ -289,19 +408,32 @@ impl Default for RetryPolicy {
             max_delay_ms: 30000,
             retry_on_errors: vec![
                 "NetworkError".to_string(),
-                "TimeoutError".to_string(), 
-                "TemporaryError".to_string(),
+                "TimeoutError".to_string(),
             ],
         }
     }
 }
 
+impl RetryPolicy {
+    /// Policy for a node type without a stored one. Nodes that post to external services
+    /// are not resent: a failure after the platform accepted the send would deliver twice.
+    pub fn default_for(node_type: &str) -> Self {
+        match node_type {
+            "telegram" | "discord" | "twitter" | "facebook" | "instagram" | "linkedin" | "email" | "webhook" => {
+                RetryPolicy { max_retries: 0, ..Self::default() }
+            }
+            _ => Self::default(),
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct EventListener {
     pub id: String,
     pub workflow_id: String,
     pub event_type: String,
-    pub conditions: HashMap<String, ConfigValue>,
+    pub conditions: HashMap<String, ConfigValue>, // Exact matches; keys may be paths
+    pub condition: Option<EventCondition>,
     pub active: bool,
 }
 
@@ -313,9 +445,85 @@ pub struct ScheduledWorkflow {
     pub next_execution: u64,
     pub active: bool,
     pub timer_id: Option<String>,
+    // Records written before one-shot and interval schedules existed are cron schedules
+    pub spec: Option<ScheduleSpec>,
+    pub missed_run_policy: Option<MissedRunPolicy>,
+    pub max_runs: Option<u64>,
+    pub end_at: Option<u64>,
+    pub run_count: Option<u64>,
+    pub last_run_at: Option<u64>,
+    pub history: Option<Vec<ScheduleRun>>,
+    pub created_at: Option<u64>,
+}
+
+impl ScheduledWorkflow {
+    pub fn spec(&self) -> ScheduleSpec {
+        self.spec.clone().unwrap_or_else(|| ScheduleSpec::Cron {
+            expression: self.cron_expression.clone(),
+            timezone: None,
+        })
+    }
+
+    pub fn missed_run_policy(&self) -> MissedRunPolicy {
+        self.missed_run_policy.clone().unwrap_or_default()
+    }
+
+    /// True once the schedule has no further runs: its run limit is reached or
+    /// the scheduler found no next fire time (stored as `next_execution == 0`).
+    pub fn is_complete(&self) -> bool {
+        let runs = self.run_count.unwrap_or(0);
+        let limit = match self.spec() {
+            ScheduleSpec::Once { .. } => Some(1),
+            _ => self.max_runs,
+        };
+        self.next_execution == 0 || limit.is_some_and(|limit| runs >= limit)
+    }
+}
+
+/// When a schedule fires. Times are nanoseconds since the epoch.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum ScheduleSpec {
+    Once { at: u64 },
+    Interval { seconds: u64, start_at: Option<u64> },
+    Cron { expression: String, timezone: Option<String> },
 }
 
-// Enhanced persistent timer system
+/// What to do with fire times that passed while the schedule could not run
+/// (paused, or the canister was stopped or upgrading).
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub enum MissedRunPolicy {
+    Skip,
+    #[default]
+    RunOnce,
+    CatchUp,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum ScheduleRunStatus {
+    Started,
+    Skipped,
+    Failed,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ScheduleRun {
+    pub scheduled_for: u64,
+    pub fired_at: u64,
+    pub status: ScheduleRunStatus,
+    pub execution_id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ScheduleRequest {
+    pub workflow_id: String,
+    pub spec: ScheduleSpec,
+    pub missed_run_policy: Option<MissedRunPolicy>,
+    pub max_runs: Option<u64>,
+    pub end_at: Option<u64>,
+}
+
+// Legacy persistent timer record, migrated into `ScheduledWorkflow` on upgrade
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct ScheduledExecution {
     pub workflow_id: String,
@@ -342,6 +550,82 @@ pub struct WebhookEvent {
     pub source: String,
 }
 
+/// Inbound webhook route served at `/webhook/<path>`.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WebhookConfig {
+    pub path: String,
+    pub workflow_id: String,
+    pub verification: WebhookVerification,
+    pub replay_protection: Option<ReplayProtection>,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum WebhookVerification {
+    None,
+    // Sent as `Authorization: Bearer <token>`, an `X-Webhook-Token` header or a `token` query parameter
+    SharedToken { token: String },
+    // Hex HMAC-SHA256 of the raw body (or `<timestamp>.<body>` with replay protection),
+    // optionally prefixed with `sha256=` as GitHub does
+    HmacSha256 { secret: String, signature_header: String },
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ReplayProtection {
+    pub timestamp_header: String,       // Unix seconds or milliseconds
+    pub nonce_header: Option<String>,   // Defaults to the signature itself
+    pub tolerance_seconds: u64,
+}
+
+/// On-chain activity polled by `chain_watchers`; matches are emitted as `chain.*` events.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ChainWatch {
+    pub id: String,
+    pub owner: String,
+    pub source: WatchSource,
+    pub confirmations: u32,
+    pub poll_interval_seconds: u64,
+    pub active: bool,
+    pub created_at: u64,
+    pub cursor: WatchCursor,
+    pub last_polled_at: Option<u64>,
+    pub last_error: Option<String>,
+    pub events_emitted: u64,
+    pub reorgs_detected: u64, // Observations that vanished or moved before reaching confirmation depth
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub enum WatchSource {
+    // topics[i] lists accepted values for topic i; an empty list matches anything
+    EvmLogs { chain: String, addresses: Vec<String>, topics: Vec<Vec<String>>, from_block: Option<u64> },
+    BitcoinUtxos { network: String, address: String },
+    SolanaAccount { network: String, address: String },
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct WatchCursor {
+    pub block: Option<u64>,        // EVM block / Bitcoin height processed up to, inclusive
+    pub signature: Option<String>, // Newest Solana signature processed
+    pub balance: Option<u64>,      // Solana balance at the last poll
+    pub seen: Vec<String>,         // "<block>:<key>" emitted from blocks the cursor has not passed
+    pub pending: Vec<PendingObservation>,
+}
+
+/// Matching activity not yet deep enough to emit.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PendingObservation {
+    pub key: String,
+    pub block_number: u64,
+    pub block_hash: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ChainWatchRequest {
+    pub source: WatchSource,
+    pub confirmations: Option<u32>,
+    pub poll_interval_seconds: Option<u64>,
+}
+
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct WorkflowEvent {
     pub id: String,
@@ -352,6 +636,191 @@ pub struct WorkflowEvent {
     pub timestamp: u64,
 }
 
+/// Event type raised by the canister itself; listeners subscribe to it like any other event.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct SystemEventDefinition {
+    pub event_type: String,
+    pub source: String,
+    pub description: String,
+    pub payload: Vec<EventPayloadField>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct EventPayloadField {
+    pub name: String,
+    pub field_type: String, // string, number or boolean
+    pub required: bool,
+    pub description: String,
+}
+
+/// Access to a workflow granted to someone other than its owner. Each role includes
+/// the ones before it.
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WorkflowRole {
+    Viewer, // Read the workflow and its executions
+    Runner, // Start, retry, pause, resume and cancel executions
+    Editor, // Change the workflow, its schedules and its webhooks
+    Admin,  // Delete, publish and share the workflow; only the owner grants Admin
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct WorkflowShare {
+    pub principal: String,
+    pub role: WorkflowRole,
+    pub granted_by: String,
+    pub granted_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct WorkflowAcl {
+    pub workflow_id: String,
+    pub shares: Vec<WorkflowShare>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct AclAuditEntry {
+    pub id: u64,
+    pub workflow_id: String,
+    pub actor: String,
+    pub change: AclChange,
+    pub timestamp: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum AclChange {
+    Granted { principal: String, role: WorkflowRole, previous: Option<WorkflowRole> },
+    Revoked { principal: String, role: WorkflowRole },
+}
+
+/// Immutable snapshot of a workflow, written on every change.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WorkflowRevision {
+    pub workflow_id: String,
+    pub revision: u64,
+    pub workflow: Workflow,
+    pub author: String,
+    pub created_at: u64,
+    pub rolled_back_from: Option<u64>, // Revision this snapshot was restored from, for rollbacks
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WorkflowRevisionSummary {
+    pub revision: u64,
+    pub author: String,
+    pub created_at: u64,
+    pub rolled_back_from: Option<u64>,
+    pub node_count: u32,
+}
+
+/// Structural differences between two revisions of a workflow.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct WorkflowDiff {
+    pub workflow_id: String,
+    pub from_revision: u64,
+    pub to_revision: u64,
+    pub fields_changed: Vec<String>, // Workflow-level fields such as name or triggers
+    pub variable_changes: Vec<ConfigChange>,
+    pub nodes_added: Vec<WorkflowNode>,
+    pub nodes_removed: Vec<WorkflowNode>,
+    pub nodes_changed: Vec<NodeDiff>,
+    pub connections_added: Vec<NodeConnection>,
+    pub connections_removed: Vec<NodeConnection>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct NodeDiff {
+    pub node_id: String,
+    pub fields_changed: Vec<String>, // node_type, position, metadata or on_error
+    pub config_changes: Vec<ConfigChange>,
+}
+
+/// A configuration value that was added (no `before`), removed (no `after`) or changed.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct ConfigChange {
+    pub path: String, // Parameter name, with dots into nested objects
+    pub before: Option<ConfigValue>,
+    pub after: Option<ConfigValue>,
+}
+
+/// A secret in the credential vault, sealed under one of the vault keys.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct StoredCredential {
+    pub id: String,
+    pub owner: String,
+    pub integration_type: String, // Node type allowed to use the secret ("http" for http_request and webhook)
+    pub ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub key_version: u32,
+    pub version: u32, // Incremented on every rotation of the secret
+    pub created_at: u64,
+    pub rotated_at: Option<u64>,
+    pub last_used_at: Option<u64>,
+}
+
+/// What callers see of a vault credential; the secret never leaves the canister.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct CredentialInfo {
+    pub id: String,
+    pub integration_type: String,
+    pub version: u32,
+    pub created_at: u64,
+    pub rotated_at: Option<u64>,
+    pub last_used_at: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct VaultKey {
+    pub version: u32,
+    pub key: Vec<u8>,
+    pub nonces_used: u64, // Nonces are a counter, so each is used once under this key
+    pub created_at: u64,
+}
+
+/// OAuth client the canister refreshes tokens with; the client secret is kept in the credential vault.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct OAuthClient {
+    pub provider: String,
+    pub client_id: String,
+    pub has_client_secret: bool,
+    pub relay_url: Option<String>, // Token relay refresh exchanges go through; none disables refreshing
+    pub configured_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct OAuthProviderInfo {
+    pub provider: String,
+    pub platforms: Vec<String>, // Platform names tokens are saved under with save_oauth_token
+    pub token_url: String,
+    pub client: Option<OAuthClient>,
+}
+
+/// Stored schema versions of every stable map, and what each migration touches.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct SchemaMigrationReport {
+    pub dry_run: bool,
+    pub stores: Vec<StoreSchemaStatus>,
+    pub migrations: Vec<MigrationStep>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct StoreSchemaStatus {
+    pub store: String,
+    pub collection: String,
+    pub current_version: u16,
+    pub records_by_version: Vec<(u16, u64)>,
+    pub unreadable: u64, // Records no migration path can decode; left as they are
+    pub migrated: u64,   // Records rewritten at the current version, always 0 in a dry run
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct MigrationStep {
+    pub collection: String,
+    pub from_version: u16,
+    pub to_version: u16,
+    pub description: String,
+    pub records: u64, // Records stored at or below `from_version`
+}
+
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct CircuitBreaker {
     pub node_type: String,
@@ -531,10 +1000,11 @@ pub struct WorkflowRecovery {
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub enum FallbackStrategy {
     UseAlternativeNode { node_id: String },
-    SkipNode,
+    SkipNode, // Continue the workflow; nodes that depend only on this one are skipped
     StopExecution,
     UseDefaultValue { value: ConfigValue },
     NotifyAndContinue,
+    RouteToErrorPort, // Activate the node's "error" output with the error message and type
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -551,7 +1021,7 @@ impl Default for WorkflowRecovery {
         Self {
             max_retries: 3,
             retry_delay_ms: 5000,
-            fallback_strategy: Some(FallbackStrategy::NotifyAndContinue),
+            fallback_strategy: None,
             emergency_actions: vec![
                 EmergencyAction::SendNotification {
                     recipient: "admin".to_string(),
@@ -592,6 +1062,10 @@ impl SubscriptionTier {
                 "condition".to_string(),
                 "transform".to_string(),
                 "timer".to_string(),
+                "for_each".to_string(),
+                "sub_workflow".to_string(),
+                "set_variable".to_string(),
+                "get_variable".to_string(),
             ],
             SubscriptionTier::Premium | SubscriptionTier::Pro => vec![
                 "telegram".to_string(),
@@ -608,6 +1082,10 @@ impl SubscriptionTier {
                 "condition".to_string(),
                 "transform".to_string(),
                 "timer".to_string(),
+                "for_each".to_string(),
+                "sub_workflow".to_string(),
+                "set_variable".to_string(),
+                "get_variable".to_string(),
                 // DeFi nodes
                 "bitcoin_portfolio".to_string(),
                 "bitcoin_send".to_string(),
@@ -638,6 +1116,14 @@ impl SubscriptionTier {
             SubscriptionTier::Pro => 0.001,       // 0.1%
         }
     }
+    
+    pub fn max_chain_watches(&self) -> usize {
+        match self {
+            SubscriptionTier::Standard => 0,
+            SubscriptionTier::Premium => 5,
+            SubscriptionTier::Pro => 20,
+        }
+    }
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -728,8 +1214,8 @@ pub struct EncryptedCredentials {
 pub struct OAuthToken {
     pub user_principal: String,
     pub platform: String,
-    pub access_token: String,    // Should be encrypted
-    pub refresh_token: Option<String>, // Should be encrypted
+    pub access_token: String,    // Empty while stored; sealed in the credential vault
+    pub refresh_token: Option<String>, // Some("") while stored when one is sealed
     pub expires_at: u64,
     pub scopes: Vec<String>,
 }
@@ -826,6 +1312,12 @@ impl Default for WorkflowExecution {
             trigger_data: None,
             node_executions: Vec::new(),
             error_message: None,
+            parent_execution_id: None,
+            checkpoint: None,
+            initiated_by: None,
+            variables: None,
+            cancellation: None,
+            workflow_revision: None,
         }
     }
 }
@@ -847,6 +1339,8 @@ impl Default for NodeExecution {
             output_data: None,
             error_message: None,
             retry_count: 0,
+            iteration: None,
+            attempts: None,
         }
     }
 }
@@ -887,6 +1381,57 @@ impl Default for ScheduledWorkflow {
             next_execution: 0,
             active: false,
             timer_id: None,
+            spec: None,
+            missed_run_policy: None,
+            max_runs: None,
+            end_at: None,
+            run_count: None,
+            last_run_at: None,
+            history: None,
+            created_at: None,
+        }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            path: "default".to_string(),
+            workflow_id: "default".to_string(),
+            verification: WebhookVerification::None,
+            replay_protection: None,
+            created_at: 0,
+        }
+    }
+}
+
+impl Default for ChainWatch {
+    fn default() -> Self {
+        Self {
+            id: "default".to_string(),
+            owner: "default".to_string(),
+            source: WatchSource::BitcoinUtxos { network: "mainnet".to_string(), address: String::new() },
+            confirmations: 0,
+            poll_interval_seconds: 0,
+            active: false,
+            created_at: 0,
+            cursor: WatchCursor::default(),
+            last_polled_at: None,
+            last_error: None,
+            events_emitted: 0,
+            reorgs_detected: 0,
+        }
+    }
+}
+
+impl Default for AclAuditEntry {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            workflow_id: "default".to_string(),
+            actor: "default".to_string(),
+            change: AclChange::Revoked { principal: "default".to_string(), role: WorkflowRole::Viewer },
+            timestamp: 0,
         }
     }
 }
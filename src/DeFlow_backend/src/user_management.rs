@@ -12,6 +12,7 @@ use crate::stable_user_storage::{
     get_api_connections, insert_api_connection,
     get_user_templates, get_public_templates, insert_template
 };
+use crate::credentials;
 use ic_cdk::{query, update, caller, api};
 use candid::{CandidType, Deserialize, Principal};
 
@@ -262,8 +263,10 @@ pub fn update_user_settings(settings: UserSettings) -> Result<UserSettings, Stri
 
 // ===== INTEGRATION CREDENTIALS API =====
 
+/// `expires_at` may be in seconds, milliseconds or nanoseconds, 0 for tokens that do not expire.
+/// Tokens with a `refresh_token` are refreshed by the canister before they expire.
 #[update]
-pub fn save_oauth_token(platform: String, access_token: String, refresh_token: Option<String>, expires_at: u64, scopes: Vec<String>) -> Result<(), String> {
+pub async fn save_oauth_token(platform: String, access_token: String, refresh_token: Option<String>, expires_at: u64, scopes: Vec<String>) -> Result<(), String> {
     let principal = caller();
     let principal_id = principal.to_text();
     
@@ -275,14 +278,14 @@ pub fn save_oauth_token(platform: String, access_token: String, refresh_token: O
     let token = OAuthToken {
         user_principal: principal_id.clone(),
         platform: platform.clone(),
-        access_token, // In production, this should be encrypted
-        refresh_token, // In production, this should be encrypted
+        access_token,
+        refresh_token,
         expires_at,
         scopes,
     };
     
-    insert_oauth_token(principal_id, platform, token);
-    Ok(())
+    credentials::ensure_vault_key().await?;
+    insert_oauth_token(principal_id, platform, token, api::time())
 }
 
 #[query]
@@ -290,7 +293,7 @@ pub fn get_oauth_token_api(platform: String) -> Result<OAuthToken, String> {
     let principal = caller();
     let principal_id = principal.to_text();
     
-    get_oauth_token(&principal_id, &platform)
+    get_oauth_token(&principal_id, &platform)?
         .ok_or_else(|| format!("OAuth token for {} not found", platform))
 }
 
@@ -0,0 +1,653 @@
+// Inbound webhooks.
+//
+// External services (GitHub, Stripe, TradingView, Alchemy Notify, ...) call
+// `POST /webhook/<path>` on the canister's HTTP interface. `http_request` is a
+// query, so it only routes the call and asks the gateway to upgrade it;
+// `http_request_update` then authenticates the request against the route's
+// `WebhookConfig`, rejects replays, turns the JSON or form body into trigger
+// data and starts the registered workflow. The response carries the execution id.
+
+use crate::acl;
+use crate::execution::trigger_execution;
+use crate::http_client::json_to_config_value;
+use crate::storage;
+use crate::types::{ConfigValue, ReplayProtection, WebhookConfig, WebhookEvent, WebhookVerification, WorkflowRole};
+use candid::{CandidType, Deserialize};
+use hmac::{Hmac, Mac};
+use ic_cdk::{api, caller, query, update};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+const WEBHOOK_PREFIX: &str = "/webhook/";
+const MAX_BODY_BYTES: usize = 512 * 1024;
+const MAX_PATH_LENGTH: usize = 128;
+const MIN_SECRET_LENGTH: usize = 16;
+const MAX_TOLERANCE_SECONDS: u64 = 3600;
+const MAX_SEEN_NONCES: usize = 10_000;
+const TOKEN_HEADER: &str = "x-webhook-token";
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub upgrade: Option<bool>,
+}
+
+#[derive(Debug, PartialEq)]
+struct WebhookError {
+    status: u16,
+    message: String,
+}
+
+impl WebhookError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+}
+
+thread_local! {
+    // "<path>:<nonce>" -> expiry. Entries only need to outlive the replay window.
+    static SEEN_NONCES: RefCell<HashMap<String, u64>> = RefCell::new(HashMap::new());
+}
+
+// =============================================================================
+// HTTP INTERFACE
+// =============================================================================
+
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    let Some((path, _)) = split_webhook_url(&request.url) else {
+        return error_response(&WebhookError::new(404, "Not found"));
+    };
+    if !request.method.eq_ignore_ascii_case("POST") {
+        return error_response(&WebhookError::new(405, "Webhooks only accept POST"));
+    }
+    if storage::get_webhook(&path).is_none() {
+        return error_response(&WebhookError::new(404, "Webhook endpoint not found"));
+    }
+
+    HttpResponse {
+        status_code: 200,
+        headers: Vec::new(),
+        body: Vec::new(),
+        upgrade: Some(true),
+    }
+}
+
+#[update]
+pub fn http_request_update(request: HttpRequest) -> HttpResponse {
+    let accepted = accept_webhook(&request, api::time()).and_then(|(webhook, data)| {
+        trigger_execution(webhook.workflow_id, Some(data))
+            .map_err(|e| WebhookError::new(422, e))
+    });
+
+    match accepted {
+        Ok(execution_id) => json_response(202, serde_json::json!({ "execution_id": execution_id })),
+        Err(error) => error_response(&error),
+    }
+}
+
+/// Route, authenticate and parse an inbound webhook call.
+fn accept_webhook(request: &HttpRequest, now: u64) -> Result<(WebhookConfig, HashMap<String, ConfigValue>), WebhookError> {
+    if !request.method.eq_ignore_ascii_case("POST") {
+        return Err(WebhookError::new(405, "Webhooks only accept POST"));
+    }
+    let (path, query) = split_webhook_url(&request.url)
+        .ok_or_else(|| WebhookError::new(404, "Not found"))?;
+    let webhook = storage::get_webhook(&path)
+        .ok_or_else(|| WebhookError::new(404, "Webhook endpoint not found"))?;
+    if request.body.len() > MAX_BODY_BYTES {
+        return Err(WebhookError::new(413, format!("Body exceeds {} bytes", MAX_BODY_BYTES)));
+    }
+
+    let headers: HashMap<String, String> = request.headers.iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let query = parse_form(&query);
+
+    let timestamp = match &webhook.replay_protection {
+        Some(protection) => Some(check_timestamp(protection, &headers, now)?),
+        None => None,
+    };
+    verify_request(&webhook.verification, &headers, &query, &request.body, timestamp.as_deref())?;
+
+    let mut data = parse_body(&headers, &request.body)?;
+    data.insert("_webhook".to_string(), webhook_metadata(&webhook, &headers, query, now));
+
+    if let (Some(protection), Some(timestamp)) = (&webhook.replay_protection, &timestamp) {
+        let nonce = match &protection.nonce_header {
+            Some(header) => headers.get(&header.to_ascii_lowercase()).cloned()
+                .ok_or_else(|| WebhookError::new(400, format!("Missing {} header", header)))?,
+            None => hex::encode(Sha256::digest(signed_payload(&request.body, Some(timestamp)))),
+        };
+        remember_nonce(&path, &nonce, now, now + protection.tolerance_seconds * NANOS_PER_SECOND)?;
+    }
+
+    Ok((webhook, data))
+}
+
+/// "/webhook/github?x=1" -> ("github", "x=1")
+fn split_webhook_url(url: &str) -> Option<(String, String)> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let path = path.strip_prefix(WEBHOOK_PREFIX)?.trim_matches('/');
+    if path.is_empty() {
+        return None;
+    }
+    Some((path.to_string(), query.to_string()))
+}
+
+// =============================================================================
+// VERIFICATION
+// =============================================================================
+
+fn verify_request(
+    verification: &WebhookVerification,
+    headers: &HashMap<String, String>,
+    query: &HashMap<String, ConfigValue>,
+    body: &[u8],
+    timestamp: Option<&str>,
+) -> Result<(), WebhookError> {
+    match verification {
+        WebhookVerification::None => Ok(()),
+        WebhookVerification::SharedToken { token } => {
+            let provided = headers.get("authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .or_else(|| headers.get(TOKEN_HEADER).map(String::as_str))
+                .or_else(|| match query.get("token") {
+                    Some(ConfigValue::String(value)) => Some(value.as_str()),
+                    _ => None,
+                });
+            match provided {
+                Some(provided) if constant_time_eq(provided.as_bytes(), token.as_bytes()) => Ok(()),
+                Some(_) => Err(WebhookError::new(401, "Invalid webhook token")),
+                None => Err(WebhookError::new(401, "Missing webhook token")),
+            }
+        }
+        WebhookVerification::HmacSha256 { secret, signature_header } => {
+            let header = headers.get(&signature_header.to_ascii_lowercase())
+                .ok_or_else(|| WebhookError::new(401, format!("Missing {} header", signature_header)))?;
+            let signature = hex::decode(header.strip_prefix("sha256=").unwrap_or(header))
+                .map_err(|_| WebhookError::new(401, "Signature is not valid hex"))?;
+
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                .map_err(|_| WebhookError::new(500, "Invalid webhook secret"))?;
+            mac.update(&signed_payload(body, timestamp));
+            mac.verify_slice(&signature)
+                .map_err(|_| WebhookError::new(401, "Invalid webhook signature"))
+        }
+    }
+}
+
+/// With replay protection the timestamp is signed too, as `<timestamp>.<body>`.
+fn signed_payload(body: &[u8], timestamp: Option<&str>) -> Vec<u8> {
+    match timestamp {
+        Some(timestamp) => [timestamp.as_bytes(), b".", body].concat(),
+        None => body.to_vec(),
+    }
+}
+
+fn check_timestamp(protection: &ReplayProtection, headers: &HashMap<String, String>, now: u64) -> Result<String, WebhookError> {
+    let raw = headers.get(&protection.timestamp_header.to_ascii_lowercase())
+        .ok_or_else(|| WebhookError::new(401, format!("Missing {} header", protection.timestamp_header)))?;
+    let value: u64 = raw.parse()
+        .map_err(|_| WebhookError::new(401, "Webhook timestamp is not a number"))?;
+    // Accept milliseconds as well as seconds
+    let seconds = if value > 100_000_000_000 { value / 1000 } else { value };
+
+    if seconds.abs_diff(now / NANOS_PER_SECOND) > protection.tolerance_seconds {
+        return Err(WebhookError::new(401, "Webhook timestamp is outside the allowed window"));
+    }
+    Ok(raw.clone())
+}
+
+fn remember_nonce(path: &str, nonce: &str, now: u64, expires_at: u64) -> Result<(), WebhookError> {
+    SEEN_NONCES.with(|seen| {
+        let mut seen = seen.borrow_mut();
+        seen.retain(|_, expiry| *expiry > now);
+
+        let key = format!("{}:{}", path, nonce);
+        if seen.contains_key(&key) {
+            return Err(WebhookError::new(409, "Webhook request was already processed"));
+        }
+        if seen.len() >= MAX_SEEN_NONCES {
+            return Err(WebhookError::new(429, "Too many webhook requests"));
+        }
+        seen.insert(key, expires_at);
+        Ok(())
+    })
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// =============================================================================
+// BODY PARSING
+// =============================================================================
+
+fn parse_body(headers: &HashMap<String, String>, body: &[u8]) -> Result<HashMap<String, ConfigValue>, WebhookError> {
+    if body.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let content_type = headers.get("content-type")
+        .map(|value| value.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    if content_type == "application/x-www-form-urlencoded" {
+        let text = std::str::from_utf8(body)
+            .map_err(|_| WebhookError::new(400, "Form body is not valid UTF-8"))?;
+        return Ok(parse_form(text));
+    }
+
+    let is_json = content_type == "application/json" || content_type.ends_with("+json");
+    let is_text = content_type.is_empty() || content_type.starts_with("text/");
+    if !is_json && !is_text {
+        return Err(WebhookError::new(415, format!("Unsupported content type '{}'", content_type)));
+    }
+
+    let text = std::str::from_utf8(body)
+        .map_err(|_| WebhookError::new(400, "Body is not valid UTF-8"))?;
+    // Some senders (TradingView among them) post JSON as text/plain
+    let looks_like_json = text.trim_start().starts_with(['{', '[']);
+    if is_json || looks_like_json {
+        match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(serde_json::Value::Object(fields)) => {
+                return Ok(fields.iter().map(|(k, v)| (k.clone(), json_to_config_value(v))).collect());
+            }
+            Ok(value) => return Ok(HashMap::from([("body".to_string(), json_to_config_value(&value))])),
+            Err(e) if is_json => return Err(WebhookError::new(400, format!("Invalid JSON body: {}", e))),
+            Err(_) => {}
+        }
+    }
+
+    Ok(HashMap::from([("body".to_string(), ConfigValue::String(text.to_string()))]))
+}
+
+/// Parse `a=1&b=x+y&a=2`; repeated keys become arrays.
+fn parse_form(text: &str) -> HashMap<String, ConfigValue> {
+    let mut fields: HashMap<String, ConfigValue> = HashMap::new();
+
+    for pair in text.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = ConfigValue::String(percent_decode(value));
+        match fields.remove(&percent_decode(key)) {
+            None => {
+                fields.insert(percent_decode(key), value);
+            }
+            Some(ConfigValue::Array(mut values)) => {
+                values.push(value);
+                fields.insert(percent_decode(key), ConfigValue::Array(values));
+            }
+            Some(existing) => {
+                fields.insert(percent_decode(key), ConfigValue::Array(vec![existing, value]));
+            }
+        }
+    }
+
+    fields
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex_digit = |b: u8| (b as char).to_digit(16);
+                match (hex_digit(bytes[i + 1]), hex_digit(bytes[i + 2])) {
+                    (Some(high), Some(low)) => {
+                        decoded.push((high * 16 + low) as u8);
+                        i += 2;
+                    }
+                    _ => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn webhook_metadata(
+    webhook: &WebhookConfig,
+    headers: &HashMap<String, String>,
+    query: HashMap<String, ConfigValue>,
+    now: u64,
+) -> ConfigValue {
+    let secret_header = match &webhook.verification {
+        WebhookVerification::HmacSha256 { signature_header, .. } => Some(signature_header.to_ascii_lowercase()),
+        _ => None,
+    };
+    let headers = headers.iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "authorization" | "cookie" | TOKEN_HEADER))
+        .filter(|(name, _)| secret_header.as_deref() != Some(name.as_str()))
+        .map(|(name, value)| (name.clone(), ConfigValue::String(value.clone())))
+        .collect();
+    let mut query = query;
+    query.remove("token");
+
+    ConfigValue::Object(HashMap::from([
+        ("path".to_string(), ConfigValue::String(webhook.path.clone())),
+        ("headers".to_string(), ConfigValue::Object(headers)),
+        ("query".to_string(), ConfigValue::Object(query)),
+        ("received_at_ms".to_string(), ConfigValue::Number((now / 1_000_000) as f64)),
+    ]))
+}
+
+fn json_response(status_code: u16, body: serde_json::Value) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: body.to_string().into_bytes(),
+        upgrade: None,
+    }
+}
+
+fn error_response(error: &WebhookError) -> HttpResponse {
+    json_response(error.status, serde_json::json!({ "error": error.message }))
+}
+
+// =============================================================================
+// REGISTRATION
+// =============================================================================
+
+/// Route `/webhook/<path>` to a workflow. Re-registering a path keeps its verification settings.
+#[update]
+pub async fn register_webhook(workflow_id: String, path: String) -> Result<(), String> {
+    let path = normalize_path(&path)?;
+    let (verification, replay_protection) = storage::get_webhook(&path)
+        .map(|existing| (existing.verification, existing.replay_protection))
+        .unwrap_or((WebhookVerification::None, None));
+    save_webhook(workflow_id, path, verification, replay_protection)
+}
+
+/// Register or update a webhook route together with how its callers are verified.
+#[update]
+pub fn configure_webhook(
+    workflow_id: String,
+    path: String,
+    verification: WebhookVerification,
+    replay_protection: Option<ReplayProtection>,
+) -> Result<(), String> {
+    let path = normalize_path(&path)?;
+    validate_verification(&verification, replay_protection.as_ref())?;
+    save_webhook(workflow_id, path, verification, replay_protection)
+}
+
+#[update]
+pub fn unregister_webhook(path: String) -> Result<(), String> {
+    let path = normalize_path(&path)?;
+    let webhook = storage::get_webhook(&path)
+        .ok_or_else(|| "Webhook endpoint not found".to_string())?;
+    acl::authorize(&webhook.workflow_id, WorkflowRole::Editor)?;
+    storage::remove_webhook(&path)
+        .map(|_| ())
+        .ok_or_else(|| "Webhook endpoint not found".to_string())
+}
+
+/// Webhooks of workflows the caller can view, with their secrets masked.
+#[query]
+pub fn list_webhooks() -> Vec<WebhookConfig> {
+    storage::list_webhooks().into_iter()
+        .filter(|webhook| {
+            storage::get_workflow(&webhook.workflow_id)
+                .is_some_and(|workflow| acl::caller_can(&workflow, WorkflowRole::Viewer))
+        })
+        .map(|mut webhook| {
+            webhook.verification = match webhook.verification {
+                WebhookVerification::SharedToken { .. } => WebhookVerification::SharedToken { token: "***".to_string() },
+                WebhookVerification::HmacSha256 { signature_header, .. } => WebhookVerification::HmacSha256 {
+                    secret: "***".to_string(),
+                    signature_header,
+                },
+                WebhookVerification::None => WebhookVerification::None,
+            };
+            webhook
+        })
+        .collect()
+}
+
+/// Candid entry point for webhooks. Routes that verify their callers only accept
+/// candid calls from the workflow owner; everyone else must use the HTTP interface.
+#[update]
+pub async fn webhook_trigger(path: String, event: WebhookEvent) -> Result<String, String> {
+    let webhook = storage::get_webhook(&normalize_path(&path)?)
+        .ok_or("Webhook endpoint not found")?;
+
+    if webhook.verification != WebhookVerification::None {
+        let owner = storage::get_workflow(&webhook.workflow_id).and_then(|w| w.owner);
+        if owner != Some(caller().to_text()) {
+            return Err("This webhook only accepts signed HTTP requests".to_string());
+        }
+    }
+
+    trigger_execution(webhook.workflow_id, Some(event.data))
+}
+
+fn save_webhook(
+    workflow_id: String,
+    path: String,
+    verification: WebhookVerification,
+    replay_protection: Option<ReplayProtection>,
+) -> Result<(), String> {
+    acl::authorize(&workflow_id, WorkflowRole::Editor)?;
+    // Taking over a path means being allowed to edit the workflow it currently routes to
+    let existing = storage::get_webhook(&path);
+    if let Some(existing) = &existing {
+        if existing.workflow_id != workflow_id && storage::get_workflow(&existing.workflow_id).is_some() {
+            acl::authorize(&existing.workflow_id, WorkflowRole::Editor)?;
+        }
+    }
+
+    let created_at = existing
+        .map(|existing| existing.created_at)
+        .unwrap_or_else(api::time);
+    storage::insert_webhook(path.clone(), WebhookConfig {
+        path,
+        workflow_id,
+        verification,
+        replay_protection,
+        created_at,
+    });
+    Ok(())
+}
+
+fn normalize_path(path: &str) -> Result<String, String> {
+    let path = path.trim().trim_start_matches(WEBHOOK_PREFIX).trim_matches('/');
+    if path.is_empty() {
+        return Err("Webhook path cannot be empty".to_string());
+    }
+    if path.len() > MAX_PATH_LENGTH {
+        return Err(format!("Webhook path cannot exceed {} characters", MAX_PATH_LENGTH));
+    }
+    if !path.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')) {
+        return Err("Webhook path may only contain letters, digits, '-', '_', '.' and '/'".to_string());
+    }
+    Ok(path.to_string())
+}
+
+fn validate_verification(verification: &WebhookVerification, replay_protection: Option<&ReplayProtection>) -> Result<(), String> {
+    match verification {
+        WebhookVerification::None => {}
+        WebhookVerification::SharedToken { token } if token.len() < MIN_SECRET_LENGTH => {
+            return Err(format!("Webhook token must be at least {} characters", MIN_SECRET_LENGTH));
+        }
+        WebhookVerification::SharedToken { .. } => {}
+        WebhookVerification::HmacSha256 { secret, .. } if secret.len() < MIN_SECRET_LENGTH => {
+            return Err(format!("Webhook secret must be at least {} characters", MIN_SECRET_LENGTH));
+        }
+        WebhookVerification::HmacSha256 { signature_header, .. } if signature_header.trim().is_empty() => {
+            return Err("Signature header cannot be empty".to_string());
+        }
+        WebhookVerification::HmacSha256 { .. } => {}
+    }
+
+    if let Some(protection) = replay_protection {
+        if protection.timestamp_header.trim().is_empty() {
+            return Err("Timestamp header cannot be empty".to_string());
+        }
+        if protection.tolerance_seconds == 0 || protection.tolerance_seconds > MAX_TOLERANCE_SECONDS {
+            return Err(format!("tolerance_seconds must be between 1 and {}", MAX_TOLERANCE_SECONDS));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_800_000_000 * NANOS_PER_SECOND;
+    const SECRET: &str = "0123456789abcdef-secret";
+
+    fn sign(secret: &str, payload: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(payload);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn register(path: &str, verification: WebhookVerification, replay_protection: Option<ReplayProtection>) {
+        storage::insert_webhook(path.to_string(), WebhookConfig {
+            path: path.to_string(),
+            workflow_id: "wf".to_string(),
+            verification,
+            replay_protection,
+            created_at: 0,
+        });
+    }
+
+    fn post(url: &str, headers: &[(&str, &str)], body: &str) -> HttpRequest {
+        HttpRequest {
+            method: "POST".to_string(),
+            url: url.to_string(),
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231_vector() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn routes_and_parses_json_bodies() {
+        register("json-hook", WebhookVerification::None, None);
+        let request = post("/webhook/json-hook?source=test", &[("Content-Type", "application/json")], r#"{"amount": 5, "token": "ETH"}"#);
+
+        let (webhook, data) = accept_webhook(&request, NOW).unwrap();
+        assert_eq!(webhook.workflow_id, "wf");
+        assert!(matches!(data.get("amount"), Some(ConfigValue::Number(n)) if *n == 5.0));
+        let Some(ConfigValue::Object(meta)) = data.get("_webhook") else { panic!("missing metadata") };
+        let Some(ConfigValue::Object(query)) = meta.get("query") else { panic!("missing query") };
+        assert!(matches!(query.get("source"), Some(ConfigValue::String(s)) if s == "test"));
+
+        let unknown = post("/webhook/missing", &[], "{}");
+        assert_eq!(accept_webhook(&unknown, NOW).unwrap_err().status, 404);
+        let mut get = post("/webhook/json-hook", &[], "");
+        get.method = "GET".to_string();
+        assert_eq!(accept_webhook(&get, NOW).unwrap_err().status, 405);
+    }
+
+    #[test]
+    fn parses_form_and_text_bodies() {
+        let form = HashMap::from([("content-type".to_string(), "application/x-www-form-urlencoded; charset=utf-8".to_string())]);
+        let data = parse_body(&form, b"name=Jane+Doe&tag=a&tag=b%26c").unwrap();
+        assert!(matches!(data.get("name"), Some(ConfigValue::String(s)) if s == "Jane Doe"));
+        assert!(matches!(data.get("tag"), Some(ConfigValue::Array(tags)) if tags.len() == 2));
+
+        let text = HashMap::from([("content-type".to_string(), "text/plain".to_string())]);
+        let alert = parse_body(&text, br#"{"ticker": "BTCUSD"}"#).unwrap();
+        assert!(matches!(alert.get("ticker"), Some(ConfigValue::String(s)) if s == "BTCUSD"));
+        let plain = parse_body(&text, b"BTC crossed 100k").unwrap();
+        assert!(matches!(plain.get("body"), Some(ConfigValue::String(s)) if s == "BTC crossed 100k"));
+
+        let json = HashMap::from([("content-type".to_string(), "application/json".to_string())]);
+        assert_eq!(parse_body(&json, b"{oops").unwrap_err().status, 400);
+        let binary = HashMap::from([("content-type".to_string(), "application/octet-stream".to_string())]);
+        assert_eq!(parse_body(&binary, b"\x00\x01").unwrap_err().status, 415);
+    }
+
+    #[test]
+    fn verifies_hmac_signatures() {
+        register("signed", WebhookVerification::HmacSha256 {
+            secret: SECRET.to_string(),
+            signature_header: "X-Hub-Signature-256".to_string(),
+        }, None);
+        let body = r#"{"action": "opened"}"#;
+        let signature = format!("sha256={}", sign(SECRET, body.as_bytes()));
+
+        let good = post("/webhook/signed", &[("X-Hub-Signature-256", &signature)], body);
+        assert!(accept_webhook(&good, NOW).is_ok());
+
+        let tampered = post("/webhook/signed", &[("X-Hub-Signature-256", &signature)], r#"{"action": "closed"}"#);
+        assert_eq!(accept_webhook(&tampered, NOW).unwrap_err().status, 401);
+        let unsigned = post("/webhook/signed", &[], body);
+        assert_eq!(accept_webhook(&unsigned, NOW).unwrap_err().status, 401);
+    }
+
+    #[test]
+    fn verifies_shared_tokens() {
+        register("token", WebhookVerification::SharedToken { token: SECRET.to_string() }, None);
+        let bearer = format!("Bearer {}", SECRET);
+        assert!(accept_webhook(&post("/webhook/token", &[("Authorization", &bearer)], ""), NOW).is_ok());
+        assert!(accept_webhook(&post(&format!("/webhook/token?token={}", SECRET), &[], ""), NOW).is_ok());
+        assert_eq!(accept_webhook(&post("/webhook/token?token=wrong", &[], ""), NOW).unwrap_err().status, 401);
+    }
+
+    #[test]
+    fn rejects_stale_and_replayed_requests() {
+        register("replay", WebhookVerification::HmacSha256 {
+            secret: SECRET.to_string(),
+            signature_header: "X-Signature".to_string(),
+        }, Some(ReplayProtection {
+            timestamp_header: "X-Timestamp".to_string(),
+            nonce_header: None,
+            tolerance_seconds: 300,
+        }));
+        let body = r#"{"event": "transfer"}"#;
+        let signed = |timestamp: u64| {
+            let timestamp = timestamp.to_string();
+            let signature = sign(SECRET, &signed_payload(body.as_bytes(), Some(&timestamp)));
+            post("/webhook/replay", &[("X-Timestamp", &timestamp), ("X-Signature", &signature)], body)
+        };
+        let now_seconds = NOW / NANOS_PER_SECOND;
+
+        let request = signed(now_seconds - 10);
+        assert!(accept_webhook(&request, NOW).is_ok());
+        assert_eq!(accept_webhook(&request, NOW).unwrap_err().status, 409);
+        assert_eq!(accept_webhook(&signed(now_seconds - 301), NOW).unwrap_err().status, 401);
+        // Milliseconds are accepted too
+        assert!(accept_webhook(&signed(now_seconds * 1000 + 5), NOW).is_ok());
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize_path("/webhook/github/").unwrap(), "github");
+        assert_eq!(normalize_path("alerts/tradingview").unwrap(), "alerts/tradingview");
+        assert!(normalize_path("").is_err());
+        assert!(normalize_path("bad path").is_err());
+        assert_eq!(split_webhook_url("/webhook/a/b?x=1"), Some(("a/b".to_string(), "x=1".to_string())));
+        assert_eq!(split_webhook_url("/other"), None);
+    }
+}
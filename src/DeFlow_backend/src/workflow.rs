@@ -1,4 +1,6 @@
-use crate::types::{Workflow, ConfigValue, ValidationError, WorkflowState};
+use crate::types::{Workflow, ConfigValue, ValidationError, WorkflowState, FallbackStrategy, WorkflowRole};
+use crate::acl;
+use crate::revisions;
 use crate::storage;
 use ic_cdk::{api, update, query, caller};
 use candid::{CandidType, Deserialize};
@@ -24,6 +26,8 @@ pub fn validate_node_configuration(node_type: &str, config: &crate::types::NodeC
         }
     }
     
+    crate::nodes::validate_node_expressions(node_type, config)?;
+    
     Ok(())
 }
 
@@ -48,9 +52,28 @@ fn get_config_value_type(value: &ConfigValue) -> String {
     }
 }
 
+thread_local! {
+    // (timestamp, count) of the last generated ID; api::time() is constant within a message
+    static LAST_ID: std::cell::RefCell<(u64, u32)> = const { std::cell::RefCell::new((0, 0)) };
+}
+
 pub fn generate_id() -> String {
     let time = api::time();
-    format!("{:x}", time)
+    let sequence = LAST_ID.with(|last| {
+        let mut last = last.borrow_mut();
+        if last.0 == time {
+            last.1 += 1;
+        } else {
+            *last = (time, 0);
+        }
+        last.1
+    });
+    
+    if sequence == 0 {
+        format!("{:x}", time)
+    } else {
+        format!("{:x}-{}", time, sequence)
+    }
 }
 
 // Workflow validation functions
@@ -69,7 +92,21 @@ pub fn validate_workflow(workflow: &Workflow) -> Result<(), ValidationError> {
     // Validate node configurations
     for node in &workflow.nodes {
         validate_node_configuration(&node.node_type, &node.configuration)
-            .map_err(|_| ValidationError::InvalidNodeConfiguration(node.id.clone()))?;
+            .map_err(|e| ValidationError::InvalidNodeConfiguration(match e {
+                ValidationError::InvalidParameterValue(detail) => format!("{}: {}", node.id, detail),
+                _ => node.id.clone(),
+            }))?;
+    }
+
+    // Alternative nodes named in on_error must exist and stand in for another node
+    for node in &workflow.nodes {
+        if let Some(FallbackStrategy::UseAlternativeNode { node_id }) = &node.on_error {
+            if node_id == &node.id || !workflow.nodes.iter().any(|n| &n.id == node_id) {
+                return Err(ValidationError::InvalidNodeConfiguration(
+                    format!("{}: alternative node '{}' not found", node.id, node_id)
+                ));
+            }
+        }
     }
 
     // Validate connections
@@ -192,36 +229,20 @@ fn validate_trigger(trigger: &crate::types::WorkflowTrigger) -> Result<(), Valid
                 Ok(())
             }
         }
-        crate::types::WorkflowTrigger::Event { event_type, conditions: _ } => {
+        crate::types::WorkflowTrigger::Event { event_type, condition, .. } => {
             if event_type.is_empty() {
-                Err(ValidationError::InvalidTrigger("Event type cannot be empty".to_string()))
-            } else {
-                Ok(())
+                return Err(ValidationError::InvalidTrigger("Event type cannot be empty".to_string()));
+            }
+            match condition {
+                Some(condition) => crate::conditions::validate_condition(condition).map_err(ValidationError::InvalidTrigger),
+                None => Ok(()),
             }
         }
     }
 }
 
 fn validate_cron_expression(cron: &str) -> Result<(), ValidationError> {
-    if cron.is_empty() {
-        return Err(ValidationError::InvalidTrigger("Cron expression cannot be empty".to_string()));
-    }
-
-    // Basic cron validation - supports simple patterns like "*/5" for every 5 minutes
-    if cron.starts_with("*/") {
-        let parts: Vec<&str> = cron.splitn(2, '/').collect();
-        if parts.len() == 2 {
-            if let Ok(interval) = parts[1].parse::<u32>() {
-                if interval > 0 && interval <= 60 {
-                    return Ok(());
-                }
-            }
-        }
-    }
-
-    // For now, accept any non-empty cron expression
-    // TODO: Implement full cron validation
-    Ok(())
+    crate::cron::validate_expression(cron, None).map_err(ValidationError::InvalidTrigger)
 }
 
 // Workflow analysis functions
@@ -302,7 +323,7 @@ pub async fn create_workflow(mut workflow: Workflow) -> Result<String, String> {
     workflow.updated_at = current_time;
     
     // Set owner to current user
-    workflow.owner = Some(principal_id);
+    workflow.owner = Some(principal_id.clone());
     
     // Workflow state is already set from frontend or defaults to Draft
     
@@ -315,48 +336,53 @@ pub async fn create_workflow(mut workflow: Workflow) -> Result<String, String> {
     validate_workflow(&workflow)
         .map_err(|e| format!("Workflow validation failed: {:?}", e))?;
     
-    storage::insert_workflow(workflow.id.clone(), workflow.clone());
+    let workflow = revisions::save(workflow, principal_id, current_time, None);
     
     Ok(workflow.id)
 }
 
 #[update]
 pub async fn update_workflow(workflow: Workflow) -> Result<(), String> {
-    if storage::get_workflow(&workflow.id).is_none() {
-        return Err("Workflow not found".to_string());
-    }
+    let existing = acl::authorize(&workflow.id, WorkflowRole::Editor)?;
     
     // Comprehensive workflow validation
     validate_workflow(&workflow)
         .map_err(|e| format!("Workflow validation failed: {:?}", e))?;
     
+    // Ownership is not editable through updates
     let mut updated_workflow = workflow;
+    updated_workflow.owner = existing.owner;
+    updated_workflow.created_at = existing.created_at;
     updated_workflow.updated_at = api::time();
     
-    storage::insert_workflow(updated_workflow.id.clone(), updated_workflow);
+    revisions::save(updated_workflow, caller().to_text(), api::time(), None);
     Ok(())
 }
 
 #[query]
 pub fn get_workflow(id: String) -> Result<Workflow, String> {
-    storage::get_workflow(&id)
-        .ok_or_else(|| "Workflow not found".to_string())
+    acl::authorize(&id, WorkflowRole::Viewer)
 }
 
+/// Workflows the caller owns or has been given access to.
 #[query]
 pub fn list_workflows() -> Vec<Workflow> {
     storage::WORKFLOWS.with(|workflows| {
         workflows.borrow().iter()
-            .map(|(_, storable)| storable.0.clone())
+            .map(|(_, storable)| storable.0)
+            .filter(|workflow| acl::caller_can(workflow, WorkflowRole::Viewer))
             .collect()
     })
 }
 
 #[update]
 pub async fn delete_workflow(id: String) -> Result<(), String> {
+    acl::authorize(&id, WorkflowRole::Admin)?;
     storage::remove_workflow(&id)
-        .ok_or_else(|| "Workflow not found".to_string())
-        .map(|_| ())
+        .ok_or_else(|| "Workflow not found".to_string())?;
+    acl::forget_workflow(&id, &caller().to_text());
+    storage::remove_workflow_revisions(&id);
+    Ok(())
 }
 
 #[query]
@@ -405,16 +431,7 @@ pub fn get_user_templates() -> Vec<Workflow> {
 
 #[update]
 pub async fn publish_workflow(workflow_id: String) -> Result<(), String> {
-    let principal = caller();
-    let principal_id = principal.to_text();
-    
-    let mut workflow = storage::get_workflow(&workflow_id)
-        .ok_or_else(|| "Workflow not found".to_string())?;
-    
-    // Verify ownership
-    if workflow.owner.as_ref() != Some(&principal_id) {
-        return Err("Access denied. You can only publish your own workflows.".to_string());
-    }
+    let mut workflow = acl::authorize(&workflow_id, WorkflowRole::Admin)?;
     
     // Change state to published
     workflow.state = WorkflowState::Published;
@@ -464,7 +481,7 @@ pub async fn save_as_template(workflow_id: String, template_name: String, catego
         original_workflow_id: Some(workflow_id),
     });
     
-    storage::insert_workflow(template_id.clone(), template_workflow);
+    revisions::save(template_workflow, principal_id, current_time, None);
     Ok(template_id)
 }
 
@@ -499,7 +516,7 @@ pub async fn create_from_template(template_id: String, workflow_name: String) ->
     let mut new_workflow = template.clone();
     new_workflow.id = new_workflow_id.clone();
     new_workflow.name = workflow_name;
-    new_workflow.owner = Some(principal_id);
+    new_workflow.owner = Some(principal_id.clone());
     new_workflow.state = WorkflowState::Draft; // New workflows start as drafts
     new_workflow.active = false;
     new_workflow.created_at = current_time;
@@ -514,7 +531,7 @@ pub async fn create_from_template(template_id: String, workflow_name: String) ->
         storage::insert_workflow(template_id, updated_template);
     }
     
-    storage::insert_workflow(new_workflow_id.clone(), new_workflow);
+    revisions::save(new_workflow, principal_id, current_time, None);
     Ok(new_workflow_id)
 }
 